@@ -0,0 +1,126 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Timings of the accumulation, validation and persistence paths, run with
+//! `cargo bench --features bench`. Each workload reports only the time the `bench` module measures
+//! for it, leaving out the signing and chain generation around it. Set `DATA_CHAIN_BENCH_BLOCKS`
+//! to cap the chain lengths, e.g. `DATA_CHAIN_BENCH_BLOCKS=10000 cargo bench --features bench`.
+
+#![allow(clippy::redundant_field_names)]
+
+#[macro_use]
+extern crate criterion;
+extern crate data_chain;
+extern crate rust_sodium;
+extern crate tempdir;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use data_chain::bench::{self, BenchConfig, BenchResult};
+use data_chain::sha3::HashAlgorithm;
+use std::env;
+use std::time::Duration;
+use tempdir::TempDir;
+
+// Number of data blocks in the largest chain, from `DATA_CHAIN_BENCH_BLOCKS`. Vote ingestion
+// workloads over it are skipped.
+fn largest() -> usize {
+    env::var("DATA_CHAIN_BENCH_BLOCKS").ok().and_then(|x| x.parse().ok()).unwrap_or(100_000)
+}
+
+// Total time of `iterations` runs of a workload timing itself.
+fn timed<F: FnMut() -> BenchResult>(iterations: u64, mut run: F) -> Duration {
+    (0..iterations).map(|_| run().elapsed).sum()
+}
+
+// `add_vote` throughput across chain lengths and group sizes.
+fn vote_ingestion(criterion: &mut Criterion) {
+    let _ = rust_sodium::init();
+    let mut group = criterion.benchmark_group("vote_ingestion");
+    let _ = group.sample_size(10);
+    for &blocks in [100, 1_000, 10_000].iter().filter(|&&x| x <= largest()) {
+        for &group_size in &[4, 8, 16] {
+            let config = BenchConfig {
+                blocks: blocks,
+                group_size: group_size,
+            };
+            let votes = bench::generate_votes(&config);
+            let _ = group.throughput(Throughput::Elements(votes.len() as u64));
+            let id = BenchmarkId::new(format!("group_{}", group_size), config.blocks);
+            let _ = group.bench_with_input(id, &votes, |bencher, votes| {
+                bencher.iter_custom(|iterations| {
+                    timed(iterations,
+                          || bench::vote_ingestion(votes.clone(), group_size))
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+// Validation and persistence of one large chain.
+fn large_chain(criterion: &mut Criterion) {
+    let _ = rust_sodium::init();
+    let config = BenchConfig {
+        blocks: largest(),
+        group_size: 8,
+    };
+    let (mut chain, _) = bench::generate_chain(&config);
+    let dir = match TempDir::new("bench_chain") {
+        Ok(dir) => dir,
+        Err(error) => panic!("no temporary directory: {}", error),
+    };
+    let mut group = criterion.benchmark_group("large_chain");
+    let _ = group.sample_size(10).throughput(Throughput::Elements(chain.len() as u64));
+    let _ = group.bench_function(BenchmarkId::new("full_validation", config.blocks), |bencher| {
+        bencher.iter_custom(|iterations| timed(iterations, || bench::full_validation(&mut chain)))
+    });
+    let _ = group.bench_function(BenchmarkId::new("disk_round_trip", config.blocks), |bencher| {
+        bencher.iter_custom(|iterations| {
+            timed(iterations,
+                  || match bench::disk_round_trip(&chain, dir.path()) {
+                      Ok(result) => result,
+                      Err(error) => panic!("round trip failed: {}", error),
+                  })
+        })
+    });
+    group.finish();
+}
+
+// Content hashing of large immutable data, which dominates `put_data`. Add `--features blake3`
+// to compare BLAKE3.
+fn content_hashing(criterion: &mut Criterion) {
+    #[allow(unused_mut)]
+    let mut algorithms = vec![HashAlgorithm::Sha3_256];
+    #[cfg(feature = "blake3")]
+    algorithms.push(HashAlgorithm::Blake3);
+    let mut group = criterion.benchmark_group("content_hashing");
+    for algorithm in algorithms {
+        for &size in &[1 << 10, 1 << 20, 16 << 20] {
+            let _ = group.throughput(Throughput::Bytes(size as u64));
+            let id = BenchmarkId::new(format!("{:?}", algorithm), size);
+            let _ = group.bench_function(id, |bencher| {
+                bencher.iter_custom(|iterations| {
+                    bench::content_hashing(algorithm, size, iterations as usize).elapsed
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, vote_ingestion, large_chain, content_hashing);
+criterion_main!(benches);
@@ -0,0 +1,236 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, ChurnEvent, DataChain, Vote};
+use error::Error;
+use messages::ChainMessage;
+use rust_sodium::crypto::sign::{PublicKey, SecretKey};
+
+/// What a routing or vault layer provides for a `ChainDriver` to run a `DataChain` on the network.
+pub trait ChainAuthority {
+    /// The members of our close group as routing currently sees them, us included.
+    fn close_group(&self) -> Vec<PublicKey>;
+
+    /// Our signing keys.
+    fn keys(&self) -> (&PublicKey, &SecretKey);
+
+    /// Send `message` to `peer`, e.g. after `ChainMessage::encode`.
+    fn send(&mut self, peer: &PublicKey, message: ChainMessage) -> Result<(), Error>;
+}
+
+/// Wires a `DataChain` to a `ChainAuthority`: votes and chains received from peers are
+/// accumulated, requests for our chain answered, and our own votes, including the links for
+/// membership changes, added locally and sent to the rest of the close group.
+pub struct ChainDriver<A> {
+    authority: A,
+    chain: DataChain,
+}
+
+impl<A: ChainAuthority> ChainDriver<A> {
+    /// Drive `chain` through `authority`.
+    pub fn new(authority: A, chain: DataChain) -> ChainDriver<A> {
+        ChainDriver {
+            authority: authority,
+            chain: chain,
+        }
+    }
+
+    /// getter
+    pub fn chain(&self) -> &DataChain {
+        &self.chain
+    }
+
+    /// The chain, e.g. to persist or subscribe to it.
+    pub fn chain_mut(&mut self) -> &mut DataChain {
+        &mut self.chain
+    }
+
+    /// getter
+    pub fn authority(&self) -> &A {
+        &self.authority
+    }
+
+    /// getter
+    pub fn authority_mut(&mut self) -> &mut A {
+        &mut self.authority
+    }
+
+    /// Take the driver apart.
+    pub fn into_parts(self) -> (A, DataChain) {
+        (self.authority, self.chain)
+    }
+
+    /// Handle a message from `peer`: votes are added to the chain, a `ChainRequest` is answered
+    /// with `blocks_since` the link asked from, a `ChainResponse` is applied as a delta and every
+    /// `Batch` is acknowledged. The messages left to the caller, for data and `Ack`s, are
+    /// returned. Fails on the first response that does not verify, though the rest of a batch is
+    /// still handled and acknowledged.
+    pub fn handle_message(&mut self,
+                          peer: &PublicKey,
+                          message: ChainMessage)
+                          -> Result<Vec<ChainMessage>, Error> {
+        let mut unhandled = vec![];
+        self.handle(peer, message, &mut unhandled)?;
+        Ok(unhandled)
+    }
+
+    /// Vote for `identifier` (see `DataChain::vote_for`), adding the vote locally and sending it
+    /// to the rest of the close group. Returns the vote.
+    pub fn vote(&mut self, identifier: BlockIdentifier) -> Result<Vote, Error> {
+        let vote = {
+            let (pub_key, secret_key) = self.authority.keys();
+            self.chain.vote_for(identifier, pub_key, secret_key)?
+        };
+        let _ = self.chain.add_vote(vote.clone());
+        self.broadcast(&[vote.clone()])?;
+        Ok(vote)
+    }
+
+    /// Routing saw our close group change: vote for the link recording it, see
+    /// `DataChain::handle_churn`, and send the votes to the rest of the group.
+    pub fn churn(&mut self, event: ChurnEvent) -> Result<Vec<Vote>, Error> {
+        let votes = {
+            let (pub_key, secret_key) = self.authority.keys();
+            self.chain.handle_churn(event, pub_key, secret_key)?
+        };
+        self.broadcast(&votes)?;
+        Ok(votes)
+    }
+
+    /// Ask `peer` for the blocks after our tip, to catch up after being offline. Fails with
+    /// `Error::MissingLink` for a chain without a valid link to ask from.
+    pub fn request_chain(&mut self, peer: &PublicKey) -> Result<(), Error> {
+        let from_link = self.chain.tip().ok_or(Error::MissingLink)?.identifier().clone();
+        self.authority.send(peer, ChainMessage::ChainRequest { from_link: from_link })
+    }
+
+    fn broadcast(&mut self, votes: &[Vote]) -> Result<(), Error> {
+        let us = *self.authority.keys().0;
+        for peer in self.authority.close_group().into_iter().filter(|x| *x != us) {
+            for vote in votes {
+                self.authority.send(&peer, ChainMessage::Vote(vote.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self,
+              peer: &PublicKey,
+              message: ChainMessage,
+              unhandled: &mut Vec<ChainMessage>)
+              -> Result<(), Error> {
+        match message {
+            ChainMessage::Vote(vote) => {
+                let _ = self.chain.add_vote_from(vote, *peer);
+            }
+            ChainMessage::ChainRequest { from_link } => {
+                if let Some(blocks) = self.chain.blocks_since(&from_link) {
+                    self.authority.send(peer, ChainMessage::ChainResponse { blocks: blocks })?;
+                }
+            }
+            ChainMessage::ChainResponse { blocks } => {
+                let _ = self.chain.apply_delta(blocks).map_err(|_| Error::Validation)?;
+            }
+            ChainMessage::Batch { id, messages } => {
+                let mut result = Ok(());
+                for message in messages {
+                    let handled = self.handle(peer, message, unhandled);
+                    result = result.and(handled);
+                }
+                self.authority.send(peer, ChainMessage::Ack(id))?;
+                return result;
+            }
+            message => unhandled.push(message),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::DataIdentifier;
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    struct Authority {
+        keys: (PublicKey, SecretKey),
+        group: Vec<PublicKey>,
+        sent: Vec<(PublicKey, ChainMessage)>,
+    }
+
+    impl ChainAuthority for Authority {
+        fn close_group(&self) -> Vec<PublicKey> {
+            self.group.clone()
+        }
+
+        fn keys(&self) -> (&PublicKey, &SecretKey) {
+            (&self.keys.0, &self.keys.1)
+        }
+
+        fn send(&mut self, peer: &PublicKey, message: ChainMessage) -> Result<(), Error> {
+            self.sent.push((*peer, message));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn drive_two_nodes() {
+        let _ = ::rust_sodium::init();
+        let keys = (0..2).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let group = keys.iter().map(|x| x.0).collect::<Vec<_>>();
+        let mut drivers = keys.iter()
+            .map(|x| {
+                let authority = Authority {
+                    keys: x.clone(),
+                    group: group.clone(),
+                    sent: vec![],
+                };
+                ChainDriver::new(authority, DataChain::from_blocks(vec![], 999))
+            })
+            .collect::<Vec<_>>();
+        let deliver = |drivers: &mut Vec<ChainDriver<Authority>>, from: usize| {
+            let sent = drivers[from].authority_mut().sent.drain(..).collect::<Vec<_>>();
+            for (peer, message) in sent {
+                assert_eq!(peer, group[1 - from]);
+                assert!(unwrap!(drivers[1 - from].handle_message(&group[from], message))
+                    .is_empty());
+            }
+        };
+
+        assert_eq!(unwrap!(drivers[0].churn(ChurnEvent::NodeGained(group[0]))).len(), 1);
+        assert_eq!(unwrap!(drivers[0].churn(ChurnEvent::NodeGained(group[1]))).len(), 1);
+        assert!(unwrap!(drivers[0].churn(ChurnEvent::NodeGained(group[0]))).is_empty());
+        deliver(&mut drivers, 0);
+        assert_eq!(drivers[1].chain().links_len(), 2);
+        assert_eq!(drivers[1].chain().chain(), drivers[0].chain().chain());
+
+        // Catching up sends what the peer lacks, and a repeat changes nothing.
+        unwrap!(drivers[1].request_chain(&group[0]));
+        deliver(&mut drivers, 1);
+        deliver(&mut drivers, 0);
+        assert_eq!(drivers[1].chain().len(), 2);
+
+        // Batches are acknowledged and data messages left to the caller.
+        let request = ChainMessage::DataRequest(DataIdentifier::Immutable([1; 32]));
+        let batch = ChainMessage::Batch {
+            id: 7,
+            messages: vec![request.clone()],
+        };
+        assert_eq!(unwrap!(drivers[1].handle_message(&group[0], batch)), vec![request]);
+        assert_eq!(drivers[1].authority().sent, vec![(group[0], ChainMessage::Ack(7))]);
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0 This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, DataChain, LinkDescriptor, Vote};
+use error::Error;
+use itertools::Itertools;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+use sha3::{HashAlgorithm, hash};
+use std::fs::File;
+use std::hint;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Size of the workloads run by this module.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BenchConfig {
+    /// Number of data blocks in the generated chain, which also holds one link.
+    pub blocks: usize,
+    /// Number of nodes in the group voting for every block.
+    pub group_size: usize,
+}
+
+impl Default for BenchConfig {
+    fn default() -> BenchConfig {
+        BenchConfig {
+            blocks: 1000,
+            group_size: 8,
+        }
+    }
+}
+
+/// Timing of one workload.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchResult {
+    /// Name of the workload.
+    pub name: &'static str,
+    /// Number of operations timed, blocks or votes depending on the workload.
+    pub operations: u64,
+    /// Wall clock time taken by those operations.
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Operations per second, or `None` if no measurable time elapsed.
+    pub fn per_second(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs() as f64 + self.elapsed.subsec_nanos() as f64 * 1e-9;
+        if seconds > 0.0 {
+            Some(self.operations as f64 / seconds)
+        } else {
+            None
+        }
+    }
+}
+
+/// Votes of a group of `config.group_size` new nodes for a chain of one link followed by
+/// `config.blocks` data blocks, in the order they should be added.
+pub fn generate_votes(config: &BenchConfig) -> Vec<Vote> {
+    let group = (0..config.group_size.max(1)).map(|_| sign::gen_keypair()).collect_vec();
+    let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(group[0].0));
+    let mut votes = group_votes(&group, &link);
+    for index in 0..config.blocks {
+        let data = BlockIdentifier::ImmutableData(hash(format!("bench {}", index).as_bytes()));
+        votes.extend(group_votes(&group, &data));
+    }
+    votes
+}
+
+fn group_votes(group: &[(PublicKey, SecretKey)], identifier: &BlockIdentifier) -> Vec<Vote> {
+    group.iter()
+        .filter_map(|keys| Vote::new(&keys.0, &keys.1, identifier.clone()).ok())
+        .collect()
+}
+
+/// Cold start: sign all votes for a chain as described by `config` and accumulate them into a
+/// new chain, which is returned along with the timing per block.
+pub fn generate_chain(config: &BenchConfig) -> (DataChain, BenchResult) {
+    let start = Instant::now();
+    let mut chain = DataChain::from_blocks(vec![], config.group_size);
+    for vote in generate_votes(config) {
+        let _ = chain.add_vote(vote);
+    }
+    let result = BenchResult {
+        name: "generate_chain",
+        operations: chain.len() as u64,
+        elapsed: start.elapsed(),
+    };
+    (chain, result)
+}
+
+/// Time accumulating the given votes into a new chain, excluding the time taken to sign them.
+pub fn vote_ingestion(votes: Vec<Vote>, group_size: usize) -> BenchResult {
+    let operations = votes.len() as u64;
+    let mut chain = DataChain::from_blocks(vec![], group_size);
+    let start = Instant::now();
+    for vote in votes {
+        let _ = chain.add_vote(vote);
+    }
+    BenchResult {
+        name: "vote_ingestion",
+        operations: operations,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Time a full `mark_blocks_valid` pass over `chain`.
+pub fn full_validation(chain: &mut DataChain) -> BenchResult {
+    let start = Instant::now();
+    chain.mark_blocks_valid();
+    BenchResult {
+        name: "full_validation",
+        operations: chain.len() as u64,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Time writing a copy of `chain` to a chain file in the existing directory `dir` and loading it
+/// back with `DataChain::from_path`.
+pub fn disk_round_trip(chain: &DataChain, dir: &Path) -> Result<BenchResult, Error> {
+    let path = dir.join("data_chain");
+    let _ = File::create(&path)?;
+    let mut copy = DataChain::from_blocks(chain.chain().clone(), chain.group_size());
+    let start = Instant::now();
+    copy.write_to_new_path(path)?;
+    let loaded = DataChain::from_path(dir.to_path_buf(), chain.group_size())?;
+    Ok(BenchResult {
+        name: "disk_round_trip",
+        operations: loaded.len() as u64,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Time hashing `rounds` items of `size` bytes with `algorithm`, as `SecuredData::put_data` does
+/// for each item it stores. Operations are bytes hashed.
+pub fn content_hashing(algorithm: HashAlgorithm, size: usize, rounds: usize) -> BenchResult {
+    let hasher = algorithm.hasher();
+    let mut content = vec![0x5a; size];
+    let start = Instant::now();
+    for _ in 0..rounds {
+        let digest = hint::black_box(hasher.hash(&content));
+        if let Some(first) = content.first_mut() {
+            *first = digest[0];
+        }
+    }
+    let name = match algorithm {
+        HashAlgorithm::Sha3_256 => "hash_sha3_256",
+        #[cfg(feature = "blake3")]
+        HashAlgorithm::Blake3 => "hash_blake3",
+    };
+    BenchResult {
+        name: name,
+        operations: (size * rounds) as u64,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Run every workload for `config`, using `dir` for the disk round trip.
+pub fn run_all(config: &BenchConfig, dir: &Path) -> Result<Vec<BenchResult>, Error> {
+    let (mut chain, generated) = generate_chain(config);
+    let ingested = vote_ingestion(generate_votes(config), config.group_size);
+    let validated = full_validation(&mut chain);
+    let round_trip = disk_round_trip(&chain, dir)?;
+    Ok(vec![generated, ingested, validated, round_trip])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn workloads() {
+        let _ = ::rust_sodium::init();
+        let config = BenchConfig {
+            blocks: 20,
+            group_size: 3,
+        };
+        assert_eq!(generate_votes(&config).len(), 63);
+        let dir = unwrap!(TempDir::new("test_bench"));
+        let results = unwrap!(run_all(&config, dir.path()));
+        assert_eq!(results.iter().map(|x| x.name).collect_vec(),
+                   vec!["generate_chain", "vote_ingestion", "full_validation", "disk_round_trip"]);
+        assert_eq!(results.iter().map(|x| x.operations).collect_vec(),
+                   vec![21, 63, 21, 21]);
+        assert!(results[1].per_second().is_some());
+        let hashed = content_hashing(HashAlgorithm::default(), 1024, 4);
+        assert_eq!((hashed.name, hashed.operations), ("hash_sha3_256", 4096));
+    }
+}
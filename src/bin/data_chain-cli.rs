@@ -0,0 +1,191 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Inspection and repair of chain files, as written by `DataChain::write` and `FileStore`.
+//!
+//! ```text
+//! data_chain-cli <command> <chain file> [--group-size <n>] [--format json]
+//! ```
+//!
+//! Commands are `inspect`, `verify`, `prune`, `export` and `repair`, `export` writing the chain as
+//! `DataChain::to_json` does. The group size (default 8) is that of the network the chain came
+//! from, as quorum depends on it. `verify` exits with 0 if every block is valid, 1 if any is not
+//! and 2 if the file can't be read; other commands exit with 0 on success and 2 on failure.
+
+#![allow(clippy::redundant_field_names)]
+
+extern crate data_chain;
+extern crate rust_sodium;
+
+use data_chain::DataChain;
+use data_chain::chain::persistence::{self, ChainStore, FileStore};
+use data_chain::error::Error;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "usage: data_chain-cli <inspect|verify|prune|export|repair> <chain file> \
+                     [--group-size <n>] [--format json]";
+
+const DEFAULT_GROUP_SIZE: usize = 8;
+
+const EXIT_INVALID: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+struct Args {
+    command: String,
+    path: PathBuf,
+    group_size: usize,
+    format: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let command = args.next().ok_or_else(|| USAGE.to_owned())?;
+    let path = PathBuf::from(args.next().ok_or_else(|| USAGE.to_owned())?);
+    let mut parsed = Args {
+        command: command,
+        path: path,
+        group_size: DEFAULT_GROUP_SIZE,
+        format: "json".to_owned(),
+    };
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or_else(|| format!("{} needs a value", flag))?;
+        match &flag[..] {
+            "--group-size" => {
+                parsed.group_size = value.parse()
+                    .map_err(|_| format!("bad group size {}", value))?
+            }
+            "--format" => parsed.format = value,
+            _ => return Err(USAGE.to_owned()),
+        }
+    }
+    Ok(parsed)
+}
+
+fn open(args: &Args) -> Result<DataChain, Error> {
+    let _ = fs::metadata(&args.path)?;
+    DataChain::open_with_store(Box::new(FileStore::new(args.path.clone())), args.group_size)
+}
+
+fn inspect(args: &Args) -> Result<i32, Error> {
+    let chain = open(args)?;
+    for (index, block) in chain.chain().iter().enumerate() {
+        println!("{:>6} {} {:<7} proofs {:>3} {:?}",
+                 index,
+                 if block.identifier().is_link() { "link" } else { "data" },
+                 if block.valid { "valid" } else { "invalid" },
+                 block.proofs().len(),
+                 block.identifier());
+    }
+    println!("{} blocks ({} links), {} valid, {} pinned",
+             chain.len(),
+             chain.links_len(),
+             chain.valid_len(),
+             chain.pinned().len());
+    Ok(0)
+}
+
+fn verify(args: &Args) -> Result<i32, Error> {
+    let mut chain = open(args)?;
+    let recorded = chain.chain().iter().map(|x| x.valid).collect::<Vec<_>>();
+    chain.mark_blocks_valid();
+    let mut code = 0;
+    for ((index, block), was_valid) in chain.chain().iter().enumerate().zip(recorded) {
+        if !block.valid {
+            code = EXIT_INVALID;
+            println!("{:>6} invalid{} {:?}",
+                     index,
+                     if was_valid { " (recorded as valid)" } else { "" },
+                     block.identifier());
+        }
+    }
+    if let Err(violation) = chain.check_invariants() {
+        code = EXIT_INVALID;
+        println!("invariant violated: {}", violation);
+    }
+    println!("{} of {} blocks valid", chain.valid_len(), chain.len());
+    Ok(code)
+}
+
+fn prune(args: &Args) -> Result<i32, Error> {
+    let mut chain = open(args)?;
+    let before = chain.len();
+    chain.prune();
+    chain.flush()?;
+    println!("removed {} invalid blocks, {} remain", before - chain.len(), chain.len());
+    Ok(0)
+}
+
+fn export(args: &Args) -> Result<i32, Error> {
+    if args.format != "json" {
+        eprintln!("unsupported format {}, only json is available", args.format);
+        return Ok(EXIT_ERROR);
+    }
+    println!("{}", open(args)?.to_json());
+    Ok(0)
+}
+
+// Keeps the damaged file alongside as `<file>.bak` before rewriting it.
+fn repair(args: &Args) -> Result<i32, Error> {
+    let mut bytes = vec![];
+    let _ = File::open(&args.path)?.read_to_end(&mut bytes)?;
+    let (blocks, pinned, good) = persistence::recover_chain(&bytes)?;
+    if good == bytes.len() {
+        println!("{} blocks, nothing to repair", blocks.len());
+        return Ok(0);
+    }
+    let _ = fs::copy(&args.path, args.path.with_extension("bak"))?;
+    let mut store = FileStore::new(args.path.clone());
+    store.set_hash_algorithm(persistence::chain_hash_algorithm(&bytes)?)?;
+    let _ = store.store(&blocks, &pinned)?;
+    println!("dropped {} damaged trailing bytes, kept {} blocks",
+             bytes.len() - good,
+             blocks.len());
+    Ok(0)
+}
+
+fn main() {
+    let _ = rust_sodium::init();
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(EXIT_ERROR);
+        }
+    };
+    let result = match &args.command[..] {
+        "inspect" => inspect(&args),
+        "verify" => verify(&args),
+        "prune" => prune(&args),
+        "export" => export(&args),
+        "repair" => repair(&args),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_ERROR);
+        }
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(error) => {
+            eprintln!("{}: {}", args.path.display(), error);
+            process::exit(EXIT_ERROR);
+        }
+    }
+}
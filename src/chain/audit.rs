@@ -0,0 +1,363 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, DataChain, LinkDescriptor, Role};
+use data::DataIdentifier;
+use rust_sodium::crypto::sign::PublicKey;
+use rustc_serialize::hex::ToHex;
+use std::fmt::Write;
+
+/// Header row of `DataChain::audit_csv`.
+pub const AUDIT_CSV_HEADER: &str = "position,time,link,proofs,event,subject,detail";
+
+/// A high level event recorded by a valid block, independent of the block model.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuditEvent {
+    /// The node with this key joined the group.
+    NodeGained(PublicKey),
+    /// The node with this key left the group.
+    NodeLost(PublicKey),
+    /// An earlier loss of the node with this key was revoked.
+    NodeReturned(PublicKey),
+    /// The group split from the section with these prefix bits.
+    Split(u64),
+    /// An earlier split from the section with these prefix bits was revoked.
+    SplitCancelled(u64),
+    /// The group merged into the section with these prefix bits.
+    Merged(u64),
+    /// The group for these prefix bits checkpointed with this many voting members.
+    CheckPoint(u64, usize),
+    /// A member rotated its signing key.
+    KeyChanged {
+        /// The retired key.
+        old: PublicKey,
+        /// The key replacing it.
+        new: PublicKey,
+    },
+    /// The group's roles were recorded, with this many elders out of this many members.
+    Membership {
+        /// Number of elders.
+        elders: usize,
+        /// Number of members of any role.
+        members: usize,
+    },
+    /// The group resized to this many members.
+    Reconfigured(u64),
+    /// The node with this key was proven to vote for conflicting links and excluded.
+    Accused(PublicKey),
+    /// The data was stored, until `expires_at` if given.
+    DataStored {
+        /// The data stored.
+        data: DataIdentifier,
+        /// Expiry time, if the data expires.
+        expires_at: Option<u64>,
+    },
+    /// The data was deleted.
+    DataDeleted(DataIdentifier),
+    /// An application defined commitment to `hash` under `tag`.
+    Custom {
+        /// The application's tag.
+        tag: u64,
+        /// The committed hash.
+        hash: [u8; 32],
+    },
+    /// `amount` was transferred from one key to another.
+    Transfer {
+        /// The payer.
+        from: PublicKey,
+        /// The payee.
+        to: PublicKey,
+        /// The amount paid.
+        amount: u64,
+    },
+}
+
+impl AuditEvent {
+    /// Snake case name of the event, e.g. `node_gained` or `data_stored`.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            AuditEvent::NodeGained(_) => "node_gained",
+            AuditEvent::NodeLost(_) => "node_lost",
+            AuditEvent::NodeReturned(_) => "node_returned",
+            AuditEvent::Split(_) => "split",
+            AuditEvent::SplitCancelled(_) => "split_cancelled",
+            AuditEvent::Merged(_) => "merged",
+            AuditEvent::CheckPoint(..) => "check_point",
+            AuditEvent::KeyChanged { .. } => "key_changed",
+            AuditEvent::Membership { .. } => "membership",
+            AuditEvent::Reconfigured(_) => "reconfigured",
+            AuditEvent::Accused(_) => "accused",
+            AuditEvent::DataStored { .. } => "data_stored",
+            AuditEvent::DataDeleted(_) => "data_deleted",
+            AuditEvent::Custom { .. } => "custom",
+            AuditEvent::Transfer { .. } => "transfer",
+        }
+    }
+
+    /// What the event happened to: a node's key or data name in lower case hex, prefix bits,
+    /// a committed hash or the payer's key. Empty for `Membership` and `Reconfigured`.
+    pub fn subject(&self) -> String {
+        match *self {
+            AuditEvent::NodeGained(ref key) |
+            AuditEvent::NodeLost(ref key) |
+            AuditEvent::NodeReturned(ref key) |
+            AuditEvent::Accused(ref key) |
+            AuditEvent::KeyChanged { old: ref key, .. } |
+            AuditEvent::Transfer { from: ref key, .. } => key.0.to_hex(),
+            AuditEvent::Split(bits) |
+            AuditEvent::SplitCancelled(bits) |
+            AuditEvent::Merged(bits) |
+            AuditEvent::CheckPoint(bits, _) => bits.to_string(),
+            AuditEvent::DataStored { ref data, .. } |
+            AuditEvent::DataDeleted(ref data) => data.name().to_hex(),
+            AuditEvent::Custom { ref hash, .. } => hash.to_hex(),
+            AuditEvent::Membership { .. } |
+            AuditEvent::Reconfigured(_) => String::new(),
+        }
+    }
+
+    /// Further detail as space separated `name=value` pairs, e.g. `to=<hex> amount=5`. Empty if
+    /// the subject says it all.
+    pub fn detail(&self) -> String {
+        match *self {
+            AuditEvent::CheckPoint(_, members) => format!("members={}", members),
+            AuditEvent::KeyChanged { ref new, .. } => format!("new={}", new.0.to_hex()),
+            AuditEvent::Membership { elders, members } => {
+                format!("elders={} members={}", elders, members)
+            }
+            AuditEvent::Reconfigured(group_size) => format!("group_size={}", group_size),
+            AuditEvent::DataStored { ref data, expires_at } => {
+                let mut detail = data_detail(data);
+                if let Some(expires_at) = expires_at {
+                    let _ = write!(detail, " expires_at={}", expires_at);
+                }
+                detail
+            }
+            AuditEvent::DataDeleted(ref data) => data_detail(data),
+            AuditEvent::Custom { tag, .. } => format!("tag={}", tag),
+            AuditEvent::Transfer { ref to, amount, .. } => {
+                format!("to={} amount={}", to.0.to_hex(), amount)
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// An `AuditEvent` with where and when the chain recorded it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditRecord {
+    /// Position in the chain of the block recording the event.
+    pub position: usize,
+    /// Earliest timestamp among the block's proofs, i.e. when voting on it began.
+    pub time: Option<u64>,
+    /// Position of the valid link the block was agreed under, `None` for the first link.
+    pub link: Option<usize>,
+    /// Number of proofs on the block.
+    pub proofs: usize,
+    /// What happened.
+    pub event: AuditEvent,
+}
+
+impl AuditRecord {
+    /// The record as a row of `DataChain::audit_csv`, without a line ending. No field contains
+    /// a comma or quote, so none is quoted.
+    pub fn to_csv(&self) -> String {
+        format!("{},{},{},{},{},{},{}",
+                self.position,
+                self.time.map_or(String::new(), |x| x.to_string()),
+                self.link.map_or(String::new(), |x| x.to_string()),
+                self.proofs,
+                self.event.kind(),
+                self.event.subject(),
+                self.event.detail())
+    }
+}
+
+impl DataChain {
+    /// The chain's history as high level events, in chain order, which is the order the group
+    /// agreed them.
+    ///
+    /// Only valid blocks are reported, so call `mark_blocks_valid` first for an up to date log.
+    /// A batch gives a `DataStored` per item, all at the batch's position. Each record names the
+    /// valid link before it, as in `mark_blocks_valid`.
+    pub fn audit_log(&self) -> Vec<AuditRecord> {
+        let mut records = Vec::new();
+        let mut link = None;
+        for (position, block) in self.chain().iter().enumerate() {
+            if !block.valid {
+                continue;
+            }
+            for event in events(block.identifier()) {
+                records.push(AuditRecord {
+                    position: position,
+                    time: block.earliest_proof_time(),
+                    link: link,
+                    proofs: block.proofs().len(),
+                    event: event,
+                });
+            }
+            if block.identifier().is_link() {
+                link = Some(position);
+            }
+        }
+        records
+    }
+
+    /// `audit_log` as CSV: `AUDIT_CSV_HEADER` then a row per record, each ending in a newline.
+    pub fn audit_csv(&self) -> String {
+        let mut csv = format!("{}\n", AUDIT_CSV_HEADER);
+        for record in self.audit_log() {
+            csv.push_str(&record.to_csv());
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+// The events a block with this identifier records.
+fn events(identifier: &BlockIdentifier) -> Vec<AuditEvent> {
+    let expires_at = identifier.expires_at();
+    match *identifier.without_expiry() {
+        BlockIdentifier::Link(ref descriptor) => vec![link_event(descriptor)],
+        BlockIdentifier::Deleted(ref data) => vec![AuditEvent::DataDeleted(*data)],
+        BlockIdentifier::Custom { tag, hash } => {
+            vec![AuditEvent::Custom {
+                     tag: tag,
+                     hash: hash,
+                 }]
+        }
+        BlockIdentifier::Transfer { ref from, ref to, amount, .. } => {
+            vec![AuditEvent::Transfer {
+                     from: *from,
+                     to: *to,
+                     amount: amount,
+                 }]
+        }
+        ref data => {
+            data.data_identifiers()
+                .into_iter()
+                .map(|data| {
+                    AuditEvent::DataStored {
+                        data: data,
+                        expires_at: expires_at,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+// The event a valid link with this descriptor records.
+fn link_event(descriptor: &LinkDescriptor) -> AuditEvent {
+    match *descriptor {
+        LinkDescriptor::NodeGained(ref key) => AuditEvent::NodeGained(*key),
+        LinkDescriptor::NodeLost(ref key) => AuditEvent::NodeLost(*key),
+        LinkDescriptor::CancelNodeLost(ref key) => AuditEvent::NodeReturned(*key),
+        LinkDescriptor::SplitFrom(ref prefix) => AuditEvent::Split(prefix.bits()),
+        LinkDescriptor::CancelSplitFrom(ref prefix) => AuditEvent::SplitCancelled(prefix.bits()),
+        LinkDescriptor::MergeTo(ref prefix) => AuditEvent::Merged(prefix.bits()),
+        LinkDescriptor::CheckPoint { ref prefix, ref members, .. } => {
+            AuditEvent::CheckPoint(prefix.bits(), members.len())
+        }
+        LinkDescriptor::KeyChanged { ref old, ref new } => {
+            AuditEvent::KeyChanged {
+                old: *old,
+                new: *new,
+            }
+        }
+        LinkDescriptor::Membership(ref membership) => {
+            AuditEvent::Membership {
+                elders: membership.with_role(Role::Elder).len(),
+                members: membership.members.len(),
+            }
+        }
+        LinkDescriptor::Reconfigure { group_size } => AuditEvent::Reconfigured(group_size),
+        LinkDescriptor::Accusation { ref first, .. } => AuditEvent::Accused(*first.proof().key()),
+    }
+}
+
+// Type and, if any, type tag of the data.
+fn data_detail(data: &DataIdentifier) -> String {
+    let kind = match *data {
+        DataIdentifier::Structured(..) => "structured",
+        DataIdentifier::Immutable(_) => "immutable",
+        DataIdentifier::Appendable(_) => "appendable",
+        DataIdentifier::Mutable(..) => "mutable",
+    };
+    match data.type_tag() {
+        Some(tag) => format!("type={} tag={}", kind, tag),
+        None => format!("type={}", kind),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain::{Block, Vote, test_blocks};
+    use rust_sodium::crypto::sign;
+
+    #[test]
+    fn audit_log() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let mut blocks = test_blocks(3);
+        let name = [7; 32];
+        let batch = BlockIdentifier::Batch(vec![DataIdentifier::Immutable(name),
+                                                DataIdentifier::Mutable(name, 9)],
+                                           [1; 32]);
+        let deleted = BlockIdentifier::Deleted(DataIdentifier::Immutable(name));
+        blocks.push(unwrap!(Block::new(unwrap!(Vote::new(&keys.0, &keys.1, batch)))));
+        let vote = unwrap!(Vote::new_timestamped(&keys.0, &keys.1, deleted, None, 42));
+        blocks.push(unwrap!(Block::new(vote)));
+        for (index, block) in blocks.iter_mut().enumerate() {
+            block.valid = index != 2;
+        }
+        let gained = match *blocks[0].identifier() {
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(key)) => key,
+            _ => panic!("first block should be a link"),
+        };
+        let chain = DataChain::from_blocks(blocks, 4);
+
+        let log = chain.audit_log();
+        let kinds = log.iter().map(|x| (x.position, x.event.kind())).collect::<Vec<_>>();
+        assert_eq!(kinds,
+                   vec![(0, "node_gained"),
+                        (1, "data_stored"),
+                        (3, "data_stored"),
+                        (3, "data_stored"),
+                        (4, "data_deleted")]);
+        assert_eq!(log[0].event, AuditEvent::NodeGained(gained));
+        assert!(log[0].link.is_none());
+        assert!(log[1..].iter().all(|x| x.link == Some(0) && x.proofs == 1));
+        assert_eq!(log[3].event,
+                   AuditEvent::DataStored {
+                       data: DataIdentifier::Mutable(name, 9),
+                       expires_at: None,
+                   });
+        assert_eq!(log[4].time, Some(42));
+        assert!(log[..4].iter().all(|x| x.time.is_none()));
+
+        let csv = chain.audit_csv();
+        let rows = csv.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], AUDIT_CSV_HEADER);
+        assert_eq!(rows[1], format!("0,,,1,node_gained,{},", gained.0.to_hex()));
+        assert_eq!(rows[4],
+                   format!("3,,0,1,data_stored,{},type=mutable tag=9", name.to_hex()));
+        assert!(rows.iter().all(|x| x.split(',').count() == 7));
+    }
+}
@@ -15,21 +15,42 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use chain::block_identifier::BlockIdentifier;
+use chain::block_identifier::{BlockIdentifier, LinkDescriptor, Role};
 use chain::proof::Proof;
+use chain::unix_time;
 use chain::vote::Vote;
-use error::Error;
-use maidsafe_utilities::serialisation;
+use debug_format::{debug_bytes, debug_list};
+use error::{Error, ValidationError};
+use maidsafe_utilities::serialisation::{self, SerialisationError};
+use rust_sodium::crypto::sign::{PublicKey, Signature};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
 
 /// Used to validate chain
 /// Block can be a data item or
 /// a chain link.
 #[allow(missing_docs)]
-#[derive(Debug, RustcEncodable, RustcDecodable, PartialEq, Clone)]
+#[derive(Clone)]
 pub struct Block {
     identifier: BlockIdentifier,
     proofs: Vec<Proof>,
     pub valid: bool,
+    metadata: BlockMetadata,
+    prev_hash: Option<[u8; 32]>,
+}
+
+/// What this node saw of a block's arrival, for debugging where votes come from and how long
+/// blocks take to accumulate. It is neither signed nor persisted, and blocks compare equal
+/// whatever their metadata.
+#[derive(Clone, Default, PartialEq)]
+pub struct BlockMetadata {
+    /// When the first vote for the block was received (seconds since the unix epoch), `None` for
+    /// blocks loaded or merged from elsewhere.
+    pub received: Option<u64>,
+    /// Peers that sent us votes for the block, see `DataChain::add_vote_from`.
+    pub origins: BTreeSet<PublicKey>,
 }
 
 impl Block {
@@ -42,9 +63,26 @@ impl Block {
             identifier: vote.identifier().clone(),
             proofs: vec![vote.proof().clone()],
             valid: false,
+            metadata: BlockMetadata {
+                received: Some(unix_time()),
+                origins: BTreeSet::new(),
+            },
+            prev_hash: None,
         })
     }
 
+    /// Reassemble a block from its parts, as read back from an export. Nothing is checked, so
+    /// revalidate with `DataChain::mark_blocks_valid` before trusting `valid`.
+    pub fn from_parts(identifier: BlockIdentifier, proofs: Vec<Proof>, valid: bool) -> Block {
+        Block {
+            identifier: identifier,
+            proofs: proofs,
+            valid: valid,
+            metadata: BlockMetadata::default(),
+            prev_hash: None,
+        }
+    }
+
     /// Add a proof from a peer
     pub fn add_proof(&mut self, proof: Proof) -> Result<(), Error> {
         if !self.validate_proof(&proof) {
@@ -57,28 +95,121 @@ impl Block {
         Err(Error::Validation)
     }
 
+    /// Add the proofs of `other`, a replica of this block, from keys that have not signed here,
+    /// skipping any that do not verify. Returns the number added, 0 if `other` is a different
+    /// block.
+    pub fn merge_proofs(&mut self, other: &Block) -> usize {
+        if other.identifier != self.identifier {
+            return 0;
+        }
+        other.proofs.iter().filter(|x| self.add_proof((*x).clone()).is_ok()).count()
+    }
+
+    /// Keep only the first `keep` proofs, dropping the rest. Returns the number dropped. Check the
+    /// block still has a quorum afterwards, see `DataChain::trim_proofs`.
+    pub fn trim_proofs(&mut self, keep: usize) -> usize {
+        let len = self.proofs.len();
+        self.proofs.truncate(keep);
+        len - self.proofs.len()
+    }
+
     /// validate signed correctly
     pub fn validate_proof(&self, proof: &Proof) -> bool {
-        match serialisation::serialise(&self.identifier) {
-            Ok(data) => proof.validate(&data[..]),
-            _ => false,
-        }
+        proof.validate_for(&self.identifier)
     }
 
     /// validate signed correctly
     pub fn validate_block_signatures(&self) -> bool {
-        match serialisation::serialise(&self.identifier) {
-            Ok(data) => self.proofs.iter().all(|proof| proof.validate(&data[..])),
-            _ => false,
-        }
+        self.proofs.iter().all(|proof| self.validate_proof(proof))
     }
 
     /// Prune any bad signatures.
     pub fn remove_invalid_signatures(&mut self) {
-        match serialisation::serialise(&self.identifier) {
-            Ok(data) => self.proofs.retain(|proof| proof.validate(&data[..])),
-            _ => self.proofs.clear(),
+        let proofs = mem::take(&mut self.proofs);
+        self.proofs = proofs.into_iter().filter(|proof| self.validate_proof(proof)).collect();
+    }
+
+    /// Earliest timestamp among the proofs, i.e. when voting on this block began.
+    pub fn earliest_proof_time(&self) -> Option<u64> {
+        self.proofs.iter().filter_map(|x| x.timestamp()).min()
+    }
+
+    /// Latest timestamp among the proofs.
+    pub fn latest_proof_time(&self) -> Option<u64> {
+        self.proofs.iter().filter_map(|x| x.timestamp()).max()
+    }
+
+    /// Keys whose signatures count towards quorum for the blocks this one governs: its signers,
+    /// less the key retired if this is a `KeyChanged` link, plus the key replacing it, or plus the
+    /// key reinstated if this is a `CancelNodeLost` link. For a `Membership` link, its elders, for
+    /// a `CheckPoint` link the members it commits to, and for an `Accusation` its signers less the
+    /// offender.
+    pub fn members(&self) -> Vec<PublicKey> {
+        let mut keys = self.proofs.iter().map(|x| *x.key()).collect::<Vec<_>>();
+        match self.identifier.link_descriptor() {
+            Some(LinkDescriptor::CancelNodeLost(back)) => {
+                if !keys.contains(back) {
+                    keys.push(*back);
+                }
+            }
+            Some(LinkDescriptor::KeyChanged { old, new }) => {
+                keys.retain(|x| x != old);
+                if !keys.contains(new) {
+                    keys.push(*new);
+                }
+            }
+            Some(LinkDescriptor::Membership(membership)) => {
+                keys = membership.with_role(Role::Elder);
+            }
+            Some(LinkDescriptor::CheckPoint { members, .. }) => keys = members.clone(),
+            Some(LinkDescriptor::Accusation { first, .. }) => {
+                keys.retain(|x| x != first.proof().key())
+            }
+            _ => (),
+        }
+        keys
+    }
+
+    /// The keys of a link's `members` in slot order, i.e. sorted. Empty for data blocks.
+    pub fn link_keys(&self) -> Vec<PublicKey> {
+        if !self.identifier.is_link() {
+            return vec![];
+        }
+        let mut keys = self.members();
+        keys.sort();
+        keys
+    }
+
+    /// This block's signatures aligned with the slots of `link` (see `link_keys`), `None` where
+    /// that member has not signed. Proofs from keys without a slot are left out.
+    pub fn slots(&self, link: &Block) -> Vec<Option<Signature>> {
+        link.link_keys()
+            .iter()
+            .map(|key| self.proofs.iter().find(|x| x.key() == key).map(|x| *x.sig()))
+            .collect()
+    }
+
+    /// Check every proof fills a slot of `link` with a valid signature, and that a majority of
+    /// the slots are filled. Stricter than the quorum check of `DataChain`, which ignores proofs
+    /// from outside the link.
+    pub fn validate_against_link(&self, link: &Block) -> Result<(), ValidationError> {
+        let keys = link.link_keys();
+        if keys.is_empty() {
+            return Err(ValidationError::MissingLink);
+        }
+        for proof in &self.proofs {
+            if keys.binary_search(proof.key()).is_err() {
+                return Err(ValidationError::UnknownSigner);
+            }
+            if !self.validate_proof(proof) {
+                return Err(ValidationError::BadSignature);
+            }
         }
+        let filled = self.slots(link).iter().filter(|x| x.is_some()).count();
+        if filled * 2 < keys.len() {
+            return Err(ValidationError::NoQuorum);
+        }
+        Ok(())
     }
 
     /// getter
@@ -95,4 +226,251 @@ impl Block {
     pub fn identifier(&self) -> &BlockIdentifier {
         &self.identifier
     }
+
+    /// How this block reached this node.
+    pub fn metadata(&self) -> &BlockMetadata {
+        &self.metadata
+    }
+
+    /// getter
+    pub fn metadata_mut(&mut self) -> &mut BlockMetadata {
+        &mut self.metadata
+    }
+
+    /// Hash of the block before this one in its chain, see `DataChain::block_hash`. Like
+    /// the metadata it is not signed, nor sent with the block, and blocks compare equal whatever
+    /// it is.
+    pub fn prev_hash(&self) -> Option<&[u8; 32]> {
+        self.prev_hash.as_ref()
+    }
+
+    /// Set the hash of the block before this one, as read back from storage.
+    pub fn set_prev_hash(&mut self, prev_hash: Option<[u8; 32]>) {
+        self.prev_hash = prev_hash;
+    }
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other: &Block) -> bool {
+        self.identifier == other.identifier && self.proofs == other.proofs &&
+        self.valid == other.valid
+    }
+}
+
+impl Debug for Block {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "Block {{ identifier: {:?}, proofs: {:?}, valid: {}, metadata: {:?}, prev_hash: \
+                {} }}",
+               self.identifier,
+               self.proofs,
+               self.valid,
+               self.metadata,
+               self.prev_hash.as_ref().map_or("None".to_owned(), debug_bytes))
+    }
+}
+
+impl Debug for BlockMetadata {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "BlockMetadata {{ received: {:?}, origins: {} }}",
+               self.received,
+               debug_list(&self.origins.iter().collect::<Vec<_>>()))
+    }
+}
+
+// Written by hand to leave out the metadata, in the same format the derives gave before it.
+impl Encodable for Block {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        encoder.emit_struct("Block", 3, |encoder| {
+            encoder.emit_struct_field("identifier", 0, |encoder| self.identifier.encode(encoder))?;
+            encoder.emit_struct_field("proofs", 1, |encoder| self.proofs.encode(encoder))?;
+            encoder.emit_struct_field("valid", 2, |encoder| self.valid.encode(encoder))
+        })
+    }
+}
+
+impl Decodable for Block {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<Block, D::Error> {
+        decoder.read_struct("Block", 3, |decoder| {
+            Ok(Block {
+                identifier: decoder.read_struct_field("identifier", 0, Decodable::decode)?,
+                proofs: decoder.read_struct_field("proofs", 1, Decodable::decode)?,
+                valid: decoder.read_struct_field("valid", 2, Decodable::decode)?,
+                metadata: BlockMetadata::default(),
+                prev_hash: None,
+            })
+        })
+    }
+}
+
+/// `Proof` as stored in chain files written before they carried a format version.
+#[derive(RustcDecodable)]
+struct UnversionedProof {
+    key: PublicKey,
+    sig: Signature,
+}
+
+/// `Block` as stored in chain files written before they carried a format version.
+#[derive(RustcDecodable)]
+struct UnversionedBlock {
+    identifier: BlockIdentifier,
+    proofs: Vec<UnversionedProof>,
+    valid: bool,
+}
+
+/// Decode a chain file written before chain files were versioned, a bare `Vec<Block>` whose
+/// proofs carry neither binding nor timestamp.
+pub fn decode_unversioned(bytes: &[u8]) -> Result<Vec<Block>, SerialisationError> {
+    let blocks = serialisation::deserialise::<Vec<UnversionedBlock>>(bytes)?;
+    Ok(blocks.into_iter()
+        .map(|block| {
+            Block {
+                identifier: block.identifier,
+                proofs: block.proofs.into_iter().map(|x| Proof::new(x.key, x.sig)).collect(),
+                valid: block.valid,
+                metadata: BlockMetadata::default(),
+                prev_hash: None,
+            }
+        })
+        .collect())
+}
+
+/// The distinct keys signing a chain's proofs, each stored once in a chain file and referred to
+/// by its index from the `KeyedBlock`s.
+#[derive(Default)]
+pub struct KeyTable {
+    keys: Vec<PublicKey>,
+    indices: BTreeMap<PublicKey, u32>,
+}
+
+impl KeyTable {
+    /// Table of the keys in `blocks`, in order of first use.
+    pub fn new(blocks: &[Block]) -> KeyTable {
+        let mut table = KeyTable::default();
+        for proof in blocks.iter().flat_map(|x| x.proofs()) {
+            if !table.indices.contains_key(proof.key()) {
+                let _ = table.indices.insert(*proof.key(), table.keys.len() as u32);
+                table.keys.push(*proof.key());
+            }
+        }
+        table
+    }
+
+    /// The keys, indexed as in the `KeyedBlock`s.
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    /// `block` with its keys replaced by their indices, or `None` if one is not in the table.
+    pub fn intern(&self, block: &Block) -> Option<KeyedBlock> {
+        let mut proofs = vec![];
+        for proof in block.proofs() {
+            proofs.push(KeyedProof {
+                key: *self.indices.get(proof.key())?,
+                sig: *proof.sig(),
+                binding: proof.binding().cloned(),
+                timestamp: proof.timestamp(),
+            });
+        }
+        Some(KeyedBlock {
+            identifier: block.identifier.clone(),
+            proofs: proofs,
+            valid: block.valid,
+            prev_hash: block.prev_hash,
+        })
+    }
+}
+
+/// `Proof` as stored in chain files since version 5, its key an index into the `KeyTable`.
+#[derive(RustcEncodable, RustcDecodable)]
+struct KeyedProof {
+    key: u32,
+    sig: Signature,
+    binding: Option<[u8; 32]>,
+    timestamp: Option<u64>,
+}
+
+/// `Block` as stored in chain files since version 6, see `KeyTable`.
+#[derive(RustcEncodable, RustcDecodable)]
+pub struct KeyedBlock {
+    identifier: BlockIdentifier,
+    proofs: Vec<KeyedProof>,
+    valid: bool,
+    prev_hash: Option<[u8; 32]>,
+}
+
+/// `KeyedBlock` as stored in version 5 chain files, without `Block::prev_hash`.
+#[derive(RustcDecodable)]
+pub struct UnlinkedKeyedBlock {
+    identifier: BlockIdentifier,
+    proofs: Vec<KeyedProof>,
+    valid: bool,
+}
+
+impl From<UnlinkedKeyedBlock> for KeyedBlock {
+    fn from(block: UnlinkedKeyedBlock) -> KeyedBlock {
+        KeyedBlock {
+            identifier: block.identifier,
+            proofs: block.proofs,
+            valid: block.valid,
+            prev_hash: None,
+        }
+    }
+}
+
+impl KeyedBlock {
+    /// The block with the keys looked up in `keys`, or `None` if an index is out of range.
+    pub fn resolve(self, keys: &[PublicKey]) -> Option<Block> {
+        let mut proofs = vec![];
+        for proof in self.proofs {
+            proofs.push(Proof::with_context(*keys.get(proof.key as usize)?,
+                                            proof.sig,
+                                            proof.binding,
+                                            proof.timestamp));
+        }
+        let mut block = Block::from_parts(self.identifier, proofs, self.valid);
+        block.prev_hash = self.prev_hash;
+        Some(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sodium::crypto::sign::{self, SecretKey};
+    use sha3::hash;
+
+    fn proof(keys: &(PublicKey, SecretKey), identifier: &BlockIdentifier) -> Proof {
+        unwrap!(Vote::new(&keys.0, &keys.1, identifier.clone())).proof().clone()
+    }
+
+    #[test]
+    fn validate_slots_against_link() {
+        let _ = ::rust_sodium::init();
+        let keys = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let stranger = sign::gen_keypair();
+        let link_id = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[0].0));
+        let mut link = Block::from_parts(link_id.clone(), vec![], true);
+        for key in &keys {
+            unwrap!(link.add_proof(proof(key, &link_id)));
+        }
+        let mut sorted = keys.iter().map(|x| x.0).collect::<Vec<_>>();
+        sorted.sort();
+        assert_eq!(link.link_keys(), sorted);
+
+        let data_id = BlockIdentifier::ImmutableData(hash(b"1"));
+        let mut block = Block::from_parts(data_id.clone(), vec![proof(&keys[1], &data_id)], false);
+        assert_eq!(block.slots(&link).iter().filter(|x| x.is_some()).count(), 1);
+        assert_eq!(block.validate_against_link(&link), Err(ValidationError::NoQuorum));
+        assert_eq!(block.validate_against_link(&block), Err(ValidationError::MissingLink));
+
+        unwrap!(block.add_proof(proof(&keys[2], &data_id)));
+        assert_eq!(block.validate_against_link(&link), Ok(()));
+        let slot = unwrap!(sorted.iter().position(|x| *x == keys[2].0));
+        assert!(block.slots(&link)[slot].is_some());
+
+        unwrap!(block.add_proof(proof(&stranger, &data_id)));
+        assert_eq!(block.validate_against_link(&link), Err(ValidationError::UnknownSigner));
+    }
 }
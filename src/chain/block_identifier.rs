@@ -15,35 +15,218 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use super::debug_bytes;
+use super::{put_bytes, put_u32, put_u64};
+use chain::vote::Vote;
 use data::DataIdentifier;
+use debug_format::debug_bytes;
+use error::Error;
 use rust_sodium::crypto::sign::PublicKey;
+use std::cmp;
 use std::fmt::{self, Debug, Formatter};
 
-/// TODO Use real prefix
-#[derive(RustcEncodable, RustcDecodable, PartialEq, Clone)]
+/// The leading bits of the names a section covers, up to 63 of them. They are stored below a
+/// marker bit, so `Prefix::new(1)` (like `Prefix::new(0)`) is the empty prefix covering every
+/// name, and `Prefix::new(0b10)` and `Prefix::new(0b11)` are its two halves.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Clone, Debug)]
 pub struct Prefix(u64);
 
+impl Prefix {
+    /// Prefix with the given bits.
+    pub fn new(bits: u64) -> Prefix {
+        Prefix(bits)
+    }
+
+    /// The bits of this prefix.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    /// Number of bits of a name this prefix fixes.
+    pub fn len(&self) -> usize {
+        if self.0 == 0 {
+            0
+        } else {
+            63 - self.0.leading_zeros() as usize
+        }
+    }
+
+    /// Whether this prefix covers every name.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The half of this prefix whose next bit is `bit`.
+    pub fn pushed(&self, bit: bool) -> Prefix {
+        Prefix((cmp::max(self.0, 1) << 1) | u64::from(bit))
+    }
+
+    /// Whether `name` starts with the bits of this prefix.
+    pub fn matches(&self, name: &[u8; 32]) -> bool {
+        let len = self.len();
+        (0..len).all(|i| {
+            let bit = (self.0 >> (len - 1 - i)) & 1;
+            let name_bit = u64::from(name[i / 8] >> (7 - i % 8)) & 1;
+            bit == name_bit
+        })
+    }
+}
+
+/// Part a member plays in its group.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Role {
+    /// Votes, and only elders' signatures count towards quorum.
+    Elder,
+    /// Holds data but does not vote.
+    Adult,
+    /// Newly joined, neither votes nor holds data yet.
+    Infant,
+}
+
+/// The members of a group and their roles, recorded by a `LinkDescriptor::Membership` link.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Clone, Debug)]
+pub struct Membership {
+    /// Each member's key and role.
+    pub members: Vec<(PublicKey, Role)>,
+}
+
+impl Membership {
+    /// Keys of the members with `role`.
+    pub fn with_role(&self, role: Role) -> Vec<PublicKey> {
+        self.members.iter().filter(|x| x.1 == role).map(|x| x.0).collect()
+    }
+}
+
 /// What caused group to change?
 #[derive(RustcEncodable, RustcDecodable, PartialEq, Clone)]
 pub enum LinkDescriptor {
+    /// The node with this key left the group.
     NodeLost(PublicKey),
+    /// Revokes an earlier `NodeLost` for this key.
     CancelNodeLost(PublicKey),
+    /// The node with this key joined the group.
     NodeGained(PublicKey),
+    /// The group split from a section with this prefix.
     SplitFrom(Prefix),
+    /// Revokes an earlier `SplitFrom`.
     CancelSplitFrom(Prefix),
+    /// The group merged into the section with this prefix.
     MergeTo(Prefix),
-    CheckPoint(Prefix),
+    /// A checkpoint of the group for `prefix`, committing to its voting `members` and to the
+    /// `DataChain::checkpoint_state` when voted. Blocks it governs need a quorum of `members`, so
+    /// a chain can be validated from it without the links before, see
+    /// `DataChain::from_checkpoint`.
+    CheckPoint {
+        /// The prefix of the group.
+        prefix: Prefix,
+        /// The voting members, sorted.
+        members: Vec<PublicKey>,
+        /// Hash of the valid links before this one.
+        state: [u8; 32],
+    },
+    /// A member rotated its signing key. Once this link is valid signatures by `new` count in
+    /// place of those by `old`, and votes signed by `old` are refused.
+    KeyChanged {
+        /// The retired key.
+        old: PublicKey,
+        /// The key replacing it.
+        new: PublicKey,
+    },
+    /// The group's members by role. Blocks this link governs need a quorum of its elders, and
+    /// from then on only elders may vote, see `DataChain::current_elders`.
+    Membership(Membership),
+    /// The group resized. Once this link is valid, quorums of the blocks after it are counted
+    /// against `group_size` rather than the size the chain was created with. It needs a majority
+    /// of the group before it, see `DataChain::current_group_size`.
+    Reconfigure {
+        /// The new group size, at least 1.
+        group_size: u64,
+    },
+    /// Two votes signed by one key for conflicting links, see `LinkDescriptor::accusation`. Once
+    /// this link is valid the key no longer counts towards quorum and its votes are refused.
+    Accusation {
+        /// The earlier vote, by its signable bytes.
+        first: Box<Vote>,
+        /// The later vote.
+        second: Box<Vote>,
+    },
 }
 
 impl LinkDescriptor {
+    /// The key of the node gained, lost or accused, if any.
     pub fn name(&self) -> Option<&[u8; 32]> {
         match *self {
             LinkDescriptor::NodeLost(ref h) |
             LinkDescriptor::NodeGained(ref h) => Some(&h.0),
+            LinkDescriptor::Accusation { .. } => self.offender().map(|x| &x.0),
             _ => None,
         }
     }
+
+    /// An `Accusation` of the key signing both votes, which must hold: see `evidence_holds`. The
+    /// votes are put in canonical order, so every member accusing the key of the same pair votes
+    /// for the same link. Fails with `Error::BadIdentifier` otherwise.
+    pub fn accusation(first: Vote, second: Vote) -> Result<LinkDescriptor, Error> {
+        let (first, second) = if first.identifier().to_signable_bytes() <=
+                                 second.identifier().to_signable_bytes() {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let accusation = LinkDescriptor::Accusation {
+            first: Box::new(first),
+            second: Box::new(second),
+        };
+        if accusation.evidence_holds() {
+            Ok(accusation)
+        } else {
+            Err(Error::BadIdentifier)
+        }
+    }
+
+    /// The key an `Accusation` is against.
+    pub fn offender(&self) -> Option<&PublicKey> {
+        match *self {
+            LinkDescriptor::Accusation { ref first, .. } => Some(first.proof().key()),
+            _ => None,
+        }
+    }
+
+    /// Whether this is an `Accusation` proving its offender signed votes for two conflicting
+    /// links: both votes verify, are signed by the same key and their links `conflicts_with` each
+    /// other. Always `true` for other links.
+    pub fn evidence_holds(&self) -> bool {
+        match *self {
+            LinkDescriptor::Accusation { ref first, ref second } => {
+                first.proof().key() == second.proof().key() && first.validate() &&
+                second.validate() &&
+                match (first.identifier().link_descriptor(),
+                       second.identifier().link_descriptor()) {
+                    (Some(a), Some(b)) => a.conflicts_with(b),
+                    _ => false,
+                }
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether a group could not agree to both this and `other` from the same state: different
+    /// replacements for one key, splits to different prefixes, different checkpoints of one
+    /// prefix, or a different `Membership` or `Reconfigure`.
+    pub fn conflicts_with(&self, other: &LinkDescriptor) -> bool {
+        if self == other {
+            return false;
+        }
+        match (self, other) {
+            (LinkDescriptor::KeyChanged { old: a, .. },
+             LinkDescriptor::KeyChanged { old: b, .. }) => a == b,
+            (LinkDescriptor::CheckPoint { prefix: a, .. },
+             LinkDescriptor::CheckPoint { prefix: b, .. }) => a == b,
+            (&LinkDescriptor::SplitFrom(_), &LinkDescriptor::SplitFrom(_)) |
+            (&LinkDescriptor::Membership(_), &LinkDescriptor::Membership(_)) |
+            (&LinkDescriptor::Reconfigure { .. }, &LinkDescriptor::Reconfigure { .. }) => true,
+            _ => false,
+        }
+    }
 }
 /// Data identifiers for use in a data Chain.
 /// The hash of each data type is available to ensure there is no confusion
@@ -55,8 +238,38 @@ pub enum BlockIdentifier {
     ImmutableData([u8; 32]),
     ///           hash   name (identity + tag) (stored localy as name in data store)
     StructuredData([u8; 32], DataIdentifier),
+    ///           hash   name, voted for on every append and owner update
+    AppendableData([u8; 32], DataIdentifier),
+    ///           hash of one entry's key, value and version   name
+    MutableDataEntry([u8; 32], DataIdentifier),
+    ///     names of the items   hash of them serialised together, all valid or none
+    Batch(Vec<DataIdentifier>, [u8; 32]),
+    ///       name of the data whose deletion was agreed
+    Deleted(DataIdentifier),
     /// Hash of group members' public keys (see `LinkDescriptor`).
     Link(LinkDescriptor),
+    ///        data block   unix time (seconds) from which it is no longer valid
+    Expiring(Box<BlockIdentifier>, u64),
+    /// A commitment defined by an application, anchored to the group's consensus like data but
+    /// with nothing stored for it, see `DataChain::set_custom_validator`.
+    Custom {
+        /// What kind of record, chosen by the application.
+        tag: u64,
+        /// Hash of the record.
+        hash: [u8; 32],
+    },
+    /// A payment of `amount` from the owner of `from` to `to`, valid once signed by `from` as well
+    /// as a quorum of the group, see `DataChain::balance_of`.
+    Transfer {
+        /// The paying key.
+        from: PublicKey,
+        /// The paid key.
+        to: PublicKey,
+        /// How much is paid.
+        amount: u64,
+        /// Hash of the payer's record of the payment, so equal payments differ.
+        hash: [u8; 32],
+    },
 }
 
 impl BlockIdentifier {
@@ -71,12 +284,94 @@ impl BlockIdentifier {
     //         BlockIdentifier::Link(ref link) => link.hash(),
     //     }
     // }
+    /// Wrap the data block `identifier` so it expires at `expires_at`, in seconds since the unix
+    /// epoch. The expiry is part of the identifier, so group members sign it along with the data.
+    /// Links, tombstones and blocks already expiring are refused.
+    pub fn expiring(identifier: BlockIdentifier,
+                    expires_at: u64)
+                    -> Result<BlockIdentifier, Error> {
+        match identifier {
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Link(_) |
+            BlockIdentifier::Expiring(..) => Err(Error::BadIdentifier),
+            identifier => Ok(BlockIdentifier::Expiring(Box::new(identifier), expires_at)),
+        }
+    }
+
+    /// When this block stops being valid, if it expires at all.
+    pub fn expires_at(&self) -> Option<u64> {
+        match *self {
+            BlockIdentifier::Expiring(_, expires_at) => Some(expires_at),
+            _ => None,
+        }
+    }
+
+    /// The data block an `Expiring` identifier wraps, or this identifier itself.
+    pub fn without_expiry(&self) -> &BlockIdentifier {
+        match *self {
+            BlockIdentifier::Expiring(ref identifier, _) => identifier,
+            _ => self,
+        }
+    }
+
+    /// The bytes group members sign for this identifier, see `Vote`. The encoding is fixed here,
+    /// apart from whatever serialiser stores and sends chains, so changing that cannot invalidate
+    /// signatures already given. It is the one votes were first signed with:
+    ///
+    /// - integers are big endian, lengths `u64`, and enum variants their `u32` index in
+    ///   declaration order followed by their fields in order
+    /// - names, hashes and keys are their length followed by their bytes, lists their length
+    ///   followed by their items
+    /// - `Expiring` ends with its `u64` expiry, `Prefix` is its `u64` bits and `Role` its index
+    /// - options are a `u8` of 0, or 1 followed by the value, and each `Vote` of an `Accusation`
+    ///   is its identifier then its proof's key, signature, timestamp and binding
+    ///
+    /// So `ImmutableData([1; 32])` is `00000000`, `0000000000000020` and 32 `01` bytes.
+    pub fn to_signable_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        put_identifier(&mut bytes, self);
+        bytes
+    }
+
     /// structured data name != hash of the data or block
     pub fn name(&self) -> Option<&[u8; 32]> {
         match *self {
             BlockIdentifier::ImmutableData(ref hash) => Some(hash),
-            BlockIdentifier::StructuredData(_hash, ref id) => Some(id.name()),
+            BlockIdentifier::StructuredData(_hash, ref id) |
+            BlockIdentifier::AppendableData(_hash, ref id) |
+            BlockIdentifier::MutableDataEntry(_hash, ref id) => Some(id.name()),
+            BlockIdentifier::Batch(..) |
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Custom { .. } |
+            BlockIdentifier::Transfer { .. } => None,
             BlockIdentifier::Link(ref link) => link.name(),
+            BlockIdentifier::Expiring(ref identifier, _) => identifier.name(),
+        }
+    }
+
+    /// Names of the data this block commits to: the `name` or, for a batch, every item's name.
+    pub fn names(&self) -> Vec<&[u8; 32]> {
+        match *self {
+            BlockIdentifier::Batch(ref items, _) => items.iter().map(|x| x.name()).collect(),
+            BlockIdentifier::Expiring(ref identifier, _) => identifier.names(),
+            _ => self.name().into_iter().collect(),
+        }
+    }
+
+    /// Identifiers of the data this block commits to: one for a data block and every item's for a
+    /// batch. Empty for links and `Deleted` tombstones.
+    pub fn data_identifiers(&self) -> Vec<DataIdentifier> {
+        match *self {
+            BlockIdentifier::ImmutableData(ref hash) => vec![DataIdentifier::Immutable(*hash)],
+            BlockIdentifier::StructuredData(_, ref id) |
+            BlockIdentifier::AppendableData(_, ref id) |
+            BlockIdentifier::MutableDataEntry(_, ref id) => vec![*id],
+            BlockIdentifier::Batch(ref items, _) => items.clone(),
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Link(_) |
+            BlockIdentifier::Custom { .. } |
+            BlockIdentifier::Transfer { .. } => vec![],
+            BlockIdentifier::Expiring(ref identifier, _) => identifier.data_identifiers(),
         }
     }
 
@@ -84,7 +379,14 @@ impl BlockIdentifier {
     pub fn link_descriptor(&self) -> Option<&LinkDescriptor> {
         match *self {
             BlockIdentifier::ImmutableData(_) |
-            BlockIdentifier::StructuredData(..) => None,
+            BlockIdentifier::StructuredData(..) |
+            BlockIdentifier::AppendableData(..) |
+            BlockIdentifier::MutableDataEntry(..) |
+            BlockIdentifier::Batch(..) |
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Expiring(..) |
+            BlockIdentifier::Custom { .. } |
+            BlockIdentifier::Transfer { .. } => None,
             BlockIdentifier::Link(ref link) => Some(link),
         }
     }
@@ -93,7 +395,14 @@ impl BlockIdentifier {
     pub fn is_link(&self) -> bool {
         match *self {
             BlockIdentifier::ImmutableData(_) |
-            BlockIdentifier::StructuredData(_, _) => false,
+            BlockIdentifier::StructuredData(_, _) |
+            BlockIdentifier::AppendableData(_, _) |
+            BlockIdentifier::MutableDataEntry(_, _) |
+            BlockIdentifier::Batch(_, _) |
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Expiring(..) |
+            BlockIdentifier::Custom { .. } |
+            BlockIdentifier::Transfer { .. } => false,
             BlockIdentifier::Link(_) => true,
         }
     }
@@ -102,7 +411,14 @@ impl BlockIdentifier {
     pub fn is_block(&self) -> bool {
         match *self {
             BlockIdentifier::ImmutableData(_) |
-            BlockIdentifier::StructuredData(_, _) => true,
+            BlockIdentifier::StructuredData(_, _) |
+            BlockIdentifier::AppendableData(_, _) |
+            BlockIdentifier::MutableDataEntry(_, _) |
+            BlockIdentifier::Batch(_, _) |
+            BlockIdentifier::Deleted(_) |
+            BlockIdentifier::Expiring(..) |
+            BlockIdentifier::Custom { .. } |
+            BlockIdentifier::Transfer { .. } => true,
             BlockIdentifier::Link(_) => false,
         }
     }
@@ -120,6 +436,39 @@ impl Debug for BlockIdentifier {
                        debug_bytes(hash),
                        name)
             }
+            BlockIdentifier::AppendableData(ref hash, ref name) => {
+                write!(formatter,
+                       "AppendableData(hash: {}, name: {:?})",
+                       debug_bytes(hash),
+                       name)
+            }
+            BlockIdentifier::MutableDataEntry(ref hash, ref name) => {
+                write!(formatter,
+                       "MutableDataEntry(hash: {}, name: {:?})",
+                       debug_bytes(hash),
+                       name)
+            }
+            BlockIdentifier::Batch(ref items, ref hash) => {
+                write!(formatter,
+                       "Batch(hash: {}, items: {:?})",
+                       debug_bytes(hash),
+                       items)
+            }
+            BlockIdentifier::Deleted(ref name) => write!(formatter, "Deleted({:?})", name),
+            BlockIdentifier::Expiring(ref identifier, expires_at) => {
+                write!(formatter, "Expiring({:?}, at: {})", identifier, expires_at)
+            }
+            BlockIdentifier::Custom { tag, ref hash } => {
+                write!(formatter, "Custom(tag: {}, hash: {})", tag, debug_bytes(hash))
+            }
+            BlockIdentifier::Transfer { ref from, ref to, amount, ref hash } => {
+                write!(formatter,
+                       "Transfer({} from {} to {}, hash: {})",
+                       amount,
+                       debug_bytes(from),
+                       debug_bytes(to),
+                       debug_bytes(hash))
+            }
             BlockIdentifier::Link(ref descriptor) => {
                 match *descriptor {
                     LinkDescriptor::NodeLost(ref h) => {
@@ -128,6 +477,34 @@ impl Debug for BlockIdentifier {
                     LinkDescriptor::NodeGained(ref h) => {
                         write!(formatter, "NodeGained Link({})", debug_bytes(h))
                     }
+                    LinkDescriptor::KeyChanged { ref old, ref new } => {
+                        write!(formatter,
+                               "KeyChanged Link({} -> {})",
+                               debug_bytes(old),
+                               debug_bytes(new))
+                    }
+                    LinkDescriptor::CheckPoint { ref members, ref state, .. } => {
+                        write!(formatter,
+                               "CheckPoint Link({} members, state: {})",
+                               members.len(),
+                               debug_bytes(state))
+                    }
+                    LinkDescriptor::Membership(ref membership) => {
+                        write!(formatter,
+                               "Membership Link({} elders of {})",
+                               membership.with_role(Role::Elder).len(),
+                               membership.members.len())
+                    }
+                    LinkDescriptor::Reconfigure { group_size } => {
+                        write!(formatter, "Reconfigure Link(group size {})", group_size)
+                    }
+                    LinkDescriptor::Accusation { ref first, ref second } => {
+                        write!(formatter,
+                               "Accusation Link({}: {:?} and {:?})",
+                               debug_bytes(first.proof().key()),
+                               first.identifier(),
+                               second.identifier())
+                    }
                     _ => write!(formatter, "TBD"),
                 }
             }
@@ -135,16 +512,173 @@ impl Debug for BlockIdentifier {
     }
 }
 
+fn put_identifier(bytes: &mut Vec<u8>, identifier: &BlockIdentifier) {
+    match *identifier {
+        BlockIdentifier::ImmutableData(ref hash) => {
+            put_u32(bytes, 0);
+            put_bytes(bytes, hash);
+        }
+        BlockIdentifier::StructuredData(ref hash, ref id) => {
+            put_u32(bytes, 1);
+            put_bytes(bytes, hash);
+            put_data_identifier(bytes, id);
+        }
+        BlockIdentifier::AppendableData(ref hash, ref id) => {
+            put_u32(bytes, 2);
+            put_bytes(bytes, hash);
+            put_data_identifier(bytes, id);
+        }
+        BlockIdentifier::MutableDataEntry(ref hash, ref id) => {
+            put_u32(bytes, 3);
+            put_bytes(bytes, hash);
+            put_data_identifier(bytes, id);
+        }
+        BlockIdentifier::Batch(ref items, ref hash) => {
+            put_u32(bytes, 4);
+            put_u64(bytes, items.len() as u64);
+            for item in items {
+                put_data_identifier(bytes, item);
+            }
+            put_bytes(bytes, hash);
+        }
+        BlockIdentifier::Deleted(ref id) => {
+            put_u32(bytes, 5);
+            put_data_identifier(bytes, id);
+        }
+        BlockIdentifier::Link(ref descriptor) => {
+            put_u32(bytes, 6);
+            put_descriptor(bytes, descriptor);
+        }
+        BlockIdentifier::Expiring(ref identifier, expires_at) => {
+            put_u32(bytes, 7);
+            put_identifier(bytes, identifier);
+            put_u64(bytes, expires_at);
+        }
+        BlockIdentifier::Custom { tag, ref hash } => {
+            put_u32(bytes, 8);
+            put_u64(bytes, tag);
+            put_bytes(bytes, hash);
+        }
+        BlockIdentifier::Transfer { ref from, ref to, amount, ref hash } => {
+            put_u32(bytes, 9);
+            put_bytes(bytes, &from.0);
+            put_bytes(bytes, &to.0);
+            put_u64(bytes, amount);
+            put_bytes(bytes, hash);
+        }
+    }
+}
+
+fn put_data_identifier(bytes: &mut Vec<u8>, id: &DataIdentifier) {
+    let (variant, tag) = match *id {
+        DataIdentifier::Structured(_, tag) => (0, Some(tag)),
+        DataIdentifier::Immutable(_) => (1, None),
+        DataIdentifier::Appendable(_) => (2, None),
+        DataIdentifier::Mutable(_, tag) => (3, Some(tag)),
+    };
+    put_u32(bytes, variant);
+    put_bytes(bytes, id.name());
+    if let Some(tag) = tag {
+        put_u64(bytes, tag);
+    }
+}
+
+fn put_descriptor(bytes: &mut Vec<u8>, descriptor: &LinkDescriptor) {
+    match *descriptor {
+        LinkDescriptor::NodeLost(ref key) => {
+            put_u32(bytes, 0);
+            put_bytes(bytes, &key.0);
+        }
+        LinkDescriptor::CancelNodeLost(ref key) => {
+            put_u32(bytes, 1);
+            put_bytes(bytes, &key.0);
+        }
+        LinkDescriptor::NodeGained(ref key) => {
+            put_u32(bytes, 2);
+            put_bytes(bytes, &key.0);
+        }
+        LinkDescriptor::SplitFrom(ref prefix) => {
+            put_u32(bytes, 3);
+            put_u64(bytes, prefix.bits());
+        }
+        LinkDescriptor::CancelSplitFrom(ref prefix) => {
+            put_u32(bytes, 4);
+            put_u64(bytes, prefix.bits());
+        }
+        LinkDescriptor::MergeTo(ref prefix) => {
+            put_u32(bytes, 5);
+            put_u64(bytes, prefix.bits());
+        }
+        LinkDescriptor::CheckPoint { ref prefix, ref members, ref state } => {
+            put_u32(bytes, 6);
+            put_u64(bytes, prefix.bits());
+            put_u64(bytes, members.len() as u64);
+            for key in members {
+                put_bytes(bytes, &key.0);
+            }
+            put_bytes(bytes, state);
+        }
+        LinkDescriptor::KeyChanged { ref old, ref new } => {
+            put_u32(bytes, 7);
+            put_bytes(bytes, &old.0);
+            put_bytes(bytes, &new.0);
+        }
+        LinkDescriptor::Membership(ref membership) => {
+            put_u32(bytes, 8);
+            put_u64(bytes, membership.members.len() as u64);
+            for &(ref key, role) in &membership.members {
+                put_bytes(bytes, &key.0);
+                put_u32(bytes, role as u32);
+            }
+        }
+        LinkDescriptor::Reconfigure { group_size } => {
+            put_u32(bytes, 9);
+            put_u64(bytes, group_size);
+        }
+        LinkDescriptor::Accusation { ref first, ref second } => {
+            put_u32(bytes, 10);
+            put_vote(bytes, first);
+            put_vote(bytes, second);
+        }
+    }
+}
+
+// A vote as its identifier then its proof's key, signature, timestamp and binding.
+fn put_vote(bytes: &mut Vec<u8>, vote: &Vote) {
+    let proof = vote.proof();
+    put_identifier(bytes, vote.identifier());
+    put_bytes(bytes, &proof.key().0);
+    put_bytes(bytes, &proof.sig().0);
+    match proof.timestamp() {
+        Some(timestamp) => {
+            bytes.push(1);
+            put_u64(bytes, timestamp);
+        }
+        None => bytes.push(0),
+    }
+    match proof.binding() {
+        Some(binding) => {
+            bytes.push(1);
+            put_bytes(bytes, binding);
+        }
+        None => bytes.push(0),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chain::proof::Proof;
+    use chain::signed_payload;
     use data::DataIdentifier;
+    use maidsafe_utilities::serialisation;
+    use rustc_serialize::hex::ToHex;
     use rust_sodium::crypto;
     use sha3::hash;
 
     #[test]
     fn create_validate_link_identifier() {
-        ::rust_sodium::init();
+        let _ = ::rust_sodium::init();
         let keys = crypto::sign::gen_keypair();
         let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
 
@@ -162,6 +696,20 @@ mod tests {
         assert!(id_block.name().is_some());
     }
 
+    #[test]
+    fn prefix_matches_leading_bits() {
+        let root = Prefix::new(1);
+        let (zero, one) = (root.pushed(false), root.pushed(true));
+        assert!(root.is_empty() && Prefix::new(0).is_empty());
+        assert_eq!((zero.bits(), one.bits()), (0b10, 0b11));
+        assert_eq!(one.pushed(false).len(), 2);
+        assert!(root.matches(&[0xff; 32]));
+        assert!(zero.matches(&[0x7f; 32]) && !zero.matches(&[0x80; 32]));
+        assert!(one.matches(&[0x80; 32]) && !one.matches(&[0x7f; 32]));
+        assert!(one.pushed(false).matches(&[0xbf; 32]));
+        assert!(!one.pushed(false).matches(&[0xc0; 32]));
+    }
+
     #[test]
     fn create_validate_structured_data_identifier() {
         let sd_block = BlockIdentifier::StructuredData(hash(b"name"),
@@ -174,4 +722,120 @@ mod tests {
         assert!(sd_block.name().is_some());
         assert_eq!(*sd_block.name().expect("sd name"), hash(b"name"))
     }
+
+    #[test]
+    fn signable_bytes() {
+        let _ = ::rust_sodium::init();
+        let (key, secret) = crypto::sign::gen_keypair();
+        let id = DataIdentifier::Mutable([2; 32], 3);
+        let grow = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 7 });
+        let shrink = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 3 });
+        let second = unwrap!(Vote::new_timestamped(&key, &secret, shrink, Some([7; 32]), 8));
+        let accusation = LinkDescriptor::Accusation {
+            first: Box::new(unwrap!(Vote::new(&key, &secret, grow.clone()))),
+            second: Box::new(second),
+        };
+        let mut identifiers =
+            vec![BlockIdentifier::ImmutableData([1; 32]),
+                 BlockIdentifier::StructuredData([1; 32], DataIdentifier::Structured([2; 32], 3)),
+                 BlockIdentifier::AppendableData([1; 32], DataIdentifier::Appendable([2; 32])),
+                 BlockIdentifier::MutableDataEntry([1; 32], id),
+                 BlockIdentifier::Batch(vec![id, DataIdentifier::Immutable([4; 32])], [1; 32]),
+                 BlockIdentifier::Deleted(id),
+                 unwrap!(BlockIdentifier::expiring(BlockIdentifier::ImmutableData([1; 32]), 9)),
+                 BlockIdentifier::Custom {
+                     tag: 5,
+                     hash: [1; 32],
+                 },
+                 BlockIdentifier::Transfer {
+                     from: key,
+                     to: key,
+                     amount: 5,
+                     hash: [1; 32],
+                 },
+                 BlockIdentifier::Link(LinkDescriptor::NodeLost(key)),
+                 BlockIdentifier::Link(LinkDescriptor::CancelNodeLost(key)),
+                 BlockIdentifier::Link(LinkDescriptor::NodeGained(key)),
+                 BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(5))),
+                 BlockIdentifier::Link(LinkDescriptor::CancelSplitFrom(Prefix::new(5))),
+                 BlockIdentifier::Link(LinkDescriptor::MergeTo(Prefix::new(5))),
+                 BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+                     prefix: Prefix::new(5),
+                     members: vec![key, key],
+                     state: [6; 32],
+                 }),
+                 BlockIdentifier::Link(LinkDescriptor::KeyChanged { old: key, new: key }),
+                 BlockIdentifier::Link(LinkDescriptor::Membership(Membership {
+                     members: vec![(key, Role::Elder), (key, Role::Infant)],
+                 })),
+                 grow];
+        identifiers.push(BlockIdentifier::Link(accusation));
+        // Signatures given before the encoding was fixed must still verify.
+        for identifier in &identifiers {
+            assert_eq!(identifier.to_signable_bytes(),
+                       unwrap!(serialisation::serialise(identifier)));
+            let bound = (identifier, Some([7u8; 32]), None::<u64>);
+            assert_eq!(signed_payload(identifier, Some(&[7; 32]), None),
+                       unwrap!(serialisation::serialise(&bound)));
+            let timestamped = (identifier, None::<[u8; 32]>, Some(8u64));
+            assert_eq!(signed_payload(identifier, None, Some(8)),
+                       unwrap!(serialisation::serialise(&timestamped)));
+        }
+
+        let expected = format!("00000000{}{}", "0000000000000020", "01".repeat(32));
+        assert_eq!(identifiers[0].to_signable_bytes().to_hex(), expected);
+        let expected = format!("00000007{}{}", expected, "0000000000000009");
+        assert_eq!(identifiers[6].to_signable_bytes().to_hex(), expected);
+        let expected = format!("00000005000000030000000000000020{}0000000000000003",
+                               "02".repeat(32));
+        assert_eq!(identifiers[5].to_signable_bytes().to_hex(), expected);
+    }
+
+    #[test]
+    fn expiring_identifier() {
+        let id_block = BlockIdentifier::ImmutableData(hash(b"1"));
+        let expiring = unwrap!(BlockIdentifier::expiring(id_block.clone(), 10));
+        assert!(expiring.is_block() && !expiring.is_link());
+        assert_eq!(expiring.expires_at(), Some(10));
+        assert_eq!(id_block.expires_at(), None);
+        assert_eq!(*expiring.without_expiry(), id_block);
+        assert_eq!(expiring.name(), id_block.name());
+        assert_eq!(expiring.data_identifiers(), id_block.data_identifiers());
+        assert!(BlockIdentifier::expiring(expiring, 20).is_err());
+        let link = BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(1)));
+        assert!(BlockIdentifier::expiring(link, 20).is_err());
+    }
+
+    #[test]
+    fn accusation_evidence() {
+        let _ = ::rust_sodium::init();
+        let (key, secret) = crypto::sign::gen_keypair();
+        let vote = |descriptor| {
+            unwrap!(Vote::new(&key, &secret, BlockIdentifier::Link(descriptor)))
+        };
+        let resize = |size| vote(LinkDescriptor::Reconfigure { group_size: size });
+        let accusation = unwrap!(LinkDescriptor::accusation(resize(5), resize(3)));
+        assert!(accusation == unwrap!(LinkDescriptor::accusation(resize(3), resize(5))));
+        assert!(accusation.evidence_holds());
+        assert_eq!(accusation.offender(), Some(&key));
+        assert_eq!(accusation.name(), Some(&key.0));
+
+        // Links that can both be valid, a vote twice or votes by two keys prove nothing.
+        let gained = vote(LinkDescriptor::NodeGained(crypto::sign::gen_keypair().0));
+        assert!(LinkDescriptor::accusation(resize(5), gained).is_err());
+        assert!(LinkDescriptor::accusation(resize(5), resize(5)).is_err());
+        let other = crypto::sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 3 });
+        let other_vote = unwrap!(Vote::new(&other.0, &other.1, link));
+        assert!(LinkDescriptor::accusation(resize(5), other_vote.clone()).is_err());
+        // Nor does a vote claiming a key that did not sign it.
+        let forged = Vote::from_parts(other_vote.identifier().clone(),
+                                      Proof::new(key, *other_vote.proof().sig()));
+        let forged = LinkDescriptor::Accusation {
+            first: Box::new(resize(5)),
+            second: Box::new(forged),
+        };
+        assert!(!forged.evidence_holds());
+        assert!(LinkDescriptor::NodeLost(key).evidence_holds());
+    }
 }
@@ -0,0 +1,179 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::BlockIdentifier;
+use chain::data_chain::DataChain;
+use chain::unix_time;
+use error::Error;
+use maidsafe_utilities::serialisation;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use sha3::hash;
+use signature::{Ed25519, SignatureScheme};
+use std::ops::Range;
+
+/// Blocks of a chain signed by the node that exported them, so they can be handed to a new group
+/// member or an auditor and any tampering on the way detected. Serialisable for sending.
+///
+/// The signature only vouches for the holder having exported these blocks; their validity is
+/// still established from their own proofs when opened.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+pub struct SignedBundle {
+    holder: PublicKey,
+    signature: Signature,
+    group_size: u64,
+    exported_at: u64,
+    tip: [u8; 32],
+    blocks: Vec<Block>,
+    pinned: Vec<BlockIdentifier>,
+}
+
+impl SignedBundle {
+    /// Key of the node that exported and signed the bundle.
+    pub fn holder(&self) -> &PublicKey {
+        &self.holder
+    }
+
+    /// Group size of the exported chain.
+    pub fn group_size(&self) -> usize {
+        self.group_size as usize
+    }
+
+    /// When the bundle was exported, in seconds since the unix epoch.
+    pub fn exported_at(&self) -> u64 {
+        self.exported_at
+    }
+
+    /// Hash of the last block exported, see `tip_hash`.
+    pub fn tip(&self) -> &[u8; 32] {
+        &self.tip
+    }
+
+    /// Check the holder's signature and tip hash, then rebuild the chain with its pins and
+    /// revalidate it. Fails with `Error::Signature` if any part was altered after signing.
+    pub fn verify_and_open(&self) -> Result<DataChain, Error> {
+        let payload = self.payload()?;
+        if !Ed25519::verify(&self.signature, &payload, &self.holder) ||
+           tip_hash(&self.blocks)? != self.tip {
+            return Err(Error::Signature);
+        }
+        let mut chain = DataChain::from_blocks(self.blocks.clone(), self.group_size());
+        for identifier in &self.pinned {
+            let _ = chain.pin(identifier);
+        }
+        chain.mark_blocks_valid();
+        Ok(chain)
+    }
+
+    // The bytes the holder signs: everything in the bundle but the signature.
+    fn payload(&self) -> Result<Vec<u8>, Error> {
+        Ok(serialisation::serialise(&(&self.holder,
+                                      self.group_size,
+                                      self.exported_at,
+                                      &self.tip,
+                                      &self.blocks,
+                                      &self.pinned))?)
+    }
+}
+
+impl DataChain {
+    /// Export the whole chain as a `SignedBundle` signed with the holder's keys.
+    pub fn export_bundle(&self,
+                         pub_key: &PublicKey,
+                         secret_key: &SecretKey)
+                         -> Result<SignedBundle, Error> {
+        self.export_range(0..self.len(), pub_key, secret_key)
+    }
+
+    /// Export the blocks at positions `range` as a `SignedBundle`, with the pins among them.
+    /// Include the links governing the first data blocks, or they can't be validated when
+    /// opened. Panics if `range` is out of bounds, as for slicing.
+    pub fn export_range(&self,
+                        range: Range<usize>,
+                        pub_key: &PublicKey,
+                        secret_key: &SecretKey)
+                        -> Result<SignedBundle, Error> {
+        let blocks = self.chain()[range].to_vec();
+        let pinned = self.pinned()
+            .iter()
+            .filter(|x| blocks.iter().any(|block| block.identifier() == *x))
+            .cloned()
+            .collect();
+        let mut bundle = SignedBundle {
+            holder: *pub_key,
+            signature: Signature([0; sign::SIGNATUREBYTES]),
+            group_size: self.group_size() as u64,
+            exported_at: unix_time(),
+            tip: tip_hash(&blocks)?,
+            blocks: blocks,
+            pinned: pinned,
+        };
+        bundle.signature = Ed25519::sign(&bundle.payload()?, secret_key);
+        Ok(bundle)
+    }
+}
+
+/// Hash of the serialised last block, proofs included, or all zeros for no blocks.
+pub fn tip_hash(blocks: &[Block]) -> Result<[u8; 32], Error> {
+    match blocks.last() {
+        Some(block) => Ok(hash(&serialisation::serialise(block)?)),
+        None => Ok([0; 32]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{DataChain, test_blocks};
+    use error::Error;
+    use maidsafe_utilities::serialisation;
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn export_verify_and_open() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let blocks = test_blocks(4);
+        let pinned = blocks[2].identifier().clone();
+        let mut chain = DataChain::from_blocks(blocks, 4);
+        let _ = chain.pin(&pinned);
+        chain.mark_blocks_valid();
+
+        let bundle = unwrap!(chain.export_bundle(&keys.0, &keys.1));
+        assert_eq!(bundle.holder(), &keys.0);
+        assert_eq!(bundle.group_size(), 4);
+        let sent: SignedBundle =
+            unwrap!(serialisation::deserialise(&unwrap!(serialisation::serialise(&bundle))));
+        let opened = unwrap!(sent.verify_and_open());
+        assert_eq!(opened.chain(), chain.chain());
+        assert!(opened.is_pinned(&pinned));
+
+        let range = unwrap!(chain.export_range(0..2, &keys.0, &keys.1));
+        assert_eq!(unwrap!(range.verify_and_open()).len(), 2);
+        assert!(range.pinned.is_empty());
+
+        let mut tampered = bundle.clone();
+        tampered.blocks.truncate(3);
+        match tampered.verify_and_open() {
+            Err(Error::Signature) => (),
+            _ => panic!("tampered bundle opened"),
+        }
+        let mut tampered = bundle;
+        tampered.group_size = 1;
+        assert!(tampered.verify_and_open().is_err());
+    }
+}
@@ -17,17 +17,145 @@
 
 use bincode::rustc_serialize;
 use chain::block::Block;
-use chain::block_identifier::BlockIdentifier;
+use chain::block_identifier::{BlockIdentifier, LinkDescriptor, Prefix};
+use chain::digest::ChainDigest;
+use chain::persistence::{self, ChainStore, FileStore, RecoveryReport, SessionLock, WritePolicy,
+                         WriteStats};
+use chain::snapshot::ChainSnapshot;
+use chain::unix_time;
 use chain::vote::Vote;
-use error::Error;
-use fs2::FileExt;
+use data::DataIdentifier;
+use error::{Error, InvariantViolation, ValidationError};
 use itertools::Itertools;
 use maidsafe_utilities::serialisation;
-use rust_sodium::crypto::sign::PublicKey;
+use metrics::{self, MetricsSink};
+use rust_sodium::crypto::secretbox;
+use rust_sodium::crypto::sign::{PublicKey, SecretKey, Signature};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use sha3::{ChainHasher, HashAlgorithm, hash};
+use signature::{Ed25519, SignatureScheme};
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{self, Debug, Formatter};
 use std::fs;
-use std::io::{self, Read, Write};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// Progress through a chain for `DataChain::mark_blocks_valid_partial`.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationCursor {
+    position: usize,
+    link: Option<Block>,
+    group_size: usize,
+    mutations: u64,
+    validated: usize,
+    // Whether a block before `position` has a `Block::prev_hash`.
+    linked: bool,
+}
+
+impl ValidationCursor {
+    /// Index of the next block to be validated.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Number of blocks validated by the last call to `mark_blocks_valid_partial`.
+    pub fn validated(&self) -> usize {
+        self.validated
+    }
+}
+
+/// Limit on how many votes each key may have verified, see `DataChain::set_rate_limit`. Each key
+/// has a bucket of `burst` tokens refilled at `per_second` tokens a second, and every vote takes
+/// one before its signature is checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Votes a key may send at once.
+    pub burst: u32,
+    /// Votes a key may send per second over time.
+    pub per_second: u32,
+}
+
+impl Default for RateLimit {
+    fn default() -> RateLimit {
+        RateLimit {
+            burst: 100,
+            per_second: 20,
+        }
+    }
+}
+
+/// Misbehaviour seen from one key, see `DataChain::offenders`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OffenceReport {
+    /// Votes with a signature that does not verify, counted against the peer that sent them.
+    pub bad_signatures: u32,
+    /// Votes by the key for a link describing itself.
+    pub self_votes: u32,
+    /// Votes by the key for a link conflicting with another link it signed, see
+    /// `LinkDescriptor::conflicts_with`.
+    pub conflicting_votes: u32,
+}
+
+impl OffenceReport {
+    /// All offences counted.
+    pub fn total(&self) -> u32 {
+        self.bad_signatures + self.self_votes + self.conflicting_votes
+    }
+}
+
+/// Which links a data block needs a quorum of, see `DataChain::set_validation_mode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// A quorum of the link governing it, the last valid link before it.
+    #[default]
+    SingleLink,
+    /// As `SingleLink`, and also a majority of the members of the next valid link after it when
+    /// there is one, so a block signed by members on their way out is not valid.
+    DualLink,
+}
+
+/// A change to our close group seen by the routing layer, see `DataChain::handle_churn`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChurnEvent {
+    /// The node with this key joined.
+    NodeGained(PublicKey),
+    /// The node with this key left.
+    NodeLost(PublicKey),
+    /// The group split from the section with this prefix.
+    Split(Prefix),
+}
+
+// Buckets kept before full ones, of keys that went quiet, are dropped.
+const MAX_RATE_BUCKETS: usize = 4096;
+
+/// A change to a `DataChain`, passed to the callbacks given to `DataChain::subscribe`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChainEvent {
+    /// A data block became valid, by votes, a delta or revalidation.
+    BlockValid(BlockIdentifier),
+    /// A link became valid, by votes, a delta or revalidation.
+    LinkValid(BlockIdentifier),
+    /// A block was removed from the chain.
+    BlockPruned(BlockIdentifier),
+    /// A vote brought a pending block to quorum. Followed by `BlockValid` or `LinkValid`.
+    QuorumReached {
+        /// The block.
+        identifier: BlockIdentifier,
+        /// Number of votes it holds.
+        proofs: usize,
+    },
+    /// A vote signed by this key was dropped unverified, the key having exceeded the `RateLimit`.
+    RateLimited(PublicKey),
+    /// The key reached the `DataChain::set_blocklist_threshold` and its votes are now refused.
+    Blocklisted(PublicKey),
+}
+
+type Subscriber = Box<dyn FnMut(ChainEvent) + Send>;
+/// Checks the tag and hash of a `BlockIdentifier::Custom` block, see
+/// `DataChain::set_custom_validator`.
+pub type CustomValidator = Box<dyn Fn(u64, &[u8; 32]) -> bool + Send>;
 
 /// Created by holder of chain, can be passed to others as proof of data held.
 /// This object is verifiable if :
@@ -39,40 +167,117 @@ use std::path::PathBuf;
 /// If there was a restart then the nodes should validate and continue.
 /// N:B this means all nodes can use a named directory for data store and clear if they restart
 /// as a new id. This allows clean-up of old data cache directories.
-#[derive(Default, PartialEq, RustcEncodable, RustcDecodable)]
+#[derive(Default)]
 pub struct DataChain {
     chain: Vec<Block>,
     group_size: usize,
     path: Option<PathBuf>,
+    pinned: Vec<BlockIdentifier>,
+    bind_votes: bool,
+    max_clock_skew: Option<u64>,
+    write_policy: WritePolicy,
+    write_stats: WriteStats,
+    written_len: u64,
+    skipped_writes: u32,
+    store: Option<Box<dyn ChainStore + Send>>,
+    mutations: u64,
+    written_mutations: u64,
+    subscribers: Vec<(u64, Subscriber)>,
+    next_subscriber: u64,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    hash_algorithm: HashAlgorithm,
+    rate_limit: Option<RateLimit>,
+    rate_buckets: HashMap<PublicKey, (f64, Instant)>,
+    checkpoint_interval: Option<usize>,
+    validation_mode: ValidationMode,
+    offences: HashMap<PublicKey, OffenceReport>,
+    blocklist_threshold: Option<u32>,
+    blocklist: HashSet<PublicKey>,
+    accusations: HashMap<PublicKey, LinkDescriptor>,
+    // The last `snapshot` with the mutation count it was taken at.
+    snapshot: Mutex<Option<(u64, ChainSnapshot)>>,
+    custom_validator: Option<CustomValidator>,
+    proof_retention: Option<usize>,
+    session: Option<SessionLock>,
+    // The latest `now` given to `expire`, validation keeping blocks expired by then invalid.
+    expired_at: u64,
 }
 
 impl DataChain {
     /// Create a new chain backed up on disk
     /// Provide the directory to create the files in
-    pub fn create_in_path(path: PathBuf, group_size: usize) -> io::Result<DataChain> {
+    /// The chain holds the directory's `SessionLock` until dropped or `unlock`ed.
+    pub fn create_in_path(path: PathBuf, group_size: usize) -> Result<DataChain, Error> {
         let path = path.join("data_chain");
-        let file = fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
-        // hold a lock on the file for the whole session
-        file.lock_exclusive()?;
+        let session = SessionLock::acquire(&path)?;
+        let _ = fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
         Ok(DataChain {
-            chain: Vec::<Block>::default(),
             group_size: group_size,
+            store: Some(Box::new(FileStore::new(path.clone()))),
             path: Some(path),
+            session: Some(session),
+            ..DataChain::default()
         })
     }
 
-    /// Open from existing directory
+    /// Open from existing directory, see `try_open`.
     pub fn from_path(path: PathBuf, group_size: usize) -> Result<DataChain, Error> {
+        Self::try_open(path, group_size)
+    }
+
+    /// Open from existing directory, holding its `SessionLock` until dropped or `unlock`ed. Fails
+    /// at once with `Error::LockHeld` naming the owner if another process has it open. A lock
+    /// left by a process that died is taken over, see `session_lock`.
+    pub fn try_open(path: PathBuf, group_size: usize) -> Result<DataChain, Error> {
         let path = path.join("data_chain");
-        let mut file = fs::OpenOptions::new().read(true).write(true).create(false).open(&path)?;
-        // hold a lock on the file for the whole session
-        file.lock_exclusive()?;
-        let mut buf = Vec::<u8>::new();
-        let _ = file.read_to_end(&mut buf)?;
-        Ok(DataChain {
-            chain: serialisation::deserialise::<Vec<Block>>(&buf[..])?,
+        let _ = fs::metadata(&path)?;
+        let session = SessionLock::acquire(&path)?;
+        let mut chain = Self::open_with_store(Box::new(FileStore::new(path.clone())), group_size)?;
+        chain.path = Some(path);
+        chain.session = Some(session);
+        Ok(chain)
+    }
+
+    /// Open an existing directory whose chain file may be damaged, loading the blocks before the
+    /// damage, see `FileStore::recover`, along with a report of what was salvaged. The damaged
+    /// file is kept until the next `write` or `flush` replaces it.
+    pub fn from_path_recover(path: PathBuf,
+                             group_size: usize)
+                             -> Result<(DataChain, RecoveryReport), Error> {
+        let path = path.join("data_chain");
+        let session = SessionLock::acquire(&path)?;
+        let mut store = FileStore::new(path.clone());
+        let (chain, pinned, report) = store.recover()?;
+        let hash_algorithm = store.hash_algorithm();
+        let chain = DataChain {
+            chain: chain,
             group_size: group_size,
+            pinned: pinned,
+            store: Some(Box::new(store)),
             path: Some(path),
+            hash_algorithm: hash_algorithm,
+            session: Some(session),
+            ..DataChain::default()
+        };
+        Ok((chain, report))
+    }
+
+    /// Open a chain persisted through the given `ChainStore` backend. All later writes go through
+    /// the store, which locks its files while loading and storing.
+    pub fn open_with_store(mut store: Box<dyn ChainStore + Send>,
+                           group_size: usize)
+                           -> Result<DataChain, Error> {
+        let (chain, pinned) = store.load()?;
+        let hash_algorithm = store.hash_algorithm();
+        let written_len = persistence::encode_chain(&chain, &pinned, hash_algorithm)?.len() as u64;
+        Ok(DataChain {
+            chain: chain,
+            group_size: group_size,
+            pinned: pinned,
+            written_len: written_len,
+            store: Some(store),
+            hash_algorithm: hash_algorithm,
+            ..DataChain::default()
         })
     }
 
@@ -81,40 +286,174 @@ impl DataChain {
         DataChain {
             chain: blocks,
             group_size: group_size,
-            path: None,
+            ..DataChain::default()
         }
     }
 
-    /// Write current data chain to supplied path
-    pub fn write(&self) -> Result<(), Error> {
-        if let Some(path) = self.path.to_owned() {
-            let mut file = fs::OpenOptions::new().read(true)
-                .write(true)
-                .create(false)
-                .open(&path.as_path())?;
-            return Ok(file.write_all(&serialisation::serialise(&self.chain)?)?);
+    /// Create a chain in memory from `blocks` starting with a trusted `CheckPoint` link, validating
+    /// them from it as from genesis, so the links before it need not be held. Fails with
+    /// `Error::MissingLink` if the first block is not a checkpoint and `Error::Validation` if it is
+    /// not signed by a quorum of the members it commits to.
+    pub fn from_checkpoint(mut blocks: Vec<Block>, group_size: usize) -> Result<DataChain, Error> {
+        match blocks.first_mut() {
+            Some(first) if first.identifier().link_descriptor().is_some_and(|x| {
+                matches!(*x, LinkDescriptor::CheckPoint { .. })
+            }) => first.set_prev_hash(None),
+            _ => return Err(Error::MissingLink),
+        }
+        let mut chain = DataChain::from_blocks(blocks, group_size);
+        chain.mark_blocks_valid();
+        if !chain.chain.first().is_some_and(|x| x.valid) {
+            return Err(Error::Validation);
+        }
+        Ok(chain)
+    }
+
+    /// Write current data chain to supplied path (or `ChainStore`)
+    /// Small changes may be deferred according to the `WritePolicy`, returning `Ok(false)`. They
+    /// are only persisted by a later `write` or `flush`, so call `flush` before dropping a chain
+    /// with a non default policy. Takes `&mut self` to keep that accounting.
+    pub fn write(&mut self) -> Result<bool, Error> {
+        if self.store.is_none() {
+            return Err(Error::NotFound);
         }
-        Err(Error::NoFile)
+        let chain_len = self.encoded_len()?;
+        let pending = chain_len.saturating_sub(self.written_len);
+        if pending < self.write_policy.min_new_bytes &&
+           self.skipped_writes < self.write_policy.max_skipped_writes {
+            self.skipped_writes += 1;
+            self.write_stats.skipped_writes += 1;
+            return Ok(false);
+        }
+        self.persist(chain_len).map(|()| true)
+    }
+
+    /// Write current data chain to supplied path (or `ChainStore`), ignoring the `WritePolicy`
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let chain_len = self.encoded_len()?;
+        self.persist(chain_len)
     }
 
     /// Write current data chain to supplied path
     pub fn write_to_new_path(&mut self, path: PathBuf) -> Result<(), Error> {
-        let mut file = fs::OpenOptions::new().read(true)
-            .write(true)
-            .create(false)
-            .open(path.as_path())?;
-        file.write_all(&serialisation::serialise(&self.chain)?)?;
+        let _ = fs::metadata(&path)?;
+        let mut store = FileStore::new(path.clone());
+        store.set_hash_algorithm(self.hash_algorithm)?;
+        self.store = Some(Box::new(store));
         self.path = Some(path);
-        Ok(file.lock_exclusive()?)
+        self.written_len = 0;
+        self.flush()
     }
 
-    /// Unlock the lock file
-    pub fn unlock(&self) {
-        if let Some(ref path) = self.path.to_owned() {
-            if let Ok(file) = fs::File::open(path.as_path()) {
-                let _ = file.unlock();
-            }
+    /// Re-encrypt the stored chain with `key`, or decrypt it if `None`, returning the number of
+    /// bytes rewritten. Needs a `ChainStore` with at-rest encryption, such as a `FileStore`.
+    ///
+    /// The chain is rewritten in a single atomic store, so after a crash it is readable with
+    /// exactly one of the old and new keys: keep the old key until this returns. Chains opened
+    /// from a path can be reopened encrypted with `open_with_store` and `FileStore::with_key`.
+    pub fn rekey(&mut self, key: Option<secretbox::Key>) -> Result<u64, Error> {
+        match self.store {
+            Some(ref mut store) => store.set_key(key)?,
+            None => return Err(Error::NotFound),
+        }
+        let written = self.write_stats.bytes_written;
+        self.flush()?;
+        Ok(self.write_stats.bytes_written - written)
+    }
+
+    fn encoded_len(&self) -> Result<u64, Error> {
+        Ok(persistence::encode_chain(&self.chain, &self.pinned, self.hash_algorithm)?.len() as u64)
+    }
+
+    fn persist(&mut self, chain_len: u64) -> Result<(), Error> {
+        let written = match self.store {
+            Some(ref mut store) => store.store(&self.chain, &self.pinned)?,
+            None => return Err(Error::NotFound),
+        };
+        self.record_write(written, chain_len);
+        Ok(())
+    }
+
+    fn record_write(&mut self, written: u64, chain_len: u64) {
+        self.write_stats.writes += 1;
+        self.write_stats.bytes_written += written;
+        self.write_stats.new_bytes += chain_len.saturating_sub(self.written_len);
+        self.written_len = chain_len;
+        self.written_mutations = self.mutations;
+        self.skipped_writes = 0;
+        if let Some(ref sink) = self.metrics {
+            sink.increment(metrics::DISK_BYTES_WRITTEN, &[], written);
+        }
+    }
+
+    /// Whether blocks changed since the chain was loaded or last written.
+    pub fn is_dirty(&self) -> bool {
+        self.mutations != self.written_mutations
+    }
+
+    /// Set how eagerly `write` persists changes, and tune the `ChainStore` layout
+    pub fn set_write_policy(&mut self, policy: WritePolicy) {
+        if let Some(ref mut store) = self.store {
+            store.set_write_policy(&policy);
+        }
+        self.write_policy = policy;
+    }
+
+    /// Current `WritePolicy`
+    pub fn write_policy(&self) -> &WritePolicy {
+        &self.write_policy
+    }
+
+    /// Compute the content hashes of data held against this chain with `algorithm` rather than
+    /// the default SHA3-256. It is recorded in the chain file, so must be chosen before any block
+    /// is added: fails with `Error::Validation` on a chain with blocks hashed another way, or
+    /// if the `ChainStore` cannot record it.
+    pub fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) -> Result<(), Error> {
+        if algorithm == self.hash_algorithm {
+            return Ok(());
+        }
+        if !self.chain.is_empty() {
+            return Err(Error::Validation);
         }
+        if let Some(ref mut store) = self.store {
+            store.set_hash_algorithm(algorithm)?;
+        }
+        self.hash_algorithm = algorithm;
+        Ok(())
+    }
+
+    /// Algorithm content hashes of data held against this chain are computed with.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Backend computing `hash_algorithm`.
+    pub fn hasher(&self) -> &'static dyn ChainHasher {
+        self.hash_algorithm.hasher()
+    }
+
+    /// Hash of `block` as recorded by the `Block::prev_hash` of the block after it: of
+    /// its identifier's `BlockIdentifier::to_signable_bytes`, with `hash_algorithm`. Proofs are
+    /// left out, as replicas of a block hold different ones.
+    pub fn block_hash(&self, block: &Block) -> [u8; 32] {
+        self.hasher().hash(&block.identifier().to_signable_bytes())
+    }
+
+    /// Disk writes made so far this session, including write amplification
+    pub fn write_stats(&self) -> &WriteStats {
+        &self.write_stats
+    }
+
+    /// Let go of the `SessionLock` on the chain's directory, released once no other chain in this
+    /// process has the directory open.
+    pub fn unlock(&mut self) {
+        self.session = None;
+    }
+
+    /// The `SessionLock` held on the chain's directory, `None` if not opened from one or after
+    /// `unlock`.
+    pub fn session_lock(&self) -> Option<&SessionLock> {
+        self.session.as_ref()
     }
 
     /// Nodes always validate a chain before accepting it
@@ -122,87 +461,520 @@ impl DataChain {
     /// Also confirm we can accept this chain, by comparing
     /// our current group with the majority of the last known link
     /// This method will NOT purge
-    pub fn validate_ownership(&mut self, my_group: &[PublicKey]) -> bool {
+    pub fn validate_ownership(&self, my_group: &[PublicKey]) -> bool {
+        self.verify_ownership(my_group).is_ok()
+    }
+
+    /// As `validate_ownership` but reports why the chain cannot be accepted.
+    pub fn verify_ownership(&self, my_group: &[PublicKey]) -> Result<(), ValidationError> {
         // ensure all links are good
-        self.mark_blocks_valid();
+        let snapshot = self.snapshot();
         // ensure last good link contains majority of current group
-        if let Some(last_link) = self.last_valid_link() {
-            return (last_link.proofs()
-                .iter()
-                .filter(|&k| my_group.iter().any(|&z| PublicKey(z.0) == *k.key()))
-                .count() * 2) > last_link.proofs().len();
-
+        if let Some(last_link) = snapshot.last_valid_link() {
+            let members = last_link.members();
+            if (members.iter()
+                .filter(|&k| my_group.iter().any(|&z| PublicKey(z.0) == *k))
+                .count() * 2) > members.len() {
+                Ok(())
+            } else {
+                Err(ValidationError::StaleGroup)
+            }
         } else {
-            false
+            Err(ValidationError::MissingLink)
+        }
+    }
+
+    /// Check a vote may be accumulated in this chain: it must be signed correctly, not by a key
+    /// rotated away with `LinkDescriptor::KeyChanged`, by one of the `current_elders` once roles
+    /// are recorded and, once the chain is started, must not be a node voting for a link about
+    /// itself. A `CheckPoint` link must match our own `checkpoint`, and while one is due no other
+    /// link is accepted.
+    pub fn verify_vote(&self, vote: &Vote) -> Result<(), ValidationError> {
+        vote.verify()?;
+        if self.bind_votes &&
+           !vote.binding().is_some_and(|x| self.accepted_bindings(vote.identifier()).contains(x)) {
+            return Err(ValidationError::BindingMismatch);
+        }
+        if let Some(skew) = self.max_clock_skew {
+            let now = unix_time();
+            match vote.proof().timestamp() {
+                Some(time) if cmp::max(time, now) - cmp::min(time, now) <= skew => (),
+                _ => return Err(ValidationError::ClockSkew),
+            }
+        }
+        if !self.chain.is_empty() && vote.identifier().is_link() && vote.is_self_vote() {
+            return Err(ValidationError::SelfVote);
+        }
+        if !vote.identifier().link_descriptor().is_none_or(LinkDescriptor::evidence_holds) {
+            return Err(ValidationError::BadEvidence);
+        }
+        if let BlockIdentifier::Custom { tag, ref hash } = *vote.identifier().without_expiry() {
+            if !self.custom_validator.as_ref().is_none_or(|validator| validator(tag, hash)) {
+                return Err(ValidationError::CustomRejected);
+            }
+        }
+        match vote.identifier().link_descriptor() {
+            Some(LinkDescriptor::CheckPoint { members, state, .. }) => {
+                let mut elders = self.current_elders();
+                elders.sort();
+                if *members != elders || *state != self.links_state(Some(vote.identifier())) {
+                    return Err(ValidationError::CheckpointMismatch);
+                }
+            }
+            Some(_) if self.checkpoint_due() => return Err(ValidationError::CheckpointDue),
+            _ => (),
+        }
+        // The payer of a transfer signs it whatever its role.
+        let payer = transfer_payer(vote.identifier()) == Some(vote.proof().key());
+        if self.is_retired(vote.proof().key()) || self.is_accused(vote.proof().key()) ||
+           self.roster().is_some_and(|elders| !payer && !elders.contains(vote.proof().key())) {
+            return Err(ValidationError::UnknownSigner);
+        }
+        Ok(())
+    }
+
+    /// Whether `key` was rotated away by a valid `KeyChanged` link and not brought back since.
+    pub fn is_retired(&self, key: &PublicKey) -> bool {
+        for block in self.chain.iter().rev().filter(|x| x.valid) {
+            match block.identifier().link_descriptor() {
+                Some(LinkDescriptor::KeyChanged { old, .. }) if old == key => return true,
+                Some(&LinkDescriptor::KeyChanged { ref new, .. }) |
+                Some(&LinkDescriptor::NodeGained(ref new)) if new == key => return false,
+                _ => (),
+            }
+        }
+        false
+    }
+
+    /// Whether a valid `Accusation` link is against `key`.
+    pub fn is_accused(&self, key: &PublicKey) -> bool {
+        self.chain
+            .iter()
+            .filter(|x| x.valid)
+            .filter_map(|x| x.identifier().link_descriptor())
+            .any(|x| x.offender() == Some(key))
+    }
+
+    /// Require all votes to carry the current `vote_binding`, rejecting replayed votes that were
+    /// signed for another chain or an earlier state of this one.
+    /// Like `group_size` this is not persisted, so must be set again after `from_path` or
+    /// `open_with_store`.
+    pub fn set_vote_binding(&mut self, enabled: bool) {
+        self.bind_votes = enabled;
+    }
+
+    /// Require all votes to carry a timestamp within `skew` seconds of our clock, rejecting stale
+    /// votes. `None` (the default) accepts votes with or without timestamps.
+    /// Not persisted, see `set_vote_binding`.
+    pub fn set_max_clock_skew(&mut self, skew: Option<u64>) {
+        self.max_clock_skew = skew;
+    }
+
+    /// Require a `CheckPoint` link after every `interval` valid links: once one is due, see
+    /// `checkpoint_due`, votes for any other link are rejected until it is valid. `None` (the
+    /// default) leaves checkpoints to the group. Not persisted, see `set_vote_binding`.
+    pub fn set_checkpoint_interval(&mut self, interval: Option<usize>) {
+        self.checkpoint_interval = interval;
+    }
+
+    /// Check votes for `BlockIdentifier::Custom` blocks with `validator`, given the block's tag
+    /// and hash, refusing those it returns `false` for. `None` (the default) accepts any. The
+    /// blocks are otherwise validated like data blocks, and ignored by `SecuredData`.
+    /// Not persisted, see `set_vote_binding`.
+    pub fn set_custom_validator(&mut self, validator: Option<CustomValidator>) {
+        self.custom_validator = validator;
+    }
+
+    /// Keep only a quorum plus `margin` of the proofs of valid data blocks in past epochs, see
+    /// `trim_proofs`, which `mark_blocks_valid` then applies. `None` (the default) keeps every
+    /// proof. Not persisted, see `set_vote_binding`.
+    pub fn set_proof_retention(&mut self, margin: Option<usize>) {
+        self.proof_retention = margin;
+    }
+
+    /// Choose which links data blocks need a quorum of, `ValidationMode::SingleLink` by default.
+    /// `DualLink` is applied by `mark_blocks_valid`, so a block valid on arrival is checked
+    /// against the link after it when the chain is next revalidated. Not persisted, see
+    /// `set_vote_binding`.
+    pub fn set_validation_mode(&mut self, mode: ValidationMode) {
+        self.validation_mode = mode;
+        self.mutated();
+    }
+
+    /// The `ValidationMode` in use.
+    pub fn validation_mode(&self) -> ValidationMode {
+        self.validation_mode
+    }
+
+    /// Whether the `set_checkpoint_interval` has passed since the last valid `CheckPoint` link, or
+    /// the start of the chain, so the group should vote for a `checkpoint`.
+    pub fn checkpoint_due(&self) -> bool {
+        let since = self.chain
+            .iter()
+            .rev()
+            .filter(|x| x.valid && x.identifier().is_link())
+            .take_while(|x| !matches!(x.identifier().link_descriptor(),
+                                      Some(&LinkDescriptor::CheckPoint { .. })))
+            .count();
+        self.checkpoint_interval.is_some_and(|interval| since >= interval)
+    }
+
+    /// The `CheckPoint` link for `prefix` committing to the `current_elders` and the
+    /// `checkpoint_state`, for the group to vote for.
+    pub fn checkpoint(&self, prefix: Prefix) -> BlockIdentifier {
+        let mut members = self.current_elders();
+        members.sort();
+        BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+            prefix: prefix,
+            members: members,
+            state: self.checkpoint_state(),
+        })
+    }
+
+    /// Hash of the identifiers of the valid links, the state a `CheckPoint` link commits to.
+    pub fn checkpoint_state(&self) -> [u8; 32] {
+        self.links_state(None)
+    }
+
+    /// The last valid `CheckPoint` link, from which the chain can be validated without the links
+    /// before it, see `from_checkpoint`.
+    pub fn last_checkpoint(&self) -> Option<&Block> {
+        self.chain.iter().rev().find(|x| {
+            x.valid &&
+            matches!(x.identifier().link_descriptor(),
+                     Some(&LinkDescriptor::CheckPoint { .. }))
+        })
+    }
+
+    // The `checkpoint_state`, leaving out the link `excluding` once it is valid itself.
+    fn links_state(&self, excluding: Option<&BlockIdentifier>) -> [u8; 32] {
+        let links = self.chain
+            .iter()
+            .filter(|x| x.valid && x.identifier().is_link() && Some(x.identifier()) != excluding)
+            .map(|x| x.identifier())
+            .collect_vec();
+        serialisation::serialise(&links).map_or([0; 32], |bytes| hash(&bytes))
+    }
+
+    /// Limit the votes verified per signing key, so a peer flooding `add_vote` costs at most
+    /// `limit.per_second` signature checks a second. Excess votes are rejected and reported as
+    /// `ChainEvent::RateLimited`. `None` (the default) verifies every vote. Keys cost nothing to
+    /// make, so this bounds the work of peers voting under their own keys only.
+    /// Not persisted, see `set_vote_binding`.
+    pub fn set_rate_limit(&mut self, limit: Option<RateLimit>) {
+        self.rate_limit = limit;
+        self.rate_buckets.clear();
+    }
+
+    /// Blocklist keys once their `OffenceReport` totals `threshold` offences, refusing any later
+    /// vote they sign or send until `clear_offender`. `None` (the default) only counts offences.
+    /// Not persisted, see `set_vote_binding`.
+    pub fn set_blocklist_threshold(&mut self, threshold: Option<u32>) {
+        self.blocklist_threshold = threshold;
+    }
+
+    /// Keys seen misbehaving, with what they did, sorted by key.
+    pub fn offenders(&self) -> Vec<(PublicKey, OffenceReport)> {
+        let mut offenders = self.offences.iter().map(|(key, report)| (*key, *report)).collect_vec();
+        offenders.sort_by(|a, b| a.0.cmp(&b.0));
+        offenders
+    }
+
+    /// Refuse votes signed or sent by `key` until `clear_offender`, whatever it has done.
+    pub fn blocklist(&mut self, key: PublicKey) {
+        let _ = self.blocklist.insert(key);
+    }
+
+    /// Whether votes signed or sent by `key` are refused.
+    pub fn is_blocklisted(&self, key: &PublicKey) -> bool {
+        self.blocklist.contains(key)
+    }
+
+    /// Forget the offences of `key`, its `accusation` and take it off the blocklist. Returns
+    /// whether there was anything to forget. A valid `Accusation` link stays in force.
+    pub fn clear_offender(&mut self, key: &PublicKey) -> bool {
+        let blocked = self.blocklist.remove(key);
+        let accused = self.accusations.remove(key).is_some();
+        self.offences.remove(key).is_some() || blocked || accused
+    }
+
+    /// An `Accusation` link against `key` for the group to vote for, made from the first pair of
+    /// conflicting votes it was seen to sign.
+    pub fn accusation(&self, key: &PublicKey) -> Option<BlockIdentifier> {
+        self.accusations.get(key).map(|x| BlockIdentifier::Link(x.clone()))
+    }
+
+    /// The value votes for this chain should be bound to (see `Vote::new_bound`). This is the hash
+    /// of the last valid link, or all zeros for a chain without one.
+    pub fn vote_binding(&self) -> [u8; 32] {
+        self.chain
+            .iter()
+            .rev()
+            .find(|x| x.identifier().is_link() && x.valid)
+            .map_or([0; 32], link_binding)
+    }
+
+    // Bindings accepted on votes for `block_identifier`: that of the link governing the block, or
+    // of any valid link after it. So votes signed before a link became valid, or by a peer with a
+    // newer tip, still count, but not those bound to older links or to other chains.
+    fn accepted_bindings(&self, block_identifier: &BlockIdentifier) -> Vec<[u8; 32]> {
+        let end = self.position(block_identifier).unwrap_or(self.chain.len());
+        let start = self.chain[..end].iter().rposition(|x| x.identifier().is_link() && x.valid);
+        let mut bindings = self.chain[start.unwrap_or(0)..]
+            .iter()
+            .filter(|x| x.identifier().is_link() && x.valid)
+            .map(link_binding)
+            .collect_vec();
+        if start.is_none() {
+            bindings.push([0; 32]);
         }
+        bindings
+    }
+
+    /// Report `metrics` to `sink` from now on, or stop reporting them with `None`.
+    /// Not persisted, see `set_vote_binding`.
+    pub fn set_metrics(&mut self, sink: Option<Arc<dyn MetricsSink>>) {
+        self.metrics = sink;
+        self.report_gauges();
+    }
+
+    /// Our vote for `identifier`, bound to the `vote_binding` and, if a `set_max_clock_skew` is
+    /// set, timestamped, so peers configured like us accept it. It is not added to the chain.
+    pub fn vote_for(&self,
+                    identifier: BlockIdentifier,
+                    pub_key: &PublicKey,
+                    secret_key: &SecretKey)
+                    -> Result<Vote, Error> {
+        let binding = self.vote_binding();
+        match self.max_clock_skew {
+            Some(_) => {
+                Vote::new_timestamped(pub_key, secret_key, identifier, Some(binding), unix_time())
+            }
+            None => Vote::new_bound(pub_key, secret_key, identifier, binding),
+        }
+    }
+
+    /// Vote for the link recording `event`, add the vote to the chain and return the votes to
+    /// send to the rest of the group. None are made for our own joining once the chain is started,
+    /// as nodes may not vote for links about themselves.
+    pub fn handle_churn(&mut self,
+                        event: ChurnEvent,
+                        pub_key: &PublicKey,
+                        secret_key: &SecretKey)
+                        -> Result<Vec<Vote>, Error> {
+        let link = match event {
+            ChurnEvent::NodeGained(ref key) if key == pub_key && !self.chain.is_empty() => {
+                return Ok(vec![]);
+            }
+            ChurnEvent::NodeGained(key) => LinkDescriptor::NodeGained(key),
+            ChurnEvent::NodeLost(key) => LinkDescriptor::NodeLost(key),
+            ChurnEvent::Split(prefix) => LinkDescriptor::SplitFrom(prefix),
+        };
+        let vote = self.vote_for(BlockIdentifier::Link(link), pub_key, secret_key)?;
+        let _ = self.add_vote(vote.clone());
+        Ok(vec![vote])
     }
 
     /// Add a vote received from a peer
     /// Uses  `lazy accumulation`
     /// If vote becomes valid, then it is returned
     pub fn add_vote(&mut self, vote: Vote) -> Option<BlockIdentifier> {
-        if !vote.validate() {
+        self.add_vote_with_origin(vote, None)
+    }
+
+    /// As `add_vote`, also recording that `peer` sent the vote in the block's `BlockMetadata`.
+    pub fn add_vote_from(&mut self, vote: Vote, peer: PublicKey) -> Option<BlockIdentifier> {
+        self.add_vote_with_origin(vote, Some(peer))
+    }
+
+    fn add_vote_with_origin(&mut self,
+                            vote: Vote,
+                            origin: Option<PublicKey>)
+                            -> Option<BlockIdentifier> {
+        if let Some(ref sink) = self.metrics {
+            sink.increment(metrics::VOTES_RECEIVED, &[], 1);
+        }
+        let result = self.accumulate_vote(vote, origin);
+        self.report_gauges();
+        result
+    }
+
+    fn accumulate_vote(&mut self,
+                       vote: Vote,
+                       origin: Option<PublicKey>)
+                       -> Option<BlockIdentifier> {
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!("add_vote", block = ?vote.identifier()).entered();
+        if self.is_blocklisted(vote.proof().key()) ||
+           origin.is_some_and(|peer| self.is_blocklisted(&peer)) {
+            chain_event!("vote rejected",
+                         block = vote.identifier(),
+                         reason = metrics::BLOCKLISTED);
+            self.reject_vote(metrics::BLOCKLISTED);
+            return None;
+        }
+        if !self.take_rate_token(vote.proof().key()) {
+            chain_event!("vote rejected",
+                         block = vote.identifier(),
+                         reason = metrics::RATE_LIMITED);
+            self.reject_vote(metrics::RATE_LIMITED);
+            self.notify(ChainEvent::RateLimited(*vote.proof().key()));
+            return None;
+        }
+        // A proof already held was verified when it arrived, and may no longer verify now, e.g. a
+        // member's vote for its own genesis link.
+        if let Some(pos) = self.chain.iter().position(|blk| {
+            blk.identifier() == vote.identifier() && blk.proofs().contains(vote.proof())
+        }) {
+            self.chain[pos].metadata_mut().origins.extend(origin);
+            chain_event!("vote rejected",
+                         block = vote.identifier(),
+                         reason = metrics::DUPLICATE_PROOF);
+            self.reject_vote(metrics::DUPLICATE_PROOF);
+            return None;
+        }
+        if let Err(error) = self.verify_vote(&vote) {
+            chain_event!("vote rejected", block = vote.identifier(), reason = error);
+            self.reject_vote(metrics::rejection_reason(error));
+            match error {
+                // Anyone can put a key on a vote, so only the peer it came from is to blame.
+                ValidationError::BadSignature => {
+                    if let Some(peer) = origin {
+                        self.record_offence(peer, |x| x.bad_signatures += 1);
+                    }
+                }
+                ValidationError::SelfVote => {
+                    self.record_offence(*vote.proof().key(), |x| x.self_votes += 1)
+                }
+                _ => (),
+            }
+            return None;
+        }
+        if let Some(earlier) = self.conflicting_vote(&vote) {
+            chain_event!("vote rejected",
+                         block = vote.identifier(),
+                         reason = metrics::CONFLICTING_VOTE);
+            self.reject_vote(metrics::CONFLICTING_VOTE);
+            let key = *vote.proof().key();
+            if let Ok(accusation) = LinkDescriptor::accusation(earlier, vote) {
+                let _ = self.accusations.entry(key).or_insert(accusation);
+            }
+            self.record_offence(key, |x| x.conflicting_votes += 1);
             return None;
         }
         let len;
-        let links;
-        let group_size;
+        let mut links;
         {
             links = self.valid_links_at_block_id(vote.identifier());
             len = self.chain.len();
-            group_size = self.group_size;
             if self.chain.is_empty() {
                 if let Ok(mut blk) = Block::new(vote.clone()) {
+                    self.mutated();
                     blk.valid = true;
-                    info!("vote good (chain start)  - marked block {:?} valid",
-                          blk.identifier());
+                    blk.metadata_mut().origins.extend(origin);
+                    chain_event!("vote good, chain start",
+                                 block = blk.identifier(),
+                                 proofs = blk.proofs().len(),
+                                 valid = blk.valid);
                     self.chain.push(blk.clone());
+                    self.notify_valid(blk.identifier());
                     return Some(blk.identifier().clone());
                 }
-            } else if vote.identifier().is_link() && vote.is_self_vote() {
-                return None;
             }
         }
         if let Some(mut pos) = self.chain
             .iter()
             .position(|blk| blk.identifier() == vote.identifier()) {
-            if self.chain[pos].identifier().is_link() {
-                // Move link to top of chain
+            if self.chain[pos].proofs().iter().any(|x| x.key() == vote.proof().key()) {
+                self.chain[pos].metadata_mut().origins.extend(origin);
+                chain_event!("vote rejected",
+                             block = vote.identifier(),
+                             reason = metrics::DUPLICATE_PROOF);
+                self.reject_vote(metrics::DUPLICATE_PROOF);
+                return None;
+            }
+            self.mutated();
+            if self.chain[pos].identifier().is_link() && !self.chain[pos].valid {
+                // Move a link still accumulating to the top of chain, so it follows the data
+                // blocks validated by its predecessor. Once valid it stays put.
                 let el = self.chain.remove(pos);
+                if pos < self.chain.len() && self.chain[pos].prev_hash().is_some() {
+                    self.record_prev_hash(pos);
+                }
                 pos = self.chain.len();
                 self.chain.push(el);
+                self.record_prev_hash(pos);
+                // It is now governed by the last valid link, not the one before its old position.
+                links = self.valid_links_at_block_id(vote.identifier());
             }
-            let blk = self.chain.get_mut(pos).unwrap();
-            if blk.proofs().iter().any(|x| x.key() == vote.proof().key()) {
-                info!("duplicate proof");
+            let group_size = links.as_ref().map_or(self.group_size, |x| self.group_size_at(x));
+            let blk = &mut self.chain[pos];
+            blk.metadata_mut().origins.extend(origin);
+            if let Err(error) = blk.add_proof(vote.proof().clone()) {
+                chain_event!("vote rejected", block = blk.identifier(), reason = error);
+                self.reject_vote(metrics::BAD_PROOF);
                 return None;
             }
-
-            blk.add_proof(vote.proof().clone()).unwrap();
-            info!("chain length {:?}", len);
-            if links.map_or(false, |x| {
+            let quorum = links.as_ref().map(|x| Self::quorum(x, group_size));
+            if links.is_some_and(|x| {
                 x.identifier() != vote.identifier() &&
                 Self::validate_block_with_proof(blk, &x, group_size)
             }) {
+                let newly_valid = !blk.valid;
                 blk.valid = true;
-                info!("vote good  - marked block {:?} valid", blk.identifier());
-                return Some(blk.identifier().clone());
+                chain_event!("vote good",
+                             block = blk.identifier(),
+                             proofs = blk.proofs().len(),
+                             quorum = quorum,
+                             chain_len = len,
+                             valid = blk.valid);
+                let (identifier, proofs) = (blk.identifier().clone(), blk.proofs().len());
+                if newly_valid {
+                    self.notify(ChainEvent::QuorumReached {
+                        identifier: identifier.clone(),
+                        proofs: proofs,
+                    });
+                    self.notify_valid(&identifier);
+                }
+                return Some(identifier);
             } else {
-                info!("Vote Ok but block not yet valid No quorum for block {:?}",
-                      blk.identifier());
-                blk.valid = false;
+                // A block once valid stays valid, a later vote cannot take its quorum away.
+                chain_event!("vote good, no quorum yet",
+                             block = blk.identifier(),
+                             proofs = blk.proofs().len(),
+                             quorum = quorum,
+                             chain_len = len,
+                             valid = blk.valid);
                 return None;
             }
 
         }
         if let Ok(ref mut blk) = Block::new(vote) {
+            self.mutated();
+            blk.metadata_mut().origins.extend(origin);
             if self.links_len() == 1 {
-                blk.valid = true;
+                // Still needs a quorum of that link, or any node could add a link on its own.
+                blk.valid = self.chain
+                    .iter()
+                    .rev()
+                    .find(|x| x.identifier().is_link() && x.valid)
+                    .is_some_and(|x| {
+                        Self::validate_block_with_proof(blk, x, self.group_size_at(x))
+                    });
             }
+            chain_event!("vote started block",
+                         block = blk.identifier(),
+                         proofs = blk.proofs().len(),
+                         valid = blk.valid);
             self.chain.push(blk.clone());
+            let pos = self.chain.len() - 1;
+            self.record_prev_hash(pos);
+            if blk.valid {
+                self.notify_valid(blk.identifier());
+            }
             return Some(blk.identifier().clone());
         }
-        info!("Could not find any block for this proof");
+        chain_event!("could not create a block for vote");
         None
 
     }
@@ -212,75 +984,381 @@ impl DataChain {
         &self.chain
     }
 
+    /// The group size the chain was created with, see `current_group_size` for the one in force.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// The group size quorums are counted against now: the one set by the latest valid
+    /// `LinkDescriptor::Reconfigure` link, or else `group_size`. Earlier blocks are checked
+    /// against the size in force at the link governing them.
+    pub fn current_group_size(&self) -> usize {
+        self.chain
+            .iter()
+            .rev()
+            .filter(|x| x.valid)
+            .find_map(reconfigured_size)
+            .unwrap_or(self.group_size)
+    }
+
     // get size of chain for storing on disk
     #[allow(unused)]
     fn size_of(&self) -> u64 {
-        rustc_serialize::encoded_size(self)
+        rustc_serialize::encoded_size(&(&self.chain, &self.pinned))
     }
 
-    /// find a block (user required to test for validity)
-    pub fn find(&self, block_identifier: &BlockIdentifier) -> Option<&Block> {
-        self.chain.iter().find(|x| x.identifier() == block_identifier)
+    /// Valid blocks in validation order: every valid link is followed by the valid data blocks it
+    /// governs, that is the blocks `mark_blocks_valid` validates against it. Links keep their chain
+    /// order, which is the order each became valid, and the data blocks of each link are sorted by
+    /// their serialised identifier rather than by when their votes arrived. Votes arriving for a
+    /// link after it became valid do not move it.
+    ///
+    /// So nodes that agree on the sequence of links and on which link governs each data block see
+    /// the same order, whatever order they received votes in, and consumers replaying the chain to
+    /// build derived state (membership, indices) get the same result on each of them. A data block
+    /// that one node accepted before a link became valid and another after it is governed by
+    /// different links on the two nodes, so is ordered differently.
+    pub fn ordered_blocks(&self) -> Vec<&Block> {
+        let mut ordered = Vec::with_capacity(self.chain.len());
+        let mut start = 0;
+        for block in self.chain.iter().filter(|x| x.valid) {
+            if block.identifier().is_link() {
+                Self::sort_data_blocks(&mut ordered[start..]);
+                ordered.push(block);
+                start = ordered.len();
+            } else {
+                ordered.push(block);
+            }
+        }
+        Self::sort_data_blocks(&mut ordered[start..]);
+        ordered
     }
 
-    /// find block by name from top (only first occurrence)
-    pub fn find_name(&self, name: &[u8; 32]) -> Option<&Block> {
-        self.chain.iter().rev().find(|x| x.valid && Some(name) == x.identifier().name())
+    fn sort_data_blocks(blocks: &mut [&Block]) {
+        blocks.sort_by_cached_key(|x| serialisation::serialise(x.identifier()).unwrap_or_default());
     }
 
-    /// Remove a block, will ignore Links
-    pub fn remove(&mut self, data_id: &BlockIdentifier) {
-        self.chain.retain(|x| x.identifier() != data_id || x.identifier().is_link());
+    /// Blocks after `link` in this chain, for a peer that already holds the chain up to that link
+    /// to catch up with `apply_delta`. `None` if `link` is not a link in this chain.
+    pub fn blocks_since(&self, link: &BlockIdentifier) -> Option<Vec<Block>> {
+        if !link.is_link() {
+            return None;
+        }
+        self.position(link).map(|pos| self.chain[pos + 1..].to_vec())
     }
 
-    /// Retains only the blocks specified by the predicate.
-    pub fn retain<F>(&mut self, pred: F)
-        where F: FnMut(&Block) -> bool
-    {
-        self.chain.retain(pred);
+    /// Append a delta received from a peer's `blocks_since`. Each new block must hold a quorum of
+    /// valid signatures from the link governing it, which is the last valid link in this chain or
+    /// a later link from the delta itself. Blocks already held are skipped. Nothing is applied
+    /// unless the whole delta verifies, otherwise the number of blocks appended is returned.
+    pub fn apply_delta(&mut self, blocks: Vec<Block>) -> Result<usize, ValidationError> {
+        let mut link = self.chain
+            .iter()
+            .rev()
+            .find(|x| x.identifier().is_link() && x.valid)
+            .cloned();
+        let mut group_size = link.as_ref().map_or(self.group_size, |x| self.group_size_at(x));
+        let mut appended = Vec::<Block>::new();
+        for mut block in blocks {
+            if self.contains(block.identifier()) ||
+               appended.iter().any(|x| x.identifier() == block.identifier()) {
+                continue;
+            }
+            let proofs_len = block.proofs().len();
+            block.remove_invalid_signatures();
+            let valid = match link {
+                Some(ref link) => Self::validate_block_with_proof(&block, link, group_size),
+                None => return Err(ValidationError::MissingLink),
+            };
+            if !valid && block.proofs().len() < proofs_len {
+                return Err(ValidationError::BadSignature);
+            } else if !valid {
+                return Err(ValidationError::NoQuorum);
+            }
+            block.valid = true;
+            if block.identifier().is_link() {
+                group_size = reconfigured_size(&block).unwrap_or(group_size);
+                link = Some(block.clone());
+            }
+            appended.push(block);
+        }
+        if !appended.is_empty() {
+            self.mutated();
+        }
+        let (count, len) = (appended.len(), self.chain.len());
+        let identifiers = appended.iter().map(|x| x.identifier().clone()).collect_vec();
+        self.chain.extend(appended);
+        for pos in len..self.chain.len() {
+            self.record_prev_hash(pos);
+        }
+        for identifier in &identifiers {
+            self.notify_valid(identifier);
+        }
+        Ok(count)
     }
 
-    /// Clear chain
-    pub fn clear(&mut self) {
-        self.chain.clear()
+    /// Check structural properties every chain built by this crate has: no identifier appears
+    /// twice, no block holds two proofs from one key, valid blocks have only good signatures, the
+    /// first valid block is a link and every later valid block holds a proof from a key that
+    /// signed an earlier valid link (with a group size of 0 any proof is a quorum, so the last is
+    /// not checked). Cheap enough to run in debug builds after merges.
+    pub fn check_invariants(&self) -> Result<(), InvariantViolation> {
+        let mut identifiers = HashSet::new();
+        let mut signers = HashSet::new();
+        let mut seen_link = false;
+        for block in &self.chain {
+            let identifier = block.identifier();
+            if !identifiers.insert(serialisation::serialise(identifier).unwrap_or_default()) {
+                return Err(InvariantViolation::DuplicateBlock(identifier.clone()));
+            }
+            let proofs = block.proofs();
+            if proofs.iter()
+                .enumerate()
+                .any(|(i, x)| proofs[..i].iter().any(|y| y.key() == x.key())) {
+                return Err(InvariantViolation::DuplicateProof(identifier.clone()));
+            }
+            if !block.valid {
+                continue;
+            }
+            if !block.validate_block_signatures() {
+                return Err(InvariantViolation::BadSignature(identifier.clone()));
+            }
+            if !seen_link && !identifier.is_link() {
+                return Err(InvariantViolation::DataBeforeLink(identifier.clone()));
+            }
+            if seen_link && self.group_size > 0 &&
+               !proofs.iter().any(|x| signers.contains(x.key())) {
+                return Err(InvariantViolation::Ungoverned(identifier.clone()));
+            }
+            if identifier.is_link() {
+                seen_link = true;
+                signers.extend(block.members());
+            }
+        }
+        Ok(())
     }
 
-    /// Check if chain contains a particular identifier
-    pub fn contains(&self, block_identifier: &BlockIdentifier) -> bool {
-        self.chain.iter().any(|x| x.identifier() == block_identifier)
+    /// Summary of the valid blocks held, to send to a peer that replies with what we lack from its
+    /// `missing_from`.
+    pub fn digest(&self) -> ChainDigest {
+        let valid = self.chain.iter().filter(|x| x.valid).map(|x| x.identifier()).collect_vec();
+        ChainDigest::new(valid.into_iter())
     }
 
-    /// Return position of block identifier
-    pub fn position(&self, block_identifier: &BlockIdentifier) -> Option<usize> {
-        self.chain.iter().position(|x| x.identifier() == block_identifier)
+    /// Identifiers of the valid blocks held here that the chain summarised by `digest` lacks, in
+    /// chain order. A few may be missed, see `ChainDigest`.
+    pub fn missing_from(&self, digest: &ChainDigest) -> Vec<BlockIdentifier> {
+        self.chain
+            .iter()
+            .filter(|x| x.valid && !digest.contains(x.identifier()))
+            .map(|x| x.identifier().clone())
+            .collect()
     }
 
-    /// Inserts an element at position index within the chain, shifting all elements
-    /// after it to the right.
+    /// find a block (user required to test for validity)
+    pub fn find(&self, block_identifier: &BlockIdentifier) -> Option<&Block> {
+        self.chain.iter().find(|x| x.identifier() == block_identifier)
+    }
+
+    /// find block by name from top (only first occurrence)
+    pub fn find_name(&self, name: &[u8; 32]) -> Option<&Block> {
+        self.chain.iter().rev().find(|x| x.valid && x.identifier().names().contains(&name))
+    }
+
+    /// The latest valid block committing to the data `identifier` (see
+    /// `BlockIdentifier::data_identifiers`). Unlike `find_name` this tells apart structured and
+    /// mutable data sharing a name by their type tag.
+    pub fn find_by_identifier(&self, identifier: &DataIdentifier) -> Option<&Block> {
+        self.chain
+            .iter()
+            .rev()
+            .find(|x| x.valid && x.identifier().data_identifiers().contains(identifier))
+    }
+
+    /// Whether the latest valid block about `name` is a `Deleted` tombstone, rather than data
+    /// put again since.
+    pub fn is_deleted(&self, name: &[u8; 32]) -> bool {
+        for block in self.chain.iter().rev().filter(|x| x.valid) {
+            match *block.identifier() {
+                BlockIdentifier::Deleted(ref id) if id.name() == name => return true,
+                ref identifier if identifier.names().contains(&name) => return false,
+                _ => (),
+            }
+        }
+        false
+    }
+
+    /// Remove a block, will ignore Links and pinned blocks
+    pub fn remove(&mut self, data_id: &BlockIdentifier) {
+        if self.is_pinned(data_id) {
+            return;
+        }
+        self.mutated();
+        self.drop_blocks(|x| x.identifier() != data_id || x.identifier().is_link());
+    }
+
+    /// Retains only the blocks specified by the predicate. Pinned blocks are always retained.
+    pub fn retain<F>(&mut self, pred: F)
+        where F: FnMut(&Block) -> bool
+    {
+        self.mutated();
+        self.drop_blocks(pred);
+    }
+
+    /// Pin a block so it survives `prune`, `remove`, `retain`, `clear` and any other trimming of
+    /// the chain.
+    /// The block need not be in the chain yet. Returns `false` if it was already pinned.
+    pub fn pin(&mut self, block_identifier: &BlockIdentifier) -> bool {
+        if self.is_pinned(block_identifier) {
+            return false;
+        }
+        self.pinned.push(block_identifier.clone());
+        self.mutated();
+        true
+    }
+
+    /// Remove a pin, the block is then subject to normal pruning again.
+    /// Returns `false` if the block was not pinned.
+    pub fn unpin(&mut self, block_identifier: &BlockIdentifier) -> bool {
+        let len = self.pinned.len();
+        self.pinned.retain(|x| x != block_identifier);
+        if len == self.pinned.len() {
+            return false;
+        }
+        self.mutated();
+        true
+    }
+
+    /// Is this block pinned
+    pub fn is_pinned(&self, block_identifier: &BlockIdentifier) -> bool {
+        self.pinned.contains(block_identifier)
+    }
+
+    /// All pinned identifiers, in the order they were pinned
+    pub fn pinned(&self) -> &[BlockIdentifier] {
+        &self.pinned
+    }
+
+    /// Clear chain, except for pinned blocks
+    pub fn clear(&mut self) {
+        self.mutated();
+        self.drop_blocks(|_| false);
+    }
+
+    /// Check if chain contains a particular identifier
+    pub fn contains(&self, block_identifier: &BlockIdentifier) -> bool {
+        self.chain.iter().any(|x| x.identifier() == block_identifier)
+    }
+
+    /// Return position of block identifier
+    pub fn position(&self, block_identifier: &BlockIdentifier) -> Option<usize> {
+        self.chain.iter().position(|x| x.identifier() == block_identifier)
+    }
+
+    /// Inserts an element at position index within the chain, shifting all elements
+    /// after it to the right, and records the `Block::prev_hash` of it and the block after it.
     /// Will not validate this block!
     /// # Panics
     ///
     /// Panics if index is greater than the chains length.
     pub fn insert(&mut self, index: usize, block: Block) {
-        self.chain.insert(index, block)
+        self.mutated();
+        self.chain.insert(index, block);
+        self.relink(index);
     }
 
     /// Validates an individual block. Will get latest link and confirm all signatures
     /// were from last known valid group.
-    pub fn validate_block(&mut self, block: &mut Block) -> bool {
-        for link in &self.valid_links_at_block_id(block.identifier()) {
-            if Self::validate_block_with_proof(block, link, self.group_size) {
-                block.valid = true;
-                return true;
+    pub fn validate_block(&self, block: &mut Block) -> bool {
+        self.verify_block(block).is_ok()
+    }
+
+    /// As `validate_block` but reports why the block is invalid. Like `mark_blocks_valid` this
+    /// first removes any invalid signatures from `block`, failing with `BadSignature` only if the
+    /// quorum was lost by doing so.
+    pub fn verify_block(&self, block: &mut Block) -> Result<(), ValidationError> {
+        let link = match self.valid_links_at_block_id(block.identifier()) {
+            Some(link) => link,
+            None => return Err(ValidationError::MissingLink),
+        };
+        let proofs_len = block.proofs().len();
+        block.remove_invalid_signatures();
+        if Self::validate_block_with_proof(block, &link, self.group_size_at(&link)) {
+            block.valid = true;
+            Ok(())
+        } else if block.proofs().len() < proofs_len {
+            Err(ValidationError::BadSignature)
+        } else {
+            Err(ValidationError::NoQuorum)
+        }
+    }
+
+    /// Collapse data blocks repeated within an epoch (see `epochs`), e.g. a chunk republished
+    /// and merged in again, to the copy with the most proofs, the earliest on a tie. Returns how
+    /// many blocks were removed. Links, and repeats in different epochs, are left alone.
+    pub fn compact(&mut self) -> usize {
+        let mut keep = vec![true; self.chain.len()];
+        let mut seen = HashMap::new();
+        for (index, block) in self.chain.iter().enumerate() {
+            if block.identifier().is_link() {
+                if block.valid {
+                    seen.clear();
+                }
+                continue;
+            }
+            let key = serialisation::serialise(block.identifier()).unwrap_or_default();
+            let best = *seen.entry(key.clone()).or_insert(index);
+            if best == index {
+                continue;
+            }
+            if block.proofs().len() > self.chain[best].proofs().len() {
+                keep[best] = false;
+                let _ = seen.insert(key, index);
+            } else {
+                keep[index] = false;
             }
         }
-        false
+        if keep.iter().all(|x| *x) {
+            return 0;
+        }
+        let len = self.chain.len();
+        self.mutated();
+        let mut keep = keep.into_iter();
+        self.drop_blocks(|_| keep.next().unwrap_or(true));
+        len - self.chain.len()
     }
 
     /// Removes all invalid blocks, does not confirm chain is valid to this group.
+    /// Pinned blocks are kept regardless of validity.
     pub fn prune(&mut self) {
         self.mark_blocks_valid();
-        self.chain.retain(|x| x.valid);
+        self.mutated();
+        self.drop_blocks(|x| x.valid);
+    }
+
+    /// Mark every block that expires (see `BlockIdentifier::expiring`) at or before `now`, in
+    /// seconds since the unix epoch, invalid and, if `prune` is set, remove them. Pinned blocks
+    /// are kept, though invalid. Returns the identifiers of the expired blocks.
+    /// Validation reads no clock: it only keeps the blocks expired by the latest `now` given here
+    /// invalid, so they are not revived.
+    pub fn expire(&mut self, now: u64, prune: bool) -> Vec<BlockIdentifier> {
+        self.expired_at = cmp::max(self.expired_at, now);
+        let expired = |block: &Block| block.identifier().expires_at().is_some_and(|x| x <= now);
+        let mut identifiers = vec![];
+        for block in self.chain.iter_mut().filter(|x| expired(x)) {
+            block.valid = false;
+            identifiers.push(block.identifier().clone());
+        }
+        if identifiers.is_empty() {
+            return identifiers;
+        }
+        self.mutated();
+        if prune {
+            self.drop_blocks(|x| !expired(x));
+        } else {
+            self.report_gauges();
+        }
+        identifiers
     }
 
     /// Total length of chain
@@ -303,48 +1381,220 @@ impl DataChain {
         self.chain.iter().filter(|x| x.identifier().is_link() && x.valid).count()
     }
 
+    /// The tip of the chain: its last valid link, which governs the blocks voted for now.
+    pub fn tip(&self) -> Option<&Block> {
+        self.chain.iter().rev().find(|x| x.identifier().is_link() && x.valid)
+    }
+
+    /// Height of the chain: the number of valid links, see `tip`.
+    pub fn height(&self) -> usize {
+        self.links_len()
+    }
+
+    /// Hash of the `tip` and its proofs, in sorted order, so peers can advertise and compare chain
+    /// heads compactly. Nodes holding different proofs of the same link hash it differently.
+    pub fn tip_hash(&self) -> Option<[u8; 32]> {
+        let tip = self.tip()?;
+        let mut proofs = tip.proofs().clone();
+        proofs.sort();
+        serialisation::serialise(&(tip.identifier(), proofs)).ok().map(|bytes| hash(&bytes))
+    }
+
+    /// Voting members as of the last valid link. These are the elders of the last valid
+    /// `Membership` link, less nodes lost and with keys rotated since, a loss undone by a valid
+    /// `CancelNodeLost` or `CancelSplitFrom` link. Without a `Membership` link every member of the
+    /// last valid link votes.
+    pub fn current_elders(&self) -> Vec<PublicKey> {
+        self.roster().unwrap_or_else(|| {
+            self.chain
+                .iter()
+                .rev()
+                .find(|x| x.identifier().is_link() && x.valid)
+                .map_or(vec![], |x| x.members())
+        })
+    }
+
+    /// Check `signatures` of `message` are from a quorum of the `current_elders`, as the proofs
+    /// of a block must be, e.g. for an action the group agreed on outside the chain. Fails with
+    /// `Error::MissingLink` without a valid link, `Error::Unsigned` for no signatures,
+    /// `Error::Signature` if any is not from an elder and `Error::Quorum` for too few elders.
+    pub fn verify_group_signatures(&self,
+                                   message: &[u8],
+                                   signatures: &[Signature])
+                                   -> Result<(), Error> {
+        let elders = self.current_elders();
+        if elders.is_empty() {
+            return Err(Error::MissingLink);
+        }
+        if signatures.is_empty() {
+            return Err(Error::Unsigned);
+        }
+        let signers = signatures.iter()
+            .map(|sig| elders.iter().find(|key| Ed25519::verify(sig, message, key)))
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::Signature)?;
+        let needed = cmp::min((elders.len() + 1) / 2, self.current_group_size());
+        let got = signers.into_iter().unique().count();
+        if got < needed {
+            return Err(Error::Quorum {
+                needed: needed,
+                got: got,
+            });
+        }
+        Ok(())
+    }
+
+    // The `current_elders`, if a valid `Membership` link recorded roles. A `CancelNodeLost` link
+    // reinstates an elder lost since, and a `CancelSplitFrom` link every elder lost since the
+    // `SplitFrom` link it cancels.
+    fn roster(&self) -> Option<Vec<PublicKey>> {
+        let start = self.chain.iter().rposition(|x| {
+            x.valid &&
+            x.identifier()
+                .link_descriptor()
+                .is_some_and(|link| matches!(*link, LinkDescriptor::Membership(_)))
+        })?;
+        let mut elders = self.chain[start].members();
+        let mut lost = vec![];
+        let mut split: Option<(&Prefix, usize)> = None;
+        for block in self.chain[start + 1..].iter().filter(|x| x.valid) {
+            match block.identifier().link_descriptor() {
+                Some(LinkDescriptor::NodeLost(gone)) => {
+                    if elders.contains(gone) {
+                        elders.retain(|x| x != gone);
+                        lost.push(*gone);
+                    }
+                }
+                Some(LinkDescriptor::CancelNodeLost(back)) => {
+                    if let Some(index) = lost.iter().rposition(|x| x == back) {
+                        elders.push(lost.remove(index));
+                        match split {
+                            Some((_, ref mut since)) if index < *since => *since -= 1,
+                            _ => (),
+                        }
+                    }
+                }
+                Some(LinkDescriptor::SplitFrom(prefix)) => split = Some((prefix, lost.len())),
+                Some(LinkDescriptor::CancelSplitFrom(prefix)) => {
+                    if let Some((_, since)) = split.filter(|x| x.0 == prefix) {
+                        elders.extend(lost.drain(since..));
+                        split = None;
+                    }
+                }
+                Some(LinkDescriptor::KeyChanged { old, new }) => {
+                    for key in elders.iter_mut().filter(|x| *x == old) {
+                        *key = *new;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Some(elders)
+    }
+
+    /// Age of the member with `key`: the number of valid links since the `NodeGained` link that
+    /// added it, so 0 for the newest member. Ages carry over a `KeyChanged` rotation and a
+    /// `CancelNodeLost`. `None` if the node is not a member as of the last valid link.
+    pub fn node_age(&self, key: &PublicKey) -> Option<u64> {
+        let mut joined = BTreeMap::new();
+        let mut lost = BTreeMap::new();
+        let mut links = 0;
+        for block in self.chain.iter().filter(|x| x.valid) {
+            let descriptor = match block.identifier().link_descriptor() {
+                Some(descriptor) => descriptor,
+                None => continue,
+            };
+            match *descriptor {
+                LinkDescriptor::NodeGained(ref gained) => {
+                    let _ = joined.entry(*gained).or_insert(links);
+                }
+                LinkDescriptor::NodeLost(ref gone) => {
+                    if let Some(link) = joined.remove(gone) {
+                        let _ = lost.insert(*gone, link);
+                    }
+                }
+                LinkDescriptor::CancelNodeLost(ref back) => {
+                    if let Some(link) = lost.remove(back) {
+                        let _ = joined.insert(*back, link);
+                    }
+                }
+                LinkDescriptor::KeyChanged { ref old, ref new } => {
+                    if let Some(link) = joined.remove(old) {
+                        let _ = joined.insert(*new, link);
+                    }
+                }
+                _ => (),
+            }
+            links += 1;
+        }
+        joined.get(key).map(|&link| links - 1 - link)
+    }
+
+    /// What `key` was paid less what it paid by the valid `Transfer` blocks. Credits from outside
+    /// the chain are not recorded here, so this may be negative.
+    pub fn balance_of(&self, key: &PublicKey) -> i128 {
+        self.chain
+            .iter()
+            .filter(|x| x.valid)
+            .map(|block| match *block.identifier().without_expiry() {
+                BlockIdentifier::Transfer { ref from, ref to, amount, .. } => {
+                    let amount = amount as i128;
+                    (if to == key { amount } else { 0 }) - (if from == key { amount } else { 0 })
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
     /// Contains no blocks that are not valid
     pub fn is_empty(&self) -> bool {
         self.chain.is_empty()
     }
 
     /// Should contain majority of the current common_close_group
-    fn last_valid_link(&mut self) -> Option<&mut Block> {
-        self.chain.iter_mut().rev().find(|x| x.identifier().is_link() && x.valid)
-    }
-
     /// Returns all links in chain
     /// Does not perform validation on links
     pub fn all_links(&self) -> Vec<Block> {
         self.chain
             .iter()
-            .cloned()
             .filter(|x| x.identifier().is_link())
+            .cloned()
             .collect_vec()
     }
 
-    /// Validates and returns all valid data blocks in chain
-    pub fn valid_data(&mut self) -> Vec<Block> {
-        self.mark_blocks_valid();
-        self.chain
-            .iter()
-            .cloned()
-            .filter(|x| !x.identifier().is_link() && x.valid)
-            .collect_vec()
+    /// Validates and returns all valid data blocks in chain, see `snapshot`.
+    pub fn valid_data(&self) -> Vec<Block> {
+        self.snapshot().valid_data()
     }
 
-    /// Validates and returns all links in chain
-    pub fn valid_links(&mut self) -> Vec<Block> {
-        self.mark_blocks_valid();
-        self.chain
-            .iter()
-            .cloned()
-            .filter(|x| x.identifier().is_link() && x.valid)
-            .collect_vec()
+    /// Validates and returns all links in chain, see `snapshot`.
+    pub fn valid_links(&self) -> Vec<Block> {
+        self.snapshot().valid_links()
+    }
+
+    /// The blocks as `mark_blocks_valid` would leave them, leaving this chain as it is. The view
+    /// is validated once per change to the chain and shared until the next, so taking one is
+    /// cheap while the chain is unchanged, and readers on other threads need no lock on the chain.
+    pub fn snapshot(&self) -> ChainSnapshot {
+        let mut cached = self.snapshot.lock().unwrap();
+        if let Some((mutations, ref snapshot)) = *cached {
+            if mutations == self.mutations {
+                return snapshot.clone();
+            }
+        }
+        let mut chain = DataChain::from_blocks(self.chain.clone(), self.group_size);
+        chain.pinned = self.pinned.clone();
+        chain.validation_mode = self.validation_mode;
+        chain.hash_algorithm = self.hash_algorithm;
+        chain.expired_at = self.expired_at;
+        chain.mark_blocks_valid();
+        let snapshot = ChainSnapshot::new(chain.chain, self.group_size);
+        *cached = Some((self.mutations, snapshot.clone()));
+        snapshot
     }
 
-    /// Validates and returns the previous valid link in chain before the target
-    pub fn valid_links_at_block_id(&mut self, block_id: &BlockIdentifier) -> Option<Block> {
+    /// Returns the previous valid link in chain before the target
+    pub fn valid_links_at_block_id(&self, block_id: &BlockIdentifier) -> Option<Block> {
         self.chain
             .iter()
             .rev()
@@ -355,149 +1605,653 @@ impl DataChain {
     }
 
 
-    /// Mark all links that are valid as such.
+    /// Mark all links that are valid as such. A block is also invalid unless its `Block::prev_hash`
+    /// is the `block_hash` of the block before it, so blocks inserted, removed or moved other than
+    /// through this chain are detected along with the block after them. Only the first block, and
+    /// blocks from before hashes were recorded, which come ahead of every block with one, may have
+    /// none.
     pub fn mark_blocks_valid(&mut self) {
-        if let Some(mut first_link) =
-            self.chain
+        let mut cursor = ValidationCursor::default();
+        let _ = self.mark_blocks_valid_partial(&mut cursor, usize::MAX);
+        let _ = self.trim_proofs();
+    }
+
+    /// Incremental form of `mark_blocks_valid`, validating at most `max_blocks` blocks starting
+    /// from `cursor`. Returns `true` once the pass has reached the end of the chain, at which point
+    /// the cursor is reset ready for the next pass. If the chain was modified since the cursor's
+    /// last call the pass restarts from the beginning, as blocks may have moved or the cached
+    /// governing link may be out of date.
+    pub fn mark_blocks_valid_partial(&mut self,
+                                     cursor: &mut ValidationCursor,
+                                     max_blocks: usize)
+                                     -> bool {
+        if cursor.position == 0 || cursor.position > self.chain.len() ||
+           cursor.mutations != self.mutations {
+            cursor.position = 0;
+            cursor.linked = false;
+            cursor.group_size = self.group_size;
+            cursor.link = self.chain
                 .iter()
-                .cloned()
-                .find(|x| x.identifier().is_link()) {
-            for block in &mut self.chain {
-                block.remove_invalid_signatures();
-                if Self::validate_block_with_proof(block, &first_link, self.group_size) {
-                    block.valid = true;
-                    if block.identifier().is_link() {
-                        first_link = block.clone();
-                    }
-                } else {
-                    block.valid = false;
+                .find(|x| x.identifier().is_link())
+                .cloned();
+        }
+        let mut first_link = match cursor.link.take() {
+            Some(link) => link,
+            None => {
+                let len = self.chain.len();
+                self.drop_blocks(|_| false);
+                if len != self.chain.len() {
+                    self.mutated();
+                }
+                *cursor = ValidationCursor::default();
+                return true;
+            }
+        };
+        let mut changed = false;
+        let mut newly_valid = vec![];
+        let mut group_size = cursor.group_size;
+        let dual = self.validation_mode == ValidationMode::DualLink;
+        let mut following: Option<(usize, Option<Block>)> = None;
+        let mut linked = cursor.linked;
+        let expired_at = self.expired_at;
+        let end = cmp::min(self.chain.len(), cursor.position.saturating_add(max_blocks));
+        for index in cursor.position..end {
+            if dual && following.as_ref().is_none_or(|x| index >= x.0) {
+                following = Some(self.following_link(index, &first_link, group_size));
+            }
+            let in_order = match self.chain[index].prev_hash() {
+                Some(prev) => {
+                    index.checked_sub(1).is_some_and(|x| *prev == self.block_hash(&self.chain[x]))
                 }
+                None => !linked,
+            };
+            linked |= self.chain[index].prev_hash().is_some();
+            let block = &mut self.chain[index];
+            let (proofs_len, valid) = (block.proofs().len(), block.valid);
+            block.remove_invalid_signatures();
+            let live = block.identifier().expires_at().is_none_or(|x| x > expired_at);
+            block.valid = in_order && live &&
+                          Self::validate_block_with_proof(block, &first_link, group_size);
+            if block.valid && !block.identifier().is_link() {
+                if let Some(&(_, Some(ref next))) = following.as_ref() {
+                    block.valid = Self::has_majority(block, next);
+                }
+            }
+            if block.valid && block.identifier().is_link() {
+                group_size = reconfigured_size(block).unwrap_or(group_size);
+                first_link = block.clone();
             }
+            if block.valid && !valid {
+                newly_valid.push(block.identifier().clone());
+            }
+            changed |= proofs_len != block.proofs().len() || valid != block.valid;
+        }
+        for identifier in &newly_valid {
+            self.notify_valid(identifier);
+        }
+        if changed {
+            // Other cursors may hold blocks or links this pass has just changed.
+            self.mutated();
+            self.report_gauges();
+        }
+        let validated = end - cursor.position;
+        if end == self.chain.len() {
+            *cursor = ValidationCursor { validated: validated, ..ValidationCursor::default() };
+            true
         } else {
-            self.chain.clear();
+            cursor.position = end;
+            cursor.link = Some(first_link);
+            cursor.linked = linked;
+            cursor.group_size = group_size;
+            cursor.mutations = self.mutations;
+            cursor.validated = validated;
+            false
+        }
+    }
+
+    /// Apply the `set_proof_retention` policy, trimming each valid data block governed by a link
+    /// before the latest valid one (see `Block::trim_proofs`) to its quorum plus the margin. The
+    /// payer of a transfer and the members of the governing link, and in
+    /// `ValidationMode::DualLink` of the link after, are kept first, and a block that would no
+    /// longer validate keeps all its proofs, so trimmed blocks still pass `mark_blocks_valid`.
+    /// Links and the latest epoch are never trimmed, as later blocks are checked against their
+    /// signers. Returns the number of proofs dropped.
+    pub fn trim_proofs(&mut self) -> usize {
+        let margin = match self.proof_retention {
+            Some(margin) => margin,
+            None => return 0,
+        };
+        let end = match self.chain.iter().rposition(|x| x.valid && x.identifier().is_link()) {
+            Some(end) => end,
+            None => return 0,
+        };
+        let dual = self.validation_mode == ValidationMode::DualLink;
+        let mut link: Option<Block> = None;
+        let mut group_size = self.group_size;
+        let mut dropped = 0;
+        for index in 0..end {
+            if !self.chain[index].valid {
+                continue;
+            }
+            if self.chain[index].identifier().is_link() {
+                group_size = reconfigured_size(&self.chain[index]).unwrap_or(group_size);
+                link = Some(self.chain[index].clone());
+                continue;
+            }
+            let link = match link {
+                Some(ref link) => link,
+                None => continue,
+            };
+            let following = if dual {
+                self.following_link(index, link, group_size).1
+            } else {
+                None
+            };
+            let mut block = self.chain[index].clone();
+            let keep = cmp::max(Self::quorum(link, group_size),
+                                following.as_ref().map_or(0, |x| (x.members().len() + 1) / 2));
+            let mut wanted = link.members();
+            wanted.extend(following.as_ref().map_or(vec![], |x| x.members()));
+            let payer = transfer_payer(block.identifier()).cloned();
+            block.proofs_mut().sort_by_key(|x| {
+                (Some(x.key()) != payer.as_ref(), !wanted.contains(x.key()), *x.key())
+            });
+            let trimmed = block.trim_proofs(keep + margin);
+            if trimmed > 0 && Self::validate_block_with_proof(&block, link, group_size) &&
+               following.as_ref().is_none_or(|x| Self::has_majority(&block, x)) {
+                self.chain[index] = block;
+                dropped += trimmed;
+            }
+        }
+        if dropped > 0 {
+            self.mutated();
+        }
+        dropped
+    }
+
+    /// Top up the proofs of the blocks we hold with those `other`, a replica of this chain, holds
+    /// for the same blocks (see `Block::merge_proofs`), then revalidate so blocks reaching quorum
+    /// become valid. Unlike `merge_chain` no blocks are added. Returns the number of proofs added.
+    pub fn merge_proofs_from(&mut self, other: &DataChain) -> usize {
+        let mut added = 0;
+        for block in &mut self.chain {
+            if let Some(theirs) = other.find(block.identifier()) {
+                added += block.merge_proofs(theirs);
+            }
+        }
+        if added > 0 {
+            self.mutated();
+            self.mark_blocks_valid();
         }
+        added
     }
 
     /// Merge any blocks from a given chain
+    ///
+    /// Fails with `Error::Validation`, merging nothing, if `chain` has another `hash_algorithm`.
+    /// Identifiers do not record the algorithm their hashes were made with, so blocks of chains
+    /// hashing differently can't be validated together; set the algorithm of received chains
+    /// before merging.
     /// FIXME - this needs a complete rewrite
-    pub fn merge_chain(&mut self, chain: &mut DataChain) {
+    pub fn merge_chain(&mut self, chain: &mut DataChain) -> Result<(), Error> {
+        if chain.hash_algorithm != self.hash_algorithm {
+            return Err(Error::Validation);
+        }
         chain.mark_blocks_valid();
         chain.prune();
+        self.mutated();
         let mut start_pos = 0;
         for new in chain.chain().iter().filter(|x| x.identifier().is_block()) {
+            if self.contains(new.identifier()) {
+                continue;
+            }
+            // Insert after the first of our valid links the block has a quorum of, so it is
+            // governed by that link.
             let mut insert = false;
             for (pos, val) in self.chain.iter().enumerate().skip(start_pos) {
-                if DataChain::validate_block_with_proof(new, val, self.group_size) {
-                    start_pos = pos;
+                if val.valid && val.identifier().is_link() &&
+                   DataChain::validate_block_with_proof(new, val, self.group_size_at(val)) {
+                    start_pos = pos + 1;
                     insert = true;
                     break;
                 }
             }
 
             if insert {
-                self.chain.insert(start_pos, new.clone());
+                let mut block = new.clone();
+                block.valid = true;
+                self.chain.insert(start_pos, block);
+                self.relink(start_pos);
                 start_pos += 1;
             }
         }
+        self.report_gauges();
+        Ok(())
     }
 
-    fn validate_block_with_proof(block: &Block, proof: &Block, group_size: usize) -> bool {
-        let p_len = proof.proofs()
-            .iter()
-            .filter(|&y| block.proofs().iter().any(|p| p.key() == y.key()))
-            .count();
-        (p_len * 2 >= proof.proofs().len()) || (p_len >= group_size)
+    /// Split the chain of the section `prefix` into the chains of its two halves, whose prefixes
+    /// are `prefix` extended by a 0 and a 1 bit (see `Prefix::pushed`). Every link is copied to
+    /// both, as the halves share the group history up to the split link, while data blocks go
+    /// to the half covering their name, or neither if outside `prefix`. Each half is revalidated
+    /// on its own and keeps the pins of the blocks it holds, but no store or subscribers.
+    pub fn split(&self, prefix: &Prefix) -> (DataChain, DataChain) {
+        let half = |bit| {
+            let prefix = prefix.pushed(bit);
+            let mut chain = DataChain::from_blocks(self.chain.clone(), self.group_size);
+            chain.drop_blocks(|x| {
+                x.identifier().is_link() ||
+                x.identifier().name().is_some_and(|name| prefix.matches(name))
+            });
+            chain.pinned = self.pinned.iter().filter(|x| chain.contains(x)).cloned().collect();
+            chain.mark_blocks_valid();
+            chain
+        };
+        (half(false), half(true))
     }
-}
 
-impl Debug for DataChain {
-    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        let print_block = |block: &Block| -> String {
-            let mut output = format!("    Block {{\n        identifier: {:?}\n        valid: {}\n",
-                                     block.identifier(),
-                                     block.valid);
-            for proof in block.proofs() {
-                output.push_str(&format!("        {:?}\n", proof))
+    /// Reverse of `split`, merging the chain of a sibling section into this one to give the chain
+    /// of their parent section `prefix`. Blocks we lack are inserted after the last block we
+    /// share that precedes them in `other`, so links and data keep their governing links. Data
+    /// outside `prefix` is dropped, unless pinned, and the result revalidated.
+    pub fn merge_on(&mut self, other: &DataChain, prefix: &Prefix) {
+        self.mutated();
+        let mut next = 0;
+        for block in &other.chain {
+            match self.position(block.identifier()) {
+                Some(pos) => next = pos + 1,
+                None => {
+                    // Merged blocks follow what precedes them here, not in the sibling.
+                    self.chain.insert(next, block.clone());
+                    self.relink(next);
+                    next += 1;
+                }
             }
-            output.push_str("    }");
-            output
-        };
-        write!(formatter,
-               "DataChain {{\n    group_size: {}\n    path: ",
-               self.group_size)?;
-        match self.path {
-            Some(ref path) => writeln!(formatter, "{}", path.display())?,
-            None => writeln!(formatter, "None")?,
         }
-        if self.chain.is_empty() {
-            write!(formatter, "    chain empty }}")
-        } else {
-            for block in &self.chain {
-                writeln!(formatter, "{}", print_block(block))?
-            }
-            write!(formatter, "}}")
+        for identifier in &other.pinned {
+            let _ = self.pin(identifier);
         }
+        self.drop_blocks(|x| {
+            x.identifier().is_link() ||
+            x.identifier().name().is_some_and(|name| prefix.matches(name))
+        });
+        self.mark_blocks_valid();
     }
-}
 
-#[cfg(test)]
-//#[cfg_attr(rustfmt, rustfmt_skip)]
-mod tests {
-    extern crate env_logger;
-    use chain::block_identifier::{BlockIdentifier, LinkDescriptor};
-    use chain::vote::Vote;
-    use itertools::Itertools;
-    use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
-    use super::*;
-    use tempdir::TempDir;
+    /// Call `callback` with every later `ChainEvent`, until `unsubscribe` is called with the
+    /// returned id. Callbacks run while the chain is borrowed, so must not use it themselves.
+    pub fn subscribe<F>(&mut self, callback: F) -> u64
+        where F: FnMut(ChainEvent) + Send + 'static
+    {
+        let id = self.next_subscriber;
+        self.next_subscriber += 1;
+        self.subscribers.push((id, Box::new(callback)));
+        id
+    }
 
-    pub struct Node {
-        pub sec_key: SecretKey,
-        pub pub_key: PublicKey,
+    /// Stop calling the callback given the id `id` by `subscribe`, returns `false` if unknown.
+    pub fn unsubscribe(&mut self, id: u64) -> bool {
+        let len = self.subscribers.len();
+        self.subscribers.retain(|x| x.0 != id);
+        len != self.subscribers.len()
     }
 
-    pub fn node() -> Node {
-        let keys = sign::gen_keypair();
-        Node {
-            sec_key: keys.1,
-            pub_key: keys.0,
+    fn notify(&mut self, event: ChainEvent) {
+        for &mut (_, ref mut callback) in &mut self.subscribers {
+            callback(event.clone());
         }
     }
 
-    #[test]
-    fn genesis() {
-        let _ = env_logger::init();
-        ::rust_sodium::init();
-        let nodes = (0..100).map(|_| node()).collect_vec();
-        let add_node_1 =
-            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key.clone()));
-        let add_node_2 =
-            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key.clone()));
-        let add_node_3 =
-            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key.clone()));
-        let add_node_4 =
-            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[4].pub_key.clone()));
-        let remove_node_3 =
-            BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[3].pub_key.clone()));
+    // Notify subscribers of a newly valid block.
+    fn notify_valid(&mut self, identifier: &BlockIdentifier) {
+        if let Some(ref sink) = self.metrics {
+            sink.increment(metrics::BLOCKS_VALIDATED, &[], 1);
+        }
+        if identifier.is_link() {
+            self.notify(ChainEvent::LinkValid(identifier.clone()));
+        } else {
+            self.notify(ChainEvent::BlockValid(identifier.clone()));
+        }
+    }
 
-        let mut chain = DataChain::default();
-        assert!(chain.is_empty());
-        assert!(chain.add_vote(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_1)
-                        .unwrap())
-                    .is_some(),
-                "Add first node, should accumulate as valid.");
-        assert!(chain.add_vote(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, add_node_2.clone())
-                        .unwrap())
-                    .is_none(),
-                "Node2 adds link claiming to be from it. Should be none as this node is not in \
-                 chain.");
-        assert!(chain.add_vote(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_2.clone())
-                        .unwrap())
-                    .is_some(),
-                "This vote should count and validate vote on its own. Node 2 should not be able \
-                 to vote for itself being added.");
-        assert!(chain.add_vote(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, add_node_2)
-                        .unwrap())
+    // Remove the blocks failing `keep`, other than pinned blocks, notifying subscribers of each.
+    // `keep` is called once for every block, in chain order. A block whose predecessor is removed
+    // is linked to the block now before it, the chain itself having removed the predecessor.
+    fn drop_blocks<F>(&mut self, mut keep: F)
+        where F: FnMut(&Block) -> bool
+    {
+        let mut pruned = vec![];
+        let mut relinked = vec![];
+        {
+            let pinned = &self.pinned;
+            let (mut kept, mut gap) = (0, false);
+            self.chain.retain(|x| if keep(x) || pinned.contains(x.identifier()) {
+                if gap && x.prev_hash().is_some() {
+                    relinked.push(kept);
+                }
+                kept += 1;
+                gap = false;
+                true
+            } else {
+                pruned.push(x.identifier().clone());
+                gap = true;
+                false
+            });
+        }
+        for pos in relinked {
+            self.record_prev_hash(pos);
+        }
+        for identifier in pruned {
+            self.notify(ChainEvent::BlockPruned(identifier));
+        }
+        self.report_gauges();
+    }
+
+    // Take a token from the bucket of `key`, or return `false` if it is empty.
+    fn take_rate_token(&mut self, key: &PublicKey) -> bool {
+        let limit = match self.rate_limit {
+            Some(limit) => limit,
+            None => return true,
+        };
+        let now = Instant::now();
+        let tokens = |bucket: &(f64, Instant)| {
+            let refill = now.duration_since(bucket.1).as_secs_f64() * limit.per_second as f64;
+            (bucket.0 + refill).min(limit.burst as f64)
+        };
+        if self.rate_buckets.len() >= MAX_RATE_BUCKETS {
+            self.rate_buckets.retain(|_, bucket| tokens(bucket) < limit.burst as f64);
+        }
+        let bucket = self.rate_buckets.entry(*key).or_insert((limit.burst as f64, now));
+        let available = tokens(bucket);
+        if available < 1.0 {
+            return false;
+        }
+        *bucket = (available - 1.0, now);
+        true
+    }
+
+    // The earlier vote of `vote`'s key for a pending link conflicting with it, so a fork of the
+    // group's state. Links already valid may be followed by any other.
+    fn conflicting_vote(&self, vote: &Vote) -> Option<Vote> {
+        let descriptor = vote.identifier().link_descriptor()?;
+        self.chain
+            .iter()
+            .filter(|x| {
+                !x.valid &&
+                x.identifier().link_descriptor().is_some_and(|x| x.conflicts_with(descriptor))
+            })
+            .filter_map(|block| {
+                block.proofs()
+                    .iter()
+                    .find(|x| x.key() == vote.proof().key())
+                    .map(|x| Vote::from_parts(block.identifier().clone(), x.clone()))
+            })
+            .next()
+    }
+
+    // Count an offence against `key`, blocklisting it once it reaches the threshold.
+    fn record_offence<F>(&mut self, key: PublicKey, offence: F)
+        where F: FnOnce(&mut OffenceReport)
+    {
+        let total = {
+            let report = self.offences.entry(key).or_default();
+            offence(report);
+            report.total()
+        };
+        if self.blocklist_threshold.is_some_and(|x| total >= x) && self.blocklist.insert(key) {
+            self.notify(ChainEvent::Blocklisted(key));
+        }
+    }
+
+    fn reject_vote(&self, reason: &'static str) {
+        if let Some(ref sink) = self.metrics {
+            sink.increment(metrics::VOTES_REJECTED, &[("reason", reason)], 1);
+        }
+    }
+
+    fn report_gauges(&self) {
+        if let Some(ref sink) = self.metrics {
+            sink.set(metrics::CHAIN_LENGTH, self.chain.len() as u64);
+            sink.set(metrics::VALID_LINKS, self.links_len() as u64);
+        }
+    }
+
+    // Record a change to the blocks or pins, invalidating any partial validation pass in progress.
+    fn mutated(&mut self) {
+        self.mutations = self.mutations.wrapping_add(1);
+    }
+
+    // Set the `prev_hash` of the block at `pos` to the hash of the block before it, or to none
+    // for the first block. A block placed ahead of one without, from before hashes were recorded,
+    // gets none either, so blocks without one stay ahead of every block with one.
+    fn record_prev_hash(&mut self, pos: usize) {
+        let unlinked = self.chain.get(pos + 1).is_some_and(|x| x.prev_hash().is_none());
+        let prev = match pos.checked_sub(1) {
+            Some(before) if !unlinked => Some(self.block_hash(&self.chain[before])),
+            _ => None,
+        };
+        self.chain[pos].set_prev_hash(prev);
+    }
+
+    // Record the `prev_hash` of a block placed at `pos` and of the block now after it.
+    fn relink(&mut self, pos: usize) {
+        self.record_prev_hash(pos);
+        if self.chain.get(pos + 1).is_some_and(|x| x.prev_hash().is_some()) {
+            self.record_prev_hash(pos + 1);
+        }
+    }
+
+    // Number of signatures from `link`'s members a block needs, see `validate_block_with_proof`.
+    fn quorum(link: &Block, group_size: usize) -> usize {
+        cmp::min((link.members().len() + 1) / 2, group_size)
+    }
+
+    // The first link after `index` that a quorum of `link` signed, with its position, or the end
+    // of the chain and `None` if there is no such link.
+    fn following_link(&self,
+                      index: usize,
+                      link: &Block,
+                      group_size: usize)
+                      -> (usize, Option<Block>) {
+        for (position, block) in self.chain.iter().enumerate().skip(index + 1) {
+            if block.identifier().is_link() {
+                let mut block = block.clone();
+                block.remove_invalid_signatures();
+                if Self::validate_block_with_proof(&block, link, group_size) {
+                    return (position, Some(block));
+                }
+            }
+        }
+        (self.chain.len(), None)
+    }
+
+    // Whether `block` holds proofs from at least half of `link`'s members, as
+    // `validate_block_with_proof` requires without a group size.
+    fn has_majority(block: &Block, link: &Block) -> bool {
+        let members = link.members();
+        let p_len = members.iter()
+            .filter(|&y| block.proofs().iter().any(|p| p.key() == y))
+            .count();
+        p_len * 2 >= members.len()
+    }
+
+    // The group size in force for blocks `link` governs: the one set by the latest valid
+    // `Reconfigure` link up to and including it, or else the configured one.
+    fn group_size_at(&self, link: &Block) -> usize {
+        let end = self.position(link.identifier()).map_or(self.chain.len(), |x| x + 1);
+        self.chain[..end]
+            .iter()
+            .rev()
+            .filter(|x| x.valid)
+            .find_map(reconfigured_size)
+            .unwrap_or(self.group_size)
+    }
+
+    fn validate_block_with_proof(block: &Block, proof: &Block, group_size: usize) -> bool {
+        let members = proof.members();
+        let p_len = members.iter()
+            .filter(|&y| block.proofs().iter().any(|p| p.key() == y))
+            .count();
+        let paid = transfer_payer(block.identifier())
+            .is_none_or(|payer| block.proofs().iter().any(|x| x.key() == payer));
+        let quorate = match reconfigured_size(block) {
+            // A resize needs a majority of the group as it was, however small the size it
+            // replaces, and cannot leave a group whose blocks need no signatures.
+            Some(size) => size > 0 && p_len > 0 && p_len * 2 >= members.len(),
+            None => (p_len * 2 >= members.len()) || (p_len >= group_size),
+        };
+        paid && quorate
+    }
+}
+
+// The group size `block` sets, if it is a `Reconfigure` link.
+fn reconfigured_size(block: &Block) -> Option<usize> {
+    match block.identifier().link_descriptor() {
+        Some(&LinkDescriptor::Reconfigure { group_size }) => Some(group_size as usize),
+        _ => None,
+    }
+}
+
+// The key that must sign `identifier` if it is a `Transfer`.
+fn transfer_payer(identifier: &BlockIdentifier) -> Option<&PublicKey> {
+    match *identifier.without_expiry() {
+        BlockIdentifier::Transfer { ref from, .. } => Some(from),
+        _ => None,
+    }
+}
+
+// The `vote_binding` of a chain whose last valid link is `link`.
+fn link_binding(link: &Block) -> [u8; 32] {
+    hash(&link.identifier().to_signable_bytes())
+}
+
+// Only the chain itself is encoded, as it was before stores were added. Settings and write
+// accounting start from their defaults when decoding, and a chain with a path writes back to it.
+impl Encodable for DataChain {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        (&self.chain, self.group_size, &self.path, &self.pinned).encode(encoder)
+    }
+}
+
+impl Decodable for DataChain {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<DataChain, D::Error> {
+        let (chain, group_size, path, pinned): (Vec<Block>,
+                                                usize,
+                                                Option<PathBuf>,
+                                                Vec<BlockIdentifier>) =
+            Decodable::decode(decoder)?;
+        let store: Option<Box<dyn ChainStore + Send>> = match path {
+            Some(ref path) => Some(Box::new(FileStore::new(path.clone()))),
+            None => None,
+        };
+        Ok(DataChain {
+            chain: chain,
+            group_size: group_size,
+            path: path,
+            store: store,
+            pinned: pinned,
+            ..DataChain::default()
+        })
+    }
+}
+
+impl PartialEq for DataChain {
+    fn eq(&self, other: &DataChain) -> bool {
+        self.chain == other.chain && self.group_size == other.group_size &&
+        self.path == other.path && self.pinned == other.pinned
+    }
+}
+
+impl Debug for DataChain {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        let print_block = |block: &Block| -> String {
+            let mut output = format!("    Block {{\n        identifier: {:?}\n        valid: {}\n",
+                                     block.identifier(),
+                                     block.valid);
+            for proof in block.proofs() {
+                output.push_str(&format!("        {:?}\n", proof))
+            }
+            output.push_str("    }");
+            output
+        };
+        write!(formatter,
+               "DataChain {{\n    group_size: {}\n    path: ",
+               self.group_size)?;
+        match self.path {
+            Some(ref path) => writeln!(formatter, "{}", path.display())?,
+            None => writeln!(formatter, "None")?,
+        }
+        if self.chain.is_empty() {
+            write!(formatter, "    chain empty }}")
+        } else {
+            for block in &self.chain {
+                writeln!(formatter, "{}", print_block(block))?
+            }
+            write!(formatter, "}}")
+        }
+    }
+}
+
+#[cfg(test)]
+//#[cfg_attr(rustfmt, rustfmt_skip)]
+mod tests {
+    extern crate env_logger;
+    use chain::block_identifier::{BlockIdentifier, LinkDescriptor, Membership, Role};
+    use chain::vote::Vote;
+    use chain::proof::Proof;
+    use itertools::Itertools;
+    use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+    use chain::persistence::DirStore;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use tempdir::TempDir;
+
+    pub struct Node {
+        pub sec_key: SecretKey,
+        pub pub_key: PublicKey,
+    }
+
+    pub fn node() -> Node {
+        let keys = sign::gen_keypair();
+        Node {
+            sec_key: keys.1,
+            pub_key: keys.0,
+        }
+    }
+
+    #[test]
+    fn genesis() {
+        let _ = env_logger::init();
+        let _ = ::rust_sodium::init();
+        let nodes = (0..100).map(|_| node()).collect_vec();
+        let add_node_1 =
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 =
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let add_node_3 =
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let add_node_4 =
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[4].pub_key));
+        let remove_node_3 =
+            BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[3].pub_key));
+
+        let mut chain = DataChain::default();
+        assert!(chain.is_empty());
+        assert!(chain.add_vote(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_1)
+                        .unwrap())
+                    .is_some(),
+                "Add first node, should accumulate as valid.");
+        assert!(chain.add_vote(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, add_node_2.clone())
+                        .unwrap())
+                    .is_none(),
+                "Node2 adds link claiming to be from it. Should be none as this node is not in \
+                 chain.");
+        assert!(chain.add_vote(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_2.clone())
+                        .unwrap())
+                    .is_some(),
+                "This vote should count and validate vote on its own. Node 2 should not be able \
+                 to vote for itself being added.");
+        assert!(chain.add_vote(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, add_node_2)
+                        .unwrap())
                     .is_none(),
                 "Again check node2 cannot vote for itself.");
         assert!(chain.add_vote(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, add_node_3.clone())
@@ -542,33 +2296,1536 @@ mod tests {
         let nodes = (0..100).map(|_| node()).collect_vec();
         let mut chain = DataChain::default();
         let add_node_1 =
-            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key.clone()));
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
         // let add_node_2 =
-        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key.clone()));
+        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
         // let add_node_3 =
-        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key.clone()));
+        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
         // let add_node_4 =
-        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[4].pub_key.clone()));
+        //     BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[4].pub_key));
         // let remove_node_3 =
-        //     BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[3].pub_key.clone()));
+        //     BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[3].pub_key));
         assert!(chain.add_vote(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_1)
                         .unwrap())
                     .is_some(),
                 "Add first node, should accumulate as valid.");
     }
 
+    #[test]
+    fn pinned_blocks_survive_prune() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"audited"));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, link)))
+            .is_some());
+        // Signed by a non member so never valid.
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[2].pub_key,
+                                                 &nodes[2].sec_key,
+                                                 data.clone())))
+            .is_some());
+        assert!(chain.pin(&data));
+        assert!(!chain.pin(&data));
+        assert!(chain.is_pinned(&data));
+        chain.prune();
+        assert!(chain.contains(&data));
+        chain.remove(&data);
+        assert!(chain.contains(&data));
+        chain.clear();
+        assert_eq!(chain.chain().iter().map(|x| x.identifier()).collect_vec(), vec![&data]);
+        assert_eq!(chain.pinned(), &[data.clone()]);
+        assert!(chain.unpin(&data));
+        assert!(!chain.unpin(&data));
+        chain.prune();
+        assert!(!chain.contains(&data));
+    }
+
+    #[test]
+    fn find_by_identifier() {
+        let _ = ::rust_sodium::init();
+        let node = node();
+        let name = ::sha3::hash(b"name");
+        let block = |id: BlockIdentifier, valid: bool| {
+            let mut block = unwrap!(Block::new(unwrap!(Vote::new(&node.pub_key,
+                                                                 &node.sec_key,
+                                                                 id))));
+            block.valid = valid;
+            block
+        };
+        let tagged = |tag: u64| {
+            BlockIdentifier::StructuredData(::sha3::hash(&[tag as u8]),
+                                            DataIdentifier::Structured(name, tag))
+        };
+        let batch = BlockIdentifier::Batch(vec![DataIdentifier::Mutable(name, 1)], [0; 32]);
+        let chain = DataChain::from_blocks(vec![block(tagged(1), true),
+                                                block(tagged(2), true),
+                                                block(tagged(3), false),
+                                                block(batch.clone(), true)],
+                                           999);
+        assert_eq!(unwrap!(chain.find_name(&name)).identifier(), &batch);
+        let found = |id: DataIdentifier| chain.find_by_identifier(&id).map(|x| x.identifier());
+        assert_eq!(found(DataIdentifier::Structured(name, 1)), Some(&tagged(1)));
+        assert_eq!(found(DataIdentifier::Structured(name, 2)), Some(&tagged(2)));
+        assert_eq!(found(DataIdentifier::Structured(name, 3)), None);
+        assert_eq!(found(DataIdentifier::Mutable(name, 1)), Some(&batch));
+        assert_eq!(found(DataIdentifier::Immutable(name)), None);
+    }
+
+    #[test]
+    fn custom_blocks() {
+        let _ = ::rust_sodium::init();
+        let keys = node();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let vote = |id| unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, id));
+        let custom = |tag| {
+            BlockIdentifier::Custom {
+                tag: tag,
+                hash: [tag as u8; 32],
+            }
+        };
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.pub_key));
+        let _ = chain.add_vote(vote(link));
+        chain.set_custom_validator(Some(Box::new(|tag: u64, hash: &[u8; 32]| {
+            tag == 1 && hash[0] == 1
+        })));
+        assert!(chain.add_vote(vote(custom(1))).is_some());
+        assert!(unwrap!(chain.find(&custom(1))).valid);
+        assert_eq!(chain.verify_vote(&vote(custom(2))),
+                   Err(ValidationError::CustomRejected));
+        let expiring = unwrap!(BlockIdentifier::expiring(custom(2), u64::MAX));
+        assert!(chain.add_vote(vote(expiring)).is_none());
+        assert_eq!(chain.len(), 2);
+        // Nothing is stored for them, so they have no name or data.
+        assert!(custom(1).name().is_none() && custom(1).data_identifiers().is_empty());
+
+        chain.set_custom_validator(None);
+        assert!(chain.add_vote(vote(custom(2))).is_some());
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn merge_refuses_other_hash_algorithm() {
+        let _ = ::rust_sodium::init();
+        let blocks = ::chain::test_blocks(2);
+        let mut chain = DataChain::from_blocks(blocks[..1].to_vec(), 999);
+        let mut other = DataChain::default();
+        unwrap!(other.set_hash_algorithm(HashAlgorithm::Blake3));
+        for (index, block) in blocks.into_iter().enumerate() {
+            other.insert(index, block);
+        }
+        match chain.merge_chain(&mut other) {
+            Err(Error::Validation) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(chain.len(), 1);
+    }
+
+    #[test]
+    fn merge_proofs_from() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let proof = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone())).proof().clone()
+        };
+        let link_id = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let mut link = Block::from_parts(link_id.clone(), vec![], false);
+        for node in &nodes {
+            unwrap!(link.add_proof(proof(node, &link_id)));
+        }
+        let data = BlockIdentifier::ImmutableData([3; 32]);
+        let replica = |signer: &Node| {
+            let block = Block::from_parts(data.clone(), vec![proof(signer, &data)], false);
+            let mut chain = DataChain::from_blocks(vec![link.clone(), block], 4);
+            chain.mark_blocks_valid();
+            chain
+        };
+        let mut ours = replica(&nodes[1]);
+        let mut theirs = replica(&nodes[2]);
+        assert!(!unwrap!(ours.find(&data)).valid);
+        let forged = unwrap!(Vote::new(&nodes[3].pub_key, &nodes[0].sec_key, data.clone()));
+        theirs.chain[1].proofs_mut().push(forged.proof().clone());
+
+        assert_eq!(ours.merge_proofs_from(&theirs), 1);
+        let block = unwrap!(ours.find(&data));
+        assert!(block.valid);
+        assert_eq!(block.proofs().len(), 2);
+        assert_eq!(ours.merge_proofs_from(&theirs), 0);
+        let other_block = theirs.chain[0].clone();
+        assert_eq!(theirs.chain[1].merge_proofs(&other_block), 0);
+        assert_eq!(theirs.merge_proofs_from(&ours), 1);
+    }
+
+    #[test]
+    fn proof_retention() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..7).map(|_| node()).collect_vec();
+        let block = |id: BlockIdentifier, signers: &[Node]| {
+            let mut block = Block::from_parts(id.clone(), vec![], false);
+            for node in signers {
+                let vote = unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()));
+                unwrap!(block.add_proof(vote.proof().clone()));
+            }
+            block
+        };
+        let members = &nodes[..6];
+        let old = BlockIdentifier::ImmutableData([1; 32]);
+        let new = BlockIdentifier::ImmutableData([2; 32]);
+        let link = |node: &Node| BlockIdentifier::Link(LinkDescriptor::NodeGained(node.pub_key));
+        let blocks = vec![block(link(&nodes[0]), members),
+                          block(old.clone(), &nodes),
+                          block(link(&nodes[1]), members),
+                          block(new.clone(), members)];
+        let mut chain = DataChain::from_blocks(blocks, 6);
+        chain.mark_blocks_valid();
+        assert_eq!(chain.trim_proofs(), 0);
+
+        chain.set_proof_retention(Some(1));
+        chain.mark_blocks_valid();
+        let trimmed = unwrap!(chain.find(&old));
+        assert!(trimmed.valid);
+        assert_eq!(trimmed.proofs().len(), 4);
+        assert!(trimmed.proofs().iter().all(|x| x.key() != &nodes[6].pub_key));
+        assert_eq!(unwrap!(chain.find(&new)).proofs().len(), 6);
+        assert!(chain.chain().iter().filter(|x| x.identifier().is_link()).all(|x| {
+            x.proofs().len() == 6
+        }));
+        chain.mark_blocks_valid();
+        assert!(chain.chain().iter().all(|x| x.valid));
+        assert_eq!(chain.trim_proofs(), 0);
+
+        let mut block = block(new, members);
+        assert_eq!(block.trim_proofs(2), 4);
+        assert_eq!(block.trim_proofs(2), 0);
+    }
+
+    #[test]
+    fn hash_linked_order() {
+        let _ = ::rust_sodium::init();
+        let keys = node();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let vote = |id| unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, id));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.pub_key));
+        let (first, second, third) = (BlockIdentifier::ImmutableData([1; 32]),
+                                      BlockIdentifier::ImmutableData([2; 32]),
+                                      BlockIdentifier::ImmutableData([3; 32]));
+        for id in vec![link, first.clone(), second, third] {
+            assert!(chain.add_vote(vote(id)).is_some());
+        }
+        let blocks = chain.chain().clone();
+        assert!(blocks[0].prev_hash().is_none());
+        for pos in 1..blocks.len() {
+            assert_eq!(blocks[pos].prev_hash(), Some(&chain.block_hash(&blocks[pos - 1])));
+        }
+        chain.mark_blocks_valid();
+        assert_eq!(chain.valid_len(), 4);
+
+        let validity = |positions: &[usize], change: &dyn Fn(&mut Vec<Block>)| {
+            let mut blocks = positions.iter().map(|&x| blocks[x].clone()).collect_vec();
+            change(&mut blocks);
+            let mut chain = DataChain::from_blocks(blocks, 999);
+            chain.mark_blocks_valid();
+            chain.chain().iter().map(|x| x.valid).collect_vec()
+        };
+        // Moving, removing or inserting a block is detected at the blocks it no longer follows
+        // or precedes, as is a hash stripped from a block.
+        assert_eq!(validity(&[0, 2, 1, 3], &|_| ()), vec![true, false, false, false]);
+        assert_eq!(validity(&[0, 2, 3], &|_| ()), vec![true, false, true]);
+        let extra = unwrap!(Block::new(vote(BlockIdentifier::ImmutableData([9; 32]))));
+        assert_eq!(validity(&[0, 1, 2, 3], &|blocks| {
+                       let mut extra = extra.clone();
+                       extra.set_prev_hash(blocks[2].prev_hash().cloned());
+                       blocks.insert(2, extra);
+                   }),
+                   vec![true, true, true, false, true]);
+        assert_eq!(validity(&[0, 1, 2, 3], &|blocks| blocks[2].set_prev_hash(None)),
+                   vec![true, true, false, true]);
+
+        // Blocks removed through the chain link their successor to the block now before it.
+        chain.remove(&first);
+        assert_eq!(chain.chain()[1].prev_hash(), Some(&chain.block_hash(&chain.chain()[0])));
+        chain.mark_blocks_valid();
+        assert_eq!(chain.valid_len(), 3);
+    }
+
+    #[test]
+    fn transfers() {
+        let _ = ::rust_sodium::init();
+        let (member, payer, payee) = (node(), node(), node());
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(member.pub_key));
+        let _ = chain.add_vote(vote(&member, &link));
+        let transfer = |amount| {
+            BlockIdentifier::Transfer {
+                from: payer.pub_key,
+                to: payee.pub_key,
+                amount: amount,
+                hash: [amount as u8; 32],
+            }
+        };
+
+        // The group's quorum is not enough without the payer, nor the payer without the group.
+        let _ = chain.add_vote(vote(&member, &transfer(5)));
+        assert!(!unwrap!(chain.find(&transfer(5))).valid);
+        let _ = chain.add_vote(vote(&payer, &transfer(5)));
+        assert!(unwrap!(chain.find(&transfer(5))).valid);
+        let _ = chain.add_vote(vote(&payer, &transfer(7)));
+        assert!(!unwrap!(chain.find(&transfer(7))).valid);
+        chain.mark_blocks_valid();
+        assert_eq!(chain.valid_len(), 2);
+
+        assert_eq!(chain.balance_of(&payer.pub_key), -5);
+        assert_eq!(chain.balance_of(&payee.pub_key), 5);
+        assert_eq!(chain.balance_of(&member.pub_key), 0);
+    }
+
+    #[test]
+    fn expire() {
+        let _ = ::rust_sodium::init();
+        let node = node();
+        let block = |id: BlockIdentifier| {
+            unwrap!(Block::new(unwrap!(Vote::new(&node.pub_key, &node.sec_key, id))))
+        };
+        let expiring = |name: u8, expires_at: u64| {
+            unwrap!(BlockIdentifier::expiring(BlockIdentifier::ImmutableData([name; 32]),
+                                              expires_at))
+        };
+        let (past, soon, later, pinned) = (expiring(1, 1), expiring(2, 10), expiring(3, 20),
+                                           expiring(4, 10));
+        let kept = BlockIdentifier::ImmutableData([5; 32]);
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(node.pub_key));
+        let mut chain = DataChain::from_blocks(vec![block(link),
+                                                    block(past.clone()),
+                                                    block(soon.clone()),
+                                                    block(later.clone()),
+                                                    block(pinned.clone()),
+                                                    block(kept.clone())],
+                                               999);
+        // Validation reads no clock, so blocks long past their expiry stay valid until `expire`.
+        chain.mark_blocks_valid();
+        assert_eq!(chain.valid_len(), 6);
+        assert!(chain.pin(&pinned));
+
+        assert_eq!(chain.expire(0, true), vec![]);
+        assert_eq!(chain.expire(10, false), vec![past.clone(), soon.clone(), pinned.clone()]);
+        assert_eq!((chain.len(), chain.valid_len()), (6, 3));
+        assert_eq!(chain.snapshot().valid_len(), 3);
+        chain.mark_blocks_valid();
+        assert_eq!((chain.len(), chain.valid_len()), (6, 3));
+        assert_eq!(chain.expire(20, true), vec![past, soon, later, pinned.clone()]);
+        assert_eq!(chain.len(), 3);
+        assert!(chain.contains(&pinned) && !unwrap!(chain.find(&pinned)).valid);
+        assert!(unwrap!(chain.find(&kept)).valid);
+    }
+
+    #[test]
+    fn reconfigure_group_size() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let mut chain = DataChain::from_blocks(vec![], 1);
+        let vote = |chain: &mut DataChain, voter: usize, id: &BlockIdentifier| {
+            let _ = chain.add_vote(unwrap!(Vote::new(&nodes[voter].pub_key,
+                                                     &nodes[voter].sec_key,
+                                                     id.clone())));
+        };
+        let valid = |chain: &DataChain, id: &BlockIdentifier| unwrap!(chain.find(id)).valid;
+        for (joined, node) in nodes.iter().enumerate() {
+            let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(node.pub_key));
+            for voter in 0..cmp::max(joined, 1) {
+                vote(&mut chain, voter, &link);
+            }
+        }
+        let before = BlockIdentifier::ImmutableData([1; 32]);
+        vote(&mut chain, 0, &before);
+
+        // A resize needs a majority of the group, a configured size of 1 is not enough.
+        let resize = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 999 });
+        vote(&mut chain, 0, &resize);
+        chain.mark_blocks_valid();
+        assert!(valid(&chain, &before) && !valid(&chain, &resize));
+        vote(&mut chain, 1, &resize);
+        assert!(valid(&chain, &resize));
+        vote(&mut chain, 2, &resize);
+        assert_eq!((chain.group_size(), chain.current_group_size()), (1, 999));
+
+        // Blocks after the resize need a majority, those before keep their single signature.
+        let after = BlockIdentifier::ImmutableData([2; 32]);
+        vote(&mut chain, 0, &after);
+        chain.mark_blocks_valid();
+        assert!(valid(&chain, &before) && valid(&chain, &resize));
+        assert!(!valid(&chain, &after));
+
+        let empty = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 0 });
+        for voter in 0..3 {
+            vote(&mut chain, voter, &empty);
+        }
+        chain.mark_blocks_valid();
+        assert!(!valid(&chain, &empty));
+        assert_eq!(chain.current_group_size(), 999);
+    }
+
+    #[test]
+    fn dual_link_validation() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let block = |id: BlockIdentifier, signers: &[usize]| {
+            let proofs = signers.iter()
+                .map(|&x| {
+                    unwrap!(Vote::new(&nodes[x].pub_key, &nodes[x].sec_key, id.clone()))
+                        .proof()
+                        .clone()
+                })
+                .collect();
+            Block::from_parts(id, proofs, false)
+        };
+        // Node 2 leaves and node 3 takes its place. Node 2's signature still counts towards the
+        // first link's quorum, so a block it signed with one other member slips in.
+        let first = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let snuck_in = BlockIdentifier::ImmutableData([1; 32]);
+        let agreed = BlockIdentifier::ImmutableData([2; 32]);
+        let next = BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[2].pub_key));
+        let last = BlockIdentifier::ImmutableData([3; 32]);
+        let mut chain = DataChain::from_blocks(vec![block(first, &[0, 1, 2]),
+                                                    block(snuck_in.clone(), &[1, 2]),
+                                                    block(agreed.clone(), &[0, 1]),
+                                                    block(next.clone(), &[0, 1, 3]),
+                                                    block(last.clone(), &[0, 3])],
+                                               999);
+        let valid = |chain: &DataChain, id: &BlockIdentifier| unwrap!(chain.find(id)).valid;
+        chain.mark_blocks_valid();
+        assert_eq!(chain.valid_len(), 5);
+
+        chain.set_validation_mode(ValidationMode::DualLink);
+        assert_eq!(chain.validation_mode(), ValidationMode::DualLink);
+        chain.mark_blocks_valid();
+        assert!(!valid(&chain, &snuck_in));
+        assert!(valid(&chain, &agreed) && valid(&chain, &next) && valid(&chain, &last));
+
+        // Partial passes agree, whichever slice the following link falls in.
+        chain.set_validation_mode(ValidationMode::SingleLink);
+        chain.mark_blocks_valid();
+        chain.set_validation_mode(ValidationMode::DualLink);
+        let mut cursor = ValidationCursor::default();
+        while !chain.mark_blocks_valid_partial(&mut cursor, 1) {}
+        assert_eq!(chain.valid_len(), 4);
+        assert!(!valid(&chain, &snuck_in));
+    }
+
+    #[test]
+    fn validation_errors() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let add_node_0 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let mut chain = DataChain::default();
+        assert_eq!(chain.verify_ownership(&[nodes[0].pub_key]),
+                   Err(ValidationError::MissingLink));
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key,
+                                                 &nodes[0].sec_key,
+                                                 add_node_0)))
+            .is_some());
+        assert_eq!(chain.verify_ownership(&[nodes[0].pub_key]), Ok(()));
+        assert_eq!(chain.verify_ownership(&[nodes[2].pub_key]),
+                   Err(ValidationError::StaleGroup));
+        let self_vote = unwrap!(Vote::new(&nodes[1].pub_key, &nodes[1].sec_key, add_node_1));
+        assert_eq!(chain.verify_vote(&self_vote), Err(ValidationError::SelfVote));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut block = unwrap!(Block::new(unwrap!(Vote::new(&nodes[2].pub_key,
+                                                             &nodes[2].sec_key,
+                                                             data))));
+        assert_eq!(chain.verify_block(&mut block), Err(ValidationError::MissingLink));
+    }
+
+    #[test]
+    fn verify_block_ignores_bad_signatures() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        let link_id = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let mut link = unwrap!(Block::new(vote(&nodes[0], &link_id)));
+        for node in &nodes[1..3] {
+            unwrap!(link.add_proof(vote(node, &link_id).proof().clone()));
+        }
+        link.valid = true;
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let other = BlockIdentifier::ImmutableData(::sha3::hash(b"other"));
+        // Block for `data` with good signatures from `good` nodes and forged ones from `bad` nodes.
+        let block = |good: &[usize], bad: &[usize]| {
+            let mut block = unwrap!(Block::new(vote(&nodes[good[0]], &data)));
+            for &i in &good[1..] {
+                unwrap!(block.add_proof(vote(&nodes[i], &data).proof().clone()));
+            }
+            for &i in bad {
+                let forged = vote(&nodes[i], &other);
+                block.proofs_mut().push(Proof::new(nodes[i].pub_key, *forged.proof().sig()));
+            }
+            block
+        };
+        let chain = DataChain::from_blocks(vec![link, block(&[0], &[])], 999);
+        let mut quorum_and_forgery = block(&[0, 1], &[2]);
+        assert_eq!(chain.verify_block(&mut quorum_and_forgery), Ok(()));
+        assert_eq!(quorum_and_forgery.proofs().len(), 2);
+        assert!(chain.validate_block(&mut block(&[0, 1], &[2])));
+        assert_eq!(chain.verify_block(&mut block(&[0], &[1, 2])),
+                   Err(ValidationError::BadSignature));
+        assert_eq!(chain.verify_block(&mut block(&[0], &[])),
+                   Err(ValidationError::NoQuorum));
+    }
+
+    #[test]
+    fn bound_votes_cannot_be_replayed() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let add_node_0 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut chain = DataChain::default();
+        chain.set_vote_binding(true);
+        assert_eq!(chain.vote_binding(), [0; 32]);
+        let unbound = unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, add_node_0.clone()));
+        assert_eq!(chain.verify_vote(&unbound), Err(ValidationError::BindingMismatch));
+        let genesis = unwrap!(Vote::new_bound(&nodes[0].pub_key,
+                                              &nodes[0].sec_key,
+                                              add_node_0,
+                                              chain.vote_binding()));
+        assert!(chain.add_vote(genesis).is_some());
+        let old_binding = [0; 32];
+        assert!(chain.vote_binding() != old_binding);
+        let replayed = unwrap!(Vote::new_bound(&nodes[0].pub_key,
+                                               &nodes[0].sec_key,
+                                               data.clone(),
+                                               old_binding));
+        assert!(replayed.validate());
+        assert!(chain.add_vote(replayed).is_none());
+        let current = unwrap!(Vote::new_bound(&nodes[0].pub_key,
+                                              &nodes[0].sec_key,
+                                              data.clone(),
+                                              chain.vote_binding()));
+        assert_eq!(chain.add_vote(current), Some(data));
+    }
+
+    #[test]
+    fn bound_votes_survive_new_links() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..5).map(|_| node()).collect_vec();
+        let vote = |node: &Node, id: &BlockIdentifier, binding: [u8; 32]| {
+            unwrap!(Vote::new_bound(&node.pub_key, &node.sec_key, id.clone(), binding))
+        };
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let add_node_3 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        chain.set_vote_binding(true);
+        assert!(chain.add_vote(vote(&nodes[0], &add_node_1, [0; 32])).is_some());
+        let first = chain.vote_binding();
+        assert!(chain.add_vote(vote(&nodes[0], &add_node_2, first)).is_some());
+        let second = chain.vote_binding();
+        assert!(second != first);
+        // A late vote for the link which just became valid, signed against its governing link.
+        assert!(chain.add_vote(vote(&nodes[1], &add_node_2, first)).is_some());
+        // Voting on `data` starts, then a new link becomes valid before it completes.
+        assert!(chain.add_vote(vote(&nodes[0], &data, second)).is_some());
+        assert!(!unwrap!(chain.find(&data)).valid);
+        assert!(chain.add_vote(vote(&nodes[0], &add_node_3, second)).is_some());
+        assert_eq!(chain.add_vote(vote(&nodes[1], &add_node_3, second)),
+                   Some(add_node_3.clone()));
+        let third = chain.vote_binding();
+        assert!(third != second);
+        // A vote still in flight for `data` is accepted, as is one from a peer on the newer tip.
+        assert_eq!(chain.add_vote(vote(&nodes[1], &data, second)), Some(data.clone()));
+        assert!(chain.add_vote(vote(&nodes[2], &data, third)).is_some());
+        assert!(unwrap!(chain.find(&data)).validate_block_signatures());
+        // New blocks only accept the current tip, so old bindings cannot be replayed.
+        let other = BlockIdentifier::ImmutableData(::sha3::hash(b"other"));
+        assert_eq!(chain.verify_vote(&vote(&nodes[0], &other, second)),
+                   Err(ValidationError::BindingMismatch));
+        // With binding off, bound and unbound proofs accumulate in the same block.
+        chain.set_vote_binding(false);
+        let unbound = unwrap!(Vote::new(&nodes[3].pub_key, &nodes[3].sec_key, data.clone()));
+        assert!(chain.add_vote(unbound).is_some());
+        assert_eq!(unwrap!(chain.find(&data)).proofs().len(), 4);
+    }
+
+    #[test]
+    fn stale_votes_rejected() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..2).map(|_| node()).collect_vec();
+        let add_node_0 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut chain = DataChain::default();
+        chain.set_max_clock_skew(Some(60));
+        let untimed = unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, add_node_0.clone()));
+        assert_eq!(chain.verify_vote(&untimed), Err(ValidationError::ClockSkew));
+        let now = unix_time();
+        assert!(chain.add_vote(unwrap!(Vote::new_timestamped(&nodes[0].pub_key,
+                                                             &nodes[0].sec_key,
+                                                             add_node_0,
+                                                             None,
+                                                             now - 10)))
+            .is_some());
+        let stale = unwrap!(Vote::new_timestamped(&nodes[0].pub_key,
+                                                  &nodes[0].sec_key,
+                                                  data.clone(),
+                                                  None,
+                                                  now - 3600));
+        assert!(stale.validate());
+        assert_eq!(chain.verify_vote(&stale), Err(ValidationError::ClockSkew));
+        assert!(chain.add_vote(unwrap!(Vote::new_timestamped(&nodes[0].pub_key,
+                                                             &nodes[0].sec_key,
+                                                             data.clone(),
+                                                             None,
+                                                             now)))
+            .is_some());
+        let block = unwrap!(chain.find(&data));
+        assert!(block.validate_block_signatures());
+        assert_eq!(block.earliest_proof_time(), Some(now));
+        assert_eq!(unwrap!(chain.find(chain.chain()[0].identifier())).latest_proof_time(),
+                   Some(now - 10));
+    }
+
+    #[test]
+    fn write_policy_and_amplification() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..2).map(|_| node()).collect_vec();
+        let dir = unwrap!(TempDir::new("test_data_chain"));
+        let mut chain = unwrap!(DataChain::create_in_path(dir.path().to_path_buf(), 999));
+        chain.set_write_policy(WritePolicy {
+            min_new_bytes: 1 << 20,
+            max_skipped_writes: 1,
+            ..WritePolicy::default()
+        });
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, link)))
+            .is_some());
+        assert!(!unwrap!(chain.write()));
+        assert_eq!(chain.write_stats().writes, 0);
+        assert_eq!(chain.write_stats().skipped_writes, 1);
+        assert_eq!(chain.write_stats().amplification(), None);
+        // Skipped too often, so this one goes to disk.
+        assert!(unwrap!(chain.write()));
+        assert_eq!(chain.write_stats().writes, 1);
+        assert_eq!(chain.write_stats().amplification(), Some(1.0));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, data)))
+            .is_some());
+        unwrap!(chain.flush());
+        assert_eq!(chain.write_stats().writes, 2);
+        assert!(unwrap!(chain.write_stats().amplification()) > 1.0);
+        assert_eq!(unwrap!(DataChain::from_path(dir.path().to_path_buf(), 999)).len(), 2);
+    }
+
+    #[test]
+    fn recover_damaged_file() {
+        let _ = ::rust_sodium::init();
+        let keys = node();
+        let dir = unwrap!(TempDir::new("test_data_chain"));
+        let mut chain = unwrap!(DataChain::create_in_path(dir.path().to_path_buf(), 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.pub_key));
+        let _ = chain.add_vote(unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, link)));
+        for name in &[b"one", b"two"] {
+            let data = BlockIdentifier::ImmutableData(::sha3::hash(&name[..]));
+            let _ = chain.add_vote(unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, data)));
+        }
+        unwrap!(chain.flush());
+        let path = dir.path().join("data_chain");
+        let len = unwrap!(fs::metadata(&path)).len();
+        unwrap!(unwrap!(fs::OpenOptions::new().write(true).open(&path)).set_len(len - 60));
+        assert!(DataChain::from_path(dir.path().to_path_buf(), 999).is_err());
+
+        let (mut recovered, report) =
+            unwrap!(DataChain::from_path_recover(dir.path().to_path_buf(), 999));
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.bytes_kept + report.bytes_dropped, len - 60);
+        assert_eq!(recovered.chain(), &chain.chain()[..2]);
+        // The next flush replaces the damaged file.
+        unwrap!(recovered.flush());
+        assert_eq!(unwrap!(DataChain::from_path(dir.path().to_path_buf(), 999)).len(), 2);
+    }
+
+    #[test]
+    fn tip_and_height() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..2).map(|_| node()).collect_vec();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        assert!(chain.tip().is_none() && chain.tip_hash().is_none());
+        assert_eq!(chain.height(), 0);
+        let links = nodes.iter()
+            .map(|x| BlockIdentifier::Link(LinkDescriptor::NodeGained(x.pub_key)))
+            .collect_vec();
+        let _ = chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key,
+                                                 &nodes[0].sec_key,
+                                                 links[0].clone())));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let _ = chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, data)));
+        assert_eq!(chain.tip().map(|x| x.identifier()), Some(&links[0]));
+        assert_eq!(chain.height(), 1);
+        let first = chain.tip_hash();
+
+        let _ = chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key,
+                                                 &nodes[0].sec_key,
+                                                 links[1].clone())));
+        assert_eq!(chain.tip().map(|x| x.identifier()), Some(&links[1]));
+        assert_eq!(chain.height(), 2);
+        assert!(chain.tip_hash().is_some() && chain.tip_hash() != first);
+    }
+
+    #[test]
+    fn rate_limited_votes() {
+        let _ = ::rust_sodium::init();
+        let keys = node();
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let sink = events.clone();
+        let _ = chain.subscribe(move |event| unwrap!(sink.lock()).push(event));
+        chain.set_rate_limit(Some(RateLimit {
+            burst: 2,
+            per_second: 0,
+        }));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.pub_key));
+        assert!(chain.add_vote(unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, link))).is_some());
+        for name in &[b"one", b"two"] {
+            let data = BlockIdentifier::ImmutableData(::sha3::hash(&name[..]));
+            let _ = chain.add_vote(unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, data)));
+        }
+        assert_eq!(chain.len(), 2);
+        assert_eq!(unwrap!(events.lock()).last(),
+                   Some(&ChainEvent::RateLimited(keys.pub_key)));
+
+        // Other keys have their own bucket.
+        let other = node();
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"three"));
+        let _ = chain.add_vote(unwrap!(Vote::new(&other.pub_key, &other.sec_key, data)));
+        assert_eq!(chain.len(), 3);
+    }
+
+    #[test]
+    fn offenders_are_blocklisted() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let sink = events.clone();
+        let _ = chain.subscribe(move |event| unwrap!(sink.lock()).push(event));
+        chain.set_blocklist_threshold(Some(2));
+        let vote = |node: &Node, id| unwrap!(Vote::new(&node.pub_key, &node.sec_key, id));
+        let link = |descriptor| BlockIdentifier::Link(descriptor);
+        let _ = chain.add_vote(vote(&nodes[0], link(LinkDescriptor::NodeGained(nodes[0].pub_key))));
+        let grow = link(LinkDescriptor::Reconfigure { group_size: 5 });
+        assert!(chain.add_vote(vote(&nodes[1], grow)).is_some());
+        let shrink = link(LinkDescriptor::Reconfigure { group_size: 3 });
+        assert!(chain.add_vote(vote(&nodes[1], shrink.clone())).is_none());
+        // Others may vote for either.
+        assert!(chain.add_vote(vote(&nodes[2], shrink)).is_some());
+        let conflicting = OffenceReport {
+            conflicting_votes: 1,
+            ..OffenceReport::default()
+        };
+        assert_eq!(chain.offenders(), vec![(nodes[1].pub_key, conflicting)]);
+        assert!(!chain.is_blocklisted(&nodes[1].pub_key));
+
+        let lost = link(LinkDescriptor::NodeLost(nodes[1].pub_key));
+        assert!(chain.add_vote(vote(&nodes[1], lost)).is_none());
+        assert_eq!(chain.offenders()[0].1.total(), 2);
+        assert!(chain.is_blocklisted(&nodes[1].pub_key));
+        assert_eq!(unwrap!(events.lock()).last(),
+                   Some(&ChainEvent::Blocklisted(nodes[1].pub_key)));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        assert!(chain.add_vote(vote(&nodes[1], data.clone())).is_none());
+        // Nor are votes it passes on from others.
+        assert!(chain.add_vote_from(vote(&nodes[0], data.clone()), nodes[1].pub_key).is_none());
+        assert_eq!(chain.len(), 3);
+
+        // A bad signature counts against the peer sending it, not the key it claims.
+        let other = BlockIdentifier::ImmutableData(::sha3::hash(b"other"));
+        let split = unwrap!(serialisation::serialise(&data)).len();
+        let mut forged = unwrap!(serialisation::serialise(&vote(&nodes[0], data.clone())));
+        forged.truncate(split);
+        forged.extend_from_slice(&unwrap!(serialisation::serialise(&vote(&nodes[0], other)))
+            [split..]);
+        let forged: Vote = unwrap!(serialisation::deserialise(&forged));
+        assert!(chain.add_vote_from(forged, nodes[2].pub_key).is_none());
+        let offender = chain.offenders().into_iter().find(|x| x.0 == nodes[2].pub_key);
+        assert_eq!(offender.map(|x| x.1.bad_signatures), Some(1));
+        assert!(!chain.offenders().iter().any(|x| x.0 == nodes[0].pub_key));
+
+        assert!(chain.clear_offender(&nodes[1].pub_key));
+        assert!(!chain.clear_offender(&nodes[1].pub_key));
+        assert!(chain.add_vote(vote(&nodes[1], data)).is_some());
+        chain.blocklist(nodes[0].pub_key);
+        assert!(chain.is_blocklisted(&nodes[0].pub_key));
+    }
+
+    #[test]
+    fn accusation_excludes_offender() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let vote = |chain: &mut DataChain, voter: usize, id: &BlockIdentifier| {
+            chain.add_vote(unwrap!(Vote::new(&nodes[voter].pub_key,
+                                             &nodes[voter].sec_key,
+                                             id.clone())))
+        };
+        for (joined, node) in nodes.iter().enumerate() {
+            let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(node.pub_key));
+            for voter in 0..cmp::max(joined, 1) {
+                let _ = vote(&mut chain, voter, &link);
+            }
+        }
+        let offender = nodes[2].pub_key;
+        let grow = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 5 });
+        let shrink = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 3 });
+        assert!(vote(&mut chain, 2, &grow).is_some());
+        assert!(chain.accusation(&offender).is_none());
+        assert!(vote(&mut chain, 2, &shrink).is_none());
+        let accusation = unwrap!(chain.accusation(&offender));
+        assert_eq!(accusation.link_descriptor().and_then(|x| x.offender()),
+                   Some(&offender));
+
+        // The offender may not vote on its own accusation.
+        let _ = vote(&mut chain, 2, &accusation);
+        let _ = vote(&mut chain, 0, &accusation);
+        assert!(!chain.is_accused(&offender));
+        let _ = vote(&mut chain, 1, &accusation);
+        assert!(chain.is_accused(&offender));
+        assert_eq!(unwrap!(chain.find(&accusation)).members().len(), 2);
+
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        let len = chain.len();
+        assert!(vote(&mut chain, 2, &data).is_none());
+        assert_eq!(chain.len(), len);
+        let _ = vote(&mut chain, 0, &data);
+        chain.mark_blocks_valid();
+        assert!(unwrap!(chain.find(&data)).valid);
+
+        // An accusation the votes do not prove is refused.
+        let gained = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let unproven = LinkDescriptor::Accusation {
+            first: Box::new(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, grow))),
+            second: Box::new(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, gained))),
+        };
+        assert!(vote(&mut chain, 1, &BlockIdentifier::Link(unproven)).is_none());
+        assert_eq!(chain.len(), len + 1);
+    }
+
+    #[test]
+    fn block_metadata() {
+        use chain::BlockMetadata;
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        let vote = unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, link));
+        let _ = chain.add_vote_from(vote.clone(), nodes[1].pub_key);
+        // Duplicates are rejected, but where they came from is still recorded.
+        let _ = chain.add_vote_from(vote, nodes[2].pub_key);
+        let metadata = chain.chain()[0].metadata().clone();
+        assert!(metadata.received.is_some());
+        let mut peers = vec![nodes[1].pub_key, nodes[2].pub_key];
+        peers.sort();
+        assert_eq!(metadata.origins.into_iter().collect_vec(), peers);
+
+        // Metadata is neither persisted nor compared.
+        let bytes = unwrap!(serialisation::serialise(&chain.chain()[0]));
+        let block: Block = unwrap!(serialisation::deserialise(&bytes));
+        assert_eq!(block, chain.chain()[0]);
+        assert_eq!(*block.metadata(), BlockMetadata::default());
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        let _ = ::rust_sodium::init();
+        let keys = node();
+        let dir = unwrap!(TempDir::new("test_data_chain"));
+        let _ = unwrap!(DataChain::create_in_path(dir.path().to_path_buf(), 999));
+        let path = dir.path().join("data_chain");
+        for garbage in &[&b"DCHN garbage"[..], &[0xff; 64][..], &[0; 3][..]] {
+            unwrap!(fs::write(&path, garbage));
+            assert!(DataChain::from_path(dir.path().to_path_buf(), 999).is_err());
+        }
+
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.pub_key));
+        let _ = chain.add_vote(unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, link)));
+        let ids = [BlockIdentifier::ImmutableData(::sha3::hash(b"one")),
+                   BlockIdentifier::ImmutableData(::sha3::hash(b"two"))];
+        let votes = ids.iter()
+            .map(|x| unwrap!(Vote::new(&keys.pub_key, &keys.sec_key, x.clone())))
+            .collect_vec();
+        // The proof of one vote spliced onto the other's identifier.
+        let split = unwrap!(serialisation::serialise(&ids[0])).len();
+        let mut forged = unwrap!(serialisation::serialise(&votes[0]));
+        forged.truncate(split);
+        forged.extend_from_slice(&unwrap!(serialisation::serialise(&votes[1]))[split..]);
+        let forged: Vote = unwrap!(serialisation::deserialise(&forged));
+        assert!(chain.add_vote(forged).is_none());
+        assert_eq!(chain.len(), 1);
+        // A second proof from the same node.
+        assert!(chain.add_vote(votes[0].clone()).is_some());
+        assert!(chain.add_vote(votes[0].clone()).is_none());
+        assert_eq!(chain.chain()[1].proofs().len(), 1);
+    }
+
+    #[test]
+    fn chain_in_dir_store() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..2).map(|_| node()).collect_vec();
+        let dir = unwrap!(TempDir::new("test_data_chain"));
+        let store = unwrap!(DirStore::new(dir.path().join("blocks")));
+        let mut chain = unwrap!(DataChain::open_with_store(Box::new(store), 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[0].pub_key));
+        assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key, &nodes[0].sec_key, link)))
+            .is_some());
+        for i in 0..20 {
+            let data = BlockIdentifier::ImmutableData(::sha3::hash(&[i]));
+            assert!(chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key,
+                                                     &nodes[0].sec_key,
+                                                     data)))
+                .is_some());
+            assert!(unwrap!(chain.write()));
+        }
+        // Only new blocks are written, plus a small framing overhead per write.
+        assert!(unwrap!(chain.write_stats().amplification()) < 2.0);
+        let store = unwrap!(DirStore::new(dir.path().join("blocks")));
+        let reopened = unwrap!(DataChain::open_with_store(Box::new(store), 999));
+        assert_eq!(reopened.chain(), chain.chain());
+    }
+
+    #[test]
+    fn partial_validation_restarts_after_mutation() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let data = (0..10)
+            .map(|i| BlockIdentifier::ImmutableData(::sha3::hash(&[i])))
+            .collect_vec();
+        let blocks = Some(&link)
+            .into_iter()
+            .chain(&data)
+            .map(|id| unwrap!(Block::new(unwrap!(Vote::new(&keys.0, &keys.1, id.clone())))))
+            .collect_vec();
+        let mut chain = DataChain::from_blocks(blocks, 1);
+        let mut cursor = ValidationCursor::default();
+        assert!(!chain.mark_blocks_valid_partial(&mut cursor, 5));
+        assert_eq!((cursor.position(), cursor.validated()), (5, 5));
+        // Shifts the unvalidated `data[4]` back into the part of the chain already covered.
+        chain.remove(&data[0]);
+        assert!(chain.mark_blocks_valid_partial(&mut cursor, 100));
+        assert_eq!(cursor.validated(), 10);
+        assert!(chain.chain().iter().all(|x| x.valid));
+    }
+
+    #[test]
+    fn rotated_key_replaces_old() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let rotated = node();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let rotation = BlockIdentifier::Link(LinkDescriptor::KeyChanged {
+            old: nodes[3].pub_key,
+            new: rotated.pub_key,
+        });
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        for node in &nodes[1..4] {
+            let _ = chain.add_vote(vote(node, &add_node_1));
+        }
+        let _ = chain.add_vote(vote(&nodes[1], &rotation));
+        assert_eq!(chain.add_vote(vote(&nodes[2], &rotation)), Some(rotation.clone()));
+        assert!(chain.is_retired(&nodes[3].pub_key));
+
+        let old_vote = vote(&nodes[3], &data);
+        assert_eq!(chain.verify_vote(&old_vote), Err(ValidationError::UnknownSigner));
+        assert!(chain.add_vote(old_vote).is_none());
+        assert!(chain.find(&data).is_none());
+        // Members are now 1, 2 and the new key, so one vote is not enough but the new key counts.
+        let _ = chain.add_vote(vote(&nodes[1], &data));
+        assert!(!unwrap!(chain.find(&data)).valid);
+        assert_eq!(chain.add_vote(vote(&rotated, &data)), Some(data.clone()));
+        assert!(unwrap!(chain.find(&data)).valid);
+        unwrap!(chain.check_invariants());
+    }
+
+    #[test]
+    fn node_age_from_links() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..5).map(|_| node()).collect_vec();
+        let link = |descriptor, valid| {
+            Block::from_parts(BlockIdentifier::Link(descriptor), vec![], valid)
+        };
+        let chain = DataChain::from_blocks(vec![link(LinkDescriptor::NodeGained(nodes[1].pub_key),
+                                                     true),
+                                                link(LinkDescriptor::NodeGained(nodes[2].pub_key),
+                                                     true),
+                                                link(LinkDescriptor::NodeGained(nodes[3].pub_key),
+                                                     true),
+                                                link(LinkDescriptor::NodeGained(nodes[0].pub_key),
+                                                     false),
+                                                link(LinkDescriptor::NodeLost(nodes[2].pub_key),
+                                                     true),
+                                                link(LinkDescriptor::KeyChanged {
+                                                         old: nodes[3].pub_key,
+                                                         new: nodes[4].pub_key,
+                                                     },
+                                                     true)],
+                                           4);
+        assert_eq!(chain.node_age(&nodes[1].pub_key), Some(4));
+        assert_eq!(chain.node_age(&nodes[2].pub_key), None);
+        assert_eq!(chain.node_age(&nodes[3].pub_key), None);
+        assert_eq!(chain.node_age(&nodes[4].pub_key), Some(2));
+        assert_eq!(chain.node_age(&nodes[0].pub_key), None);
+    }
+
+    #[test]
+    fn quorum_of_elders() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..5).map(|_| node()).collect_vec();
+        let proof = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone())).proof().clone()
+        };
+        let membership = Membership {
+            members: vec![(nodes[1].pub_key, Role::Elder),
+                          (nodes[2].pub_key, Role::Elder),
+                          (nodes[3].pub_key, Role::Elder),
+                          (nodes[4].pub_key, Role::Adult)],
+        };
+        let link = BlockIdentifier::Link(LinkDescriptor::Membership(membership));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let link_proofs = nodes[1..4].iter().map(|x| proof(x, &link)).collect();
+        let data_proofs = vec![proof(&nodes[1], &data), proof(&nodes[4], &data)];
+        let mut chain = DataChain::from_blocks(vec![Block::from_parts(link, link_proofs, true),
+                                                    Block::from_parts(data.clone(),
+                                                                      data_proofs,
+                                                                      false)],
+                                               999);
+        chain.mark_blocks_valid();
+        // The adult's signature does not count, one elder of three is no quorum.
+        assert!(!unwrap!(chain.find(&data)).valid);
+        assert_eq!(chain.current_elders(),
+                   vec![nodes[1].pub_key, nodes[2].pub_key, nodes[3].pub_key]);
+        let adult_vote = unwrap!(Vote::new(&nodes[4].pub_key, &nodes[4].sec_key, data.clone()));
+        assert_eq!(chain.verify_vote(&adult_vote), Err(ValidationError::UnknownSigner));
+        let elder_vote = unwrap!(Vote::new(&nodes[2].pub_key, &nodes[2].sec_key, data.clone()));
+        assert_eq!(chain.add_vote(elder_vote), Some(data.clone()));
+        assert!(unwrap!(chain.find(&data)).valid);
+    }
+
+    #[test]
+    fn cancelled_losses() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let keys = nodes.iter().map(|x| x.pub_key).collect_vec();
+        let vote = |i: usize, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&nodes[i].pub_key, &nodes[i].sec_key, id.clone()))
+        };
+        let block = |descriptor: Option<LinkDescriptor>, signers: &[usize]| {
+            let id = descriptor.map_or(BlockIdentifier::ImmutableData([1; 32]),
+                                       BlockIdentifier::Link);
+            let proofs = signers.iter().map(|&i| vote(i, &id).proof().clone()).collect();
+            Block::from_parts(id, proofs, false)
+        };
+        let chain = |blocks: &[Block]| {
+            let mut chain = DataChain::from_blocks(blocks.to_vec(), 999);
+            chain.mark_blocks_valid();
+            chain
+        };
+        let membership = Membership { members: keys.iter().map(|&x| (x, Role::Elder)).collect() };
+        let mut blocks = vec![block(Some(LinkDescriptor::Membership(membership)), &[0, 1, 2]),
+                              block(Some(LinkDescriptor::NodeLost(keys[3])), &[0, 1, 2])];
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        assert_eq!(chain(&blocks).current_elders(), keys[..3].to_vec());
+        assert_eq!(chain(&blocks).verify_vote(&vote(3, &data)),
+                   Err(ValidationError::UnknownSigner));
+
+        // The loss is cancelled before the node went: it votes again and counts towards quorum.
+        blocks.push(block(Some(LinkDescriptor::CancelNodeLost(keys[3])), &[0, 1]));
+        assert_eq!(chain(&blocks).current_elders(), keys);
+        assert_eq!(chain(&blocks).verify_vote(&vote(3, &data)), Ok(()));
+        let mut with_data = blocks.clone();
+        with_data.push(block(None, &[0]));
+        assert!(!unwrap!(chain(&with_data).find(&data)).valid);
+        with_data[3] = block(None, &[0, 3]);
+        assert!(unwrap!(chain(&with_data).find(&data)).valid);
+
+        // Elders lost in a split are all back once the split is cancelled.
+        let prefix = Prefix::new(0b10);
+        blocks.push(block(Some(LinkDescriptor::SplitFrom(prefix.clone())), &[0, 1, 2, 3]));
+        blocks.push(block(Some(LinkDescriptor::NodeLost(keys[2])), &[0, 1, 3]));
+        blocks.push(block(Some(LinkDescriptor::NodeLost(keys[3])), &[0, 1]));
+        blocks.push(block(Some(LinkDescriptor::CancelSplitFrom(Prefix::new(0b11))), &[0, 1]));
+        assert_eq!(chain(&blocks).current_elders(), keys[..2].to_vec());
+        blocks.push(block(Some(LinkDescriptor::CancelSplitFrom(prefix)), &[0, 1]));
+        let cancelled = chain(&blocks);
+        assert!(cancelled.chain().iter().all(|x| x.valid));
+        assert_eq!(cancelled.current_elders(), keys);
+    }
+
+    #[test]
+    fn checkpoints() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let vote = |i: usize, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&nodes[i].pub_key, &nodes[i].sec_key, id.clone()))
+        };
+        let gained = |i: usize| BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[i].pub_key));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        chain.set_checkpoint_interval(Some(3));
+        for &(i, ref id) in &[(0, gained(0)), (0, gained(1)), (0, gained(2)), (1, gained(2))] {
+            let _ = chain.add_vote(vote(i, id));
+        }
+        assert_eq!(chain.links_len(), 3);
+        assert!(chain.checkpoint_due());
+
+        // Other links wait for the checkpoint, which must match our own.
+        let lost = BlockIdentifier::Link(LinkDescriptor::NodeLost(nodes[2].pub_key));
+        assert_eq!(chain.verify_vote(&vote(0, &lost)), Err(ValidationError::CheckpointDue));
+        let prefix = Prefix::new(1);
+        let stale = BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+            prefix: prefix.clone(),
+            members: vec![nodes[0].pub_key],
+            state: [0; 32],
+        });
+        assert_eq!(chain.verify_vote(&vote(0, &stale)),
+                   Err(ValidationError::CheckpointMismatch));
+        let checkpoint = chain.checkpoint(prefix);
+        let _ = chain.add_vote(vote(0, &checkpoint));
+        assert_eq!(chain.add_vote(vote(1, &checkpoint)), Some(checkpoint.clone()));
+        assert_eq!(chain.last_checkpoint().map(|x| x.identifier()), Some(&checkpoint));
+        assert!(!chain.checkpoint_due());
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let _ = chain.add_vote(vote(0, &data));
+        assert_eq!(chain.add_vote(vote(1, &data)), Some(data.clone()));
+
+        // The chain validates from the checkpoint without the links before it.
+        let position = unwrap!(chain.position(&checkpoint));
+        let trimmed = unwrap!(DataChain::from_checkpoint(chain.chain()[position..].to_vec(), 999));
+        assert!(trimmed.chain().iter().all(|x| x.valid));
+        assert_eq!(trimmed.current_elders(), chain.current_elders());
+        match DataChain::from_checkpoint(chain.chain()[1..].to_vec(), 999) {
+            Err(Error::MissingLink) => (),
+            result => panic!("unexpected {:?}", result.map(|x| x.len())),
+        }
+    }
+
+    #[test]
+    fn churn_votes() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..2).map(|_| node()).collect_vec();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let churn = |chain: &mut DataChain, event| {
+            unwrap!(chain.handle_churn(event, &nodes[0].pub_key, &nodes[0].sec_key))
+        };
+        let votes = churn(&mut chain, ChurnEvent::NodeGained(nodes[0].pub_key));
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].binding(), Some(&[0; 32]));
+        assert!(unwrap!(chain.find(votes[0].identifier())).valid);
+        assert!(churn(&mut chain, ChurnEvent::NodeGained(nodes[0].pub_key)).is_empty());
+
+        let binding = chain.vote_binding();
+        let votes = churn(&mut chain, ChurnEvent::NodeGained(nodes[1].pub_key));
+        assert_eq!(votes[0].binding(), Some(&binding));
+        assert_eq!(chain.links_len(), 2);
+        let votes = churn(&mut chain, ChurnEvent::Split(Prefix::new(0b10)));
+        assert_eq!(votes[0].identifier(),
+                   &BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(0b10))));
+        assert!(chain.contains(votes[0].identifier()));
+
+        chain.set_max_clock_skew(Some(60));
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        let vote = unwrap!(chain.vote_for(data, &nodes[0].pub_key, &nodes[0].sec_key));
+        assert!(vote.proof().timestamp().is_some());
+        assert_eq!(chain.verify_vote(&vote), Ok(()));
+    }
+
+    #[test]
+    fn compact_epochs() {
+        let _ = ::rust_sodium::init();
+        let blocks = ::chain::test_blocks(3);
+        let mut fewer = blocks[1].clone();
+        fewer.proofs_mut().clear();
+        let mut more = blocks[2].clone();
+        more.proofs_mut().push(blocks[1].proofs()[0].clone());
+        let mut next_link = ::chain::test_blocks(1);
+        next_link[0].valid = true;
+        // Epoch one: link, a, b, a (fewer proofs), b (more proofs). Epoch two: a again.
+        let chain = vec![blocks[0].clone(),
+                         blocks[1].clone(),
+                         blocks[2].clone(),
+                         fewer,
+                         more.clone(),
+                         next_link[0].clone(),
+                         blocks[1].clone()];
+        let mut chain = DataChain::from_blocks(chain, 4);
+        let events = Arc::new(Mutex::new(vec![]));
+        let sink = events.clone();
+        let _ = chain.subscribe(move |event| unwrap!(sink.lock()).push(event));
+        assert_eq!(chain.compact(), 2);
+        assert_eq!(*unwrap!(events.lock()),
+                   vec![ChainEvent::BlockPruned(blocks[2].identifier().clone()),
+                        ChainEvent::BlockPruned(blocks[1].identifier().clone())]);
+        assert_eq!(chain.chain(),
+                   &vec![blocks[0].clone(),
+                         blocks[1].clone(),
+                         more,
+                         next_link[0].clone(),
+                         blocks[1].clone()]);
+        assert_eq!(chain.compact(), 0);
+    }
+
+    #[test]
+    fn split_and_merge_on_prefix() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let low = BlockIdentifier::ImmutableData([0x12; 32]);
+        let high = BlockIdentifier::ImmutableData([0xf0; 32]);
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        for id in &[&add_node_1, &low, &high] {
+            for node in &nodes[1..] {
+                let vote = unwrap!(Vote::new(&node.pub_key, &node.sec_key, (*id).clone()));
+                let _ = chain.add_vote(vote);
+            }
+        }
+        assert_eq!(chain.valid_len(), 3);
+        let _ = chain.pin(&high);
+
+        let root = Prefix::new(1);
+        let (mut zero, one) = chain.split(&root);
+        let ids = |chain: &DataChain| {
+            chain.chain().iter().map(|x| x.identifier().clone()).collect_vec()
+        };
+        assert_eq!(ids(&zero), vec![add_node_1.clone(), low.clone()]);
+        assert_eq!(ids(&one), vec![add_node_1.clone(), high.clone()]);
+        assert_eq!(zero.valid_len(), 2);
+        assert_eq!(one.valid_len(), 2);
+        assert!(one.is_pinned(&high) && !zero.is_pinned(&high));
+
+        zero.merge_on(&one, &root);
+        // The sibling's data follows the last link both chains share.
+        assert_eq!(ids(&zero), vec![add_node_1, high.clone(), low]);
+        assert_eq!(zero.valid_len(), 3);
+        assert!(zero.is_pinned(&high));
+        unwrap!(zero.check_invariants());
+    }
+
+    #[test]
+    fn late_votes_keep_order() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let add_node_3 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut chain = DataChain::default();
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        for &(voter, id) in &[(1, &add_node_1),
+                              (1, &add_node_2),
+                              (1, &add_node_3),
+                              (2, &add_node_3),
+                              (1, &data),
+                              (2, &data)] {
+            let _ = chain.add_vote(vote(&nodes[voter], id));
+        }
+        let order = chain.ordered_blocks().iter().map(|x| x.identifier().clone()).collect_vec();
+        assert_eq!(order, vec![add_node_1, add_node_2.clone(), add_node_3, data]);
+        // A late vote for an already valid link does not move it.
+        let _ = chain.add_vote(vote(&nodes[3], &add_node_2));
+        assert_eq!(chain.ordered_blocks().iter().map(|x| x.identifier().clone()).collect_vec(),
+                   order);
+    }
+
+    #[test]
+    fn pending_link_is_not_a_predecessor() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| node()).collect_vec();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let add_node_3 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[3].pub_key));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let mut chain = DataChain::default();
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        // `add_node_3` is still collecting votes when `data` reaches quorum, then moves to the
+        // top of the chain on its next vote.
+        for &(voter, id) in &[(1, &add_node_1),
+                              (1, &add_node_2),
+                              (1, &add_node_3),
+                              (1, &data),
+                              (2, &data),
+                              (2, &add_node_3)] {
+            let _ = chain.add_vote(vote(&nodes[voter], id));
+        }
+        assert!(unwrap!(chain.find(&data)).valid);
+        chain.mark_blocks_valid();
+        assert!(unwrap!(chain.find(&data)).valid);
+        assert!(unwrap!(chain.find(&add_node_3)).valid);
+    }
+
+    #[test]
+    fn ordered_blocks_agree_across_nodes() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let data = (0..8u8)
+            .map(|i| BlockIdentifier::ImmutableData(::sha3::hash(&[i])))
+            .collect_vec();
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        // Both nodes see the same links, but the data votes of each link in a different order.
+        // With two links new data blocks need a second vote to become valid.
+        let rounds = [(vec![&add_node_1], vec![1]),
+                      (data[..5].iter().collect_vec(), vec![1]),
+                      (vec![&add_node_2], vec![1]),
+                      (data[5..].iter().collect_vec(), vec![1, 2])];
+        let mut chains = vec![];
+        for reverse in &[false, true] {
+            let mut chain = DataChain::default();
+            for (ids, voters) in &rounds {
+                let mut ids = ids.clone();
+                if *reverse {
+                    ids.reverse();
+                }
+                for id in ids {
+                    for &voter in voters {
+                        let _ = chain.add_vote(vote(&nodes[voter], id));
+                    }
+                }
+            }
+            chains.push(chain);
+        }
+        assert!(chains[0].chain() != chains[1].chain());
+        let orders = chains.iter()
+            .map(|x| x.ordered_blocks().iter().map(|x| x.identifier().clone()).collect_vec())
+            .collect_vec();
+        assert_eq!(orders[0], orders[1]);
+        assert_eq!(orders[0].len(), 10);
+        assert_eq!(orders[0][0], add_node_1);
+        assert_eq!(orders[0][6], add_node_2);
+    }
+
+    #[test]
+    fn catch_up_from_link() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        let mut chain = DataChain::default();
+        let _ = chain.add_vote(vote(&nodes[1], &add_node_1));
+        for i in 0..3u8 {
+            let _ = chain.add_vote(vote(&nodes[1], &BlockIdentifier::ImmutableData([i; 32])));
+        }
+        let _ = chain.add_vote(vote(&nodes[1], &add_node_2));
+        for i in 3..5u8 {
+            let data = BlockIdentifier::ImmutableData([i; 32]);
+            let _ = chain.add_vote(vote(&nodes[1], &data));
+            let _ = chain.add_vote(vote(&nodes[2], &data));
+        }
+        assert_eq!(chain.valid_len(), 7);
+        assert!(chain.blocks_since(&BlockIdentifier::ImmutableData([0; 32])).is_none());
+        let delta = unwrap!(chain.blocks_since(&add_node_1));
+        assert_eq!(delta.len(), 6);
+
+        // A peer holding the chain up to an older state catches up.
+        let mut peer = DataChain::from_blocks(chain.chain()[..3].to_vec(), 999);
+        assert_eq!(peer.apply_delta(delta.clone()), Ok(4));
+        assert_eq!(peer.chain(), chain.chain());
+        assert_eq!(peer.apply_delta(delta.clone()), Ok(0));
+
+        // A block signed only by a stranger is refused, and nothing is applied.
+        let mut peer = DataChain::from_blocks(chain.chain()[..1].to_vec(), 999);
+        let mut forged = delta.clone();
+        forged.push(unwrap!(Block::new(vote(&nodes[0], &BlockIdentifier::ImmutableData([9; 32])))));
+        assert_eq!(peer.apply_delta(forged), Err(ValidationError::NoQuorum));
+        assert_eq!(peer.len(), 1);
+        assert_eq!(DataChain::default().apply_delta(delta), Err(ValidationError::MissingLink));
+    }
+
+    #[test]
+    fn events() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| node()).collect_vec();
+        let vote = |node: &Node, id: &BlockIdentifier| {
+            unwrap!(Vote::new(&node.pub_key, &node.sec_key, id.clone()))
+        };
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[1].pub_key));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].pub_key));
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let sink = events.clone();
+        let id = chain.subscribe(move |event| unwrap!(sink.lock()).push(event));
+
+        let _ = chain.add_vote(vote(&nodes[1], &add_node_1));
+        let _ = chain.add_vote(vote(&nodes[1], &add_node_2));
+        let _ = chain.add_vote(vote(&nodes[0], &data));
+        let _ = chain.add_vote(vote(&nodes[1], &data));
+        chain.prune();
+        assert_eq!(*unwrap!(events.lock()),
+                   vec![ChainEvent::LinkValid(add_node_1.clone()),
+                        ChainEvent::LinkValid(add_node_2.clone()),
+                        ChainEvent::QuorumReached {
+                            identifier: data.clone(),
+                            proofs: 2,
+                        },
+                        ChainEvent::BlockValid(data.clone())]);
+
+        unwrap!(events.lock()).clear();
+        chain.remove(&data);
+        assert!(chain.unsubscribe(id));
+        assert!(!chain.unsubscribe(id));
+        chain.clear();
+        assert_eq!(*unwrap!(events.lock()), vec![ChainEvent::BlockPruned(data)]);
+    }
+
+    #[test]
+    fn rekey() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_rekey"));
+        let path = dir.path().to_path_buf();
+        let file = path.join("data_chain");
+        let (old_key, new_key) = (secretbox::gen_key(), secretbox::gen_key());
+        let open = |key: secretbox::Key| {
+            DataChain::open_with_store(Box::new(FileStore::with_key(file.clone(), key)), 999)
+        };
+        let mut chain = unwrap!(DataChain::create_in_path(path.clone(), 999));
+        chain.chain = ::chain::test_blocks(5);
+        unwrap!(chain.flush());
+
+        // Encrypt a chain stored in the clear, then rotate its key.
+        assert!(unwrap!(chain.rekey(Some(old_key.clone()))) > 0);
+        assert!(DataChain::from_path(path.clone(), 999).is_err());
+        assert_eq!(unwrap!(open(old_key.clone())).chain(), chain.chain());
+        let mut reopened = unwrap!(open(old_key.clone()));
+        let _ = unwrap!(reopened.rekey(Some(new_key.clone())));
+        match open(old_key) {
+            Err(Error::Crypto) => (),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+        assert_eq!(unwrap!(open(new_key)).chain(), chain.chain());
+
+        let _ = unwrap!(reopened.rekey(None));
+        assert_eq!(unwrap!(DataChain::from_path(path, 999)).chain(), chain.chain());
+        assert!(DataChain::default().rekey(None).is_err());
+    }
+
+    #[test]
+    fn anti_entropy() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = ::chain::test_blocks(50);
+        for block in &mut blocks {
+            block.valid = true;
+        }
+        let ours = DataChain::from_blocks(blocks[..40].to_vec(), 999);
+        let mut theirs = DataChain::from_blocks(blocks[..1].to_vec(), 999);
+        theirs.chain.extend(blocks[30..].iter().cloned());
+        theirs.chain[1].valid = false;
+
+        let missing = ours.missing_from(&theirs.digest());
+        assert!(missing.len() <= 30 && missing.len() >= 25, "{}", missing.len());
+        assert!(missing.iter().all(|x| blocks[1..31].iter().any(|y| y.identifier() == x)));
+        let missing = theirs.missing_from(&ours.digest());
+        assert!(missing.len() <= 10 && missing.iter().all(|x| !ours.contains(x)));
+        assert!(ours.missing_from(&ours.digest()).is_empty());
+    }
+
+    #[test]
+    fn random_votes_keep_invariants() {
+        use rand::{Rng, SeedableRng, XorShiftRng};
+        let _ = ::rust_sodium::init();
+        for seed in 0..30 {
+            let mut rng = XorShiftRng::from_seed([1, 2, 3, seed]);
+            let nodes = (0..6).map(|_| node()).collect_vec();
+            let mut identifiers = nodes.iter()
+                .map(|x| BlockIdentifier::Link(LinkDescriptor::NodeGained(x.pub_key)))
+                .collect_vec();
+            identifiers.extend((0..6u8).map(|i| BlockIdentifier::ImmutableData([i; 32])));
+            let mut chains = vec![DataChain::from_blocks(vec![], 4),
+                                  DataChain::from_blocks(vec![], 4)];
+            for chain in &mut chains {
+                let _ = chain.add_vote(unwrap!(Vote::new(&nodes[0].pub_key,
+                                                         &nodes[0].sec_key,
+                                                         identifiers[0].clone())));
+            }
+            for _ in 0..80 {
+                let signer = &nodes[rng.gen_range(0, nodes.len())];
+                let identifier = identifiers[rng.gen_range(0, identifiers.len())].clone();
+                let vote = unwrap!(Vote::new(&signer.pub_key, &signer.sec_key, identifier));
+                let chain = &mut chains[rng.gen_range(0, 2)];
+                let _ = chain.add_vote(vote);
+                unwrap!(chain.check_invariants(), "seed {}", seed);
+            }
+            let mut other = chains.pop().unwrap_or_default();
+            let chain = &mut chains[0];
+            chain.mark_blocks_valid();
+            unwrap!(chain.check_invariants(), "seed {}", seed);
+            unwrap!(chain.merge_chain(&mut other));
+            unwrap!(chain.check_invariants(), "seed {}", seed);
+            chain.prune();
+            unwrap!(chain.check_invariants(), "seed {}", seed);
+        }
+
+        let keys = sign::gen_keypair();
+        let data = BlockIdentifier::ImmutableData([0; 32]);
+        let vote = unwrap!(Vote::new(&keys.0, &keys.1, data.clone()));
+        let mut chain = DataChain::from_blocks(vec![unwrap!(Block::new(vote.clone()))], 999);
+        chain.chain[0].valid = true;
+        assert_eq!(chain.check_invariants(),
+                   Err(InvariantViolation::DataBeforeLink(data.clone())));
+        chain.chain[0].valid = false;
+        chain.chain.push(unwrap!(Block::new(vote)));
+        assert_eq!(chain.check_invariants(), Err(InvariantViolation::DuplicateBlock(data)));
+    }
+
+    #[test]
+    fn encoding_skips_store_and_settings() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_data_chain"));
+        let mut chain = unwrap!(DataChain::create_in_path(dir.path().to_path_buf(), 999));
+        let blocks = ::chain::test_blocks(3);
+        for (index, block) in blocks.iter().enumerate() {
+            chain.insert(index, block.clone());
+        }
+        assert!(chain.pin(blocks[1].identifier()));
+        chain.set_vote_binding(true);
+        let bytes = unwrap!(serialisation::serialise(&chain));
+        assert_eq!(bytes,
+                   unwrap!(serialisation::serialise(&(&blocks,
+                                                      999usize,
+                                                      Some(dir.path().join("data_chain")),
+                                                      vec![blocks[1].identifier()]))));
+        let mut decoded = unwrap!(serialisation::deserialise::<DataChain>(&bytes));
+        assert_eq!(decoded, chain);
+        assert!(unwrap!(decoded.write()));
+        assert_eq!(unwrap!(DataChain::from_path(dir.path().to_path_buf(), 999)), chain);
+    }
+
     #[test]
     fn file_based_chain() {
         let _ = env_logger::init();
-        ::rust_sodium::init();
+        let _ = ::rust_sodium::init();
         info!("creating keys");
         let keys = (0..10)
             .map(|_| sign::gen_keypair())
             .collect_vec();
-        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[1].0.clone()));
-        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[2].0.clone()));
-        let add_node_3 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[3].0.clone()));
-        let add_node_4 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[4].0.clone()));
+        let add_node_1 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[1].0));
+        let add_node_2 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[2].0));
+        let add_node_3 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[3].0));
+        let add_node_4 = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys[4].0));
         // #################### Create chain ########################
         if let Ok(dir) = TempDir::new("test_data_chain") {
             if let Ok(mut chain) = DataChain::create_in_path(dir.path().to_path_buf(), 999) {
@@ -580,6 +3837,7 @@ mod tests {
                 assert!(chain.add_vote(Vote::new(&keys[3].0, &keys[3].1, add_node_4.clone()) .unwrap()).is_some());
                 assert!(chain.add_vote(Vote::new(&keys[1].0, &keys[1].1, add_node_4.clone()).unwrap()).is_some());
                 assert!(chain.add_vote(Vote::new(&keys[2].0, &keys[2].1, add_node_4.clone()).unwrap()).is_some());
+                assert!(chain.pin(&add_node_2));
                 assert!(chain.write().is_ok());
                 let chain2 = DataChain::from_path(dir.path().to_path_buf(), 999);
                 assert!(chain2.is_ok());
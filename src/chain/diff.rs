@@ -0,0 +1,179 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, DataChain, Proof};
+use rust_sodium::crypto::sign::PublicKey;
+
+/// How two chains differ, from `DataChain::diff`. Blocks are matched by identifier, valid or not.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainDiff {
+    /// Blocks held only by the chain diffed, in its order.
+    pub only_in_self: Vec<BlockIdentifier>,
+    /// Blocks held only by the chain diffed against, in its order.
+    pub only_in_other: Vec<BlockIdentifier>,
+    /// Blocks both hold but signed by different keys, in the order of the chain diffed.
+    pub differing_proofs: Vec<ProofDifference>,
+    /// Where the chains' links first differ, `None` if they hold the same links in the same
+    /// order.
+    pub divergence: Option<LinkDivergence>,
+}
+
+impl ChainDiff {
+    /// Whether the chains hold the same blocks, signed by the same keys, with links in the same
+    /// order.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() &&
+        self.differing_proofs.is_empty() && self.divergence.is_none()
+    }
+}
+
+/// A block both chains hold with different signers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProofDifference {
+    /// The block.
+    pub identifier: BlockIdentifier,
+    /// Keys with a proof only in the chain diffed, sorted.
+    pub only_in_self: Vec<PublicKey>,
+    /// Keys with a proof only in the chain diffed against, sorted.
+    pub only_in_other: Vec<PublicKey>,
+}
+
+/// The first link at which two chains' sequences of links differ.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkDivergence {
+    /// Number of links, from the first, the chains agree on.
+    pub common: usize,
+    /// The next link of the chain diffed, `None` if it has no more.
+    pub ours: Option<BlockIdentifier>,
+    /// The next link of the chain diffed against, `None` if it has no more.
+    pub theirs: Option<BlockIdentifier>,
+}
+
+impl DataChain {
+    /// How this chain differs from `other`, e.g. a peer's chain received before deciding whether
+    /// to merge it with `merge_chain`.
+    ///
+    /// Validity flags are not compared, run `mark_blocks_valid` on both first to compare what
+    /// each would accept.
+    pub fn diff(&self, other: &DataChain) -> ChainDiff {
+        let mut only_in_self = Vec::new();
+        let mut differing_proofs = Vec::new();
+        for block in self.chain() {
+            match other.find(block.identifier()) {
+                None => only_in_self.push(block.identifier().clone()),
+                Some(theirs) => {
+                    let ours = signers(block.proofs());
+                    let theirs = signers(theirs.proofs());
+                    if ours != theirs {
+                        differing_proofs.push(ProofDifference {
+                            identifier: block.identifier().clone(),
+                            only_in_self: missing(&ours, &theirs),
+                            only_in_other: missing(&theirs, &ours),
+                        });
+                    }
+                }
+            }
+        }
+        let only_in_other = other.chain()
+            .iter()
+            .filter(|x| self.find(x.identifier()).is_none())
+            .map(|x| x.identifier().clone())
+            .collect();
+        ChainDiff {
+            only_in_self: only_in_self,
+            only_in_other: only_in_other,
+            differing_proofs: differing_proofs,
+            divergence: divergence(self, other),
+        }
+    }
+}
+
+// Sorted keys of the proofs.
+fn signers(proofs: &[Proof]) -> Vec<PublicKey> {
+    let mut keys = proofs.iter().map(|x| *x.key()).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+// Keys in `keys` but not in `others`, both sorted.
+fn missing(keys: &[PublicKey], others: &[PublicKey]) -> Vec<PublicKey> {
+    keys.iter().filter(|x| others.binary_search(x).is_err()).cloned().collect()
+}
+
+// The first link the chains disagree on.
+fn divergence(ours: &DataChain, theirs: &DataChain) -> Option<LinkDivergence> {
+    let links = |chain: &DataChain| {
+        chain.chain()
+            .iter()
+            .filter(|x| x.identifier().is_link())
+            .map(|x| x.identifier().clone())
+            .collect::<Vec<_>>()
+    };
+    let (ours, theirs) = (links(ours), links(theirs));
+    let common = ours.iter().zip(theirs.iter()).take_while(|&(a, b)| a == b).count();
+    if common == ours.len() && common == theirs.len() {
+        return None;
+    }
+    Some(LinkDivergence {
+        common: common,
+        ours: ours.get(common).cloned(),
+        theirs: theirs.get(common).cloned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain::{Block, LinkDescriptor, Vote, test_blocks};
+    use rust_sodium::crypto::sign;
+
+    #[test]
+    fn diff() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let blocks = test_blocks(4);
+        let ours = DataChain::from_blocks(blocks.clone(), 4);
+        assert!(ours.diff(&ours).is_empty());
+
+        let mut theirs = blocks[..3].to_vec();
+        let vote = unwrap!(Vote::new(&keys.0, &keys.1, theirs[1].identifier().clone()));
+        theirs[1].proofs_mut().push(vote.proof().clone());
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        theirs.push(unwrap!(Block::new(unwrap!(Vote::new(&keys.0, &keys.1, link.clone())))));
+        let theirs = DataChain::from_blocks(theirs, 4);
+
+        let diff = ours.diff(&theirs);
+        assert_eq!(diff.only_in_self, vec![blocks[3].identifier().clone()]);
+        assert_eq!(diff.only_in_other, vec![link.clone()]);
+        assert_eq!(diff.differing_proofs,
+                   vec![ProofDifference {
+                            identifier: blocks[1].identifier().clone(),
+                            only_in_self: vec![],
+                            only_in_other: vec![keys.0],
+                        }]);
+        assert_eq!(diff.divergence,
+                   Some(LinkDivergence {
+                       common: 1,
+                       ours: None,
+                       theirs: Some(link),
+                   }));
+        let reverse = theirs.diff(&ours);
+        assert_eq!(reverse.only_in_self, diff.only_in_other);
+        assert_eq!(reverse.differing_proofs[0].only_in_self, vec![keys.0]);
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block_identifier::BlockIdentifier;
+use maidsafe_utilities::serialisation;
+use sha3::hash;
+use std::cmp;
+
+/// Bits of filter per block, which with `HASHES` gives about 1% false positives.
+const BITS_PER_BLOCK: usize = 10;
+/// Number of bits set per block.
+const HASHES: u64 = 7;
+
+/// Compact summary of the valid blocks a chain holds, from `DataChain::digest`, so a peer can
+/// work out with `DataChain::missing_from` which of its blocks the sender lacks without the full
+/// chain being sent.
+///
+/// This is a bloom filter over the block identifiers: a block the sender holds is never reported
+/// missing, but about 1% of the blocks it lacks are taken as held and only found on a later
+/// exchange, once the chain has grown and the filter is rebuilt.
+#[derive(Clone, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct ChainDigest {
+    len: u64,
+    bits: Vec<u64>,
+}
+
+impl ChainDigest {
+    /// Digest of the given identifiers.
+    pub fn new<'a, I>(identifiers: I) -> ChainDigest
+        where I: ExactSizeIterator<Item = &'a BlockIdentifier>
+    {
+        let words = cmp::max(1, (identifiers.len() * BITS_PER_BLOCK).div_ceil(64));
+        let mut digest = ChainDigest {
+            len: identifiers.len() as u64,
+            bits: vec![0; words],
+        };
+        for identifier in identifiers {
+            for bit in digest.bits_of(identifier) {
+                digest.bits[bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        digest
+    }
+
+    /// Number of blocks summarised.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether no blocks are summarised.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether `identifier` was probably summarised. Never `false` for one that was.
+    pub fn contains(&self, identifier: &BlockIdentifier) -> bool {
+        !self.bits.is_empty() &&
+        self.bits_of(identifier).iter().all(|&bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    // Double hashing of the serialised identifier into `HASHES` bit positions.
+    fn bits_of(&self, identifier: &BlockIdentifier) -> Vec<usize> {
+        let key = hash(&serialisation::serialise(identifier).unwrap_or_default());
+        let word = |i: usize| key[i..i + 8].iter().fold(0u64, |acc, &x| (acc << 8) | x as u64);
+        let (first, second) = (word(0), word(8) | 1);
+        let len = self.bits.len() as u64 * 64;
+        (0..HASHES)
+            .map(|i| (first.wrapping_add(i.wrapping_mul(second)) % len) as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::test_blocks;
+    use super::*;
+
+    #[test]
+    fn membership() {
+        let _ = ::rust_sodium::init();
+        let held = test_blocks(200);
+        let other = test_blocks(1000);
+        let digest = ChainDigest::new(held.iter().map(|x| x.identifier()));
+        assert_eq!(digest.len(), 200);
+        assert!(held.iter().all(|x| digest.contains(x.identifier())));
+        let false_positives = other.iter().filter(|x| digest.contains(x.identifier())).count();
+        assert!(false_positives < 50, "{} false positives", false_positives);
+
+        let empty = ChainDigest::new(Vec::new().iter());
+        assert!(empty.is_empty());
+        assert!(!empty.contains(held[0].identifier()));
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::DataChain;
+use std::fmt::Write;
+
+impl DataChain {
+    /// Render the chain as a Graphviz DOT graph, e.g. for `dot -Tsvg`.
+    ///
+    /// Links are drawn as boxes and data blocks as ellipses, labelled with their position,
+    /// identifier and number of proofs; pinned blocks get a double border. Each block has an edge
+    /// from the link it is validated against, the last valid link before it, as in
+    /// `mark_blocks_valid`. Invalid blocks and their edges are red and dashed. Validity is drawn
+    /// as currently recorded, so call `mark_blocks_valid` first for an up to date picture.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph data_chain {\n    node [fontname=\"monospace\"];\n".to_owned();
+        let mut governing = self.chain().iter().position(|x| x.identifier().is_link());
+        for (index, block) in self.chain().iter().enumerate() {
+            let label = format!("{}: {:?}\\n{} proofs",
+                                index,
+                                block.identifier(),
+                                block.proofs().len())
+                .replace('"', "\\\"");
+            let shape = if block.identifier().is_link() { "box" } else { "ellipse" };
+            let style = if block.valid {
+                ""
+            } else {
+                ", color=red, fontcolor=red, style=dashed"
+            };
+            let peripheries = if self.is_pinned(block.identifier()) {
+                ", peripheries=2"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot,
+                             "    b{} [label=\"{}\", shape={}{}{}];",
+                             index,
+                             label,
+                             shape,
+                             style,
+                             peripheries);
+            if let Some(link) = governing.filter(|&x| x != index) {
+                let _ = writeln!(dot, "    b{} -> b{}{};", link, index, edge_style(block.valid));
+            }
+            if block.valid && block.identifier().is_link() {
+                governing = Some(index);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn edge_style(valid: bool) -> &'static str {
+    if valid {
+        ""
+    } else {
+        " [color=red, style=dashed]"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{DataChain, test_blocks};
+
+    #[test]
+    fn dot_graph() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(3);
+        blocks[0].valid = true;
+        blocks[1].valid = true;
+        let pinned = blocks[1].identifier().clone();
+        let mut chain = DataChain::from_blocks(blocks, 4);
+        let _ = chain.pin(&pinned);
+        let dot = chain.to_dot();
+        assert!(dot.starts_with("digraph data_chain {"));
+        assert!(dot.contains("b0 [label=\"0: NodeGained Link("));
+        assert!(dot.contains("shape=box"));
+        assert!(dot.contains("b1 [label=\"1: ImmutableData("));
+        assert!(dot.contains("shape=ellipse, peripheries=2];"));
+        assert!(dot.contains("b0 -> b1;"));
+        assert!(dot.contains("b0 -> b2 [color=red, style=dashed];"));
+        assert!(!dot.contains("-> b0"));
+    }
+}
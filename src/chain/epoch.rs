@@ -0,0 +1,122 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::BlockIdentifier;
+use chain::data_chain::DataChain;
+
+/// The blocks from one valid link up to the next, all agreed under the group of that link.
+#[derive(Clone, Copy, Debug)]
+pub struct Epoch<'a> {
+    link: &'a Block,
+    blocks: &'a [Block],
+}
+
+impl<'a> Epoch<'a> {
+    /// The valid link governing the epoch.
+    pub fn link(&self) -> &'a Block {
+        self.link
+    }
+
+    /// Every block after the link and before the next valid link, including invalid ones.
+    pub fn blocks(&self) -> &'a [Block] {
+        self.blocks
+    }
+
+    /// The valid data blocks of the epoch.
+    pub fn data(&self) -> Vec<&'a Block> {
+        self.blocks.iter().filter(|x| x.valid && x.identifier().is_block()).collect()
+    }
+}
+
+/// Iterator over the `Epoch`s of a chain in order, see `DataChain::epochs`.
+pub struct Epochs<'a> {
+    remaining: &'a [Block],
+}
+
+impl<'a> Iterator for Epochs<'a> {
+    type Item = Epoch<'a>;
+
+    fn next(&mut self) -> Option<Epoch<'a>> {
+        let remaining = self.remaining;
+        let start = remaining.iter().position(is_valid_link)?;
+        let rest = &remaining[start + 1..];
+        let end = rest.iter().position(is_valid_link).unwrap_or(rest.len());
+        self.remaining = &rest[end..];
+        Some(Epoch {
+            link: &remaining[start],
+            blocks: &rest[..end],
+        })
+    }
+}
+
+impl DataChain {
+    /// The blocks strictly between the links `from_link` and `to_link`, e.g. everything agreed
+    /// while the group changed from one to the other. Empty if either is missing or `to_link`
+    /// comes first.
+    pub fn blocks_between(&self,
+                          from_link: &BlockIdentifier,
+                          to_link: &BlockIdentifier)
+                          -> &[Block] {
+        match (self.position(from_link), self.position(to_link)) {
+            (Some(from), Some(to)) if from < to => &self.chain()[from + 1..to],
+            _ => &[],
+        }
+    }
+
+    /// Iterate over the chain's epochs, the intervals between consecutive valid links. Blocks
+    /// before the first valid link belong to no epoch.
+    pub fn epochs(&self) -> Epochs {
+        Epochs { remaining: self.chain() }
+    }
+}
+
+fn is_valid_link(block: &Block) -> bool {
+    block.valid && block.identifier().is_link()
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{DataChain, test_blocks};
+
+    #[test]
+    fn epochs_and_ranges() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(3);
+        let mut second = test_blocks(3);
+        second[1].valid = true;
+        blocks.append(&mut second);
+        blocks[0].valid = true;
+        blocks[1].valid = true;
+        blocks[3].valid = true;
+        let first_link = blocks[0].identifier().clone();
+        let second_link = blocks[3].identifier().clone();
+        let chain = DataChain::from_blocks(blocks, 4);
+
+        let between = chain.blocks_between(&first_link, &second_link);
+        assert_eq!(between, &chain.chain()[1..3]);
+        assert!(chain.blocks_between(&second_link, &first_link).is_empty());
+
+        let epochs = chain.epochs().collect::<Vec<_>>();
+        assert_eq!(epochs.len(), 2);
+        assert_eq!(epochs[0].link().identifier(), &first_link);
+        assert_eq!(epochs[0].blocks().len(), 2);
+        assert_eq!(epochs[0].data().len(), 1);
+        assert_eq!(epochs[1].link().identifier(), &second_link);
+        assert_eq!(epochs[1].data(), vec![&chain.chain()[4]]);
+    }
+}
@@ -0,0 +1,505 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{AuditRecord, Block, BlockIdentifier, DataChain, LinkDescriptor, Membership, Prefix,
+            Proof, Role, Vote};
+use data::DataIdentifier;
+use error::Error;
+use rust_sodium::crypto::sign::{PublicKey, Signature};
+use rustc_serialize::hex::{FromHex, ToHex};
+use rustc_serialize::json::{self, Json};
+use std::collections::BTreeMap;
+use std::io;
+
+/// Version of the JSON layout written by `DataChain::to_json`.
+pub const JSON_FORMAT: u64 = 1;
+
+impl DataChain {
+    /// Render the chain as pretty printed JSON for auditing, diffing and non-Rust tooling.
+    ///
+    /// The layout is stable within a `JSON_FORMAT`: an object holding `format`, `group_size`,
+    /// `blocks` and `pinned`. Each block has its `identifier`, `valid` flag, `proofs` (`key`,
+    /// `signature`, `binding` and `timestamp`) and `prev_hash`, which may be absent or `null`.
+    /// Identifiers and link descriptors are objects tagged by a snake case `type`, such as
+    /// `{"type": "link", "descriptor": "node_gained", "key": ...}`. Keys, signatures and hashes are
+    /// lower case hex.
+    pub fn to_json(&self) -> String {
+        let mut root = BTreeMap::new();
+        let _ = root.insert("format".to_owned(), Json::U64(JSON_FORMAT));
+        let _ = root.insert("group_size".to_owned(), Json::U64(self.group_size() as u64));
+        let _ = root.insert("blocks".to_owned(),
+                            Json::Array(self.chain().iter().map(block_to_json).collect()));
+        let _ = root.insert("pinned".to_owned(),
+                            Json::Array(self.pinned().iter().map(identifier_to_json).collect()));
+        format!("{}", json::as_pretty_json(&Json::Object(root)))
+    }
+
+    /// Read back a chain written by `to_json`. Validity flags are taken as given, run
+    /// `mark_blocks_valid` before trusting them.
+    pub fn from_json(input: &str) -> Result<DataChain, Error> {
+        let root = Json::from_str(input).map_err(|error| bad_json(&error.to_string()))?;
+        match root.find("format").and_then(Json::as_u64) {
+            Some(JSON_FORMAT) => (),
+            Some(format) => return Err(Error::Version(format as u32)),
+            None => return Err(bad_json("missing format")),
+        }
+        let group_size = field(&root, "group_size")?
+            .as_u64()
+            .ok_or_else(|| bad_json("group_size"))?;
+        let blocks = array(&root, "blocks")?
+            .iter()
+            .map(block_from_json)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut chain = DataChain::from_blocks(blocks, group_size as usize);
+        for pinned in array(&root, "pinned")? {
+            let _ = chain.pin(&identifier_from_json(pinned)?);
+        }
+        Ok(chain)
+    }
+
+    /// Render `audit_log` as a pretty printed JSON array. Each record is an object holding
+    /// `position`, `time`, `link` (`null` when absent), `proofs`, and the event's `event`,
+    /// `subject` and `detail` strings as in `audit_csv`.
+    pub fn audit_json(&self) -> String {
+        let records = self.audit_log().iter().map(audit_record_to_json).collect();
+        format!("{}", json::as_pretty_json(&Json::Array(records)))
+    }
+}
+
+fn object(pairs: Vec<(&str, Json)>) -> Json {
+    Json::Object(pairs.into_iter().map(|(key, value)| (key.to_owned(), value)).collect())
+}
+
+fn hex(bytes: &[u8]) -> Json {
+    Json::String(bytes.to_hex())
+}
+
+fn audit_record_to_json(record: &AuditRecord) -> Json {
+    object(vec![("position", Json::U64(record.position as u64)),
+                ("time", record.time.map_or(Json::Null, Json::U64)),
+                ("link", record.link.map_or(Json::Null, |x| Json::U64(x as u64))),
+                ("proofs", Json::U64(record.proofs as u64)),
+                ("event", Json::String(record.event.kind().to_owned())),
+                ("subject", Json::String(record.event.subject())),
+                ("detail", Json::String(record.event.detail()))])
+}
+
+fn block_to_json(block: &Block) -> Json {
+    let proofs = block.proofs().iter().map(proof_to_json).collect();
+    object(vec![("identifier", identifier_to_json(block.identifier())),
+                ("valid", Json::Boolean(block.valid)),
+                ("proofs", Json::Array(proofs)),
+                ("prev_hash", block.prev_hash().map_or(Json::Null, |x| hex(x)))])
+}
+
+fn proof_to_json(proof: &Proof) -> Json {
+    object(vec![("key", hex(&proof.key().0)),
+                ("signature", hex(&proof.sig().0)),
+                ("binding", proof.binding().map_or(Json::Null, |x| hex(x))),
+                ("timestamp", proof.timestamp().map_or(Json::Null, Json::U64))])
+}
+
+fn vote_to_json(vote: &Vote) -> Json {
+    object(vec![("identifier", identifier_to_json(vote.identifier())),
+                ("proof", proof_to_json(vote.proof()))])
+}
+
+fn identifier_to_json(identifier: &BlockIdentifier) -> Json {
+    let data = |kind, hash: &[u8; 32], id: &DataIdentifier| {
+        object(vec![("type", Json::String(kind)),
+                    ("hash", hex(hash)),
+                    ("data", data_identifier_to_json(id))])
+    };
+    match *identifier {
+        BlockIdentifier::ImmutableData(ref hash) => {
+            object(vec![("type", Json::String("immutable_data".to_owned())),
+                        ("hash", hex(hash))])
+        }
+        BlockIdentifier::StructuredData(ref hash, ref id) => {
+            data("structured_data".to_owned(), hash, id)
+        }
+        BlockIdentifier::AppendableData(ref hash, ref id) => {
+            data("appendable_data".to_owned(), hash, id)
+        }
+        BlockIdentifier::MutableDataEntry(ref hash, ref id) => {
+            data("mutable_data_entry".to_owned(), hash, id)
+        }
+        BlockIdentifier::Batch(ref items, ref hash) => {
+            let items = items.iter().map(data_identifier_to_json).collect();
+            object(vec![("type", Json::String("batch".to_owned())),
+                        ("hash", hex(hash)),
+                        ("items", Json::Array(items))])
+        }
+        BlockIdentifier::Deleted(ref id) => {
+            object(vec![("type", Json::String("deleted".to_owned())),
+                        ("data", data_identifier_to_json(id))])
+        }
+        BlockIdentifier::Expiring(ref identifier, expires_at) => {
+            object(vec![("type", Json::String("expiring".to_owned())),
+                        ("block", identifier_to_json(identifier)),
+                        ("expires_at", Json::U64(expires_at))])
+        }
+        BlockIdentifier::Custom { tag, ref hash } => {
+            object(vec![("type", Json::String("custom".to_owned())),
+                        ("tag", Json::U64(tag)),
+                        ("hash", hex(hash))])
+        }
+        BlockIdentifier::Transfer { ref from, ref to, amount, ref hash } => {
+            object(vec![("type", Json::String("transfer".to_owned())),
+                        ("from", hex(&from.0)),
+                        ("to", hex(&to.0)),
+                        ("amount", Json::U64(amount)),
+                        ("hash", hex(hash))])
+        }
+        BlockIdentifier::Link(ref descriptor) => {
+            let (kind, details) = match *descriptor {
+                LinkDescriptor::NodeLost(ref key) => ("node_lost", vec![("key", hex(&key.0))]),
+                LinkDescriptor::CancelNodeLost(ref key) => {
+                    ("cancel_node_lost", vec![("key", hex(&key.0))])
+                }
+                LinkDescriptor::NodeGained(ref key) => {
+                    ("node_gained", vec![("key", hex(&key.0))])
+                }
+                LinkDescriptor::SplitFrom(ref prefix) => {
+                    ("split_from", vec![("prefix", Json::U64(prefix.bits()))])
+                }
+                LinkDescriptor::CancelSplitFrom(ref prefix) => {
+                    ("cancel_split_from", vec![("prefix", Json::U64(prefix.bits()))])
+                }
+                LinkDescriptor::MergeTo(ref prefix) => {
+                    ("merge_to", vec![("prefix", Json::U64(prefix.bits()))])
+                }
+                LinkDescriptor::CheckPoint { ref prefix, ref members, ref state } => {
+                    let members = members.iter().map(|key| hex(&key.0)).collect();
+                    ("check_point",
+                     vec![("prefix", Json::U64(prefix.bits())),
+                          ("members", Json::Array(members)),
+                          ("state", hex(state))])
+                }
+                LinkDescriptor::KeyChanged { ref old, ref new } => {
+                    ("key_changed", vec![("old", hex(&old.0)), ("new", hex(&new.0))])
+                }
+                LinkDescriptor::Membership(ref membership) => {
+                    let members = membership.members
+                        .iter()
+                        .map(|&(ref key, role)| {
+                            object(vec![("key", hex(&key.0)),
+                                        ("role", Json::String(role_name(role).to_owned()))])
+                        })
+                        .collect();
+                    ("membership", vec![("members", Json::Array(members))])
+                }
+                LinkDescriptor::Reconfigure { group_size } => {
+                    ("reconfigure", vec![("group_size", Json::U64(group_size))])
+                }
+                LinkDescriptor::Accusation { ref first, ref second } => {
+                    ("accusation",
+                     vec![("first", vote_to_json(first)), ("second", vote_to_json(second))])
+                }
+            };
+            let mut pairs = vec![("type", Json::String("link".to_owned())),
+                                 ("descriptor", Json::String(kind.to_owned()))];
+            pairs.extend(details);
+            object(pairs)
+        }
+    }
+}
+
+fn data_identifier_to_json(id: &DataIdentifier) -> Json {
+    let (kind, tag) = match *id {
+        DataIdentifier::Structured(_, tag) => ("structured", Some(tag)),
+        DataIdentifier::Immutable(_) => ("immutable", None),
+        DataIdentifier::Appendable(_) => ("appendable", None),
+        DataIdentifier::Mutable(_, tag) => ("mutable", Some(tag)),
+    };
+    let mut pairs = vec![("type", Json::String(kind.to_owned())), ("name", hex(id.name()))];
+    if let Some(tag) = tag {
+        pairs.push(("tag", Json::U64(tag)));
+    }
+    object(pairs)
+}
+
+fn block_from_json(input: &Json) -> Result<Block, Error> {
+    let identifier = identifier_from_json(field(input, "identifier")?)?;
+    let valid = field(input, "valid")?.as_boolean().ok_or_else(|| bad_json("valid"))?;
+    let proofs = array(input, "proofs")?
+        .iter()
+        .map(proof_from_json)
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut block = Block::from_parts(identifier, proofs, valid);
+    // Absent from chains written before blocks recorded it.
+    match input.find("prev_hash") {
+        None | Some(&Json::Null) => (),
+        Some(prev_hash) => block.set_prev_hash(Some(bytes32(prev_hash)?)),
+    }
+    Ok(block)
+}
+
+fn proof_from_json(input: &Json) -> Result<Proof, Error> {
+    let binding = match *field(input, "binding")? {
+        Json::Null => None,
+        ref binding => Some(bytes32(binding)?),
+    };
+    let timestamp = match *field(input, "timestamp")? {
+        Json::Null => None,
+        ref timestamp => Some(timestamp.as_u64().ok_or_else(|| bad_json("timestamp"))?),
+    };
+    let signature = Signature::from_slice(&hex_bytes(field(input, "signature")?)?)
+        .ok_or_else(|| bad_json("signature"))?;
+    Ok(Proof::with_context(key(field(input, "key")?)?, signature, binding, timestamp))
+}
+
+fn vote_from_json(input: &Json) -> Result<Box<Vote>, Error> {
+    Ok(Box::new(Vote::from_parts(identifier_from_json(field(input, "identifier")?)?,
+                                 proof_from_json(field(input, "proof")?)?)))
+}
+
+fn identifier_from_json(input: &Json) -> Result<BlockIdentifier, Error> {
+    let data = || -> Result<([u8; 32], DataIdentifier), Error> {
+        Ok((bytes32(field(input, "hash")?)?, data_identifier_from_json(field(input, "data")?)?))
+    };
+    let prefix = || -> Result<Prefix, Error> {
+        Ok(Prefix::new(field(input, "prefix")?.as_u64().ok_or_else(|| bad_json("prefix"))?))
+    };
+    Ok(match string(input, "type")? {
+        "immutable_data" => BlockIdentifier::ImmutableData(bytes32(field(input, "hash")?)?),
+        "structured_data" => {
+            let (hash, id) = data()?;
+            BlockIdentifier::StructuredData(hash, id)
+        }
+        "appendable_data" => {
+            let (hash, id) = data()?;
+            BlockIdentifier::AppendableData(hash, id)
+        }
+        "mutable_data_entry" => {
+            let (hash, id) = data()?;
+            BlockIdentifier::MutableDataEntry(hash, id)
+        }
+        "batch" => {
+            let items = array(input, "items")?
+                .iter()
+                .map(data_identifier_from_json)
+                .collect::<Result<Vec<_>, Error>>()?;
+            BlockIdentifier::Batch(items, bytes32(field(input, "hash")?)?)
+        }
+        "deleted" => BlockIdentifier::Deleted(data_identifier_from_json(field(input, "data")?)?),
+        "expiring" => {
+            let expires_at = field(input, "expires_at")?
+                .as_u64()
+                .ok_or_else(|| bad_json("expires_at"))?;
+            BlockIdentifier::expiring(identifier_from_json(field(input, "block")?)?, expires_at)
+                .map_err(|_| bad_json("expiring"))?
+        }
+        "custom" => {
+            BlockIdentifier::Custom {
+                tag: field(input, "tag")?.as_u64().ok_or_else(|| bad_json("tag"))?,
+                hash: bytes32(field(input, "hash")?)?,
+            }
+        }
+        "transfer" => {
+            BlockIdentifier::Transfer {
+                from: key(field(input, "from")?)?,
+                to: key(field(input, "to")?)?,
+                amount: field(input, "amount")?.as_u64().ok_or_else(|| bad_json("amount"))?,
+                hash: bytes32(field(input, "hash")?)?,
+            }
+        }
+        "link" => {
+            BlockIdentifier::Link(match string(input, "descriptor")? {
+                "node_lost" => LinkDescriptor::NodeLost(key(field(input, "key")?)?),
+                "cancel_node_lost" => LinkDescriptor::CancelNodeLost(key(field(input, "key")?)?),
+                "node_gained" => LinkDescriptor::NodeGained(key(field(input, "key")?)?),
+                "split_from" => LinkDescriptor::SplitFrom(prefix()?),
+                "cancel_split_from" => LinkDescriptor::CancelSplitFrom(prefix()?),
+                "merge_to" => LinkDescriptor::MergeTo(prefix()?),
+                "check_point" => {
+                    LinkDescriptor::CheckPoint {
+                        prefix: prefix()?,
+                        members: array(input, "members")?
+                            .iter()
+                            .map(key)
+                            .collect::<Result<Vec<_>, Error>>()?,
+                        state: bytes32(field(input, "state")?)?,
+                    }
+                }
+                "membership" => {
+                    let members = array(input, "members")?
+                        .iter()
+                        .map(|member| {
+                            let role = match string(member, "role")? {
+                                "elder" => Role::Elder,
+                                "adult" => Role::Adult,
+                                "infant" => Role::Infant,
+                                other => return Err(bad_json(&format!("unknown role {}", other))),
+                            };
+                            Ok((key(field(member, "key")?)?, role))
+                        })
+                        .collect::<Result<Vec<_>, Error>>()?;
+                    LinkDescriptor::Membership(Membership { members: members })
+                }
+                "key_changed" => {
+                    LinkDescriptor::KeyChanged {
+                        old: key(field(input, "old")?)?,
+                        new: key(field(input, "new")?)?,
+                    }
+                }
+                "reconfigure" => {
+                    LinkDescriptor::Reconfigure {
+                        group_size: field(input, "group_size")?
+                            .as_u64()
+                            .ok_or_else(|| bad_json("group_size"))?,
+                    }
+                }
+                "accusation" => {
+                    LinkDescriptor::Accusation {
+                        first: vote_from_json(field(input, "first")?)?,
+                        second: vote_from_json(field(input, "second")?)?,
+                    }
+                }
+                other => return Err(bad_json(&format!("unknown link descriptor {}", other))),
+            })
+        }
+        other => return Err(bad_json(&format!("unknown identifier type {}", other))),
+    })
+}
+
+fn data_identifier_from_json(input: &Json) -> Result<DataIdentifier, Error> {
+    let name = bytes32(field(input, "name")?)?;
+    let tag = || field(input, "tag").and_then(|x| x.as_u64().ok_or_else(|| bad_json("tag")));
+    Ok(match string(input, "type")? {
+        "structured" => DataIdentifier::Structured(name, tag()?),
+        "immutable" => DataIdentifier::Immutable(name),
+        "appendable" => DataIdentifier::Appendable(name),
+        "mutable" => DataIdentifier::Mutable(name, tag()?),
+        other => return Err(bad_json(&format!("unknown data identifier type {}", other))),
+    })
+}
+
+fn field<'a>(input: &'a Json, name: &str) -> Result<&'a Json, Error> {
+    input.find(name).ok_or_else(|| bad_json(&format!("missing {}", name)))
+}
+
+fn array<'a>(input: &'a Json, name: &str) -> Result<&'a Vec<Json>, Error> {
+    field(input, name)?.as_array().ok_or_else(|| bad_json(name))
+}
+
+fn string<'a>(input: &'a Json, name: &str) -> Result<&'a str, Error> {
+    field(input, name)?.as_string().ok_or_else(|| bad_json(name))
+}
+
+fn hex_bytes(input: &Json) -> Result<Vec<u8>, Error> {
+    input.as_string()
+        .and_then(|x| x.from_hex().ok())
+        .ok_or_else(|| bad_json("expected hex"))
+}
+
+fn bytes32(input: &Json) -> Result<[u8; 32], Error> {
+    let bytes = hex_bytes(input)?;
+    if bytes.len() != 32 {
+        return Err(bad_json("expected 32 bytes of hex"));
+    }
+    let mut array = [0; 32];
+    array.copy_from_slice(&bytes);
+    Ok(array)
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::Elder => "elder",
+        Role::Adult => "adult",
+        Role::Infant => "infant",
+    }
+}
+
+fn key(input: &Json) -> Result<PublicKey, Error> {
+    Ok(PublicKey(bytes32(input)?))
+}
+
+fn bad_json(detail: &str) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::InvalidData, format!("bad chain JSON: {}", detail)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, DataChain, LinkDescriptor, Prefix, Vote, test_blocks};
+    use data::DataIdentifier;
+    use rustc_serialize::json::Json;
+
+    #[test]
+    fn round_trip() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(3);
+        blocks[1].valid = false;
+        let mut chain = DataChain::from_blocks(blocks, 4);
+        let _ = chain.pin(&BlockIdentifier::StructuredData([5; 32],
+                                                           DataIdentifier::Structured([6; 32], 7)));
+        let _ = chain.pin(&BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(3))));
+        let batch = vec![DataIdentifier::Immutable([8; 32])];
+        let _ = chain.pin(&BlockIdentifier::Batch(batch, [9; 32]));
+        let _ = chain.pin(&BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: 5 }));
+        let _ = chain.pin(&BlockIdentifier::Deleted(DataIdentifier::Immutable([10; 32])));
+        let expiring = BlockIdentifier::ImmutableData([11; 32]);
+        let _ = chain.pin(&unwrap!(BlockIdentifier::expiring(expiring, 12)));
+        let _ = chain.pin(&BlockIdentifier::Custom {
+            tag: 14,
+            hash: [15; 32],
+        });
+        let keys = ::rust_sodium::crypto::sign::gen_keypair();
+        let votes = [3, 5]
+            .iter()
+            .map(|&size| {
+                let link = BlockIdentifier::Link(LinkDescriptor::Reconfigure { group_size: size });
+                unwrap!(Vote::new_timestamped(&keys.0, &keys.1, link, None, 13))
+            })
+            .collect::<Vec<_>>();
+        let accusation = unwrap!(LinkDescriptor::accusation(votes[0].clone(), votes[1].clone()));
+        let _ = chain.pin(&BlockIdentifier::Link(accusation));
+        let _ = chain.pin(&BlockIdentifier::Transfer {
+            from: keys.0,
+            to: keys.0,
+            amount: 16,
+            hash: [17; 32],
+        });
+        let json = chain.to_json();
+        assert!(json.contains("\"group_size\": 4"));
+        assert!(json.contains("\"descriptor\": \"node_gained\""));
+        assert_eq!(unwrap!(DataChain::from_json(&json)), chain);
+        assert_eq!(unwrap!(DataChain::from_json(&json)).to_json(), json);
+        assert!(DataChain::from_json(&json.replace("\"format\": 1", "\"format\": 2")).is_err());
+        assert!(DataChain::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn audit_json() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(2);
+        blocks[0].valid = true;
+        blocks[1].valid = true;
+        let chain = DataChain::from_blocks(blocks, 4);
+        let audit = unwrap!(Json::from_str(&chain.audit_json()));
+        let records = unwrap!(audit.as_array());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].find("event").and_then(Json::as_string),
+                   Some("node_gained"));
+        assert_eq!(records[0].find("link"), Some(&Json::Null));
+        assert_eq!(records[1].find("event").and_then(Json::as_string),
+                   Some("data_stored"));
+        assert_eq!(records[1].find("link").and_then(Json::as_u64), Some(0));
+        assert_eq!(records[1].find("detail").and_then(Json::as_string),
+                   Some("type=immutable"));
+    }
+}
@@ -22,7 +22,7 @@
 //!
 //! A chain may look like
 //!
-//! ```norun
+//! ```text
 //! `link` - all current group members - cryptographically secured as valid
 //!
 //!  data - DataIdentifiers interspersed in a chain of links
@@ -75,30 +75,135 @@ pub mod proof;
 /// Identify the variant parts of a block, for links this is the Digest of the hash of that group.
 mod block_identifier;
 
-pub use chain::block::Block;
-pub use chain::block_identifier::BlockIdentifier;
-pub use chain::data_chain::DataChain;
+/// Shares incremental validation work fairly across the many chains a node may hold.
+pub mod scheduler;
+
+/// Compact summaries of chains, so peers can find the blocks each lacks in one round trip.
+pub mod digest;
+
+/// Monitors accumulation health and raises alerts on stuck blocks, stalled quorum and failing keys.
+pub mod watchdog;
+
+/// Storage backends for chains, plus tuning and accounting of their disk writes.
+pub mod persistence;
+
+/// Scores how reliably nodes have signed a chain's latest links and data blocks.
+pub mod trust;
+
+/// Ranges of a chain between links, and the epochs each valid link governs.
+pub mod epoch;
+
+/// One call health summaries of chains: block and link counts, quorum margins and churn.
+pub mod stats;
+
+/// Chains exported with the holder's signature, to hand to new group members or auditors.
+pub mod bundle;
+
+/// Proofs that a block was agreed by the group responsible for it, linked to the current group,
+/// and the light chains of links clients check them against.
+pub mod responsibility;
+
+/// Graphviz rendering of chains, showing which link validates each block.
+pub mod dot;
+
+/// Immutable views of chains, validated once and shared by readers on any thread.
+pub mod snapshot;
+
+/// Typed iterators over a chain's links and data blocks.
+pub mod views;
+
+/// Chain history as high level events, such as nodes joining and data stored, for audit tooling.
+pub mod audit;
+
+/// Comparison of two chains' blocks, signers and links, e.g. before merging a peer's chain.
+pub mod diff;
+
+/// Stable JSON rendering of chains for auditing and non-Rust tooling, enabled by the `json`
+/// feature.
+#[cfg(feature = "json")]
+pub mod json;
+
+pub use chain::audit::{AUDIT_CSV_HEADER, AuditEvent, AuditRecord};
+pub use chain::block::{Block, BlockMetadata};
+pub use chain::bundle::SignedBundle;
+pub use chain::responsibility::{LightChain, ResponsibilityProof};
+pub use chain::block_identifier::{BlockIdentifier, LinkDescriptor, Membership, Prefix, Role};
+pub use chain::data_chain::{ChainEvent, ChurnEvent, CustomValidator, DataChain, OffenceReport,
+                            RateLimit, ValidationMode};
+pub use chain::diff::{ChainDiff, LinkDivergence, ProofDifference};
+pub use chain::digest::ChainDigest;
+pub use chain::epoch::{Epoch, Epochs};
+pub use chain::persistence::{ChainStore, DirStore, FileStore, RecoveryReport, SessionLock,
+                             WritePolicy, WriteStats};
 pub use chain::proof::Proof;
+pub use chain::scheduler::ValidationScheduler;
+pub use chain::snapshot::ChainSnapshot;
+pub use chain::views::{DataBlockRef, DataBlocks, LinkRef, Links};
+pub use chain::stats::ChainStats;
+pub use chain::trust::{TrustConfig, TrustReport};
 pub use chain::vote::Vote;
-use std::fmt::Write;
+pub use chain::watchdog::{HealthAlert, Watchdog, WatchdogConfig};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-fn debug_bytes<V: AsRef<[u8]>>(input: V) -> String {
-    let input_ref = input.as_ref();
-    if input_ref.is_empty() {
-        return "<empty>".to_owned();
+/// The bytes a `Vote` signs: `BlockIdentifier::to_signable_bytes`, followed by the chain binding
+/// and the signer's timestamp as options when either is present.
+fn signed_payload(identifier: &BlockIdentifier,
+                  binding: Option<&[u8; 32]>,
+                  timestamp: Option<u64>)
+                  -> Vec<u8> {
+    let mut payload = identifier.to_signable_bytes();
+    if binding.is_none() && timestamp.is_none() {
+        return payload;
+    }
+    match binding {
+        Some(binding) => {
+            payload.push(1);
+            put_bytes(&mut payload, binding);
+        }
+        None => payload.push(0),
     }
-    if input_ref.len() <= 6 {
-        let mut ret = String::new();
-        for byte in input_ref.iter() {
-            write!(ret, "{:02x}", byte).unwrap_or(());
+    match timestamp {
+        Some(timestamp) => {
+            payload.push(1);
+            put_u64(&mut payload, timestamp);
         }
-        return ret;
+        None => payload.push(0),
+    }
+    payload
+}
+
+// Canonical encoding of signed bytes, see `BlockIdentifier::to_signable_bytes`.
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    put_u64(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
+
+/// Seconds since the unix epoch, as used for proof timestamps.
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+/// Blocks for tests: a link followed by `count - 1` data blocks, all signed by one new key so
+/// identifiers differ between calls.
+#[cfg(test)]
+pub fn test_blocks(count: usize) -> Vec<Block> {
+    use chain::block_identifier::LinkDescriptor;
+    use rust_sodium::crypto::sign;
+    use sha3::hash;
+    let keys = sign::gen_keypair();
+    let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+    let mut blocks = vec![unwrap!(Block::new(unwrap!(Vote::new(&keys.0, &keys.1, link))))];
+    for i in 1..count {
+        let data = BlockIdentifier::ImmutableData(hash(format!("{:?} {}", keys.0, i).as_bytes()));
+        blocks.push(unwrap!(Block::new(unwrap!(Vote::new(&keys.0, &keys.1, data)))));
     }
-    format!("{:02x}{:02x}{:02x}..{:02x}{:02x}{:02x}",
-            input_ref[0],
-            input_ref[1],
-            input_ref[2],
-            input_ref[input_ref.len() - 3],
-            input_ref[input_ref.len() - 2],
-            input_ref[input_ref.len() - 1])
+    blocks
 }
@@ -0,0 +1,1409 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0 This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::{self, Block, KeyTable, KeyedBlock, UnlinkedKeyedBlock};
+use bincode::SizeLimit;
+use bincode::rustc_serialize::decode_from;
+use chain::block_identifier::BlockIdentifier;
+use error::Error;
+use fs2::FileExt;
+use itertools::Itertools;
+use maidsafe_utilities::serialisation;
+use rust_sodium::crypto::secretbox;
+use rust_sodium::crypto::sign::PublicKey;
+use rust_sodium::randombytes;
+use rustc_serialize::Decodable;
+use rustc_serialize::hex::ToHex;
+use sha3::{HashAlgorithm, hash};
+use signature::{Ed25519, SchemeId, SignatureScheme};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex, Weak};
+
+/// Storage backend for a `DataChain`, see `DataChain::open_with_store`.
+///
+/// Backends are free to store the chain however suits them, but must hand back exactly the blocks
+/// and pins last stored. This crate provides `FileStore` and `DirStore` only; an embedded database
+/// backend (sled, for instance) would pull a large dependency into every user, so is best
+/// implemented outside it against this trait.
+pub trait ChainStore {
+    /// Read back the blocks and pins last stored, empty if nothing was stored yet.
+    fn load(&mut self) -> Result<(Vec<Block>, Vec<BlockIdentifier>), Error>;
+
+    /// Persist the given chain state, returning the number of bytes actually written to disk.
+    fn store(&mut self, blocks: &[Block], pinned: &[BlockIdentifier]) -> Result<u64, Error>;
+
+    /// Apply the store tuning in `policy`, see `DataChain::set_write_policy`. Backends without
+    /// such tuning ignore it.
+    fn set_write_policy(&mut self, _policy: &WritePolicy) {}
+
+    /// Encrypt everything stored from now on with `key`, or store in the clear if `None`, see
+    /// `DataChain::rekey`. Loads must still read back what was stored under the previous key.
+    /// Backends without at-rest encryption fail.
+    fn set_key(&mut self, _key: Option<secretbox::Key>) -> Result<(), Error> {
+        Err(Error::Io(io::Error::new(io::ErrorKind::Other, "store does not support encryption")))
+    }
+
+    /// The hash algorithm recorded with the chain last loaded or stored, see
+    /// `DataChain::hash_algorithm`. Backends that record none always use the default.
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::default()
+    }
+
+    /// Record `algorithm` with everything stored from now on. Backends that record none only
+    /// accept the default.
+    fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) -> Result<(), Error> {
+        if algorithm == HashAlgorithm::default() {
+            Ok(())
+        } else {
+            Err(Error::Io(io::Error::new(io::ErrorKind::Other,
+                                         "store does not record the hash algorithm")))
+        }
+    }
+}
+
+/// The original single file backend, rewriting the whole chain on every store. This is what
+/// `DataChain::create_in_path` and `DataChain::from_path` use.
+///
+/// Each store writes a temporary file and renames it over the chain file, so an interrupted store
+/// leaves the previous chain intact. Loads and stores hold an exclusive lock on `<path>.lock`.
+///
+/// With a key the file is encrypted at rest. A file stored in the clear still loads, and is
+/// encrypted by the next store.
+pub struct FileStore {
+    path: PathBuf,
+    key: Option<secretbox::Key>,
+    algorithm: HashAlgorithm,
+}
+
+impl FileStore {
+    /// Store the chain in the file at `path`, which is created on first store.
+    pub fn new(path: PathBuf) -> FileStore {
+        FileStore {
+            path: path,
+            key: None,
+            algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    /// As `new`, but encrypting the file with `key`.
+    pub fn with_key(path: PathBuf, key: secretbox::Key) -> FileStore {
+        FileStore {
+            path: path,
+            key: Some(key),
+            algorithm: HashAlgorithm::default(),
+        }
+    }
+
+    /// Load what is still intact of a damaged chain file, see `recover_chain`, with a report of
+    /// what was salvaged. Nothing is written: the next store replaces the file.
+    pub fn recover(&mut self)
+                   -> Result<(Vec<Block>, Vec<BlockIdentifier>, RecoveryReport), Error> {
+        let _lock = lock(&self.path.with_extension("lock"))?;
+        let mut buf = Vec::<u8>::new();
+        let _ = File::open(&self.path)?.read_to_end(&mut buf)?;
+        if buf.starts_with(ENCRYPTED_MAGIC) {
+            buf = decrypt(&buf, self.key.as_ref())?;
+        }
+        let (blocks, pinned, good) = recover_chain(&buf)?;
+        self.algorithm = chain_hash_algorithm(&buf)?;
+        let report = RecoveryReport {
+            blocks: blocks.len(),
+            pinned: pinned.len(),
+            bytes_kept: good as u64,
+            bytes_dropped: (buf.len() - good) as u64,
+        };
+        Ok((blocks, pinned, report))
+    }
+}
+
+impl ChainStore for FileStore {
+    fn load(&mut self) -> Result<(Vec<Block>, Vec<BlockIdentifier>), Error> {
+        let _lock = lock(&self.path.with_extension("lock"))?;
+        match File::open(&self.path) {
+            Ok(mut file) => {
+                let mut buf = Vec::<u8>::new();
+                let _ = file.read_to_end(&mut buf)?;
+                if buf.starts_with(ENCRYPTED_MAGIC) {
+                    buf = decrypt(&buf, self.key.as_ref())?;
+                }
+                self.algorithm = chain_hash_algorithm(&buf)?;
+                decode_chain(&buf)
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok((vec![], vec![])),
+            Err(err) => Err(From::from(err)),
+        }
+    }
+
+    fn store(&mut self, blocks: &[Block], pinned: &[BlockIdentifier]) -> Result<u64, Error> {
+        let _lock = lock(&self.path.with_extension("lock"))?;
+        let mut bytes = encode_chain(blocks, pinned, self.algorithm)?;
+        if let Some(ref key) = self.key {
+            bytes = encrypt(&bytes, key);
+        }
+        let temp = self.path.with_extension("tmp");
+        {
+            let mut file = File::create(&temp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+        fs::rename(&temp, &self.path)?;
+        if let Some(dir) = self.path.parent() {
+            sync_dir(dir)?;
+        }
+        Ok(bytes.len() as u64)
+    }
+
+    fn set_key(&mut self, key: Option<secretbox::Key>) -> Result<(), Error> {
+        self.key = key;
+        Ok(())
+    }
+
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm) -> Result<(), Error> {
+        self.algorithm = algorithm;
+        Ok(())
+    }
+}
+
+/// Leading bytes of a chain file encrypted by `FileStore`, followed by the nonce and the sealed
+/// output of `encode_chain`.
+const ENCRYPTED_MAGIC: &[u8] = b"DCHE";
+
+fn encrypt(bytes: &[u8], key: &secretbox::Key) -> Vec<u8> {
+    let nonce = secretbox::gen_nonce();
+    let mut encrypted = ENCRYPTED_MAGIC.to_vec();
+    encrypted.extend_from_slice(&nonce.0);
+    encrypted.extend(secretbox::seal(bytes, &nonce, key));
+    encrypted
+}
+
+// Fails with `Error::Crypto` without a key or with the wrong one.
+fn decrypt(bytes: &[u8], key: Option<&secretbox::Key>) -> Result<Vec<u8>, Error> {
+    let start = ENCRYPTED_MAGIC.len();
+    let nonce = bytes.get(start..start + secretbox::NONCEBYTES)
+        .and_then(secretbox::Nonce::from_slice)
+        .ok_or(Error::Crypto)?;
+    let key = key.ok_or(Error::Crypto)?;
+    secretbox::open(&bytes[start + secretbox::NONCEBYTES..], &nonce, key)
+        .map_err(|()| Error::Crypto)
+}
+
+/// Leading bytes of every `DirStore` segment, followed by `SEGMENT_VERSION`.
+const SEGMENT_MAGIC: &[u8] = b"DCHS";
+
+/// Segment format written by `DirStore`.
+pub const SEGMENT_VERSION: u32 = 1;
+
+const SEGMENT_HEADER: u64 = 8;
+
+// Each batch is framed by its length and hash, so a torn final batch is detected and discarded.
+const FRAME_HEADER: usize = 40;
+
+// Spacing of the sequence numbers given to appended blocks, leaving room to insert between them.
+const SEQUENCE_GAP: u64 = 1 << 20;
+
+// A stored batch: whether it replaces all earlier state, the blocks added or changed along with
+// their sequence numbers, the identifier hashes of removed blocks and the pins if they changed.
+type Batch = (bool, Vec<(u64, Block)>, Vec<[u8; 32]>, Option<Vec<BlockIdentifier>>);
+
+#[derive(Clone, Copy)]
+struct Entry {
+    seq: u64,
+    content: [u8; 32],
+}
+
+/// Incremental backend keeping the chain as a log under a directory.
+///
+/// Blocks are keyed by the hash of their identifier and ordered by sequence numbers, so each
+/// store appends a single batch holding only the blocks that are new, changed or moved, plus the
+/// keys of those removed. Appending to or pruning a very long chain therefore costs about the size
+/// of the change rather than the whole history. Batches are checksummed and synced before a store
+/// returns, and a torn batch left by a crash is dropped on the next load.
+///
+/// The log is split into segment files of about `WritePolicy::segment_size` bytes. Once at least a
+/// segment's worth of log is more than `WritePolicy::compaction_threshold` superseded data it is
+/// compacted: the whole chain is written to a fresh segment and older segments are deleted. Loads
+/// and stores hold an exclusive lock on the `lock` file in the directory.
+///
+/// Blocks must have unique identifiers, as they do in any chain built by `DataChain::add_vote`.
+pub struct DirStore {
+    root: PathBuf,
+    loaded: bool,
+    entries: HashMap<[u8; 32], Entry>,
+    pinned: Vec<BlockIdentifier>,
+    max_seq: u64,
+    segment: u64,
+    segment_len: u64,
+    log_len: u64,
+    segment_size: u64,
+    compaction_threshold: f64,
+}
+
+impl DirStore {
+    /// Store the chain under directory `root`, which is created if needed.
+    pub fn new(root: PathBuf) -> Result<DirStore, Error> {
+        fs::create_dir_all(&root)?;
+        Ok(DirStore {
+            root: root,
+            loaded: false,
+            entries: HashMap::new(),
+            pinned: vec![],
+            max_seq: 0,
+            segment: 0,
+            segment_len: 0,
+            log_len: 0,
+            segment_size: WritePolicy::default().segment_size,
+            compaction_threshold: WritePolicy::default().compaction_threshold,
+        })
+    }
+
+    fn segment_path(&self, number: u64) -> PathBuf {
+        self.root.join(format!("segment_{}", number))
+    }
+
+    fn segments(&self) -> Result<Vec<u64>, Error> {
+        let mut segments = vec![];
+        for entry in fs::read_dir(&self.root)? {
+            if let Some(number) = entry?
+                .file_name()
+                .to_str()
+                .and_then(|x| x.strip_prefix("segment_"))
+                .and_then(|x| x.parse::<u64>().ok()) {
+                segments.push(number);
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    // Rebuild the chain, and our view of the log, by replaying every segment in order.
+    fn replay(&mut self) -> Result<(Vec<Block>, Vec<BlockIdentifier>), Error> {
+        let mut blocks = HashMap::<[u8; 32], (u64, Block)>::new();
+        let mut pinned = vec![];
+        let segments = self.segments()?;
+        self.segment = segments.last().map_or(0, |x| *x);
+        self.segment_len = 0;
+        self.log_len = 0;
+        for (index, &number) in segments.iter().enumerate() {
+            let last = index + 1 == segments.len();
+            let path = self.segment_path(number);
+            let mut bytes = Vec::<u8>::new();
+            let _ = File::open(&path)?.read_to_end(&mut bytes)?;
+            if (bytes.len() as u64) < SEGMENT_HEADER && last {
+                // Interrupted while starting this segment, it is rewritten by the next store.
+                break;
+            }
+            if !bytes.starts_with(SEGMENT_MAGIC) || (bytes.len() as u64) < SEGMENT_HEADER {
+                return Err(Error::Corrupt { offset: 0 });
+            }
+            match serialisation::deserialise::<u32>(&bytes[SEGMENT_MAGIC.len()..8])? {
+                SEGMENT_VERSION => (),
+                version => return Err(Error::Version(version)),
+            }
+            let mut offset = SEGMENT_HEADER as usize;
+            while let Some((payload, used)) = unframe(&bytes[offset..]) {
+                let (reset, changed, removed, pins) = serialisation::deserialise::<Batch>(payload)?;
+                if reset {
+                    blocks.clear();
+                }
+                for (seq, block) in changed {
+                    let _ = blocks.insert(block_key(&block)?, (seq, block));
+                }
+                for key in removed {
+                    let _ = blocks.remove(&key);
+                }
+                if let Some(pins) = pins {
+                    pinned = pins;
+                }
+                offset += used;
+            }
+            if offset < bytes.len() {
+                if !last {
+                    return Err(Error::Corrupt { offset: offset as u64 });
+                }
+                // A batch torn by a crash was never acknowledged, so is safe to drop.
+                OpenOptions::new().write(true).open(&path)?.set_len(offset as u64)?;
+            }
+            self.log_len += offset as u64;
+            if last {
+                self.segment_len = offset as u64;
+            }
+        }
+        self.entries.clear();
+        for (key, &(seq, ref block)) in &blocks {
+            let content = hash(&serialisation::serialise(block)?);
+            let _ = self.entries.insert(*key,
+                                        Entry {
+                                            seq: seq,
+                                            content: content,
+                                        });
+        }
+        self.max_seq = self.entries.values().map(|x| x.seq).max().unwrap_or(0);
+        self.pinned = pinned.clone();
+        self.loaded = true;
+        let mut blocks = blocks.into_values().collect_vec();
+        blocks.sort_by_key(|x| x.0);
+        Ok((blocks.into_iter().map(|x| x.1).collect(), pinned))
+    }
+
+    // Sequence numbers putting blocks in the order of `keys`. Stored blocks keep theirs wherever
+    // that preserves the order and the rest are numbered into the gaps, or `None` if a gap is too
+    // small and the log must be compacted.
+    fn sequence(&self, keys: &[[u8; 32]]) -> Option<Vec<u64>> {
+        let mut seqs = vec![0; keys.len()];
+        let mut kept = vec![false; keys.len()];
+        let mut last = 0;
+        for (index, key) in keys.iter().enumerate() {
+            if let Some(entry) = self.entries.get(key) {
+                if entry.seq > last {
+                    kept[index] = true;
+                    seqs[index] = entry.seq;
+                    last = entry.seq;
+                }
+            }
+        }
+        let mut low = 0;
+        let mut index = 0;
+        while index < keys.len() {
+            if kept[index] {
+                low = seqs[index];
+                index += 1;
+                continue;
+            }
+            let end = (index..keys.len()).find(|&x| kept[x]).unwrap_or(keys.len());
+            let (start, step) = if end < keys.len() {
+                (low, (seqs[end] - low) / (end - index + 1) as u64)
+            } else {
+                (cmp::max(low, self.max_seq), SEQUENCE_GAP)
+            };
+            if step == 0 {
+                return None;
+            }
+            for (count, seq) in seqs[index..end].iter_mut().enumerate() {
+                *seq = start.checked_add(step.checked_mul(count as u64 + 1)?)?;
+            }
+            index = end;
+        }
+        Some(seqs)
+    }
+
+    // Append a framed batch to the log, starting a new segment if the current one is full or the
+    // batch `reset`s the chain, in which case all older segments are then deleted.
+    fn append(&mut self, payload: &[u8], reset: bool) -> Result<u64, Error> {
+        let frame = frame(payload);
+        let number;
+        let mut file;
+        let mut written = frame.len() as u64;
+        let new_segment = reset || self.segment_len == 0 || self.segment_len >= self.segment_size;
+        if new_segment {
+            number = if self.segment_len == 0 {
+                self.segment
+            } else {
+                // Drop anything a failed append left in the segment we are leaving.
+                OpenOptions::new().write(true)
+                    .open(self.segment_path(self.segment))?
+                    .set_len(self.segment_len)?;
+                self.segment + 1
+            };
+            file = File::create(self.segment_path(number))?;
+            file.write_all(SEGMENT_MAGIC)?;
+            file.write_all(&serialisation::serialise(&SEGMENT_VERSION)?)?;
+            written += SEGMENT_HEADER;
+        } else {
+            number = self.segment;
+            file = OpenOptions::new().write(true).open(self.segment_path(number))?;
+            // Drop anything a failed append left, so this frame follows the last good one.
+            file.set_len(self.segment_len)?;
+            let _ = file.seek(SeekFrom::End(0))?;
+        }
+        file.write_all(&frame)?;
+        file.sync_all()?;
+        if new_segment {
+            sync_dir(&self.root)?;
+            self.segment_len = 0;
+        }
+        if reset {
+            for old in self.segments()? {
+                if old < number {
+                    fs::remove_file(self.segment_path(old))?;
+                }
+            }
+            sync_dir(&self.root)?;
+            self.log_len = 0;
+        }
+        self.segment = number;
+        self.segment_len += written;
+        self.log_len += written;
+        Ok(written)
+    }
+}
+
+impl ChainStore for DirStore {
+    fn load(&mut self) -> Result<(Vec<Block>, Vec<BlockIdentifier>), Error> {
+        let _lock = lock(&self.root.join("lock"))?;
+        self.replay()
+    }
+
+    fn store(&mut self, blocks: &[Block], pinned: &[BlockIdentifier]) -> Result<u64, Error> {
+        let _lock = lock(&self.root.join("lock"))?;
+        if !self.loaded {
+            let _ = self.replay()?;
+        }
+        let mut keys = Vec::with_capacity(blocks.len());
+        let mut contents = Vec::with_capacity(blocks.len());
+        let mut live = 0;
+        for block in blocks {
+            let bytes = serialisation::serialise(block)?;
+            keys.push(block_key(block)?);
+            contents.push(hash(&bytes));
+            live += bytes.len() as u64;
+        }
+        if keys.iter().collect::<HashSet<_>>().len() < keys.len() {
+            return Err(Error::BadIdentifier);
+        }
+        let mut payload = None;
+        if let Some(seqs) = self.sequence(&keys) {
+            let mut changed = vec![];
+            for (index, block) in blocks.iter().enumerate() {
+                if !self.entries
+                    .get(&keys[index])
+                    .is_some_and(|x| x.seq == seqs[index] && x.content == contents[index]) {
+                    changed.push((seqs[index], block));
+                }
+            }
+            let present = keys.iter().collect::<HashSet<_>>();
+            let removed = self.entries.keys().filter(|x| !present.contains(x)).collect_vec();
+            let pins = if pinned == &self.pinned[..] {
+                None
+            } else {
+                Some(pinned)
+            };
+            if changed.is_empty() && removed.is_empty() && pins.is_none() {
+                return Ok(0);
+            }
+            let bytes = serialisation::serialise(&(false, &changed, &removed, &pins))?;
+            let log_len = self.log_len + (FRAME_HEADER + bytes.len()) as u64;
+            let superseded = log_len.saturating_sub(live) as f64;
+            if log_len < self.segment_size ||
+               superseded < self.compaction_threshold * log_len as f64 {
+                payload = Some((bytes, seqs));
+            }
+        }
+        let (written, seqs) = match payload {
+            Some((bytes, seqs)) => (self.append(&bytes, false)?, seqs),
+            None => {
+                let seqs = (1..blocks.len() as u64 + 1).map(|x| x * SEQUENCE_GAP).collect_vec();
+                let all = seqs.iter().zip(blocks).collect_vec();
+                let bytes = serialisation::serialise(&(true,
+                                                       &all,
+                                                       &Vec::<[u8; 32]>::new(),
+                                                       &Some(pinned)))?;
+                (self.append(&bytes, true)?, seqs)
+            }
+        };
+        self.entries = keys.into_iter()
+            .zip(seqs.iter().zip(contents).map(|(seq, content)| {
+                Entry {
+                    seq: *seq,
+                    content: content,
+                }
+            }))
+            .collect();
+        self.max_seq = cmp::max(self.max_seq, seqs.last().map_or(0, |x| *x));
+        self.pinned = pinned.to_vec();
+        Ok(written)
+    }
+
+    fn set_write_policy(&mut self, policy: &WritePolicy) {
+        self.segment_size = policy.segment_size;
+        self.compaction_threshold = policy.compaction_threshold;
+    }
+}
+
+fn block_key(block: &Block) -> Result<[u8; 32], Error> {
+    Ok(hash(&serialisation::serialise(block.identifier())?))
+}
+
+fn frame(payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(FRAME_HEADER + payload.len());
+    bytes.extend_from_slice(&(payload.len() as u64).to_be_bytes());
+    bytes.extend_from_slice(&hash(payload));
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+// The payload of the frame at the start of `bytes` and the frame's length, or `None` if the frame
+// is incomplete or fails its checksum.
+fn unframe(bytes: &[u8]) -> Option<(&[u8], usize)> {
+    if bytes.len() < FRAME_HEADER {
+        return None;
+    }
+    let mut len = [0; 8];
+    len.copy_from_slice(&bytes[..8]);
+    let len = u64::from_be_bytes(len);
+    if len > (bytes.len() - FRAME_HEADER) as u64 {
+        return None;
+    }
+    let end = FRAME_HEADER + len as usize;
+    let payload = &bytes[FRAME_HEADER..end];
+    if hash(payload)[..] == bytes[8..FRAME_HEADER] {
+        Some((payload, end))
+    } else {
+        None
+    }
+}
+
+/// Suffix of the file holding the `SessionLock` on a chain file, next to it.
+pub const SESSION_LOCK_EXTENSION: &str = "session";
+
+/// Lock held on a chain file for as long as a `DataChain` has it open, see `DataChain::try_open`,
+/// so two processes never work on the same chain.
+///
+/// The lock file records its owner as `pid <pid> token <hex>`, the token telling apart processes
+/// that reuse a pid. The lock itself is an OS file lock, which the OS drops when its holder dies,
+/// however it dies, so a record left behind by a killed process is stale once the lock can be
+/// taken: it is overwritten and reported by `broken`. Where the file system refuses OS locks the
+/// record alone guards the chain, and is taken over once a probe finds its pid is no longer
+/// running. That probe is `/proc` on Linux and `kill(pid, 0)` on other Unixes; elsewhere a pid
+/// is always taken to be running, so such a record is never taken over and has to be removed by
+/// hand.
+///
+/// Chains opened on the same file within one process share the lock, which is released when the
+/// last of them drops it.
+#[derive(Clone)]
+pub struct SessionLock {
+    inner: Arc<LockInner>,
+    broken: Option<String>,
+}
+
+impl SessionLock {
+    /// Take the session lock for the chain file at `path`, failing at once with
+    /// `Error::LockHeld` naming the owner if another live process holds it.
+    pub fn acquire(path: &Path) -> Result<SessionLock, Error> {
+        let path = path.with_extension(SESSION_LOCK_EXTENSION);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let path = fs::canonicalize(&path)?;
+        let mut held = SESSION_LOCKS.lock().unwrap_or_else(|error| error.into_inner());
+        held.retain(|x| x.1.strong_count() > 0);
+        if let Some(inner) = held.iter().find(|x| x.0 == path).and_then(|x| x.1.upgrade()) {
+            return Ok(SessionLock {
+                inner: inner,
+                broken: None,
+            });
+        }
+        let recorded = read_owner(&mut file);
+        let os_lock = match file.try_lock_exclusive() {
+            Ok(()) => true,
+            Err(ref error) if error.kind() == ::fs2::lock_contended_error().kind() => {
+                return Err(Error::LockHeld { owner: recorded.map(|x| x.to_string()) });
+            }
+            Err(_) => {
+                match recorded {
+                    Some(ref owner) if owner.is_running() => {
+                        return Err(Error::LockHeld { owner: Some(owner.to_string()) });
+                    }
+                    _ => false,
+                }
+            }
+        };
+        let owner = LockOwner {
+            pid: process::id(),
+            token: randombytes::randombytes(16).to_hex(),
+        };
+        write_owner(&mut file, &owner)?;
+        let inner = Arc::new(LockInner {
+            file: file,
+            owner: owner,
+            os_lock: os_lock,
+        });
+        held.push((path, Arc::downgrade(&inner)));
+        // A lock released cleanly leaves no record.
+        Ok(SessionLock {
+            inner: inner,
+            broken: recorded.map(|x| x.to_string()),
+        })
+    }
+
+    /// The owner recorded by this lock, as `pid <pid> token <hex>`.
+    pub fn owner(&self) -> String {
+        self.inner.owner.to_string()
+    }
+
+    /// The owner of a stale lock taken over by `acquire`, left by a process that died holding it.
+    pub fn broken(&self) -> Option<&str> {
+        self.broken.as_ref().map(|x| &x[..])
+    }
+}
+
+// Exclusive lock over a store for the duration of one load or store, so processes sharing a
+// store never interleave their writes. Released when the returned file is dropped. Fails with
+// `Error::LockHeld` rather than wait on another process.
+fn lock(path: &Path) -> Result<File, Error> {
+    let file = OpenOptions::new().write(true).create(true).truncate(false).open(path)?;
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(file),
+        Err(ref error) if error.kind() == ::fs2::lock_contended_error().kind() => {
+            Err(Error::LockHeld { owner: None })
+        }
+        Err(error) => Err(Error::Io(error)),
+    }
+}
+
+// Session locks held by this process, by canonical lock file path.
+static SESSION_LOCKS: Mutex<Vec<(PathBuf, Weak<LockInner>)>> = Mutex::new(Vec::new());
+
+// The locked file of a `SessionLock`, clearing its record and unlocking once the last chain
+// sharing it lets go.
+struct LockInner {
+    file: File,
+    owner: LockOwner,
+    os_lock: bool,
+}
+
+impl Drop for LockInner {
+    fn drop(&mut self) {
+        // Without an OS lock, a process that took over a record it thought stale owns it now.
+        if self.os_lock || read_owner(&mut self.file).as_ref() == Some(&self.owner) {
+            let _ = self.file.set_len(0);
+        }
+        if self.os_lock {
+            let _ = FileExt::unlock(&self.file);
+        }
+    }
+}
+
+// The process recorded in a session lock file.
+#[derive(PartialEq)]
+struct LockOwner {
+    pid: u32,
+    token: String,
+}
+
+impl LockOwner {
+    fn parse(record: &str) -> Option<LockOwner> {
+        let mut words = record.split_whitespace();
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("pid"), Some(pid), Some("token"), Some(token)) => {
+                Some(LockOwner {
+                    pid: pid.parse().ok()?,
+                    token: token.to_owned(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    // Whether the process is still running. Where that cannot be told it is taken to be.
+    #[cfg(target_os = "linux")]
+    fn is_running(&self) -> bool {
+        Path::new("/proc").join(self.pid.to_string()).exists()
+    }
+
+    // Signal 0 only checks the process exists; `EPERM` means it does but belongs to another user.
+    #[cfg(all(unix, not(target_os = "linux")))]
+    #[allow(unsafe_code)]
+    fn is_running(&self) -> bool {
+        use std::convert::TryFrom;
+        let pid = match ::libc::pid_t::try_from(self.pid) {
+            Ok(pid) => pid,
+            Err(_) => return true,
+        };
+        let signalled = unsafe { ::libc::kill(pid, 0) } == 0;
+        signalled || io::Error::last_os_error().raw_os_error() == Some(::libc::EPERM)
+    }
+
+    #[cfg(not(unix))]
+    fn is_running(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for LockOwner {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "pid {} token {}", self.pid, self.token)
+    }
+}
+
+// The owner recorded in a session lock file, if it holds a readable record.
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut record = String::new();
+    let _ = file.seek(SeekFrom::Start(0)).ok()?;
+    let _ = file.read_to_string(&mut record).ok()?;
+    LockOwner::parse(&record)
+}
+
+// Replace the record of a session lock file with `owner`.
+fn write_owner(file: &mut File, owner: &LockOwner) -> Result<(), Error> {
+    file.set_len(0)?;
+    let _ = file.seek(SeekFrom::Start(0))?;
+    file.write_all(format!("{}\n", owner).as_bytes())?;
+    file.sync_data()?;
+    Ok(())
+}
+
+// Make a rename or new file in `dir` durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> Result<(), Error> {
+    let dir = if dir == Path::new("") {
+        Path::new(".")
+    } else {
+        dir
+    };
+    Ok(File::open(dir)?.sync_all()?)
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Leading bytes of a versioned chain file. Older files hold a bare serialised `Vec<Block>`, which
+/// starts with its big endian length and so with zero bytes for any real chain.
+const CHAIN_FILE_MAGIC: &[u8] = b"DCHN";
+
+/// Chain file format written by `encode_chain`.
+pub const CHAIN_FILE_VERSION: u32 = 6;
+
+// Version 5 stores blocks without `Block::prev_hash`, and version 4 frames each `Block` whole,
+// without the key table.
+const CHAIN_FILE_VERSION_KEYED: u32 = 5;
+const CHAIN_FILE_VERSION_FRAMED: u32 = 4;
+// Earlier formats, each a single serialised tuple without checksums: version 1 records neither
+// the hash algorithm nor the signature scheme, always SHA3-256 and Ed25519, version 2 only the
+// hash algorithm and version 3 both.
+const CHAIN_FILE_VERSION_SHA3: u32 = 1;
+const CHAIN_FILE_VERSION_HASH: u32 = 2;
+const CHAIN_FILE_VERSION_SCHEME: u32 = 3;
+
+/// Serialise chain state in the format used by `DataChain::write` and `FileStore`: the magic
+/// bytes, then `CHAIN_FILE_VERSION`, the hash algorithm, the signature scheme and the number of
+/// blocks, followed by the table of keys signing the blocks, each block and lastly the pinned
+/// identifiers. Blocks refer to their keys by index into the table, as the same few keys sign
+/// most proofs. The table, blocks and pins are each framed by their length and hash, so damage is
+/// detected and confined to the block it hit.
+pub fn encode_chain(blocks: &[Block],
+                    pinned: &[BlockIdentifier],
+                    algorithm: HashAlgorithm)
+                    -> Result<Vec<u8>, Error> {
+    let mut bytes = CHAIN_FILE_MAGIC.to_vec();
+    bytes.extend(serialisation::serialise(&(CHAIN_FILE_VERSION,
+                                             algorithm,
+                                             Ed25519::ID,
+                                             blocks.len() as u64))?);
+    let keys = KeyTable::new(blocks);
+    bytes.extend(frame(&serialisation::serialise(&keys.keys())?));
+    for block in blocks.iter().filter_map(|x| keys.intern(x)) {
+        bytes.extend(frame(&serialisation::serialise(&block)?));
+    }
+    bytes.extend(frame(&serialisation::serialise(&pinned)?));
+    Ok(bytes)
+}
+
+/// Inverse of `encode_chain`, see `chain_hash_algorithm` and `chain_signature_scheme` for the
+/// rest of the header. Fails with `Error::Corrupt` at the first damaged block, see
+/// `recover_chain`. Also reads unversioned files, which hold no pins.
+pub fn decode_chain(bytes: &[u8]) -> Result<(Vec<Block>, Vec<BlockIdentifier>), Error> {
+    if !bytes.starts_with(CHAIN_FILE_MAGIC) {
+        return Ok((block::decode_unversioned(bytes)?, vec![]));
+    }
+    let body = &bytes[CHAIN_FILE_MAGIC.len()..];
+    let version = serialisation::deserialise::<u32>(&body[..cmp::min(4, body.len())])?;
+    match version {
+        CHAIN_FILE_VERSION | CHAIN_FILE_VERSION_KEYED | CHAIN_FILE_VERSION_FRAMED => {
+            let mut cursor = Cursor::new(body);
+            let _ = read_header(&mut cursor)?;
+            let count = decode_from::<_, u64>(&mut cursor, SizeLimit::Infinite)
+                .map_err(|_| Error::Corrupt { offset: CHAIN_FILE_MAGIC.len() as u64 })?;
+            let offset = CHAIN_FILE_MAGIC.len() + cursor.position() as usize;
+            match read_frames(bytes, offset, count, version) {
+                (blocks, Some(pinned), end) if end == bytes.len() => Ok((blocks, pinned)),
+                (_, _, end) => Err(Error::Corrupt { offset: end as u64 }),
+            }
+        }
+        CHAIN_FILE_VERSION_SCHEME => {
+            type Chain = (u32, HashAlgorithm, SchemeId, Vec<Block>, Vec<BlockIdentifier>);
+            let (_, _, _, blocks, pinned) = serialisation::deserialise::<Chain>(body)?;
+            Ok((blocks, pinned))
+        }
+        CHAIN_FILE_VERSION_HASH => {
+            type Chain = (u32, HashAlgorithm, Vec<Block>, Vec<BlockIdentifier>);
+            let (_, _, blocks, pinned) = serialisation::deserialise::<Chain>(body)?;
+            Ok((blocks, pinned))
+        }
+        CHAIN_FILE_VERSION_SHA3 => {
+            let (_, blocks, pinned) =
+                serialisation::deserialise::<(u32, Vec<Block>, Vec<BlockIdentifier>)>(body)?;
+            Ok((blocks, pinned))
+        }
+        version => Err(Error::Version(version)),
+    }
+}
+
+/// The hash algorithm recorded in the header of a chain file in the `encode_chain` format. Files
+/// from before it was recorded use SHA3-256.
+pub fn chain_hash_algorithm(bytes: &[u8]) -> Result<HashAlgorithm, Error> {
+    if !bytes.starts_with(CHAIN_FILE_MAGIC) {
+        return Ok(HashAlgorithm::Sha3_256);
+    }
+    let mut cursor = Cursor::new(&bytes[CHAIN_FILE_MAGIC.len()..]);
+    Ok(read_header(&mut cursor)?.1)
+}
+
+/// The signature scheme recorded in the header of a chain file in the `encode_chain` format.
+/// Files from before it was recorded use Ed25519.
+pub fn chain_signature_scheme(bytes: &[u8]) -> Result<SchemeId, Error> {
+    if !bytes.starts_with(CHAIN_FILE_MAGIC) {
+        return Ok(SchemeId::Ed25519);
+    }
+    let mut cursor = Cursor::new(&bytes[CHAIN_FILE_MAGIC.len()..]);
+    Ok(read_header(&mut cursor)?.2)
+}
+
+// Read the version and what it records of the hash algorithm and signature scheme from the start
+// of a chain file after the magic bytes, leaving `cursor` at the blocks.
+fn read_header(cursor: &mut Cursor<&[u8]>) -> Result<(u32, HashAlgorithm, SchemeId), Error> {
+    let header = || Error::Corrupt { offset: CHAIN_FILE_MAGIC.len() as u64 };
+    let version = decode_from::<_, u32>(cursor, SizeLimit::Infinite).map_err(|_| header())?;
+    let algorithm = match version {
+        CHAIN_FILE_VERSION |
+        CHAIN_FILE_VERSION_KEYED |
+        CHAIN_FILE_VERSION_FRAMED |
+        CHAIN_FILE_VERSION_SCHEME |
+        CHAIN_FILE_VERSION_HASH => {
+            decode_from::<_, HashAlgorithm>(cursor, SizeLimit::Infinite).map_err(|_| header())?
+        }
+        CHAIN_FILE_VERSION_SHA3 => HashAlgorithm::Sha3_256,
+        version => return Err(Error::Version(version)),
+    };
+    let scheme = match version {
+        CHAIN_FILE_VERSION |
+        CHAIN_FILE_VERSION_KEYED |
+        CHAIN_FILE_VERSION_FRAMED |
+        CHAIN_FILE_VERSION_SCHEME => {
+            decode_from::<_, SchemeId>(cursor, SizeLimit::Infinite).map_err(|_| header())?
+        }
+        _ => SchemeId::Ed25519,
+    };
+    Ok((version, algorithm, scheme))
+}
+
+// Read `count` framed blocks and then the framed pins from `bytes` at `offset`, stopping at the
+// first frame that is torn, fails its checksum or does not decode. From `CHAIN_FILE_VERSION_KEYED`
+// the blocks are `KeyedBlock`s, or before `CHAIN_FILE_VERSION` `UnlinkedKeyedBlock`s, preceded by
+// their framed key table. Returns the blocks read, the pins if they were reached intact, and the
+// offset just past the last intact frame.
+fn read_frames(bytes: &[u8],
+               mut offset: usize,
+               count: u64,
+               version: u32)
+               -> (Vec<Block>, Option<Vec<BlockIdentifier>>, usize) {
+    let mut blocks = vec![];
+    let mut keys = vec![];
+    if version != CHAIN_FILE_VERSION_FRAMED {
+        match read_frame::<Vec<PublicKey>>(&bytes[offset..]) {
+            Some((table, used)) => {
+                keys = table;
+                offset += used;
+            }
+            None => return (blocks, None, offset),
+        }
+    }
+    while (blocks.len() as u64) < count {
+        let keyed = match version {
+            CHAIN_FILE_VERSION_FRAMED => None,
+            CHAIN_FILE_VERSION_KEYED => {
+                read_frame::<UnlinkedKeyedBlock>(&bytes[offset..])
+                    .map(|(block, used)| (KeyedBlock::from(block), used))
+            }
+            _ => read_frame::<KeyedBlock>(&bytes[offset..]),
+        };
+        let block = if version == CHAIN_FILE_VERSION_FRAMED {
+            read_frame::<Block>(&bytes[offset..])
+        } else {
+            keyed.and_then(|(block, used)| block.resolve(&keys).map(|block| (block, used)))
+        };
+        match block {
+            Some((block, used)) => {
+                blocks.push(block);
+                offset += used;
+            }
+            None => return (blocks, None, offset),
+        }
+    }
+    match read_frame::<Vec<BlockIdentifier>>(&bytes[offset..]) {
+        Some((pinned, used)) => (blocks, Some(pinned), offset + used),
+        None => (blocks, None, offset),
+    }
+}
+
+fn read_frame<T: Decodable>(bytes: &[u8]) -> Option<(T, usize)> {
+    let (payload, used) = unframe(bytes)?;
+    serialisation::deserialise(payload).ok().map(|value| (value, used))
+}
+
+/// Salvage a damaged chain file in the `encode_chain` format, returning the blocks that still
+/// decode in full, the pins if they too are intact, and the length of the leading bytes that held
+/// them: anything after that is damage. Fails with `Error::Corrupt` if not even the header is
+/// readable.
+pub fn recover_chain(bytes: &[u8])
+                     -> Result<(Vec<Block>, Vec<BlockIdentifier>, usize), Error> {
+    if let Ok((blocks, pinned)) = decode_chain(bytes) {
+        return Ok((blocks, pinned, bytes.len()));
+    }
+    if !bytes.starts_with(CHAIN_FILE_MAGIC) {
+        return Err(Error::Corrupt { offset: 0 });
+    }
+    let header = || Error::Corrupt { offset: CHAIN_FILE_MAGIC.len() as u64 };
+    let mut cursor = Cursor::new(&bytes[CHAIN_FILE_MAGIC.len()..]);
+    let (version, _, _) = read_header(&mut cursor)?;
+    let len = decode_from::<_, u64>(&mut cursor, SizeLimit::Infinite)
+        .map_err(|_| header())?;
+    if version == CHAIN_FILE_VERSION || version == CHAIN_FILE_VERSION_KEYED ||
+       version == CHAIN_FILE_VERSION_FRAMED {
+        let offset = CHAIN_FILE_MAGIC.len() + cursor.position() as usize;
+        let (blocks, pinned, end) = read_frames(bytes, offset, len, version);
+        return Ok((blocks, pinned.unwrap_or_default(), end));
+    }
+    let mut blocks = vec![];
+    let mut good = cursor.position();
+    while (blocks.len() as u64) < len {
+        match decode_from::<_, Block>(&mut cursor, SizeLimit::Infinite) {
+            Ok(block) => blocks.push(block),
+            Err(_) => break,
+        }
+        good = cursor.position();
+    }
+    let mut pinned = vec![];
+    if blocks.len() as u64 == len {
+        if let Ok(pins) = decode_from::<_, Vec<BlockIdentifier>>(&mut cursor,
+                                                                  SizeLimit::Infinite) {
+            pinned = pins;
+            good = cursor.position();
+        }
+    }
+    Ok((blocks, pinned, CHAIN_FILE_MAGIC.len() + good as usize))
+}
+
+/// What `FileStore::recover` salvaged from a damaged chain file, see
+/// `DataChain::from_path_recover`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RecoveryReport {
+    /// Blocks read back intact.
+    pub blocks: usize,
+    /// Pinned identifiers read back intact, none if the pins were damaged.
+    pub pinned: usize,
+    /// Length of the leading bytes of the file holding them.
+    pub bytes_kept: u64,
+    /// Damaged bytes after those, zero if the file was intact.
+    pub bytes_dropped: u64,
+}
+
+/// Tuning for how eagerly `DataChain::write` persists the chain, and how the store lays it out.
+///
+/// A `FileStore` rewrites the whole chain on every write, so on wear-sensitive disks it can pay to
+/// let small changes accumulate. A `DirStore` writes only what changed, but its log grows until
+/// compacted. The default policy writes on every call.
+#[derive(Clone, Copy, Debug, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct WritePolicy {
+    /// Skip a write while fewer than this many bytes of new chain data are pending.
+    pub min_new_bytes: u64,
+    /// Write regardless once this many consecutive writes have been skipped.
+    pub max_skipped_writes: u32,
+    /// Size in bytes at which a `DirStore` starts a new segment file. Compaction is also held off
+    /// until the log is at least this long.
+    pub segment_size: u64,
+    /// Fraction of a `DirStore` log that may be superseded data before it is compacted. Lower
+    /// values save disk space at the cost of more frequent full rewrites.
+    pub compaction_threshold: f64,
+}
+
+impl Default for WritePolicy {
+    fn default() -> WritePolicy {
+        WritePolicy {
+            min_new_bytes: 0,
+            max_skipped_writes: 0,
+            segment_size: 4 << 20,
+            compaction_threshold: 0.5,
+        }
+    }
+}
+
+/// Record of the disk writes made for a chain, see `DataChain::write_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct WriteStats {
+    /// Number of times the chain file was written.
+    pub writes: u64,
+    /// Number of writes skipped under the `WritePolicy`.
+    pub skipped_writes: u64,
+    /// Total bytes written to disk.
+    pub bytes_written: u64,
+    /// Growth in serialised chain size covered by those writes.
+    pub new_bytes: u64,
+}
+
+impl WriteStats {
+    /// Bytes written to disk per byte of new chain data, or `None` before any growth was written.
+    pub fn amplification(&self) -> Option<f64> {
+        if self.new_bytes == 0 {
+            None
+        } else {
+            Some(self.bytes_written as f64 / self.new_bytes as f64)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, test_blocks};
+    use rust_sodium::crypto::sign;
+    use std::env;
+    use std::process::{Command, Stdio};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn file_store_round_trip() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_chain_store"));
+        let mut store = FileStore::new(dir.path().join("chain"));
+        assert_eq!(unwrap!(store.load()), (vec![], vec![]));
+        let blocks = test_blocks(3);
+        let pinned = vec![blocks[1].identifier().clone()];
+        let written = unwrap!(store.store(&blocks, &pinned));
+        let bytes = unwrap!(encode_chain(&blocks, &pinned, HashAlgorithm::default()));
+        assert_eq!(written, bytes.len() as u64);
+        assert_eq!(unwrap!(chain_signature_scheme(&bytes)), SchemeId::Ed25519);
+        assert_eq!(unwrap!(FileStore::new(dir.path().join("chain")).load()),
+                   (blocks, pinned));
+
+        // Another holder of the lock makes the store fail rather than wait.
+        let held = unwrap!(File::create(dir.path().join("chain.lock")));
+        unwrap!(held.try_lock_exclusive());
+        match store.load() {
+            Err(Error::LockHeld { owner: None }) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn unversioned_files_still_load() {
+        #[derive(RustcEncodable)]
+        struct OldProof {
+            key: PublicKey,
+            sig: sign::Signature,
+        }
+        #[derive(RustcEncodable)]
+        struct OldBlock {
+            identifier: BlockIdentifier,
+            proofs: Vec<OldProof>,
+            valid: bool,
+        }
+        let _ = ::rust_sodium::init();
+        let blocks = test_blocks(3);
+        let old_blocks = blocks.iter()
+            .map(|block| {
+                OldBlock {
+                    identifier: block.identifier().clone(),
+                    proofs: block.proofs()
+                        .iter()
+                        .map(|x| {
+                            OldProof {
+                                key: *x.key(),
+                                sig: *x.sig(),
+                            }
+                        })
+                        .collect(),
+                    valid: block.valid,
+                }
+            })
+            .collect::<Vec<_>>();
+        let bytes = unwrap!(serialisation::serialise(&old_blocks));
+        assert_eq!(unwrap!(decode_chain(&bytes)), (blocks.clone(), vec![]));
+        assert_eq!(unwrap!(chain_hash_algorithm(&bytes)), HashAlgorithm::Sha3_256);
+        // Version 1 files predate the recorded hash algorithm.
+        let mut sha3_only = CHAIN_FILE_MAGIC.to_vec();
+        sha3_only.extend(unwrap!(serialisation::serialise(&(1u32,
+                                                              &blocks,
+                                                              Vec::<BlockIdentifier>::new()))));
+        assert_eq!(unwrap!(decode_chain(&sha3_only)), (blocks.clone(), vec![]));
+        assert_eq!(unwrap!(chain_hash_algorithm(&sha3_only)), HashAlgorithm::Sha3_256);
+        // Version 2 files predate the recorded signature scheme.
+        let mut hash_only = CHAIN_FILE_MAGIC.to_vec();
+        hash_only.extend(unwrap!(serialisation::serialise(&(2u32,
+                                                              HashAlgorithm::Sha3_256,
+                                                              &blocks,
+                                                              Vec::<BlockIdentifier>::new()))));
+        assert_eq!(unwrap!(decode_chain(&hash_only)), (blocks.clone(), vec![]));
+        assert_eq!(unwrap!(chain_signature_scheme(&hash_only)), SchemeId::Ed25519);
+        let mut future = CHAIN_FILE_MAGIC.to_vec();
+        future.extend(unwrap!(serialisation::serialise(&(CHAIN_FILE_VERSION + 1, &blocks))));
+        match decode_chain(&future) {
+            Err(Error::Version(version)) => assert_eq!(version, CHAIN_FILE_VERSION + 1),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn keys_stored_once() {
+        let _ = ::rust_sodium::init();
+        let blocks = test_blocks(20);
+        let pinned = vec![blocks[1].identifier().clone()];
+        let bytes = unwrap!(encode_chain(&blocks, &pinned, HashAlgorithm::default()));
+        assert_eq!(unwrap!(decode_chain(&bytes)), (blocks.clone(), pinned.clone()));
+
+        // Version 4 files hold each block whole.
+        let mut framed = CHAIN_FILE_MAGIC.to_vec();
+        framed.extend(unwrap!(serialisation::serialise(&(CHAIN_FILE_VERSION_FRAMED,
+                                                           HashAlgorithm::default(),
+                                                           Ed25519::ID,
+                                                           blocks.len() as u64))));
+        for block in &blocks {
+            framed.extend(frame(&unwrap!(serialisation::serialise(block))));
+        }
+        framed.extend(frame(&unwrap!(serialisation::serialise(&pinned))));
+        assert_eq!(unwrap!(decode_chain(&framed)), (blocks.clone(), pinned));
+        assert!(bytes.len() < framed.len());
+        assert_eq!(unwrap!(recover_chain(&framed[..framed.len() - 1])).0, blocks);
+
+        // Version 6 keeps the hash of each block's predecessor.
+        let mut linked = blocks.clone();
+        linked[1].set_prev_hash(Some([7; 32]));
+        let bytes = unwrap!(encode_chain(&linked, &[], HashAlgorithm::default()));
+        assert_eq!(unwrap!(decode_chain(&bytes)).0[1].prev_hash(), Some(&[7; 32]));
+    }
+
+    #[test]
+    fn recover_damaged_chain() {
+        let _ = ::rust_sodium::init();
+        let blocks = test_blocks(3);
+        let pinned = vec![blocks[0].identifier().clone()];
+        let bytes = unwrap!(encode_chain(&blocks, &pinned, HashAlgorithm::default()));
+        assert_eq!(unwrap!(recover_chain(&bytes)),
+                   (blocks.clone(), pinned.clone(), bytes.len()));
+
+        // Trailing garbage is dropped, keeping everything before it.
+        let mut padded = bytes.clone();
+        padded.extend_from_slice(&[0xff; 7]);
+        assert_eq!(unwrap!(recover_chain(&padded)),
+                   (blocks.clone(), pinned, bytes.len()));
+
+        // A torn final block loses that block and the pins.
+        let header = unwrap!(serialisation::serialise(&(CHAIN_FILE_VERSION,
+                                                        HashAlgorithm::default(),
+                                                        Ed25519::ID,
+                                                        0u64)))
+            .len();
+        // Past the key table and `count` blocks.
+        let end_of = |count: usize| {
+            (0..count + 1).fold(CHAIN_FILE_MAGIC.len() + header,
+                                |offset, _| offset + unwrap!(unframe(&bytes[offset..])).1)
+        };
+        let two_blocks = end_of(2);
+        assert_eq!(unwrap!(recover_chain(&bytes[..two_blocks + 10])),
+                   (blocks[..2].to_vec(), vec![], two_blocks));
+        match recover_chain(b"DCHN") {
+            Err(Error::Corrupt { offset: 4 }) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // A damaged byte inside a block fails its checksum, keeping only the blocks before it.
+        let mut damaged = bytes.clone();
+        damaged[end_of(1) + FRAME_HEADER + 5] ^= 1;
+        match decode_chain(&damaged) {
+            Err(Error::Corrupt { offset }) => assert_eq!(offset, end_of(1) as u64),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(unwrap!(recover_chain(&damaged)),
+                   (blocks[..1].to_vec(), vec![], end_of(1)));
+    }
+
+    #[test]
+    fn file_store_recovery() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_chain_store"));
+        let path = dir.path().join("chain");
+        let blocks = test_blocks(3);
+        let written = unwrap!(FileStore::new(path.clone()).store(&blocks, &[])) as usize;
+        let mut bytes = vec![];
+        let _ = unwrap!(unwrap!(File::open(&path)).read_to_end(&mut bytes));
+        // Cut the pins and the end of the last block.
+        unwrap!(unwrap!(File::create(&path)).write_all(&bytes[..written - 60]));
+
+        let mut store = FileStore::new(path);
+        assert!(store.load().is_err());
+        let (recovered, pinned, report) = unwrap!(store.recover());
+        assert_eq!((recovered, pinned), (blocks[..2].to_vec(), vec![]));
+        assert_eq!(report.blocks, 2);
+        assert_eq!(report.bytes_kept + report.bytes_dropped, written as u64 - 60);
+    }
+
+    #[test]
+    fn dir_store_writes_incrementally() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_chain_store"));
+        let mut store = unwrap!(DirStore::new(dir.path().join("chain")));
+        let mut blocks = test_blocks(20);
+        let full = unwrap!(store.store(&blocks, &[]));
+        assert_eq!(unwrap!(store.store(&blocks, &[])), 0);
+        blocks.extend(test_blocks(2).pop());
+        let appended = unwrap!(store.store(&blocks, &[]));
+        assert!(appended > 0 && appended * 10 < full);
+        // Removing an early block or moving one to the top does not rewrite those after it.
+        let _ = blocks.remove(1);
+        assert!(unwrap!(store.store(&blocks, &[])) * 10 < full);
+        let moved = blocks.remove(2);
+        blocks.push(moved);
+        assert!(unwrap!(store.store(&blocks, &[])) * 10 < full);
+        let pinned = vec![blocks[3].identifier().clone()];
+        let _ = unwrap!(store.store(&blocks, &pinned));
+        let mut reopened = unwrap!(DirStore::new(dir.path().join("chain")));
+        assert_eq!(unwrap!(reopened.load()), (blocks.clone(), pinned.clone()));
+        assert_eq!(unwrap!(reopened.store(&blocks, &pinned)), 0);
+        let duplicated = vec![blocks[0].clone(), blocks[0].clone()];
+        assert!(reopened.store(&duplicated, &[]).is_err());
+    }
+
+    #[test]
+    fn dir_store_drops_torn_batch() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_chain_store"));
+        let mut store = unwrap!(DirStore::new(dir.path().join("chain")));
+        let mut blocks = test_blocks(5);
+        let _ = unwrap!(store.store(&blocks, &[]));
+        let stored = blocks.clone();
+        blocks.extend(test_blocks(2));
+        let _ = unwrap!(store.store(&blocks, &[]));
+        // Cut the last batch short, as a crash part way through a store would.
+        let segment = dir.path().join("chain").join("segment_0");
+        let len = unwrap!(fs::metadata(&segment)).len();
+        unwrap!(unwrap!(OpenOptions::new().write(true).open(&segment)).set_len(len - 10));
+        let mut reopened = unwrap!(DirStore::new(dir.path().join("chain")));
+        assert_eq!(unwrap!(reopened.load()), (stored.clone(), vec![]));
+        let _ = unwrap!(reopened.store(&blocks, &[]));
+        assert_eq!(unwrap!(DirStore::new(dir.path().join("chain")).and_then(|mut x| x.load())),
+                   (blocks, vec![]));
+    }
+
+    #[test]
+    fn dir_store_compacts() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_chain_store"));
+        let mut store = unwrap!(DirStore::new(dir.path().join("chain")));
+        store.set_write_policy(&WritePolicy {
+            segment_size: 2048,
+            compaction_threshold: 0.25,
+            ..WritePolicy::default()
+        });
+        let mut blocks = test_blocks(10);
+        let _ = unwrap!(store.store(&blocks, &[]));
+        // Keep moving blocks to the top, superseding their earlier copies.
+        for _ in 0..50 {
+            let block = blocks.remove(1);
+            blocks.push(block);
+            let _ = unwrap!(store.store(&blocks, &[]));
+        }
+        let live = blocks.iter().map(|x| unwrap!(serialisation::serialise(x)).len()).sum::<usize>();
+        let on_disk = unwrap!(fs::read_dir(dir.path().join("chain")))
+            .map(|x| unwrap!(unwrap!(x).metadata()).len())
+            .sum::<u64>();
+        assert!(on_disk < 2 * 2048 + 2 * live as u64);
+        assert_eq!(unwrap!(unwrap!(DirStore::new(dir.path().join("chain"))).load()),
+                   (blocks, vec![]));
+    }
+
+    #[test]
+    fn amplification() {
+        let mut stats = WriteStats::default();
+        assert_eq!(stats.amplification(), None);
+        stats.bytes_written = 300;
+        stats.new_bytes = 100;
+        assert_eq!(stats.amplification(), Some(3.0));
+    }
+
+    // Run by `session_lock_recovery` in a child process, holding the session lock on the chain
+    // file named by `DATA_CHAIN_LOCK_PATH` until killed.
+    #[test]
+    #[ignore]
+    fn hold_session_lock() {
+        if let Ok(path) = env::var("DATA_CHAIN_LOCK_PATH") {
+            let _lock = unwrap!(SessionLock::acquire(Path::new(&path)));
+            loop {
+                thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+
+    // Unix only, as Windows file locks also keep other processes from reading the owner.
+    #[cfg(unix)]
+    #[test]
+    fn session_lock_recovery() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_session_lock"));
+        let path = dir.path().join("chain");
+        let lock_file = path.with_extension(SESSION_LOCK_EXTENSION);
+
+        // Shared within a process, and cleared once the last holder lets go.
+        let first = unwrap!(SessionLock::acquire(&path));
+        let second = unwrap!(SessionLock::acquire(&path));
+        assert_eq!(first.owner(), second.owner());
+        assert!(first.owner().starts_with(&format!("pid {} token ", process::id())));
+        assert!(first.broken().is_none());
+        drop(first);
+        drop(second);
+        assert!(unwrap!(fs::read(&lock_file)).is_empty());
+
+        let mut child = unwrap!(Command::new(unwrap!(env::current_exe()))
+            .args(["--ignored", "--exact", "chain::persistence::tests::hold_session_lock"])
+            .env("DATA_CHAIN_LOCK_PATH", &path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn());
+        let owner = format!("pid {} ", child.id());
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !fs::read_to_string(&lock_file).map(|x| x.starts_with(&owner)).unwrap_or(false) {
+            assert!(Instant::now() < deadline, "child never took the lock");
+            thread::sleep(Duration::from_millis(10));
+        }
+        match SessionLock::acquire(&path) {
+            Err(Error::LockHeld { owner: Some(ref held) }) => assert!(held.starts_with(&owner)),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        // Killed without releasing, its record is left behind but the lock is free.
+        unwrap!(child.kill());
+        let _ = unwrap!(child.wait());
+        let lock = unwrap!(SessionLock::acquire(&path));
+        assert!(unwrap!(lock.broken()).starts_with(&owner));
+        assert!(unwrap!(fs::read_to_string(&lock_file)).starts_with(&lock.owner()));
+    }
+}
@@ -15,24 +15,51 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use super::debug_bytes;
-use rust_sodium::crypto::sign::{self, PublicKey, Signature};
+use super::signed_payload;
+use chain::block_identifier::BlockIdentifier;
+use debug_format::debug_bytes;
+use rust_sodium::crypto::sign::{PublicKey, Signature};
+use signature::{Ed25519, SignatureScheme};
 use std::fmt::{self, Debug, Formatter};
 
 /// Proof as provided by a close group member
 /// This nay be extracted from a `Vote` to be inserted into a `Block`
+///
+/// Besides the block identifier the signature may cover a binding to the state of the chain the
+/// vote was meant for (see `DataChain::vote_binding`) and the signer's timestamp, both carried here
+/// so each proof can be checked on its own.
 #[derive(RustcEncodable, RustcDecodable, PartialOrd, Ord, PartialEq, Eq, Clone)]
 pub struct Proof {
     key: PublicKey,
     sig: Signature,
+    timestamp: Option<u64>,
+    binding: Option<[u8; 32]>,
 }
 
 impl Proof {
     /// cstr
     pub fn new(key: PublicKey, sig: Signature) -> Proof {
+        Self::with_context(key, sig, None, None)
+    }
+
+    /// cstr for a proof whose signature also covers the signer's `timestamp` (seconds since the
+    /// unix epoch).
+    pub fn with_timestamp(key: PublicKey, sig: Signature, timestamp: u64) -> Proof {
+        Self::with_context(key, sig, None, Some(timestamp))
+    }
+
+    /// cstr for a proof whose signature also covers a chain `binding` and/or the signer's
+    /// `timestamp`.
+    pub fn with_context(key: PublicKey,
+                        sig: Signature,
+                        binding: Option<[u8; 32]>,
+                        timestamp: Option<u64>)
+                        -> Proof {
         Proof {
             key: key,
             sig: sig,
+            timestamp: timestamp,
+            binding: binding,
         }
     }
 
@@ -46,9 +73,25 @@ impl Proof {
         &self.sig
     }
 
+    /// When the signer claims to have signed, if timestamped
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    /// The chain binding signed over, if bound
+    pub fn binding(&self) -> Option<&[u8; 32]> {
+        self.binding.as_ref()
+    }
+
     /// Validates `data` against this `Proof`'s `key` and `sig`.
     pub fn validate(&self, data: &[u8]) -> bool {
-        sign::verify_detached(&self.sig, data, &self.key)
+        Ed25519::verify(&self.sig, data, &self.key)
+    }
+
+    /// Validates this `Proof` as a signature of `identifier`, along with its own binding and
+    /// timestamp.
+    pub fn validate_for(&self, identifier: &BlockIdentifier) -> bool {
+        self.validate(&signed_payload(identifier, self.binding(), self.timestamp))
     }
 }
 
@@ -0,0 +1,293 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::{BlockIdentifier, LinkDescriptor};
+use chain::data_chain::DataChain;
+use error::{Error, ValidationError};
+
+/// Evidence that a block was agreed by the group of the link governing it, and that every valid
+/// link since, up to the current one, was agreed by a quorum of the group before it. Lets a node
+/// show an auditor or a new group member that data it holds was accepted by the network, without
+/// sending the whole chain. Serialisable for sending.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+pub struct ResponsibilityProof {
+    block: Block,
+    links: Vec<Block>,
+}
+
+impl ResponsibilityProof {
+    /// The block proven.
+    pub fn block(&self) -> &Block {
+        &self.block
+    }
+
+    /// The valid links from the one governing the block to the current one, in chain order.
+    pub fn links(&self) -> &[Block] {
+        &self.links
+    }
+
+    /// Check the block is signed by a quorum of the first link and each link after by a quorum of
+    /// the one before, with every signature valid, and that the last link is `current`, the link
+    /// the verifier trusts as its group's latest, e.g. the identifier of its own `DataChain::tip`.
+    /// Quorum is as for `DataChain` with the given `group_size`.
+    pub fn verify(&self,
+                  current: &BlockIdentifier,
+                  group_size: usize)
+                  -> Result<(), ValidationError> {
+        let first = self.links.first().ok_or(ValidationError::MissingLink)?;
+        if self.links.iter().any(|x| !x.identifier().is_link()) {
+            return Err(ValidationError::MissingLink);
+        }
+        quorate(&self.block, first, group_size)?;
+        for pair in self.links.windows(2) {
+            quorate(&pair[1], &pair[0], group_size)?;
+        }
+        match self.links.last() {
+            Some(link) if link.identifier() == current => Ok(()),
+            _ => Err(ValidationError::StaleGroup),
+        }
+    }
+}
+
+impl DataChain {
+    /// Prove the valid block `block_identifier` was agreed by the group then responsible for it,
+    /// see `ResponsibilityProof`. Fails with `Error::NotFound` if the block is not held or not
+    /// valid, and `Error::MissingLink` if no valid link precedes it.
+    pub fn prove_responsibility(&self,
+                                block_identifier: &BlockIdentifier)
+                                -> Result<ResponsibilityProof, Error> {
+        let position = self.position(block_identifier).ok_or(Error::NotFound)?;
+        let block = &self.chain()[position];
+        if !block.valid {
+            return Err(Error::NotFound);
+        }
+        let governing = self.chain()[..position]
+            .iter()
+            .rposition(|x| x.valid && x.identifier().is_link())
+            .ok_or(Error::MissingLink)?;
+        Ok(ResponsibilityProof {
+            block: block.clone(),
+            links: self.chain()[governing..]
+                .iter()
+                .filter(|x| x.valid && x.identifier().is_link())
+                .cloned()
+                .collect(),
+        })
+    }
+}
+
+/// The links of a chain from a trusted `CheckPoint` link on, without any data blocks, for clients
+/// to check a `ResponsibilityProof` from a node holding the full chain. Each link added must be
+/// signed by a quorum of the one before, so the client only has to trust the checkpoint.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+pub struct LightChain {
+    links: Vec<Block>,
+    group_size: u64,
+}
+
+impl LightChain {
+    /// Start from the trusted `checkpoint`, which must be a `CheckPoint` link signed by a quorum
+    /// of the members it commits to. Quorum is as for `DataChain` with the given `group_size`.
+    pub fn new(checkpoint: Block, group_size: usize) -> Result<LightChain, ValidationError> {
+        match checkpoint.identifier().link_descriptor() {
+            Some(&LinkDescriptor::CheckPoint { .. }) => (),
+            _ => return Err(ValidationError::MissingLink),
+        }
+        quorate(&checkpoint, &checkpoint, group_size)?;
+        Ok(LightChain {
+            links: vec![checkpoint],
+            group_size: group_size as u64,
+        })
+    }
+
+    /// Append the next valid link of the chain, checking it is signed by a quorum of the current
+    /// tip. Fails with `ValidationError::MissingLink` for a data block.
+    pub fn add_link(&mut self, link: Block) -> Result<(), ValidationError> {
+        if !link.identifier().is_link() {
+            return Err(ValidationError::MissingLink);
+        }
+        quorate(&link, self.tip(), self.group_size as usize)?;
+        self.links.push(link);
+        Ok(())
+    }
+
+    /// The links held, starting with the checkpoint.
+    pub fn links(&self) -> &[Block] {
+        &self.links
+    }
+
+    /// The last link held, governing the blocks voted for now.
+    pub fn tip(&self) -> &Block {
+        &self.links[self.links.len() - 1]
+    }
+
+    /// Check the block of `proof` is signed by a quorum of the link governing it, which must be
+    /// one of ours, so fails with `ValidationError::MissingLink` for a block governed by a link
+    /// from before the checkpoint or after the tip. The links the proof carries are not needed,
+    /// our own being trusted.
+    pub fn verify(&self, proof: &ResponsibilityProof) -> Result<(), ValidationError> {
+        let governing = proof.links.first().ok_or(ValidationError::MissingLink)?;
+        let link = self.links
+            .iter()
+            .find(|x| x.identifier() == governing.identifier())
+            .ok_or(ValidationError::MissingLink)?;
+        quorate(&proof.block, link, self.group_size as usize)
+    }
+}
+
+impl DataChain {
+    /// The `LightChain` of our valid links from the `last_checkpoint` on, to hand to clients.
+    /// Fails with `Error::MissingLink` without a valid checkpoint.
+    pub fn light_chain(&self) -> Result<LightChain, Error> {
+        let start = self.last_checkpoint()
+            .and_then(|x| self.position(x.identifier()))
+            .ok_or(Error::MissingLink)?;
+        let mut light = LightChain::new(self.chain()[start].clone(), self.group_size())
+            .map_err(|_| Error::Validation)?;
+        for link in self.chain()[start + 1..]
+            .iter()
+            .filter(|x| x.valid && x.identifier().is_link()) {
+            light.add_link(link.clone()).map_err(|_| Error::Validation)?;
+        }
+        Ok(light)
+    }
+}
+
+// Check every proof of `block` is validly signed and that it meets the quorum rule of `DataChain`
+// for the members of `link`.
+fn quorate(block: &Block, link: &Block, group_size: usize) -> Result<(), ValidationError> {
+    if !block.validate_block_signatures() {
+        return Err(ValidationError::BadSignature);
+    }
+    let members = link.members();
+    let signed = members.iter()
+        .filter(|&key| block.proofs().iter().any(|x| x.key() == key))
+        .count();
+    if signed * 2 >= members.len() || signed >= group_size {
+        Ok(())
+    } else {
+        Err(ValidationError::NoQuorum)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Prefix, Vote};
+    use error::{Error, ValidationError};
+    use maidsafe_utilities::serialisation;
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn prove_and_verify() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let gained = |i: usize| BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[i].0));
+        let data = BlockIdentifier::ImmutableData(::sha3::hash(b"data"));
+        let block = |identifier: &BlockIdentifier, signers: &[usize]| {
+            let mut votes = signers.iter()
+                .map(|&i| unwrap!(Vote::new(&nodes[i].0, &nodes[i].1, identifier.clone())));
+            let mut block = unwrap!(Block::new(unwrap!(votes.next())));
+            for vote in votes {
+                unwrap!(block.add_proof(vote.proof().clone()));
+            }
+            block
+        };
+        let mut chain = DataChain::from_blocks(vec![block(&gained(0), &[0]),
+                                                    block(&gained(1), &[0, 1]),
+                                                    block(&data, &[0]),
+                                                    block(&gained(2), &[0, 1])],
+                                               999);
+        chain.mark_blocks_valid();
+        let current = gained(2);
+
+        let proof = unwrap!(chain.prove_responsibility(&data));
+        assert_eq!(proof.block().identifier(), &data);
+        assert_eq!(proof.links().iter().map(|x| x.identifier().clone()).collect::<Vec<_>>(),
+                   vec![gained(1), gained(2)]);
+        let sent: ResponsibilityProof =
+            unwrap!(serialisation::deserialise(&unwrap!(serialisation::serialise(&proof))));
+        assert_eq!(sent.verify(&current, 999), Ok(()));
+        assert_eq!(proof.verify(&gained(0), 999), Err(ValidationError::StaleGroup));
+
+        let mut outsider = proof.clone();
+        outsider.block = block(&data, &[2]);
+        assert_eq!(outsider.verify(&current, 999), Err(ValidationError::NoQuorum));
+        let mut unlinked = proof;
+        unlinked.links.clear();
+        assert_eq!(unlinked.verify(&current, 999), Err(ValidationError::MissingLink));
+
+        let missing = BlockIdentifier::ImmutableData(::sha3::hash(b"missing"));
+        match chain.prove_responsibility(&missing) {
+            Err(Error::NotFound) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn light_chain() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let block = |identifier: &BlockIdentifier, signers: &[usize]| {
+            let mut votes = signers.iter()
+                .map(|&i| unwrap!(Vote::new(&nodes[i].0, &nodes[i].1, identifier.clone())));
+            let mut block = unwrap!(Block::new(unwrap!(votes.next())));
+            for vote in votes {
+                unwrap!(block.add_proof(vote.proof().clone()));
+            }
+            block
+        };
+        let mut members = vec![nodes[0].0, nodes[1].0];
+        members.sort();
+        let checkpoint = BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+            prefix: Prefix::new(1),
+            members: members,
+            state: [0; 32],
+        });
+        let gained = BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[2].0));
+        let data = (0..2u8)
+            .map(|x| BlockIdentifier::ImmutableData(::sha3::hash(&[x])))
+            .collect::<Vec<_>>();
+        let mut chain = DataChain::from_blocks(vec![block(&checkpoint, &[0, 1]),
+                                                    block(&data[0], &[0]),
+                                                    block(&gained, &[0, 1]),
+                                                    block(&data[1], &[0, 1])],
+                                               999);
+        chain.mark_blocks_valid();
+
+        let light = unwrap!(chain.light_chain());
+        assert_eq!(light.links().len(), 2);
+        assert_eq!(light.tip().identifier(), &gained);
+        for identifier in &data {
+            assert_eq!(light.verify(&unwrap!(chain.prove_responsibility(identifier))), Ok(()));
+        }
+        let mut outsider = unwrap!(chain.prove_responsibility(&data[1]));
+        outsider.block = block(&data[1], &[2]);
+        assert_eq!(light.verify(&outsider), Err(ValidationError::NoQuorum));
+
+        // A client behind the tip cannot check blocks the newer link governs.
+        let mut behind = unwrap!(LightChain::new(chain.chain()[0].clone(), 999));
+        assert_eq!(behind.verify(&unwrap!(chain.prove_responsibility(&data[1]))),
+                   Err(ValidationError::MissingLink));
+        assert_eq!(behind.add_link(chain.chain()[1].clone()),
+                   Err(ValidationError::MissingLink));
+        unwrap!(behind.add_link(chain.chain()[2].clone()));
+        assert_eq!(behind, light);
+        assert!(LightChain::new(chain.chain()[2].clone(), 999).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0 This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::data_chain::{DataChain, ValidationCursor};
+use itertools::Itertools;
+use rayon::prelude::*;
+use std::cmp;
+use std::sync::{Arc, Mutex};
+
+/// Spreads incremental validation of the many `DataChain`s a node may hold over repeated calls
+/// to `run`, so that one very large chain cannot starve the maintenance of the others.
+///
+/// Every call to `run` validates at most `budget` blocks in total. Chains are served round robin
+/// in slices of `slice_len` blocks, with up to `threads` chains validated in parallel. A chain
+/// resumes from where its last slice stopped, so each chain keeps making progress in proportion
+/// to the budget rather than to its size.
+pub struct ValidationScheduler {
+    chains: Vec<ScheduledChain>,
+    next: usize,
+    slice_len: usize,
+    threads: usize,
+}
+
+struct ScheduledChain {
+    chain: Arc<Mutex<DataChain>>,
+    cursor: ValidationCursor,
+    passes: u64,
+    processed: usize,
+}
+
+impl ValidationScheduler {
+    /// Create a scheduler validating up to `threads` chains at once, `slice_len` blocks at a time.
+    pub fn new(threads: usize, slice_len: usize) -> ValidationScheduler {
+        ValidationScheduler {
+            chains: Vec::new(),
+            next: 0,
+            slice_len: cmp::max(1, slice_len),
+            threads: cmp::max(1, threads),
+        }
+    }
+
+    /// Start scheduling validation of `chain`.
+    pub fn register(&mut self, chain: Arc<Mutex<DataChain>>) {
+        self.chains.push(ScheduledChain {
+            chain: chain,
+            cursor: ValidationCursor::default(),
+            passes: 0,
+            processed: 0,
+        });
+    }
+
+    /// Stop scheduling validation of `chain`, returns `false` if it was not registered.
+    pub fn unregister(&mut self, chain: &Arc<Mutex<DataChain>>) -> bool {
+        let len = self.chains.len();
+        self.chains.retain(|x| !Arc::ptr_eq(&x.chain, chain));
+        if self.next >= self.chains.len() {
+            self.next = 0;
+        }
+        len != self.chains.len()
+    }
+
+    /// Number of chains being scheduled.
+    pub fn len(&self) -> usize {
+        self.chains.len()
+    }
+
+    /// No chains are being scheduled.
+    pub fn is_empty(&self) -> bool {
+        self.chains.is_empty()
+    }
+
+    /// Number of full validation passes completed over `chain`, if registered.
+    pub fn completed_passes(&self, chain: &Arc<Mutex<DataChain>>) -> Option<u64> {
+        self.chains.iter().find(|x| Arc::ptr_eq(&x.chain, chain)).map(|x| x.passes)
+    }
+
+    /// Validate up to `budget` blocks across all registered chains, continuing the round robin
+    /// from where the previous call stopped. Returns the number of blocks validated.
+    pub fn run(&mut self, budget: usize) -> usize {
+        let mut remaining = budget;
+        let mut total = 0;
+        while remaining > 0 && !self.chains.is_empty() {
+            let batch_len = cmp::min(cmp::min(self.threads, self.chains.len()), remaining);
+            let slice_len = cmp::max(1, cmp::min(self.slice_len, remaining / batch_len));
+            let start = self.next;
+            let chain_count = self.chains.len();
+            let in_batch = |index: usize| (index + chain_count - start) % chain_count < batch_len;
+            self.chains
+                .iter_mut()
+                .enumerate()
+                .filter(|&(index, _)| in_batch(index))
+                .map(|(_, entry)| entry)
+                .collect_vec()
+                .into_par_iter()
+                .for_each(|entry| entry.validate_slice(slice_len));
+            self.next = (start + batch_len) % chain_count;
+            for (_, entry) in self.chains.iter_mut().enumerate().filter(|&(i, _)| in_batch(i)) {
+                // Count empty slices against the budget too, so `run` always terminates.
+                let used = cmp::min(remaining, cmp::max(1, entry.processed));
+                remaining -= used;
+                total += entry.processed;
+                entry.processed = 0;
+            }
+        }
+        total
+    }
+}
+
+impl ScheduledChain {
+    fn validate_slice(&mut self, slice_len: usize) {
+        let mut chain = match self.chain.lock() {
+            Ok(chain) => chain,
+            Err(_) => return,
+        };
+        if chain.mark_blocks_valid_partial(&mut self.cursor, slice_len) {
+            self.passes += 1;
+        }
+        self.processed = self.cursor.validated();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{DataChain, test_blocks};
+    use itertools::Itertools;
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    fn chain_of(len: usize) -> Arc<Mutex<DataChain>> {
+        Arc::new(Mutex::new(DataChain::from_blocks(test_blocks(len + 1), 1)))
+    }
+
+    #[test]
+    fn large_chain_does_not_starve_small_ones() {
+        let _ = ::rust_sodium::init();
+        let large = chain_of(1000);
+        let small = (0..3).map(|_| chain_of(5)).collect_vec();
+        let mut scheduler = ValidationScheduler::new(2, 10);
+        scheduler.register(large.clone());
+        for chain in &small {
+            scheduler.register(chain.clone());
+        }
+        assert_eq!(scheduler.len(), 4);
+        assert!(scheduler.run(100) <= 100);
+        for chain in &small {
+            assert!(unwrap!(scheduler.completed_passes(chain)) > 0);
+            assert_eq!(unwrap!(chain.lock()).valid_len(), 6);
+        }
+        assert_eq!(scheduler.completed_passes(&large), Some(0));
+        while scheduler.completed_passes(&large) == Some(0) {
+            assert!(scheduler.run(100) > 0);
+        }
+        assert_eq!(unwrap!(large.lock()).valid_len(), 1001);
+        assert!(scheduler.unregister(&large));
+        assert!(!scheduler.unregister(&large));
+        assert_eq!(scheduler.completed_passes(&large), None);
+    }
+
+    #[test]
+    fn budget_smaller_than_batch() {
+        let _ = ::rust_sodium::init();
+        let chains = (0..2).map(|_| chain_of(5)).collect_vec();
+        let mut scheduler = ValidationScheduler::new(2, 10);
+        for chain in &chains {
+            scheduler.register(chain.clone());
+        }
+        assert_eq!(scheduler.run(0), 0);
+        assert_eq!(scheduler.run(1), 1);
+        assert_eq!(scheduler.run(3), 3);
+        assert_eq!(chains.iter().map(|x| unwrap!(x.lock()).valid_len()).sum::<usize>(), 4);
+    }
+}
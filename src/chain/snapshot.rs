@@ -0,0 +1,129 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::BlockIdentifier;
+use std::sync::Arc;
+
+/// An immutable view of a chain's blocks as `DataChain::mark_blocks_valid` would leave them, see
+/// `DataChain::snapshot`. Clones share the blocks, so are cheap, and may be sent to and read from
+/// other threads while the chain goes on changing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainSnapshot {
+    blocks: Arc<Vec<Block>>,
+    group_size: usize,
+}
+
+impl ChainSnapshot {
+    /// A view of `blocks`, taken as validated.
+    pub fn new(blocks: Vec<Block>, group_size: usize) -> ChainSnapshot {
+        ChainSnapshot {
+            blocks: Arc::new(blocks),
+            group_size: group_size,
+        }
+    }
+
+    /// All blocks, valid or not, in chain order.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// The group size the chain was created with.
+    pub fn group_size(&self) -> usize {
+        self.group_size
+    }
+
+    /// The valid data blocks.
+    pub fn valid_data(&self) -> Vec<Block> {
+        self.blocks.iter().filter(|x| !x.identifier().is_link() && x.valid).cloned().collect()
+    }
+
+    /// The valid links.
+    pub fn valid_links(&self) -> Vec<Block> {
+        self.blocks.iter().filter(|x| x.identifier().is_link() && x.valid).cloned().collect()
+    }
+
+    /// The last valid link, if any.
+    pub fn last_valid_link(&self) -> Option<&Block> {
+        self.blocks.iter().rev().find(|x| x.identifier().is_link() && x.valid)
+    }
+
+    /// The block for `block_identifier`, if held.
+    pub fn find(&self, block_identifier: &BlockIdentifier) -> Option<&Block> {
+        self.blocks.iter().find(|x| x.identifier() == block_identifier)
+    }
+
+    /// Number of blocks, valid or not.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Number of valid blocks and links.
+    pub fn valid_len(&self) -> usize {
+        self.blocks.iter().filter(|x| x.valid).count()
+    }
+
+    /// Whether there are no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{DataChain, test_blocks};
+    use std::{ptr, thread};
+
+    #[test]
+    fn snapshot() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(3);
+        let late = unwrap!(blocks.pop());
+        let mut chain = DataChain::from_blocks(blocks, 1);
+        let snapshot = chain.snapshot();
+        // Validated without touching the chain.
+        assert_eq!(snapshot.valid_len(), 2);
+        assert_eq!(chain.valid_len(), 0);
+        assert_eq!(chain.valid_data(), snapshot.valid_data());
+        assert_eq!(unwrap!(snapshot.last_valid_link()).identifier(),
+                   chain.chain()[0].identifier());
+        assert!(ptr::eq(chain.snapshot().blocks(), snapshot.blocks()));
+
+        let reader = snapshot.clone();
+        let handle = thread::spawn(move || reader.valid_data().len());
+        chain.insert(2, late);
+        assert_eq!(unwrap!(handle.join()), 1);
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(chain.snapshot().valid_len(), 3);
+        assert!(!ptr::eq(chain.snapshot().blocks(), snapshot.blocks()));
+    }
+
+    #[test]
+    fn swapped_pins() {
+        let _ = ::rust_sodium::init();
+        // Without a link only pinned blocks survive validation.
+        let blocks = test_blocks(3).split_off(1);
+        let (first, second) = (blocks[0].identifier().clone(), blocks[1].identifier().clone());
+        let mut chain = DataChain::from_blocks(blocks, 1);
+        assert!(chain.pin(&first));
+        assert_eq!(unwrap!(chain.snapshot().blocks().first()).identifier(), &first);
+        assert!(chain.unpin(&first));
+        assert!(chain.pin(&second));
+        assert_eq!(unwrap!(chain.snapshot().blocks().first()).identifier(), &second);
+        assert_eq!(chain.snapshot().len(), 1);
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::LinkDescriptor;
+use chain::data_chain::DataChain;
+use chain::persistence;
+use std::collections::BTreeMap;
+
+/// A one call summary of a chain's health, for dashboards, see `DataChain::stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChainStats {
+    /// Data blocks, valid or not.
+    pub blocks: usize,
+    /// Valid data blocks.
+    pub valid_blocks: usize,
+    /// Links, valid or not.
+    pub links: usize,
+    /// Valid links.
+    pub valid_links: usize,
+    /// Mean number of proofs on a valid link.
+    pub mean_link_proofs: f64,
+    /// Number of valid blocks and links by how many signatures they hold beyond the quorum of the
+    /// link governing them. Blocks before the first valid link are not counted.
+    pub quorum_margins: BTreeMap<usize, usize>,
+    /// Valid links other than checkpoints per interval between checkpoint links, a measure of
+    /// churn. The whole chain is one interval until the first checkpoint.
+    pub churn_rate: f64,
+    /// Most valid links in a row without a checkpoint link among them.
+    pub max_checkpoint_gap: usize,
+    /// Size of the chain in the chain file format, see `persistence::encode_chain`.
+    pub encoded_size: u64,
+}
+
+impl DataChain {
+    /// Counts of blocks and links, signatures, churn and size of this chain.
+    pub fn stats(&self) -> ChainStats {
+        let (links, blocks): (Vec<&Block>, Vec<&Block>) =
+            self.chain().iter().partition(|x| x.identifier().is_link());
+        let valid_links = links.iter().filter(|x| x.valid).cloned().collect::<Vec<_>>();
+        let proofs = valid_links.iter().map(|x| x.proofs().len()).sum::<usize>();
+
+        let mut quorum_margins = BTreeMap::new();
+        let mut governing: Option<&Block> = None;
+        for block in self.chain().iter().filter(|x| x.valid) {
+            if let Some(link) = governing {
+                *quorum_margins.entry(margin(block, link, self.group_size())).or_insert(0) += 1;
+            }
+            if block.identifier().is_link() {
+                governing = Some(block);
+            }
+        }
+
+        let (mut checkpoints, mut gap, mut max_checkpoint_gap) = (0, 0, 0usize);
+        for link in &valid_links {
+            match link.identifier().link_descriptor() {
+                Some(&LinkDescriptor::CheckPoint { .. }) => {
+                    checkpoints += 1;
+                    gap = 0;
+                }
+                _ => {
+                    gap += 1;
+                    max_checkpoint_gap = max_checkpoint_gap.max(gap);
+                }
+            }
+        }
+
+        ChainStats {
+            blocks: blocks.len(),
+            valid_blocks: blocks.iter().filter(|x| x.valid).count(),
+            links: links.len(),
+            valid_links: valid_links.len(),
+            mean_link_proofs: if valid_links.is_empty() {
+                0.0
+            } else {
+                proofs as f64 / valid_links.len() as f64
+            },
+            quorum_margins: quorum_margins,
+            churn_rate: (valid_links.len() - checkpoints) as f64 / (checkpoints + 1) as f64,
+            max_checkpoint_gap: max_checkpoint_gap,
+            encoded_size: persistence::encode_chain(self.chain(),
+                                                    self.pinned(),
+                                                    self.hash_algorithm())
+                .map_or(0, |x| x.len() as u64),
+        }
+    }
+}
+
+// Signatures on `block` from members of `link` beyond the quorum it needs, see
+// `DataChain::validate_block_with_proof`.
+fn margin(block: &Block, link: &Block, group_size: usize) -> usize {
+    let members = link.members();
+    let signed = members.iter()
+        .filter(|&key| block.proofs().iter().any(|x| x.key() == key))
+        .count();
+    let needed = ((members.len() + 1) / 2).min(group_size);
+    signed.saturating_sub(needed)
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Prefix, Vote};
+    use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+
+    #[test]
+    fn stats() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..4).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let block = |id: BlockIdentifier, signers: &[&(PublicKey, SecretKey)], valid| {
+            let proofs = signers.iter()
+                .map(|x| unwrap!(Vote::new(&x.0, &x.1, id.clone())).proof().clone())
+                .collect();
+            Block::from_parts(id, proofs, valid)
+        };
+        let link = |node: &(PublicKey, SecretKey)| {
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(node.0))
+        };
+        let checkpoint = BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+            prefix: Prefix::new(1),
+            members: vec![nodes[0].0, nodes[1].0, nodes[2].0],
+            state: [0; 32],
+        });
+        let data = |byte| BlockIdentifier::ImmutableData([byte; 32]);
+        let (a, b, c, d) = (&nodes[0], &nodes[1], &nodes[2], &nodes[3]);
+        let chain = DataChain::from_blocks(vec![block(link(a), &[a, b, c, d], true),
+                                                block(data(1), &[a, b, c, d], true),
+                                                block(data(2), &[a, b], true),
+                                                block(link(b), &[a, b, c], true),
+                                                block(link(c), &[a, b, c], true),
+                                                block(checkpoint, &[a, b, c], true),
+                                                block(link(d), &[a, b], true),
+                                                block(data(3), &[a], false)],
+                                           8);
+        let stats = chain.stats();
+        assert_eq!((stats.blocks, stats.valid_blocks), (3, 2));
+        assert_eq!((stats.links, stats.valid_links), (5, 5));
+        assert_eq!(stats.mean_link_proofs, 3.0);
+        // Four members need two signatures, as do three.
+        let margins = stats.quorum_margins.into_iter().collect::<Vec<_>>();
+        assert_eq!(margins, vec![(0, 2), (1, 3), (2, 1)]);
+        assert_eq!(stats.churn_rate, 2.0);
+        assert_eq!(stats.max_checkpoint_gap, 3);
+        assert!(stats.encoded_size > 0);
+    }
+}
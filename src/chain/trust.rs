@@ -0,0 +1,175 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::data_chain::DataChain;
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::BTreeSet;
+
+/// How `DataChain::trust_report` scores nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrustConfig {
+    /// Most recent valid links that link participation is measured over.
+    pub link_window: usize,
+    /// Most recent valid data blocks that data vouching is measured over.
+    pub data_window: usize,
+    /// Factor a score is multiplied by for each of the latest events in a row the node missed,
+    /// so a node that stopped taking part loses trust quickly whatever its history.
+    pub decay: f64,
+}
+
+impl Default for TrustConfig {
+    fn default() -> TrustConfig {
+        TrustConfig {
+            link_window: 16,
+            data_window: 64,
+            decay: 0.5,
+        }
+    }
+}
+
+/// Trust in one node, from its signatures on the latest links and data blocks of a chain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrustReport {
+    /// The node scored.
+    pub key: PublicKey,
+    /// Links in the window the node signed.
+    pub links_signed: usize,
+    /// Links in the window.
+    pub links_seen: usize,
+    /// Data blocks in the window the node signed.
+    pub blocks_vouched: usize,
+    /// Data blocks in the window.
+    pub blocks_seen: usize,
+    /// Share of the links signed, decayed for the latest links missed, from 0 to 1.
+    pub link_score: f64,
+    /// Share of the data blocks signed, decayed for the latest blocks missed, from 0 to 1.
+    pub data_score: f64,
+}
+
+impl TrustReport {
+    /// Overall trust from 0 to 1, the mean of `link_score` and `data_score`, or just
+    /// `link_score` while the window holds no data.
+    pub fn score(&self) -> f64 {
+        if self.blocks_seen == 0 {
+            self.link_score
+        } else {
+            (self.link_score + self.data_score) / 2.0
+        }
+    }
+}
+
+impl DataChain {
+    /// Score `key` on its part in the latest valid links and data blocks, see `TrustConfig`.
+    pub fn trust_report(&self, key: &PublicKey, config: &TrustConfig) -> TrustReport {
+        let links = self.latest(config.link_window, true);
+        let blocks = self.latest(config.data_window, false);
+        let (links_signed, link_score) = participation(&links, key, config.decay);
+        let (blocks_vouched, data_score) = participation(&blocks, key, config.decay);
+        TrustReport {
+            key: *key,
+            links_signed: links_signed,
+            links_seen: links.len(),
+            blocks_vouched: blocks_vouched,
+            blocks_seen: blocks.len(),
+            link_score: link_score,
+            data_score: data_score,
+        }
+    }
+
+    /// A `trust_report` for every node that signed a link or data block in the windows, most
+    /// trusted first.
+    pub fn trust_reports(&self, config: &TrustConfig) -> Vec<TrustReport> {
+        let keys = self.latest(config.link_window, true)
+            .into_iter()
+            .chain(self.latest(config.data_window, false))
+            .flat_map(|x| x.proofs().iter().map(|proof| *proof.key()))
+            .collect::<BTreeSet<_>>();
+        let mut reports = keys.iter().map(|x| self.trust_report(x, config)).collect::<Vec<_>>();
+        reports.sort_by(|a, b| b.score().partial_cmp(&a.score()).unwrap_or(a.key.cmp(&b.key)));
+        reports
+    }
+
+    // Up to `count` of the latest valid links, or data blocks, newest first.
+    fn latest(&self, count: usize, links: bool) -> Vec<&Block> {
+        self.chain()
+            .iter()
+            .rev()
+            .filter(|x| x.valid && x.identifier().is_link() == links)
+            .take(count)
+            .collect()
+    }
+}
+
+// Events of `events` (newest first) signed by `key`, and the decayed share signed.
+fn participation(events: &[&Block], key: &PublicKey, decay: f64) -> (usize, f64) {
+    if events.is_empty() {
+        return (0, 0.0);
+    }
+    let signed = |block: &Block| block.proofs().iter().any(|x| x.key() == key);
+    let count = events.iter().filter(|x| signed(x)).count();
+    let missed = events.iter().take_while(|x| !signed(x)).count();
+    let share = count as f64 / events.len() as f64;
+    (count, share * decay.powi(missed as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Vote};
+    use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+    use super::*;
+
+    #[test]
+    fn participation_and_decay() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let block = |id: BlockIdentifier, signers: &[&(PublicKey, SecretKey)]| {
+            let proofs = signers.iter()
+                .map(|x| unwrap!(Vote::new(&x.0, &x.1, id.clone())).proof().clone())
+                .collect();
+            Block::from_parts(id, proofs, true)
+        };
+        let link = |node: &(PublicKey, SecretKey)| {
+            BlockIdentifier::Link(LinkDescriptor::NodeGained(node.0))
+        };
+        let data = |byte| BlockIdentifier::ImmutableData([byte; 32]);
+        let (a, b, c) = (&nodes[0], &nodes[1], &nodes[2]);
+        let chain = DataChain::from_blocks(vec![block(link(a), &[a, b]),
+                                                block(data(1), &[a, b]),
+                                                block(link(b), &[a, b, c]),
+                                                block(data(2), &[a, c]),
+                                                block(link(c), &[a, c])],
+                                           4);
+        let config = TrustConfig::default();
+
+        let report = chain.trust_report(&a.0, &config);
+        assert_eq!((report.links_signed, report.links_seen), (3, 3));
+        assert_eq!((report.blocks_vouched, report.blocks_seen), (2, 2));
+        assert_eq!(report.score(), 1.0);
+        // `b` signed two of three links but missed the latest, and one of the two data blocks
+        // but not the latest.
+        let report = chain.trust_report(&b.0, &config);
+        assert_eq!(report.link_score, 2.0 / 3.0 * 0.5);
+        assert_eq!(report.data_score, 0.5 * 0.5);
+
+        let narrow = TrustConfig { link_window: 2, ..config };
+        assert_eq!(chain.trust_report(&c.0, &narrow).link_score, 1.0);
+        let ranked = chain.trust_reports(&config).into_iter().map(|x| x.key).collect::<Vec<_>>();
+        assert_eq!(ranked[0], a.0);
+        assert_eq!(ranked.len(), 3);
+    }
+}
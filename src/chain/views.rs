@@ -0,0 +1,294 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block::Block;
+use chain::block_identifier::{BlockIdentifier, LinkDescriptor, Prefix};
+use chain::data_chain::DataChain;
+use data::DataIdentifier;
+use rust_sodium::crypto::sign::PublicKey;
+use std::fmt::{self, Debug, Formatter};
+use std::slice;
+
+/// A link of a chain, with getters for the fields of its `LinkDescriptor`, see
+/// `DataChain::links`.
+#[derive(Clone, Copy)]
+pub struct LinkRef<'a> {
+    block: &'a Block,
+    descriptor: &'a LinkDescriptor,
+}
+
+impl<'a> LinkRef<'a> {
+    /// The link's block.
+    pub fn block(&self) -> &'a Block {
+        self.block
+    }
+
+    /// What changed the group.
+    pub fn descriptor(&self) -> &'a LinkDescriptor {
+        self.descriptor
+    }
+
+    /// Whether the link is marked valid.
+    pub fn is_valid(&self) -> bool {
+        self.block.valid
+    }
+
+    /// Keys counting towards quorum of the blocks the link governs, see `Block::members`.
+    pub fn members(&self) -> Vec<PublicKey> {
+        self.block.members()
+    }
+
+    /// The node that joined, for a `NodeGained` link.
+    pub fn node_gained(&self) -> Option<&'a PublicKey> {
+        match *self.descriptor {
+            LinkDescriptor::NodeGained(ref key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The node that left, for a `NodeLost` link.
+    pub fn node_lost(&self) -> Option<&'a PublicKey> {
+        match *self.descriptor {
+            LinkDescriptor::NodeLost(ref key) => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The section prefix of a split, merge or checkpoint link.
+    pub fn prefix(&self) -> Option<&'a Prefix> {
+        match *self.descriptor {
+            LinkDescriptor::SplitFrom(ref prefix) |
+            LinkDescriptor::CancelSplitFrom(ref prefix) |
+            LinkDescriptor::MergeTo(ref prefix) |
+            LinkDescriptor::CheckPoint { ref prefix, .. } => Some(prefix),
+            _ => None,
+        }
+    }
+
+    /// The new group size, for a `Reconfigure` link.
+    pub fn group_size(&self) -> Option<u64> {
+        match *self.descriptor {
+            LinkDescriptor::Reconfigure { group_size } => Some(group_size),
+            _ => None,
+        }
+    }
+
+    /// The key accused, for an `Accusation` link.
+    pub fn offender(&self) -> Option<&'a PublicKey> {
+        self.descriptor.offender()
+    }
+}
+
+impl Debug for LinkRef<'_> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "LinkRef({:?})", self.block.identifier())
+    }
+}
+
+/// A data block of a chain, with getters for the fields of its `BlockIdentifier`, see
+/// `DataChain::data_blocks`. An `Expiring` block is viewed as the block it wraps.
+#[derive(Clone, Copy, Debug)]
+pub struct DataBlockRef<'a> {
+    block: &'a Block,
+}
+
+impl<'a> DataBlockRef<'a> {
+    /// The block.
+    pub fn block(&self) -> &'a Block {
+        self.block
+    }
+
+    /// The block's identifier, as signed.
+    pub fn identifier(&self) -> &'a BlockIdentifier {
+        self.block.identifier()
+    }
+
+    /// Whether the block is marked valid.
+    pub fn is_valid(&self) -> bool {
+        self.block.valid
+    }
+
+    /// Hash of the data, or of a batch's items, absent for a tombstone.
+    pub fn hash(&self) -> Option<&'a [u8; 32]> {
+        match *self.identifier().without_expiry() {
+            BlockIdentifier::ImmutableData(ref hash) |
+            BlockIdentifier::StructuredData(ref hash, _) |
+            BlockIdentifier::AppendableData(ref hash, _) |
+            BlockIdentifier::MutableDataEntry(ref hash, _) |
+            BlockIdentifier::Batch(_, ref hash) |
+            BlockIdentifier::Custom { ref hash, .. } |
+            BlockIdentifier::Transfer { ref hash, .. } => Some(hash),
+            _ => None,
+        }
+    }
+
+    /// The application's tag, for a `Custom` block.
+    pub fn tag(&self) -> Option<u64> {
+        match *self.identifier().without_expiry() {
+            BlockIdentifier::Custom { tag, .. } => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Name of the data, absent for a batch, see `BlockIdentifier::name`.
+    pub fn name(&self) -> Option<&'a [u8; 32]> {
+        self.identifier().name()
+    }
+
+    /// Identifier of the data held or, for a tombstone, deleted. Absent for immutable data and
+    /// batches, named by their hash and items.
+    pub fn data_identifier(&self) -> Option<&'a DataIdentifier> {
+        match *self.identifier().without_expiry() {
+            BlockIdentifier::StructuredData(_, ref id) |
+            BlockIdentifier::AppendableData(_, ref id) |
+            BlockIdentifier::MutableDataEntry(_, ref id) |
+            BlockIdentifier::Deleted(ref id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// The items of a batch, empty otherwise.
+    pub fn batch(&self) -> &'a [DataIdentifier] {
+        match *self.identifier().without_expiry() {
+            BlockIdentifier::Batch(ref items, _) => items,
+            _ => &[],
+        }
+    }
+
+    /// When the block expires, if it does.
+    pub fn expires_at(&self) -> Option<u64> {
+        self.identifier().expires_at()
+    }
+
+    /// Whether this is a tombstone recording agreed deletion.
+    pub fn is_deleted(&self) -> bool {
+        matches!(*self.identifier(), BlockIdentifier::Deleted(_))
+    }
+}
+
+/// Iterator over the links of a chain in order, see `DataChain::links`.
+pub struct Links<'a> {
+    blocks: slice::Iter<'a, Block>,
+}
+
+impl<'a> Iterator for Links<'a> {
+    type Item = LinkRef<'a>;
+
+    fn next(&mut self) -> Option<LinkRef<'a>> {
+        for block in &mut self.blocks {
+            if let Some(descriptor) = block.identifier().link_descriptor() {
+                return Some(LinkRef {
+                    block: block,
+                    descriptor: descriptor,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over data blocks of a chain in order, see `DataChain::data_blocks`.
+pub struct DataBlocks<'a> {
+    blocks: slice::Iter<'a, Block>,
+    filter: fn(&BlockIdentifier) -> bool,
+}
+
+impl<'a> Iterator for DataBlocks<'a> {
+    type Item = DataBlockRef<'a>;
+
+    fn next(&mut self) -> Option<DataBlockRef<'a>> {
+        let filter = self.filter;
+        self.blocks
+            .find(|x| x.identifier().is_block() && filter(x.identifier().without_expiry()))
+            .map(|block| DataBlockRef { block: block })
+    }
+}
+
+impl DataChain {
+    /// Iterate over the links, valid or not, in chain order.
+    pub fn links(&self) -> Links {
+        Links { blocks: self.chain().iter() }
+    }
+
+    /// Iterate over the data blocks, valid or not, in chain order.
+    pub fn data_blocks(&self) -> DataBlocks {
+        self.data_blocks_where(|_| true)
+    }
+
+    /// As `data_blocks`, only those for immutable data.
+    pub fn immutable_blocks(&self) -> DataBlocks {
+        self.data_blocks_where(|x| matches!(*x, BlockIdentifier::ImmutableData(_)))
+    }
+
+    /// As `data_blocks`, only those for structured data.
+    pub fn structured_blocks(&self) -> DataBlocks {
+        self.data_blocks_where(|x| matches!(*x, BlockIdentifier::StructuredData(..)))
+    }
+
+    fn data_blocks_where(&self, filter: fn(&BlockIdentifier) -> bool) -> DataBlocks {
+        DataBlocks {
+            blocks: self.chain().iter(),
+            filter: filter,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Prefix, test_blocks};
+    use data::DataIdentifier;
+
+    #[test]
+    fn typed_views() {
+        let _ = ::rust_sodium::init();
+        let mut blocks = test_blocks(2);
+        let key = unwrap!(blocks[0].identifier().link_descriptor().and_then(|x| x.name()));
+        let key = ::rust_sodium::crypto::sign::PublicKey(*key);
+        let structured = DataIdentifier::Structured([2; 32], 3);
+        let expiring = BlockIdentifier::StructuredData([1; 32], structured);
+        for identifier in vec![BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(1))),
+                               unwrap!(BlockIdentifier::expiring(expiring, 9)),
+                               BlockIdentifier::Deleted(structured),
+                               BlockIdentifier::Batch(vec![structured], [4; 32])] {
+            blocks.push(Block::from_parts(identifier, vec![], false));
+        }
+        let chain = DataChain::from_blocks(blocks, 4);
+
+        let links = chain.links().collect::<Vec<_>>();
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].node_gained(), Some(&key));
+        assert!(links[0].node_lost().is_none() && links[0].prefix().is_none());
+        assert_eq!(links[1].prefix().map(Prefix::bits), Some(1));
+        assert_eq!(links[1].block(), &chain.chain()[2]);
+
+        let data = chain.data_blocks().collect::<Vec<_>>();
+        assert_eq!(data.len(), 4);
+        assert_eq!(data[1].hash(), Some(&[1; 32]));
+        assert_eq!(data[1].data_identifier(), Some(&structured));
+        assert_eq!(data[1].expires_at(), Some(9));
+        assert!(data[2].is_deleted() && data[2].hash().is_none());
+        assert_eq!(data[3].batch(), &[structured]);
+
+        let immutable = chain.immutable_blocks().collect::<Vec<_>>();
+        assert_eq!(immutable.len(), 1);
+        assert_eq!(immutable[0].identifier(), chain.chain()[1].identifier());
+        assert!(immutable[0].data_identifier().is_none());
+        let structured_blocks = chain.structured_blocks().collect::<Vec<_>>();
+        assert_eq!(structured_blocks.len(), 1);
+        assert_eq!(structured_blocks[0].name(), Some(&[2; 32]));
+    }
+}
@@ -17,14 +17,19 @@
 
 use chain::block_identifier::BlockIdentifier;
 use chain::proof::Proof;
-use error::Error;
-use maidsafe_utilities::serialisation;
-use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+use chain::signed_payload;
+use error::{Error, ValidationError};
+use rust_sodium::crypto::sign::{PublicKey, SecretKey};
+use signature::{Ed25519, SignatureScheme};
 
 /// If data block then this is sent by any group member when data is `Put`, `Post` or `Delete`.
 /// If this is a link then it is sent with a `churn` event.
 /// A `Link` is a vote that each member must send each other in times of churn.
 /// These will not accumulate but be `ManagedNode`  to `ManagedNode` messages in the routing layer
+///
+/// A vote may also be bound to the state of the chain it is meant for (see
+/// `DataChain::vote_binding`). The binding is signed along with the identifier so a captured vote
+/// cannot be replayed into a different chain context.
 #[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
 pub struct Vote {
     identifier: BlockIdentifier,
@@ -37,69 +42,146 @@ impl Vote {
                secret_key: &SecretKey,
                data_identifier: BlockIdentifier)
                -> Result<Vote, Error> {
-        let signature = sign::sign_detached(&serialisation::serialise(&data_identifier)?[..],
-                                            secret_key);
+        Self::sign(pub_key, secret_key, data_identifier, None, None)
+    }
+
+    /// Create a vote bound to a chain's current `vote_binding`.
+    pub fn new_bound(pub_key: &PublicKey,
+                     secret_key: &SecretKey,
+                     data_identifier: BlockIdentifier,
+                     binding: [u8; 32])
+                     -> Result<Vote, Error> {
+        Self::sign(pub_key, secret_key, data_identifier, Some(binding), None)
+    }
+
+    /// Create a vote whose proof carries a signed `timestamp` (seconds since the unix epoch),
+    /// optionally also bound to a chain.
+    pub fn new_timestamped(pub_key: &PublicKey,
+                           secret_key: &SecretKey,
+                           data_identifier: BlockIdentifier,
+                           binding: Option<[u8; 32]>,
+                           timestamp: u64)
+                           -> Result<Vote, Error> {
+        Self::sign(pub_key, secret_key, data_identifier, binding, Some(timestamp))
+    }
+
+    fn sign(pub_key: &PublicKey,
+            secret_key: &SecretKey,
+            data_identifier: BlockIdentifier,
+            binding: Option<[u8; 32]>,
+            timestamp: Option<u64>)
+            -> Result<Vote, Error> {
+        let payload = signed_payload(&data_identifier, binding.as_ref(), timestamp);
+        let signature = Ed25519::sign(&payload[..], secret_key);
         Ok(Vote {
             identifier: data_identifier,
-            proof: Proof::new(*pub_key, signature),
+            proof: Proof::with_context(*pub_key, signature, binding, timestamp),
         })
     }
 
+    /// A vote from its identifier and proof, as read back from storage. Check it with `verify`.
+    pub fn from_parts(identifier: BlockIdentifier, proof: Proof) -> Vote {
+        Vote {
+            identifier: identifier,
+            proof: proof,
+        }
+    }
+
     /// Getter
     pub fn identifier(&self) -> &BlockIdentifier {
         &self.identifier
     }
     /// Getter
+    pub fn binding(&self) -> Option<&[u8; 32]> {
+        self.proof.binding()
+    }
+    /// Getter
     pub fn proof(&self) -> &Proof {
         &self.proof
     }
 
     /// validate signed correctly
     pub fn validate(&self) -> bool {
-        self.validate_detached(&self.identifier)
+        self.verify().is_ok()
     }
 
-    /// Check vote is not for self added/removed
-    pub fn is_self_vote(&self) -> bool {
-        if let Some(name) = self.identifier.name() {
-            &self.proof.key().0 == name
+    /// As `validate` but reports why the vote is invalid.
+    pub fn verify(&self) -> Result<(), ValidationError> {
+        if self.validate_detached(&self.identifier) {
+            Ok(())
         } else {
-            false
+            Err(ValidationError::BadSignature)
         }
     }
 
+    /// Whether this is a vote for a link adding or removing the voter itself. A node's vote does
+    /// not count towards its own membership change. Votes for data blocks are never self votes.
+    pub fn is_self_vote(&self) -> bool {
+        self.identifier
+            .link_descriptor()
+            .and_then(|link| link.name())
+            .is_some_and(|name| &self.proof.key().0 == name)
+    }
+
     /// validate signed correctly
     pub fn validate_detached(&self, identifier: &BlockIdentifier) -> bool {
-
-        match serialisation::serialise(identifier) {
-            Ok(data) => self.proof.validate(&data[..]),
-            _ => false,
-        }
+        self.proof.validate_for(identifier)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
-    // use chain::block_identifier::BlockIdentifier;
-    // use rust_sodium::crypto::sign;
-    // use sha3::hash;
-
-    // #[test]
-    // fn vote_comparisons() {
-    //     ::rust_sodium::init();
-    //     let keys = sign::gen_keypair();
-    //     let test_data1 = BlockIdentifier::Link(hash(b"1"));
-    //     let test_data2 = BlockIdentifier::Link(hash(b"1"));
-    //     let test_data3 = BlockIdentifier::ImmutableData(hash(b"1"));
-    //     let test_node_data_block1 = Vote::new(&keys.0, &keys.1, test_data1).expect("fail1");
-    //     let test_node_data_block2 = Vote::new(&keys.0, &keys.1, test_data2).expect("fail2");
-    //     let test_node_data_block3 = Vote::new(&keys.0, &keys.1, test_data3).expect("fail3");
-    //     assert!(test_node_data_block1.validate());
-    //     assert!(test_node_data_block2.validate());
-    //     assert!(test_node_data_block3.validate());
-    //     assert_eq!(test_node_data_block1.clone(), test_node_data_block2.clone());
-    //     assert!(test_node_data_block1 != test_node_data_block3.clone());
-    //     assert!(test_node_data_block2 != test_node_data_block3);
-    // }
+    use super::*;
+    use chain::block_identifier::{BlockIdentifier, LinkDescriptor};
+    use maidsafe_utilities::serialisation;
+    use rust_sodium::crypto::sign;
+    use sha3::hash;
+
+    #[test]
+    fn vote_comparisons() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let test_data1 = BlockIdentifier::ImmutableData(hash(b"1"));
+        let test_data2 = BlockIdentifier::ImmutableData(hash(b"1"));
+        let test_data3 = BlockIdentifier::ImmutableData(hash(b"2"));
+        let test_node_data_block1 = Vote::new(&keys.0, &keys.1, test_data1).expect("fail1");
+        let test_node_data_block2 = Vote::new(&keys.0, &keys.1, test_data2).expect("fail2");
+        let test_node_data_block3 = Vote::new(&keys.0, &keys.1, test_data3).expect("fail3");
+        assert!(test_node_data_block1.validate());
+        assert!(test_node_data_block2.validate());
+        assert!(test_node_data_block3.validate());
+        assert_eq!(test_node_data_block1.clone(), test_node_data_block2.clone());
+        assert!(test_node_data_block1 != test_node_data_block3.clone());
+        assert!(test_node_data_block2 != test_node_data_block3);
+        assert!(!test_node_data_block1.validate_detached(test_node_data_block3.identifier()));
+    }
+
+    #[test]
+    fn self_votes() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let other = sign::gen_keypair();
+        let gained = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let lost = BlockIdentifier::Link(LinkDescriptor::NodeLost(keys.0));
+        assert!(unwrap!(Vote::new(&keys.0, &keys.1, gained.clone())).is_self_vote());
+        assert!(unwrap!(Vote::new(&keys.0, &keys.1, lost)).is_self_vote());
+        assert!(!unwrap!(Vote::new(&other.0, &other.1, gained)).is_self_vote());
+        // Data named after the voter's key is still not a self vote.
+        let data = BlockIdentifier::ImmutableData((keys.0).0);
+        assert!(!unwrap!(Vote::new(&keys.0, &keys.1, data)).is_self_vote());
+    }
+
+    #[test]
+    fn serialisation() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let identifier = BlockIdentifier::ImmutableData(hash(b"1"));
+        let vote = unwrap!(Vote::new_timestamped(&keys.0, &keys.1, identifier, Some([1; 32]), 7));
+        let serialised = unwrap!(serialisation::serialise(&vote));
+        let parsed: Vote = unwrap!(serialisation::deserialise(&serialised));
+        assert_eq!(parsed, vote);
+        assert!(parsed.validate());
+        assert_eq!(parsed.binding(), Some(&[1; 32]));
+        assert_eq!(parsed.proof().timestamp(), Some(7));
+    }
 }
@@ -0,0 +1,248 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::block_identifier::BlockIdentifier;
+use chain::data_chain::DataChain;
+use error::ValidationError;
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::HashMap;
+
+/// Early warning of a degraded or attacked section, raised by a `Watchdog`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HealthAlert {
+    /// A block is still pending although `links` valid links have been added after it.
+    StuckBlock {
+        /// The pending block.
+        identifier: BlockIdentifier,
+        /// Valid links after it in the chain.
+        links: usize,
+    },
+    /// A pending block has gained no votes over `checks` consecutive checks.
+    QuorumStalled {
+        /// The pending block.
+        identifier: BlockIdentifier,
+        /// Votes it holds.
+        proofs: usize,
+        /// Checks without progress.
+        checks: u32,
+    },
+    /// Votes from `key` failed validation `failures` times in a row.
+    RepeatedFailures {
+        /// The signer of the failing votes.
+        key: PublicKey,
+        /// Consecutive failures.
+        failures: u32,
+        /// The latest failure.
+        error: ValidationError,
+    },
+}
+
+/// Thresholds at which a `Watchdog` raises alerts.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchdogConfig {
+    /// Valid links after a pending block before it is reported stuck.
+    pub stuck_links: usize,
+    /// Checks a pending block may go without new votes before quorum is reported stalled.
+    pub stall_checks: u32,
+    /// Consecutive validation failures from one key before it is reported.
+    pub max_failures: u32,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> WatchdogConfig {
+        WatchdogConfig {
+            stuck_links: 2,
+            stall_checks: 3,
+            max_failures: 5,
+        }
+    }
+}
+
+/// Monitors accumulation on a chain. Call `check` periodically with the chain, and `record_vote`
+/// with the outcome of validating each incoming vote, e.g. from `DataChain::verify_vote`. Each
+/// condition is reported once, when its threshold is reached.
+pub struct Watchdog {
+    config: WatchdogConfig,
+    pending: Vec<Pending>,
+    failures: HashMap<PublicKey, u32>,
+}
+
+struct Pending {
+    identifier: BlockIdentifier,
+    proofs: usize,
+    checks: u32,
+    stuck: bool,
+}
+
+impl Watchdog {
+    /// Create a watchdog with the given thresholds.
+    pub fn new(config: WatchdogConfig) -> Watchdog {
+        Watchdog {
+            config: config,
+            pending: Vec::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Thresholds in use.
+    pub fn config(&self) -> &WatchdogConfig {
+        &self.config
+    }
+
+    /// Look for blocks stuck or stalled since the last check.
+    pub fn check(&mut self, chain: &DataChain) -> Vec<HealthAlert> {
+        let mut alerts = Vec::new();
+        let mut pending = Vec::new();
+        let mut links = 0;
+        for block in chain.chain().iter().rev() {
+            if block.valid {
+                if block.identifier().is_link() {
+                    links += 1;
+                }
+                continue;
+            }
+            let mut entry = match self.pending
+                .iter()
+                .position(|x| x.identifier == *block.identifier()) {
+                Some(index) => self.pending.swap_remove(index),
+                None => {
+                    Pending {
+                        identifier: block.identifier().clone(),
+                        proofs: block.proofs().len(),
+                        checks: 0,
+                        stuck: false,
+                    }
+                }
+            };
+            if entry.proofs == block.proofs().len() {
+                entry.checks += 1;
+                if entry.checks == self.config.stall_checks {
+                    alerts.push(HealthAlert::QuorumStalled {
+                        identifier: entry.identifier.clone(),
+                        proofs: entry.proofs,
+                        checks: entry.checks,
+                    });
+                }
+            } else {
+                entry.proofs = block.proofs().len();
+                entry.checks = 0;
+            }
+            if !entry.stuck && links >= self.config.stuck_links {
+                entry.stuck = true;
+                alerts.push(HealthAlert::StuckBlock {
+                    identifier: entry.identifier.clone(),
+                    links: links,
+                });
+            }
+            pending.push(entry);
+        }
+        self.pending = pending;
+        alerts
+    }
+
+    /// Record whether a vote signed by `key` passed validation. Consecutive failures from one key
+    /// raise an alert on reaching the threshold, a success resets the count.
+    pub fn record_vote(&mut self,
+                       key: &PublicKey,
+                       result: Result<(), ValidationError>)
+                       -> Option<HealthAlert> {
+        let error = match result {
+            Ok(()) => {
+                let _ = self.failures.remove(key);
+                return None;
+            }
+            Err(error) => error,
+        };
+        let failures = self.failures.entry(*key).or_insert(0);
+        *failures += 1;
+        if *failures == self.config.max_failures {
+            Some(HealthAlert::RepeatedFailures {
+                key: *key,
+                failures: *failures,
+                error: error,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for Watchdog {
+    fn default() -> Watchdog {
+        Watchdog::new(WatchdogConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::block_identifier::LinkDescriptor;
+    use chain::vote::Vote;
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn alerts() {
+        let _ = ::rust_sodium::init();
+        let nodes = (0..3).map(|_| sign::gen_keypair()).collect::<Vec<_>>();
+        let vote = |node: usize, identifier: &BlockIdentifier| {
+            unwrap!(Vote::new(&nodes[node].0, &nodes[node].1, identifier.clone()))
+        };
+        let links = (0..3)
+            .map(|i| BlockIdentifier::Link(LinkDescriptor::NodeGained(nodes[i].0)))
+            .collect::<Vec<_>>();
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let _ = chain.add_vote(vote(0, &links[0]));
+        let _ = chain.add_vote(vote(0, &links[1]));
+        let _ = chain.add_vote(vote(1, &data));
+        assert!(!unwrap!(chain.find(&data)).valid);
+
+        let mut watchdog = Watchdog::new(WatchdogConfig {
+            stuck_links: 1,
+            stall_checks: 2,
+            max_failures: 2,
+        });
+        assert!(watchdog.check(&chain).is_empty());
+        assert_eq!(watchdog.check(&chain),
+                   vec![HealthAlert::QuorumStalled {
+                            identifier: data.clone(),
+                            proofs: 1,
+                            checks: 2,
+                        }]);
+        assert!(watchdog.check(&chain).is_empty());
+
+        // The pending block is overtaken by a new link.
+        let _ = chain.add_vote(vote(0, &links[2]));
+        let _ = chain.add_vote(vote(1, &links[2]));
+        assert_eq!(watchdog.check(&chain),
+                   vec![HealthAlert::StuckBlock {
+                            identifier: data.clone(),
+                            links: 1,
+                        }]);
+
+        let key = nodes[2].0;
+        assert_eq!(watchdog.record_vote(&key, Err(ValidationError::NoQuorum)), None);
+        assert_eq!(watchdog.record_vote(&key, Ok(())), None);
+        assert_eq!(watchdog.record_vote(&key, Err(ValidationError::NoQuorum)), None);
+        assert_eq!(watchdog.record_vote(&key, Err(ValidationError::BadSignature)),
+                   Some(HealthAlert::RepeatedFailures {
+                       key: key,
+                       failures: 2,
+                       error: ValidationError::BadSignature,
+                   }));
+    }
+}
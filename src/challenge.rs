@@ -0,0 +1,48 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::BlockIdentifier;
+use sha3::hash;
+
+/// A request for a holder to prove it stores the data of `identifier`, see
+/// `SecuredData::create_challenge`. A fresh `nonce` per challenge stops answers being reused.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+pub struct Challenge {
+    /// The block whose data is challenged.
+    pub identifier: BlockIdentifier,
+    /// Mixed into the proof so it can only be computed from the data itself.
+    pub nonce: [u8; 32],
+}
+
+/// A holder's answer to a `Challenge`, see `SecuredData::respond`.
+#[derive(RustcEncodable, RustcDecodable, PartialEq, Debug, Clone)]
+pub struct ChallengeResponse {
+    /// The block whose data was challenged.
+    pub identifier: BlockIdentifier,
+    /// The nonce of the challenge answered.
+    pub nonce: [u8; 32],
+    /// `storage_proof` of the serialised data and the nonce.
+    pub proof: [u8; 32],
+}
+
+/// Hash of `content` followed by `nonce`.
+pub fn storage_proof(content: &[u8], nonce: &[u8; 32]) -> [u8; 32] {
+    let mut input = Vec::with_capacity(content.len() + nonce.len());
+    input.extend_from_slice(content);
+    input.extend_from_slice(nonce);
+    hash(&input)
+}
@@ -0,0 +1,186 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chunk_store::ChunkStorage;
+use error::Error;
+use maidsafe_utilities::serialisation;
+use rustc_serialize::{Decodable, Encodable};
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io;
+use std::marker::PhantomData;
+
+/// A failure `MockChunkStore` can be told to inject.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fault {
+    /// Every `put` fails with `Error::StorageFull`, as on a full disk.
+    DiskFull,
+    /// The fallible operation (`put`, `delete` or `get`) with this index fails with `Error::Io`.
+    /// Operations are counted from zero when the store is created.
+    IoErrorOnOp(u64),
+    /// `get` reads back the stored bytes with the last one flipped, which either fails to
+    /// deserialise or yields a different value.
+    CorruptReads,
+}
+
+/// In-memory `ChunkStorage`, serialising values as `ChunkStore` does, into which failures can be
+/// injected to exercise error handling without real disk faults.
+pub struct MockChunkStore<Key, Value> {
+    chunks: BTreeMap<Vec<u8>, Vec<u8>>,
+    max_space: u64,
+    used_space: u64,
+    faults: Vec<Fault>,
+    operations: Cell<u64>,
+    phantom: PhantomData<(Key, Value)>,
+}
+
+impl<Key, Value> MockChunkStore<Key, Value>
+    where Key: Decodable + Encodable,
+          Value: Decodable + Encodable
+{
+    /// Create an empty store allowing `max_space` bytes of serialised values.
+    pub fn new(max_space: u64) -> MockChunkStore<Key, Value> {
+        MockChunkStore {
+            chunks: BTreeMap::new(),
+            max_space: max_space,
+            used_space: 0,
+            faults: vec![],
+            operations: Cell::new(0),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Start injecting `fault`.
+    pub fn inject(&mut self, fault: Fault) {
+        self.faults.push(fault);
+    }
+
+    /// Stop injecting any faults.
+    pub fn clear_faults(&mut self) {
+        self.faults.clear();
+    }
+
+    /// Number of fallible operations made so far.
+    pub fn operations(&self) -> u64 {
+        self.operations.get()
+    }
+
+    // Count an operation, failing it if so programmed.
+    fn operation(&self) -> Result<(), Error> {
+        let index = self.operations.get();
+        self.operations.set(index + 1);
+        if self.faults.contains(&Fault::IoErrorOnOp(index)) {
+            return Err(Error::Io(io::Error::new(io::ErrorKind::Other, "injected fault")));
+        }
+        Ok(())
+    }
+}
+
+impl<Key, Value> ChunkStorage<Key, Value> for MockChunkStore<Key, Value>
+    where Key: Decodable + Encodable,
+          Value: Decodable + Encodable
+{
+    fn put(&mut self, key: &Key, value: &Value) -> Result<(), Error> {
+        self.operation()?;
+        let key = serialisation::serialise(key)?;
+        let value = serialisation::serialise(value)?;
+        let replaced = self.chunks.get(&key).map_or(0, |x| x.len() as u64);
+        if self.faults.contains(&Fault::DiskFull) ||
+           self.used_space - replaced + value.len() as u64 > self.max_space {
+            return Err(Error::StorageFull);
+        }
+        self.used_space = self.used_space - replaced + value.len() as u64;
+        let _ = self.chunks.insert(key, value);
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &Key) -> Result<(), Error> {
+        self.operation()?;
+        if let Some(value) = self.chunks.remove(&serialisation::serialise(key)?) {
+            self.used_space -= value.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &Key) -> Result<Value, Error> {
+        self.operation()?;
+        let mut value = match self.chunks.get(&serialisation::serialise(key)?) {
+            Some(value) => value.clone(),
+            None => return Err(Error::NotFound),
+        };
+        if self.faults.contains(&Fault::CorruptReads) {
+            if let Some(byte) = value.last_mut() {
+                *byte ^= 0xff;
+            }
+        }
+        Ok(serialisation::deserialise(&value)?)
+    }
+
+    fn has(&self, key: &Key) -> bool {
+        serialisation::serialise(key).ok().is_some_and(|x| self.chunks.contains_key(&x))
+    }
+
+    fn keys(&self) -> Vec<Key> {
+        self.chunks.keys().filter_map(|x| serialisation::deserialise(x).ok()).collect()
+    }
+
+    fn max_space(&self) -> u64 {
+        self.max_space
+    }
+
+    fn used_space(&self) -> u64 {
+        self.used_space
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults() {
+        let mut store = MockChunkStore::<u8, Vec<u8>>::new(64);
+        unwrap!(store.put(&1, &vec![1, 2, 3]));
+        assert_eq!(unwrap!(store.get(&1)), vec![1, 2, 3]);
+        assert_eq!(store.keys(), vec![1]);
+        match store.put(&2, &vec![0; 64]) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        store.inject(Fault::IoErrorOnOp(4));
+        unwrap!(store.put(&2, &vec![4]));
+        match store.get(&2) {
+            Err(Error::Io(_)) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(unwrap!(store.get(&2)), vec![4]);
+        assert_eq!(store.operations(), 6);
+
+        store.inject(Fault::CorruptReads);
+        assert!(store.get(&1).ok() != Some(vec![1, 2, 3]));
+        store.inject(Fault::DiskFull);
+        match store.put(&3, &vec![]) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        store.clear_faults();
+        unwrap!(store.delete(&1));
+        assert!(!store.has(&1) && store.has(&2));
+        assert_eq!(store.used_space(), unwrap!(serialisation::serialise(&vec![4u8])).len() as u64);
+    }
+}
@@ -18,30 +18,69 @@
 //! # Chunk Store
 //! A simple, non-persistent, disk-based key-value store.
 
+/// In-memory `ChunkStorage` with programmable failures, for testing error handling.
+mod mock;
+
+/// Streaming of immutable data in and out of a store.
+mod stream;
+
+#[cfg(test)]
+mod tests;
+
+pub use chunk_store::mock::{Fault, MockChunkStore};
+pub use chunk_store::stream::StreamStorage;
 
 use error::Error;
 
 use maidsafe_utilities::serialisation;
 use rustc_serialize::{Decodable, Encodable};
 use rustc_serialize::hex::{FromHex, ToHex};
+use sha3::hash;
 use std::cmp;
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{self, Read, Write};
 use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
 /// The max name length for a chunk file.
 const MAX_CHUNK_FILE_NAME_LENGTH: usize = 104;
+/// Number of directories chunk files are spread over, each named by two hex digits.
+const SHARDS: usize = 256;
+
 
+/// Storage for the data held by `SecuredData`. `ChunkStore` keeps it on disk, while
+/// `MockChunkStore` keeps it in memory and can be made to fail.
+pub trait ChunkStorage<Key, Value> {
+    /// Store `value` under `key`, replacing any value already there.
+    fn put(&mut self, key: &Key, value: &Value) -> Result<(), Error>;
+    /// Remove the value stored under `key`, succeeding if there is none.
+    fn delete(&mut self, key: &Key) -> Result<(), Error>;
+    /// The value stored under `key`.
+    fn get(&self, key: &Key) -> Result<Value, Error>;
+    /// Whether a value is stored under `key`.
+    fn has(&self, key: &Key) -> bool;
+    /// Keys of all stored values.
+    fn keys(&self) -> Vec<Key>;
+    /// Maximum space the values may use.
+    fn max_space(&self) -> u64;
+    /// Space used by the values so far.
+    fn used_space(&self) -> u64;
+}
 
 /// `ChunkStore` is a store of data held as serialised files on disk, implementing a maximum disk
 /// usage to restrict storage.
 ///
-/// The data chunks are deleted when the `ChunkStore` goes out of scope.
+/// Chunk files are spread over `SHARDS` directories by a hash of their name, so no directory
+/// grows too large, and an index of the files in each is kept in memory behind a lock of its
+/// own. Chunks in different directories may be written and read from several threads at once.
+/// Files left in the root directory by earlier versions are moved into place when opened.
 pub struct ChunkStore<Key, Value> {
     rootdir: PathBuf,
     max_space: u64,
-    used_space: u64,
+    used_space: Mutex<u64>,
+    shards: Vec<Mutex<HashMap<String, u64>>>,
     phantom: PhantomData<(Key, Value)>,
 }
 
@@ -57,26 +96,53 @@ impl<Key, Value> ChunkStore<Key, Value>
 
         // Verify that chunk files can be created.
         let name: String = (0..MAX_CHUNK_FILE_NAME_LENGTH).map(|_| '0').collect();
-        let _ = File::create(&root.join(name.clone()))?;
-        fs::remove_file(&root.join(name))?;
-        Ok(ChunkStore {
-            rootdir: root,
-            max_space: max_space,
-            used_space: 0,
-            phantom: PhantomData,
-        })
+        let _ = File::create(root.join(name.clone()))?;
+        fs::remove_file(root.join(name))?;
+        ChunkStore::from_path(root, max_space)
     }
 
     /// Open existing `ChunkStore` with `max_space` allowed storage space.
     ///
-    /// The data is stored in a root directory.
+    /// The data is stored in a root directory, which is read to build the index of chunks.
     pub fn from_path(root: PathBuf, max_space: u64) -> Result<ChunkStore<Key, Value>, Error> {
-        Ok(ChunkStore {
+        let store = ChunkStore {
             rootdir: root,
             max_space: max_space,
-            used_space: 0,
+            used_space: Mutex::new(0),
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
             phantom: PhantomData,
-        })
+        };
+        let entries = match fs::read_dir(&store.rootdir) {
+            Ok(entries) => entries,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(store),
+            Err(error) => return Err(From::from(error)),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if entry.file_type()?.is_dir() {
+                if Self::is_shard(&name) {
+                    for file in fs::read_dir(entry.path())? {
+                        let file = file?;
+                        if let Ok(name) = file.file_name().into_string() {
+                            if file.file_type()?.is_file() && Self::is_chunk(&name) {
+                                store.index(name, file.metadata()?.len());
+                            }
+                        }
+                    }
+                }
+            } else if Self::is_chunk(&name) {
+                let len = entry.metadata()?.len();
+                let path = store.shard_path(&name);
+                fs::create_dir_all(path.parent().unwrap_or(&store.rootdir))?;
+                fs::rename(entry.path(), path)?;
+                store.index(name, len);
+            }
+        }
+        Ok(store)
     }
 
     /// Stores a new data chunk under `key`.
@@ -85,79 +151,83 @@ impl<Key, Value> ChunkStore<Key, Value>
     /// an IO error, it returns `Error::Io`.
     ///
     /// If the key already exists, it will be overwritten.
-    pub fn put(&mut self, key: &Key, value: &Value) -> Result<(), Error> {
+    pub fn put(&self, key: &Key, value: &Value) -> Result<(), Error> {
         let serialised_value = serialisation::serialise(value)?;
-        if self.used_space + serialised_value.len() as u64 > self.max_space {
-            return Err(Error::NoSpace);
-        }
-
-        // If a file corresponding to 'key' already exists, delete it.
-        let file_path = self.file_path(key)?;
-        let _ = self.do_delete(&file_path);
+        let name = Self::file_name(key)?;
+        let path = self.shard_path(&name);
+        let mut index = self.shard(&name).lock().unwrap();
+        let replaced = index.get(&name).cloned().unwrap_or(0);
+        self.reserve(serialised_value.len() as u64, replaced)?;
 
-        // Write the file.
-        File::create(&file_path)
+        // Write the file, replacing any file of the same name.
+        let written = fs::create_dir_all(path.parent().unwrap_or(&self.rootdir))
+            .and_then(|()| File::create(&path))
             .and_then(|mut file| {
                 file.write_all(&serialised_value)
                     .and_then(|()| file.sync_all())
-                    .and_then(|()| file.metadata())
-                    .map(|metadata| {
-                        self.used_space += metadata.len();
-                    })
-            })
-            .map_err(From::from)
+            });
+        match written {
+            Ok(_) => {
+                self.release(replaced);
+                let _ = index.insert(name, serialised_value.len() as u64);
+                Ok(())
+            }
+            Err(error) => {
+                // Whatever was under the name is gone or damaged.
+                let _ = fs::remove_file(&path);
+                let _ = index.remove(&name);
+                self.release(replaced + serialised_value.len() as u64);
+                Err(From::from(error))
+            }
+        }
     }
 
     /// Deletes the data chunk stored under `key`.
     ///
     /// If the data doesn't exist, it does nothing and returns `Ok`.  In the case of an IO error, it
     /// returns `Error::Io`.
-    pub fn delete(&mut self, key: &Key) -> Result<(), Error> {
-        let file_path = self.file_path(key)?;
-        self.do_delete(&file_path)
+    pub fn delete(&self, key: &Key) -> Result<(), Error> {
+        let name = Self::file_name(key)?;
+        let mut index = self.shard(&name).lock().unwrap();
+        self.remove(&mut index, &name)
     }
 
     /// Returns a data chunk previously stored under `key`.
     ///
     /// If the data file can't be accessed, it returns `Error::ChunkNotFound`.
     pub fn get(&self, key: &Key) -> Result<Value, Error> {
-        match File::open(self.file_path(key)?) {
+        let name = Self::file_name(key)?;
+        let index = self.shard(&name).lock().unwrap();
+        if !index.contains_key(&name) {
+            return Err(Error::NotFound);
+        }
+        match File::open(self.shard_path(&name)) {
             Ok(mut file) => {
                 let mut contents = Vec::<u8>::new();
                 let _ = file.read_to_end(&mut contents)?;
                 Ok(serialisation::deserialise::<Value>(&contents)?)
             }
-            Err(_) => Err(Error::NoFile),
+            Err(_) => Err(Error::NotFound),
         }
     }
 
     /// Tests if a data chunk has been previously stored under `key`.
     pub fn has(&self, key: &Key) -> bool {
-        let file_path = if let Ok(path) = self.file_path(key) {
-            path
-        } else {
-            return false;
-        };
-        if let Ok(metadata) = fs::metadata(file_path) {
-            return metadata.is_file();
-        } else {
-            false
+        match Self::file_name(key) {
+            Ok(name) => self.shard(&name).lock().unwrap().contains_key(&name),
+            Err(_) => false,
         }
     }
 
     /// Lists all keys of currently-data stored.
     pub fn keys(&self) -> Vec<Key> {
-        fs::read_dir(&self.rootdir)
-            .and_then(|dir_entries| {
-                let dir_entry_to_routing_name = |dir_entry: io::Result<fs::DirEntry>| {
-                    dir_entry.ok()
-                        .and_then(|entry| entry.file_name().into_string().ok())
-                        .and_then(|hex_name| hex_name.from_hex().ok())
-                        .and_then(|bytes| serialisation::deserialise::<Key>(&*bytes).ok())
-                };
-                Ok(dir_entries.filter_map(dir_entry_to_routing_name).collect())
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.lock().unwrap().keys().cloned().collect::<Vec<_>>())
+            .filter_map(|name| {
+                name.from_hex().ok().and_then(|bytes| serialisation::deserialise(&bytes).ok())
             })
-            .unwrap_or_else(|_| Vec::new())
+            .collect()
     }
 
     /// Returns the maximum amount of storage space available for this ChunkStore.
@@ -167,21 +237,119 @@ impl<Key, Value> ChunkStore<Key, Value>
 
     /// Returns the amount of storage space already used by this ChunkStore.
     pub fn used_space(&self) -> u64 {
-        self.used_space
+        *self.used_space.lock().unwrap()
+    }
+
+    // Move the complete chunk file `from`, of `len` bytes, into place as the chunk under `key`.
+    fn adopt(&self, key: &Key, from: &Path, len: u64) -> Result<(), Error> {
+        let name = Self::file_name(key)?;
+        let path = self.shard_path(&name);
+        let mut index = self.shard(&name).lock().unwrap();
+        self.remove(&mut index, &name)?;
+        fs::create_dir_all(path.parent().unwrap_or(&self.rootdir))?;
+        fs::rename(from, &path)?;
+        *self.used_space.lock().unwrap() += len;
+        let _ = index.insert(name, len);
+        Ok(())
     }
 
-    fn do_delete(&mut self, file_path: &Path) -> Result<(), Error> {
-        if let Ok(metadata) = fs::metadata(file_path) {
-            self.used_space -= cmp::min(metadata.len(), self.used_space);
-            fs::remove_file(file_path).map_err(From::from)
-        } else {
-            Ok(())
+    // Remove the chunk file `name` from disk and from the locked `index` of its shard.
+    fn remove(&self, index: &mut HashMap<String, u64>, name: &str) -> Result<(), Error> {
+        let len = match index.get(name) {
+            Some(&len) => len,
+            None => return Ok(()),
+        };
+        match fs::remove_file(self.shard_path(name)) {
+            Ok(()) => (),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => (),
+            Err(error) => return Err(From::from(error)),
         }
+        let _ = index.remove(name);
+        self.release(len);
+        Ok(())
+    }
+
+    // Take `len` bytes of space for a chunk replacing one of `replaced` bytes, which is released
+    // by the caller once gone.
+    fn reserve(&self, len: u64, replaced: u64) -> Result<(), Error> {
+        let mut used_space = self.used_space.lock().unwrap();
+        if *used_space - cmp::min(replaced, *used_space) + len > self.max_space {
+            return Err(Error::StorageFull);
+        }
+        *used_space += len;
+        Ok(())
+    }
+
+    fn release(&self, len: u64) {
+        let mut used_space = self.used_space.lock().unwrap();
+        *used_space -= cmp::min(len, *used_space);
+    }
+
+    fn index(&self, name: String, len: u64) {
+        *self.used_space.lock().unwrap() += len;
+        let _ = self.shard(&name).lock().unwrap().insert(name, len);
+    }
+
+    fn shard(&self, name: &str) -> &Mutex<HashMap<String, u64>> {
+        &self.shards[Self::shard_index(name)]
+    }
+
+    fn shard_path(&self, name: &str) -> PathBuf {
+        self.rootdir.join(format!("{:02x}", Self::shard_index(name))).join(name)
+    }
+
+    fn shard_index(name: &str) -> usize {
+        hash(name.as_bytes())[0] as usize % SHARDS
+    }
+
+    // Whether `name` is the name of a shard directory.
+    fn is_shard(name: &str) -> bool {
+        name.len() == 2 && name.from_hex().is_ok()
+    }
+
+    // Whether `name` is the name of a chunk file, the hex of a serialised key.
+    fn is_chunk(name: &str) -> bool {
+        name.from_hex().ok().is_some_and(|bytes| serialisation::deserialise::<Key>(&bytes).is_ok())
+    }
+
+    fn file_name(key: &Key) -> Result<String, Error> {
+        Ok(serialisation::serialise(key)?.to_hex())
     }
 
     fn file_path(&self, key: &Key) -> Result<PathBuf, Error> {
-        let filename = serialisation::serialise(key)?.to_hex();
-        let path_name = Path::new(&filename);
-        Ok(self.rootdir.join(path_name))
+        Ok(self.shard_path(&Self::file_name(key)?))
+    }
+}
+
+impl<Key, Value> ChunkStorage<Key, Value> for ChunkStore<Key, Value>
+    where Key: Decodable + Encodable,
+          Value: Decodable + Encodable
+{
+    fn put(&mut self, key: &Key, value: &Value) -> Result<(), Error> {
+        ChunkStore::put(self, key, value)
+    }
+
+    fn delete(&mut self, key: &Key) -> Result<(), Error> {
+        ChunkStore::delete(self, key)
+    }
+
+    fn get(&self, key: &Key) -> Result<Value, Error> {
+        ChunkStore::get(self, key)
+    }
+
+    fn has(&self, key: &Key) -> bool {
+        ChunkStore::has(self, key)
+    }
+
+    fn keys(&self) -> Vec<Key> {
+        ChunkStore::keys(self)
+    }
+
+    fn max_space(&self) -> u64 {
+        ChunkStore::max_space(self)
+    }
+
+    fn used_space(&self) -> u64 {
+        ChunkStore::used_space(self)
     }
 }
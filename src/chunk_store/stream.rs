@@ -0,0 +1,224 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chunk_store::{ChunkStorage, ChunkStore, MockChunkStore};
+use data::{Data, ImmutableData};
+use error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use tiny_keccak::Keccak;
+
+/// Size of the pieces content is copied in.
+const PIECE_SIZE: usize = 64 * 1024;
+/// `Data::Immutable` serialises as this variant index, then the content length, then the content.
+const IMMUTABLE_VARIANT: u32 = 1;
+const HEADER_LEN: usize = 12;
+/// Name of the file content is streamed into before its name is known.
+const PARTIAL_FILE: &str = "streaming.partial";
+
+/// Storage taking in and handing out the content of `ImmutableData` as a stream, so data larger
+/// than memory can be held. Streamed content is stored as `put` stores the equivalent
+/// `Data::Immutable`, so either may be used to read it back.
+pub trait StreamStorage {
+    /// Store the content read from `reader` as immutable data, returning its name.
+    fn put_immutable(&mut self, reader: &mut dyn Read) -> Result<[u8; 32], Error>;
+    /// Read back the content of the immutable data `name`. The reader fails with
+    /// `io::ErrorKind::InvalidData` at the end if the content no longer matches the name.
+    fn get_immutable(&self, name: &[u8; 32]) -> Result<Box<dyn Read>, Error>;
+}
+
+impl StreamStorage for ChunkStore<[u8; 32], Data> {
+    fn put_immutable(&mut self, reader: &mut dyn Read) -> Result<[u8; 32], Error> {
+        let partial = self.rootdir.join(PARTIAL_FILE);
+        let result = self.write_partial(&partial, reader);
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        let (name, len) = result?;
+        if let Err(error) = self.adopt(&name, &partial, len) {
+            let _ = fs::remove_file(&partial);
+            return Err(error);
+        }
+        Ok(name)
+    }
+
+    fn get_immutable(&self, name: &[u8; 32]) -> Result<Box<dyn Read>, Error> {
+        let mut file = File::open(self.file_path(name)?).map_err(|_| Error::NotFound)?;
+        let mut header = [0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let len = content_len(&header)?;
+        Ok(Box::new(VerifiedReader::new(file.take(len), *name)))
+    }
+}
+
+impl ChunkStore<[u8; 32], Data> {
+    // Copy `reader` into the file `path` in pieces, hashing as it goes. Returns the name and the
+    // size of the file.
+    fn write_partial(&self,
+                     path: &Path,
+                     reader: &mut dyn Read)
+                     -> Result<([u8; 32], u64), Error> {
+        let mut file = OpenOptions::new().write(true).create(true).truncate(true).open(path)?;
+        file.write_all(&header(0))?;
+        let mut sha3 = Keccak::new_sha3_256();
+        let mut piece = vec![0; PIECE_SIZE];
+        let mut len = 0u64;
+        loop {
+            let read = match reader.read(&mut piece) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(From::from(error)),
+            };
+            len += read as u64;
+            if self.used_space() + len + HEADER_LEN as u64 > self.max_space {
+                return Err(Error::StorageFull);
+            }
+            sha3.update(&piece[..read]);
+            file.write_all(&piece[..read])?;
+        }
+        let _ = file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header(len))?;
+        file.sync_all()?;
+        let mut name = [0; 32];
+        sha3.finalize(&mut name);
+        Ok((name, len + HEADER_LEN as u64))
+    }
+}
+
+impl StreamStorage for MockChunkStore<[u8; 32], Data> {
+    fn put_immutable(&mut self, reader: &mut dyn Read) -> Result<[u8; 32], Error> {
+        let mut content = vec![];
+        let _ = reader.read_to_end(&mut content)?;
+        let data = ImmutableData::new(content);
+        let name = *data.name();
+        self.put(&name, &Data::Immutable(data))?;
+        Ok(name)
+    }
+
+    fn get_immutable(&self, name: &[u8; 32]) -> Result<Box<dyn Read>, Error> {
+        match self.get(name)? {
+            Data::Immutable(data) => {
+                Ok(Box::new(VerifiedReader::new(Cursor::new(data.value().clone()), *name)))
+            }
+            Data::Structured(_) |
+            Data::Appendable(_) => Err(Error::BadIdentifier),
+        }
+    }
+}
+
+fn header(len: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&IMMUTABLE_VARIANT.to_be_bytes());
+    header[4..].copy_from_slice(&len.to_be_bytes());
+    header
+}
+
+fn content_len(header: &[u8; HEADER_LEN]) -> Result<u64, Error> {
+    let mut variant = [0; 4];
+    let mut len = [0; 8];
+    variant.copy_from_slice(&header[..4]);
+    len.copy_from_slice(&header[4..]);
+    if u32::from_be_bytes(variant) != IMMUTABLE_VARIANT {
+        return Err(Error::BadIdentifier);
+    }
+    Ok(u64::from_be_bytes(len))
+}
+
+// Hashes the content as it is read, failing at the end if it does not match `name`.
+struct VerifiedReader<R> {
+    inner: R,
+    sha3: Option<Keccak>,
+    name: [u8; 32],
+}
+
+impl<R: Read> VerifiedReader<R> {
+    fn new(inner: R, name: [u8; 32]) -> VerifiedReader<R> {
+        VerifiedReader {
+            inner: inner,
+            sha3: Some(Keccak::new_sha3_256()),
+            name: name,
+        }
+    }
+}
+
+impl<R: Read> Read for VerifiedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0 {
+            if let Some(ref mut sha3) = self.sha3 {
+                sha3.update(&buf[..read]);
+            }
+        } else if !buf.is_empty() {
+            if let Some(sha3) = self.sha3.take() {
+                let mut hash = [0; 32];
+                sha3.finalize(&mut hash);
+                if hash != self.name {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              "content does not match its name"));
+                }
+            }
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maidsafe_utilities::serialisation;
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn round_trip() {
+        let content = (0..PIECE_SIZE * 2 + 10).map(|x| x as u8).collect::<Vec<u8>>();
+        let data = Data::Immutable(ImmutableData::new(content.clone()));
+        assert_eq!(unwrap!(serialisation::serialise(&data))[..HEADER_LEN],
+                   header(content.len() as u64));
+
+        let dir = unwrap!(TempDir::new("test_stream"));
+        let mut store = unwrap!(ChunkStore::new(dir.path().to_path_buf(), 1 << 20));
+        let name = unwrap!(store.put_immutable(&mut &content[..]));
+        assert_eq!(name, *data.name());
+        assert_eq!(unwrap!(store.get(&name)), data);
+        assert_eq!(store.keys(), vec![name]);
+        assert_eq!(store.used_space(), (content.len() + HEADER_LEN) as u64);
+        let mut read = vec![];
+        let _ = unwrap!(unwrap!(store.get_immutable(&name)).read_to_end(&mut read));
+        assert_eq!(read, content);
+
+        // Content no longer matching its name is detected at the end of the stream.
+        let path = unwrap!(store.file_path(&name));
+        let mut corrupt = unwrap!(serialisation::serialise(&data));
+        if let Some(byte) = corrupt.last_mut() {
+            *byte ^= 0xff;
+        }
+        unwrap!(unwrap!(File::create(&path)).write_all(&corrupt));
+        let mut read = vec![];
+        let error = unwrap!(unwrap!(store.get_immutable(&name)).read_to_end(&mut read).err());
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut small = unwrap!(ChunkStore::new(dir.path().join("small"), 1024));
+        match small.put_immutable(&mut &content[..]) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(small.keys().is_empty() && small.used_space() == 0);
+        assert!(!dir.path().join("small").join(PARTIAL_FILE).exists());
+    }
+}
@@ -15,7 +15,6 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-#![cfg(test)]
 use chunk_store::ChunkStore;
 use error::Error;
 use maidsafe_utilities::serialisation;
@@ -64,8 +63,8 @@ fn create_multiple_instances_in_the_same_root() {
     {
         let root = unwrap!(TempDir::new("test"));
 
-        let _1 = unwrap!(ChunkStore::<u64, u64>::new(root.path().join("store-1"), 64));
-        let _2 = unwrap!(ChunkStore::<u64, u64>::new(root.path().join("store-2"), 64));
+        let _store_1 = unwrap!(ChunkStore::<u64, u64>::new(root.path().join("store-1"), 64));
+        let _store_2 = unwrap!(ChunkStore::<u64, u64>::new(root.path().join("store-2"), 64));
     }
 
     // root doesn't exist yet
@@ -73,8 +72,8 @@ fn create_multiple_instances_in_the_same_root() {
         let root = unwrap!(TempDir::new("test"));
         let root_path = root.path().join("foo").join("bar");
 
-        let _1 = unwrap!(ChunkStore::<u64, u64>::new(root_path.join("store-1"), 64));
-        let _2 = unwrap!(ChunkStore::<u64, u64>::new(root_path.join("store-2"), 64));
+        let _store_1 = unwrap!(ChunkStore::<u64, u64>::new(root_path.join("store-1"), 64));
+        let _store_2 = unwrap!(ChunkStore::<u64, u64>::new(root_path.join("store-2"), 64));
     }
 }
 
@@ -82,9 +81,9 @@ fn create_multiple_instances_in_the_same_root() {
 fn successful_put() {
     let chunks = generate_random_chunks();
     let root = unwrap!(TempDir::new("test"));
-    let mut chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
+    let chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
     {
-        let mut put = |key, value, size| {
+        let put = |key, value, size| {
             let size_before_insert = chunk_store.used_space();
             assert!(!chunk_store.has(&key));
             unwrap!(chunk_store.put(&key, value));
@@ -93,7 +92,7 @@ fn successful_put() {
             assert!(chunk_store.used_space() <= chunks.total_size);
         };
 
-        for (index, &(ref data, ref size)) in
+        for (index, (data, size)) in
             chunks.data_and_sizes
                 .iter()
                 .enumerate()
@@ -112,19 +111,19 @@ fn successful_put() {
 fn failed_put_when_not_enough_space() {
     let k_disk_size = 32usize;
     let root = unwrap!(TempDir::new("test"));
-    let mut store = unwrap!(ChunkStore::new(root.path().to_path_buf(), k_disk_size as u64));
+    let store = unwrap!(ChunkStore::new(root.path().to_path_buf(), k_disk_size as u64));
     let key: u8 = rand::random();
     let data = generate_random_bytes(k_disk_size + 1);
 
-    assert_err!(store.put(&key, &data), Error::NoSpace);
+    assert_err!(store.put(&key, &data), Error::StorageFull);
 }
 
 #[test]
 fn delete() {
     let chunks = generate_random_chunks();
     let root = unwrap!(TempDir::new("test"));
-    let mut chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
-    let mut put_and_delete = |key, value, size| {
+    let chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
+    let put_and_delete = |key, value, size| {
         unwrap!(chunk_store.put(&key, value));
         assert_eq!(chunk_store.used_space(), size);
         assert!(chunk_store.has(&key));
@@ -133,7 +132,7 @@ fn delete() {
         assert_eq!(chunk_store.used_space(), 0);
     };
 
-    for (index, &(ref data, ref size)) in chunks.data_and_sizes.iter().enumerate() {
+    for (index, (data, size)) in chunks.data_and_sizes.iter().enumerate() {
         put_and_delete(index, data, *size);
     }
 }
@@ -142,11 +141,11 @@ fn delete() {
 fn put_and_get_value_should_be_same() {
     let chunks = generate_random_chunks();
     let root = unwrap!(TempDir::new("test"));
-    let mut chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
-    for (index, &(ref data, _)) in chunks.data_and_sizes.iter().enumerate() {
+    let chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         unwrap!(chunk_store.put(&(index as u32), data));
     }
-    for (index, &(ref data, _)) in chunks.data_and_sizes.iter().enumerate() {
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         let retrieved_value = unwrap!(chunk_store.get(&(index as u32)));
         assert!(*data == retrieved_value);
     }
@@ -156,7 +155,7 @@ fn put_and_get_value_should_be_same() {
 fn overwrite_value() {
     let chunks = generate_random_chunks();
     let root = unwrap!(TempDir::new("test"));
-    let mut chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
+    let chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
     for (ref data, ref size) in chunks.data_and_sizes {
         unwrap!(chunk_store.put(&0, data));
         assert_eq!(chunk_store.used_space(), *size);
@@ -170,16 +169,16 @@ fn get_fails_when_key_does_not_exist() {
     let root = unwrap!(TempDir::new("test"));
     let chunk_store = unwrap!(ChunkStore::<u8, u8>::new(root.path().to_path_buf(), 64));
     let key = rand::random();
-    assert_err!(chunk_store.get(&key), Error::NoFile);
+    assert_err!(chunk_store.get(&key), Error::NotFound);
 }
 
 #[test]
 fn keys() {
     let chunks = generate_random_chunks();
     let root = unwrap!(TempDir::new("test"));
-    let mut chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
+    let chunk_store = unwrap!(ChunkStore::new(root.path().to_path_buf(), chunks.total_size));
 
-    for (index, &(ref data, _)) in chunks.data_and_sizes.iter().enumerate() {
+    for (index, (data, _)) in chunks.data_and_sizes.iter().enumerate() {
         assert!(!chunk_store.keys().contains(&index));
         unwrap!(chunk_store.put(&index, data));
         assert!(chunk_store.keys().contains(&index));
@@ -194,3 +193,54 @@ fn keys() {
                    chunks.data_and_sizes.len() - index - 1);
     }
 }
+
+#[test]
+fn concurrent_puts() {
+    use std::sync::Arc;
+    use std::thread;
+    let root = unwrap!(TempDir::new("test"));
+    let chunk_store = Arc::new(unwrap!(ChunkStore::new(root.path().to_path_buf(), 1 << 20)));
+    let threads = (0..8u64)
+        .map(|thread| {
+            let chunk_store = chunk_store.clone();
+            thread::spawn(move || for key in thread * 100..thread * 100 + 50 {
+                unwrap!(chunk_store.put(&key, &key));
+                assert_eq!(unwrap!(chunk_store.get(&key)), key);
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        unwrap!(thread.join());
+    }
+    let size = unwrap!(serialisation::serialise(&0u64)).len() as u64;
+    assert_eq!(chunk_store.keys().len(), 400);
+    assert_eq!(chunk_store.used_space(), 400 * size);
+
+    // The index and used space are rebuilt from the shard directories.
+    let reopened = unwrap!(ChunkStore::<u64, u64>::from_path(root.path().to_path_buf(), 1 << 20));
+    let mut keys = reopened.keys();
+    keys.sort();
+    assert_eq!(keys, (0..8).flat_map(|x| x * 100..x * 100 + 50).collect::<Vec<_>>());
+    assert_eq!(reopened.used_space(), 400 * size);
+}
+
+#[test]
+fn flat_layout_moved_into_shards() {
+    use rustc_serialize::hex::ToHex;
+    use std::fs::{self, File};
+    use std::io::Write;
+    let root = unwrap!(TempDir::new("test"));
+    let flat = root.path().join(unwrap!(serialisation::serialise(&7u64)).to_hex());
+    let value = unwrap!(serialisation::serialise(&b"flat".to_vec()));
+    unwrap!(unwrap!(File::create(&flat)).write_all(&value));
+    unwrap!(unwrap!(File::create(root.path().join("other"))).write_all(b"not a chunk"));
+
+    let chunk_store = unwrap!(ChunkStore::<u64, Vec<u8>>::from_path(root.path().to_path_buf(),
+                                                                    64));
+    assert!(!flat.exists());
+    assert!(root.path().join("other").exists());
+    assert_eq!(chunk_store.keys(), vec![7]);
+    assert_eq!(unwrap!(chunk_store.get(&7)), b"flat".to_vec());
+    assert_eq!(chunk_store.used_space(), value.len() as u64);
+    assert!(unwrap!(fs::metadata(unwrap!(chunk_store.file_path(&7)))).is_file());
+}
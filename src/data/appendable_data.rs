@@ -0,0 +1,371 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use data::{DataIdentifier, MAX_BYTES};
+use debug_format::{debug_bytes, debug_list};
+use error::Error;
+use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug, Formatter};
+
+/// Largest number of items an `AppendableData` may be created to hold.
+pub const MAX_APPENDED_ITEMS: usize = 1000;
+
+/// Which keys may append to an `AppendableData`. Owners may always append.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
+pub enum Filter {
+    /// Anyone except these keys.
+    BlackList(BTreeSet<PublicKey>),
+    /// Only these keys.
+    WhiteList(BTreeSet<PublicKey>),
+}
+
+impl Filter {
+    /// Whether `key` passes the filter.
+    pub fn allows(&self, key: &PublicKey) -> bool {
+        match *self {
+            Filter::BlackList(ref keys) => !keys.contains(key),
+            Filter::WhiteList(ref keys) => keys.contains(key),
+        }
+    }
+}
+
+/// An item appended to an `AppendableData`, pointing at data stored elsewhere and signed by the
+/// appender.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct AppendedData {
+    /// The data this item refers to.
+    pub pointer: DataIdentifier,
+    /// Key of the appender.
+    pub sign_key: PublicKey,
+    signature: Signature,
+}
+
+impl AppendedData {
+    /// Creates an item pointing at `pointer`, signed by the appender's `secret_key`.
+    pub fn new(pointer: DataIdentifier,
+               sign_key: PublicKey,
+               secret_key: &SecretKey)
+               -> Result<AppendedData, Error> {
+        let data = serialise(&(&pointer, &sign_key))?;
+        Ok(AppendedData {
+            pointer: pointer,
+            sign_key: sign_key,
+            signature: sign::sign_detached(&data, secret_key),
+        })
+    }
+
+    /// Whether the signature is from `sign_key` over this item.
+    pub fn verify_signature(&self) -> bool {
+        match serialise(&(&self.pointer, &self.sign_key)) {
+            Ok(data) => sign::verify_detached(&self.signature, &data, &self.sign_key),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Owned data that others may append to.
+///
+/// Like `StructuredData`, the owners sign each new version to change the filter, ownership or
+/// remove items, and a majority of them must sign. Appending does not change the version: each
+/// item is instead signed by its appender, who must pass the filter, and the number of items is
+/// limited to the capacity given at creation.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
+pub struct AppendableData {
+    name: [u8; 32],
+    version: u64,
+    filter: Filter,
+    capacity: usize,
+    previous_owner_keys: Vec<PublicKey>,
+    current_owner_keys: Vec<PublicKey>,
+    previous_owner_signatures: Vec<Signature>,
+    data: BTreeSet<AppendedData>,
+}
+
+impl AppendableData {
+    /// Creates a new `AppendableData` holding up to `capacity` items, signed with `signing_key`.
+    /// Fails if `capacity` is more than `MAX_APPENDED_ITEMS`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(name: [u8; 32],
+               version: u64,
+               current_owner_keys: Vec<PublicKey>,
+               previous_owner_keys: Vec<PublicKey>,
+               filter: Filter,
+               capacity: usize,
+               signing_key: Option<&SecretKey>)
+               -> Result<AppendableData, Error> {
+        if capacity > MAX_APPENDED_ITEMS {
+            return Err(Error::Validation);
+        }
+        let mut appendable_data = AppendableData {
+            name: name,
+            version: version,
+            filter: filter,
+            capacity: capacity,
+            previous_owner_keys: previous_owner_keys,
+            current_owner_keys: current_owner_keys,
+            previous_owner_signatures: vec![],
+            data: BTreeSet::new(),
+        };
+
+        if let Some(key) = signing_key {
+            let _ = appendable_data.add_signature(key)?;
+        }
+        Ok(appendable_data)
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &[u8; 32] {
+        &self.name
+    }
+
+    /// Version, only changed by the owners.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns `DataIdentifier` for this data element.
+    pub fn identifier(&self) -> DataIdentifier {
+        DataIdentifier::Appendable(self.name)
+    }
+
+    /// Which keys other than the owners may append.
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// Most items this may hold.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The items appended so far.
+    pub fn data(&self) -> &BTreeSet<AppendedData> {
+        &self.data
+    }
+
+    /// Get the current owner keys
+    pub fn get_owner_keys(&self) -> &Vec<PublicKey> {
+        &self.current_owner_keys
+    }
+
+    /// Adds `appended` if it is signed by its appender, the appender is an owner or passes the
+    /// filter and there is room for it. Appending an item already held succeeds without change.
+    pub fn append(&mut self, appended: AppendedData) -> Result<(), Error> {
+        if !appended.verify_signature() {
+            return Err(Error::Signature);
+        }
+        if !self.current_owner_keys.contains(&appended.sign_key) &&
+           !self.filter.allows(&appended.sign_key) {
+            return Err(Error::Validation);
+        }
+        if self.data.contains(&appended) {
+            return Ok(());
+        }
+        if self.data.len() >= self.capacity {
+            return Err(Error::StorageFull);
+        }
+        let _ = self.data.insert(appended.clone());
+        if self.payload_size() > MAX_BYTES {
+            let _ = self.data.remove(&appended);
+            return Err(Error::StorageFull);
+        }
+        Ok(())
+    }
+
+    /// Returns the next version with `filter` and without the items in `remove`, not yet signed.
+    pub fn next_version(&self, filter: Filter, remove: &BTreeSet<AppendedData>) -> AppendableData {
+        AppendableData {
+            name: self.name,
+            version: self.version + 1,
+            filter: filter,
+            capacity: self.capacity,
+            previous_owner_keys: vec![],
+            current_owner_keys: self.current_owner_keys.clone(),
+            previous_owner_signatures: vec![],
+            data: self.data.difference(remove).cloned().collect(),
+        }
+    }
+
+    /// Replaces this with the owner signed successor `other`, see
+    /// `validate_self_against_successor`. Items appended since `other` was made are dropped and
+    /// must be appended again.
+    pub fn update_with_other(&mut self, other: AppendableData) -> Result<(), Error> {
+        self.validate_self_against_successor(&other)?;
+        *self = other;
+        Ok(())
+    }
+
+    /// Verifies that `other` is a valid owner update of `self`: it keeps the name, increases the
+    /// version by 1, holds only items appended to `self` and is signed by a majority of the
+    /// owners. As with `StructuredData`, ownership is transferred by setting the
+    /// `previous_owner_keys` of `other` to the `current_owner_keys` of `self`.
+    pub fn validate_self_against_successor(&self, other: &AppendableData) -> Result<(), Error> {
+        let owner_keys_to_match = if other.previous_owner_keys.is_empty() {
+            &other.current_owner_keys
+        } else {
+            &other.previous_owner_keys
+        };
+        if other.name != self.name || other.version != self.version + 1 ||
+           *owner_keys_to_match != self.current_owner_keys {
+            return Err(Error::Signature);
+        }
+        if !other.data.is_subset(&self.data) {
+            return Err(Error::Validation);
+        }
+        other.verify_previous_owner_signatures(owner_keys_to_match)
+    }
+
+    /// Adds a signature with the given `secret_key` to the `previous_owner_signatures` and returns
+    /// the number of signatures that are still required.
+    pub fn add_signature(&mut self, secret_key: &SecretKey) -> Result<usize, Error> {
+        let data = self.data_to_sign()?;
+        self.previous_owner_signatures.push(sign::sign_detached(&data, secret_key));
+        let owner_keys = if self.previous_owner_keys.is_empty() {
+            &self.current_owner_keys
+        } else {
+            &self.previous_owner_keys
+        };
+        Ok(((owner_keys.len() / 2) + 1).saturating_sub(self.previous_owner_signatures.len()))
+    }
+
+    /// Return data size.
+    pub fn payload_size(&self) -> usize {
+        serialise(&self.data).map(|x| x.len()).unwrap_or(0)
+    }
+
+    // Confirms unique and valid signatures from more than half of `owner_keys`.
+    fn verify_previous_owner_signatures(&self, owner_keys: &[PublicKey]) -> Result<(), Error> {
+        let data = self.data_to_sign()?;
+        let signed = owner_keys.iter()
+            .filter(|key| {
+                self.previous_owner_signatures
+                    .iter()
+                    .any(|sig| sign::verify_detached(sig, &data, key))
+            })
+            .count();
+        if signed < owner_keys.len() / 2 + owner_keys.len() % 2 {
+            return Err(Error::Validation);
+        }
+        Ok(())
+    }
+
+    // The owners sign everything but the items, which are signed by their appenders and may
+    // change without a new version.
+    fn data_to_sign(&self) -> Result<Vec<u8>, Error> {
+        Ok(serialise(&(&self.name,
+                       self.version,
+                       &self.filter,
+                       self.capacity as u64,
+                       &self.previous_owner_keys,
+                       &self.current_owner_keys))?)
+    }
+}
+
+impl Debug for Filter {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            Filter::BlackList(ref keys) => {
+                write!(formatter, "BlackList({})", debug_list(&keys.iter().collect::<Vec<_>>()))
+            }
+            Filter::WhiteList(ref keys) => {
+                write!(formatter, "WhiteList({})", debug_list(&keys.iter().collect::<Vec<_>>()))
+            }
+        }
+    }
+}
+
+impl Debug for AppendableData {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "AppendableData {{ name: {}, version: {}, filter: {:?}, capacity: {}, \
+                current_owner_keys: {}, items: {} }}",
+               debug_bytes(self.name()),
+               self.version,
+               self.filter,
+               self.capacity,
+               debug_list(&self.current_owner_keys),
+               self.data.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use std::iter;
+
+    #[test]
+    fn append_and_update() {
+        let _ = ::rust_sodium::init();
+        let owner = sign::gen_keypair();
+        let friend = sign::gen_keypair();
+        let stranger = sign::gen_keypair();
+        let whitelist = Filter::WhiteList(iter::once(friend.0).collect());
+        let mut appendable = unwrap!(AppendableData::new(rand::random(),
+                                                         0,
+                                                         vec![owner.0],
+                                                         vec![],
+                                                         whitelist,
+                                                         2,
+                                                         Some(&owner.1)));
+        assert!(AppendableData::new([0; 32],
+                                    0,
+                                    vec![owner.0],
+                                    vec![],
+                                    Filter::BlackList(BTreeSet::new()),
+                                    MAX_APPENDED_ITEMS + 1,
+                                    None)
+            .is_err());
+
+        let item = |keys: &(PublicKey, SecretKey), name| {
+            unwrap!(AppendedData::new(DataIdentifier::Immutable(name), keys.0, &keys.1))
+        };
+        let from_friend = item(&friend, [1; 32]);
+        unwrap!(appendable.append(from_friend.clone()));
+        match appendable.append(item(&stranger, [2; 32])) {
+            Err(Error::Validation) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let mut forged = item(&friend, [3; 32]);
+        forged.pointer = DataIdentifier::Immutable([4; 32]);
+        match appendable.append(forged) {
+            Err(Error::Signature) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        // Owners pass any filter, but not the capacity.
+        unwrap!(appendable.append(item(&owner, [5; 32])));
+        unwrap!(appendable.append(from_friend.clone()));
+        match appendable.append(item(&friend, [6; 32])) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        // Owners drop the friend's item and open to everyone but the friend.
+        let removed = iter::once(from_friend.clone()).collect();
+        let blacklist = Filter::BlackList(iter::once(friend.0).collect());
+        let mut next = appendable.next_version(blacklist, &removed);
+        assert!(appendable.validate_self_against_successor(&next).is_err());
+        assert_eq!(unwrap!(next.add_signature(&owner.1)), 0);
+        unwrap!(appendable.update_with_other(next));
+        assert_eq!(appendable.version(), 1);
+        assert_eq!(appendable.data().len(), 1);
+        unwrap!(appendable.append(item(&stranger, [2; 32])));
+        assert!(appendable.append(item(&friend, [7; 32])).is_err());
+    }
+}
@@ -16,6 +16,7 @@
 // relating to use of the SAFE Network Software.
 
 use data::DataIdentifier;
+use debug_format::debug_bytes;
 use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use sha3::hash;
 use std::fmt::{self, Debug, Formatter};
@@ -76,7 +77,7 @@ impl Decodable for ImmutableData {
 
 impl Debug for ImmutableData {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
-        write!(formatter, "ImmutableData {:?}", self.name())
+        write!(formatter, "ImmutableData {}", debug_bytes(self.name()))
     }
 }
 
@@ -25,19 +25,43 @@
 //! - Structured : This is `owned` data and reflects a location in the address space that contains
 //!                content that may be altered by the owner(s).
 //!
+//! - Appendable : This is `owned` data that others, subject to a filter set by the owner(s), may
+//!                append signed pointers to.
+//!
+//! - Mutable : This is `owned` key/value data, each entry versioned on its own and changed by
+//!             the owner(s) or keys they have given permission.
+//!
 //! - Plain : This is data with no fixed content or name. It is currently unused in SAFE
 //!
 
 
+/// Data that others may append to but only the owners may otherwise change
+pub mod appendable_data;
 /// Data that will not change it's contents
 pub mod immutable_data;
+/// Data holding versioned entries that may be changed subject to permissions
+pub mod mutable_data;
+/// Data stored under a name of the creator's choosing, neither owned nor self validating
+pub mod plain_data;
 /// Data that will retain it's name but allow dynamic content or transfer of ownership
 pub mod structured_data;
 
+/// Convergent encryption of immutable content into chunks, enabled by the `self_encryption`
+/// feature.
+#[cfg(feature = "self_encryption")]
+pub mod self_encryption;
+
+pub use data::appendable_data::{AppendableData, AppendedData, Filter, MAX_APPENDED_ITEMS};
 pub use data::immutable_data::ImmutableData;
+pub use data::mutable_data::{Action, EntryAction, MAX_MUTABLE_DATA_ENTRIES, MutableData, User,
+                             Value};
+pub use data::plain_data::PlainData;
+#[cfg(feature = "self_encryption")]
+pub use data::self_encryption::{ChunkDetails, DataMap};
 pub use data::structured_data::{MAX_BYTES, StructuredData};
 
 
+use debug_format::debug_bytes;
 use error::Error;
 use maidsafe_utilities::serialisation::serialise;
 use std::fmt::{self, Debug, Formatter};
@@ -50,6 +74,8 @@ pub enum Data {
     Structured(StructuredData),
     /// `ImmutableData` data type.
     Immutable(ImmutableData),
+    /// `AppendableData` data type.
+    Appendable(AppendableData),
 }
 
 impl Data {
@@ -58,6 +84,7 @@ impl Data {
         match *self {
             Data::Structured(ref data) => data.name(),
             Data::Immutable(ref data) => data.name(),
+            Data::Appendable(ref data) => data.name(),
         }
     }
 
@@ -66,6 +93,7 @@ impl Data {
         match *self {
             Data::Structured(ref data) => data.identifier(),
             Data::Immutable(ref data) => data.identifier(),
+            Data::Appendable(ref data) => data.identifier(),
         }
     }
 
@@ -74,17 +102,31 @@ impl Data {
         match *self {
             Data::Structured(ref data) => data.payload_size(),
             Data::Immutable(ref data) => data.payload_size(),
+            Data::Appendable(ref data) => data.payload_size(),
+        }
+    }
+
+    /// Return data version, `None` for `ImmutableData` which has none.
+    pub fn version(&self) -> Option<u64> {
+        match *self {
+            Data::Structured(ref data) => Some(data.version()),
+            Data::Immutable(_) => None,
+            Data::Appendable(ref data) => Some(data.version()),
         }
     }
 }
 
-#[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, RustcEncodable, RustcDecodable)]
+#[derive(Hash, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, RustcEncodable, RustcDecodable)]
 /// An identifier to address a data chunk.
 pub enum DataIdentifier {
     /// Data request, (Identifier, TypeTag) pair for name resolution, for StructuredData.
     Structured([u8; 32], u64),
     /// Data request, (Identifier), for `ImmutableData`.
     Immutable([u8; 32]),
+    /// Data request, (Identifier), for `AppendableData`.
+    Appendable([u8; 32]),
+    /// Data request, (Identifier, TypeTag) pair for name resolution, for `MutableData`.
+    Mutable([u8; 32], u64),
 }
 
 impl Debug for Data {
@@ -92,6 +134,26 @@ impl Debug for Data {
         match *self {
             Data::Structured(ref data) => data.fmt(formatter),
             Data::Immutable(ref data) => data.fmt(formatter),
+            Data::Appendable(ref data) => data.fmt(formatter),
+        }
+    }
+}
+
+impl Debug for DataIdentifier {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            DataIdentifier::Structured(ref name, tag) => {
+                write!(formatter, "Structured({}, {})", debug_bytes(name), tag)
+            }
+            DataIdentifier::Immutable(ref name) => {
+                write!(formatter, "Immutable({})", debug_bytes(name))
+            }
+            DataIdentifier::Appendable(ref name) => {
+                write!(formatter, "Appendable({})", debug_bytes(name))
+            }
+            DataIdentifier::Mutable(ref name, tag) => {
+                write!(formatter, "Mutable({}, {})", debug_bytes(name), tag)
+            }
         }
     }
 }
@@ -101,14 +163,28 @@ impl DataIdentifier {
     pub fn name(&self) -> &[u8; 32] {
         match *self {
             DataIdentifier::Structured(ref name, _) |
-            DataIdentifier::Immutable(ref name) => name,
+            DataIdentifier::Mutable(ref name, _) |
+            DataIdentifier::Immutable(ref name) |
+            DataIdentifier::Appendable(ref name) => name,
         }
     }
+
+    /// Type tag, for the `Structured` and `Mutable` identifiers that have one.
+    pub fn type_tag(&self) -> Option<u64> {
+        match *self {
+            DataIdentifier::Structured(_, tag) |
+            DataIdentifier::Mutable(_, tag) => Some(tag),
+            DataIdentifier::Immutable(_) |
+            DataIdentifier::Appendable(_) => None,
+        }
+    }
+
     /// check for ledger
     /// DataIdentifier local name (for store).
     pub fn local_name(&self) -> Result<[u8; 32], Error> {
         match *self {
-            DataIdentifier::Structured(ref name, ref tag) => {
+            DataIdentifier::Structured(ref name, ref tag) |
+            DataIdentifier::Mutable(ref name, ref tag) => {
                 let mut sha3 = Keccak::new_sha3_256();
                 sha3.update(name);
                 sha3.update(&serialise(tag)?);
@@ -116,7 +192,8 @@ impl DataIdentifier {
                 sha3.finalize(&mut res);
                 Ok(res)
             }
-            DataIdentifier::Immutable(name) => Ok(name),
+            DataIdentifier::Immutable(name) |
+            DataIdentifier::Appendable(name) => Ok(name),
         }
     }
 }
@@ -177,7 +254,8 @@ mod tests {
                                   true) {
             Ok(structured_data) => {
                 assert_eq!(structured_data.payload_size(),
-                           Data::Structured(structured_data).payload_size());
+                           Data::Structured(structured_data.clone()).payload_size());
+                assert_eq!(Data::Structured(structured_data).version(), Some(0));
             }
             Err(error) => panic!("Error: {:?}", error),
         }
@@ -197,6 +275,8 @@ mod tests {
         // name() resolves correctly for StructuredData
         let tag = 0;
         assert_eq!(&name, DataIdentifier::Structured(name, tag).name());
+        assert_eq!(DataIdentifier::Structured(name, 7).type_tag(), Some(7));
+        assert_eq!(DataIdentifier::Immutable(name).type_tag(), None);
 
         // name() resolves correctly for ImmutableData
         assert_eq!(&name, DataIdentifier::Immutable(name).name());
@@ -0,0 +1,336 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, DataChain};
+use data::{DataIdentifier, MAX_BYTES};
+use debug_format::{debug_bytes, debug_list};
+use error::Error;
+use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::sign::PublicKey;
+use sha3::hash;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+
+/// Most entries a `MutableData` may hold.
+pub const MAX_MUTABLE_DATA_ENTRIES: usize = 100;
+
+/// A value with the version of its entry, which is increased by every update or deletion.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct Value {
+    /// Content of the entry. Empty content marks a deleted entry.
+    pub content: Vec<u8>,
+    /// Version of the entry.
+    pub entry_version: u64,
+}
+
+/// A change to one entry. Updates and deletions must give the next version of the entry.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Debug, RustcDecodable, RustcEncodable)]
+pub enum EntryAction {
+    /// Insert a new entry, or reuse a deleted one.
+    Ins(Value),
+    /// Update an existing entry.
+    Update(Value),
+    /// Delete an entry, keeping its version so it can't be replayed.
+    Del(u64),
+}
+
+/// What a key other than an owner may do.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Debug, RustcDecodable,
+         RustcEncodable)]
+pub enum Action {
+    /// Insert new entries.
+    Insert,
+    /// Update existing entries.
+    Update,
+    /// Delete entries.
+    Delete,
+    /// Change the permissions.
+    ManagePermissions,
+}
+
+/// Who a permission is granted to.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Debug, RustcDecodable,
+         RustcEncodable)]
+pub enum User {
+    /// Any key.
+    Anyone,
+    /// Only this key.
+    Key(PublicKey),
+}
+
+/// Key/value data with per entry versions.
+///
+/// The name is computed from the type tag and identifier, so these two fields are immutable.
+/// Owners may do anything, other keys only what `permissions` allows them. Each mutated entry is
+/// committed to by a `BlockIdentifier::MutableDataEntry`, see `entry_identifier`, so once its
+/// block is valid the chain proves the entry held that value at that version.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
+pub struct MutableData {
+    name: [u8; 32],
+    tag: u64,
+    data: BTreeMap<Vec<u8>, Value>,
+    permissions: BTreeMap<User, BTreeSet<Action>>,
+    owners: BTreeSet<PublicKey>,
+}
+
+impl MutableData {
+    /// Creates a new `MutableData`. Fails if `data` has too many entries or is too large.
+    pub fn new(name: [u8; 32],
+               tag: u64,
+               permissions: BTreeMap<User, BTreeSet<Action>>,
+               data: BTreeMap<Vec<u8>, Value>,
+               owners: BTreeSet<PublicKey>)
+               -> Result<MutableData, Error> {
+        let mutable_data = MutableData {
+            name: name,
+            tag: tag,
+            data: data,
+            permissions: permissions,
+            owners: owners,
+        };
+        mutable_data.validate()?;
+        Ok(mutable_data)
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &[u8; 32] {
+        &self.name
+    }
+
+    /// Returns the type tag.
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// Returns `DataIdentifier` for this data element.
+    pub fn identifier(&self) -> DataIdentifier {
+        DataIdentifier::Mutable(self.name, self.tag)
+    }
+
+    /// Returns the value of entry `key`, including deleted entries.
+    pub fn get(&self, key: &[u8]) -> Option<&Value> {
+        self.data.get(key)
+    }
+
+    /// Returns all entries.
+    pub fn entries(&self) -> &BTreeMap<Vec<u8>, Value> {
+        &self.data
+    }
+
+    /// Returns the permissions.
+    pub fn permissions(&self) -> &BTreeMap<User, BTreeSet<Action>> {
+        &self.permissions
+    }
+
+    /// Returns the owners.
+    pub fn owners(&self) -> &BTreeSet<PublicKey> {
+        &self.owners
+    }
+
+    /// Whether `requester` may perform `action`, either as an owner or by permission given to
+    /// them or to anyone.
+    pub fn is_action_allowed(&self, requester: &PublicKey, action: Action) -> bool {
+        self.owners.contains(requester) ||
+        [User::Key(*requester), User::Anyone]
+            .iter()
+            .any(|user| self.permissions.get(user).is_some_and(|x| x.contains(&action)))
+    }
+
+    /// Applies `actions` on behalf of `requester`, all or none of them. Returns the identifiers
+    /// committing to each mutated entry, to be voted into the chain.
+    pub fn mutate_entries(&mut self,
+                          actions: BTreeMap<Vec<u8>, EntryAction>,
+                          requester: &PublicKey)
+                          -> Result<Vec<BlockIdentifier>, Error> {
+        let mut data = self.data.clone();
+        for (key, action) in &actions {
+            let new_value = match *action {
+                EntryAction::Ins(ref value) => {
+                    // A deleted entry may be inserted again, but only at its next version.
+                    let next = data.get(key).map(|x| (x.content.is_empty(), x.entry_version + 1));
+                    match next {
+                        None if value.entry_version == 0 => (),
+                        Some((true, version)) if value.entry_version == version => (),
+                        _ => return Err(Error::Version(value.entry_version as u32)),
+                    }
+                    self.check_allowed(requester, Action::Insert)?;
+                    value.clone()
+                }
+                EntryAction::Update(ref value) => {
+                    self.check_next_version(&data, key, value.entry_version)?;
+                    self.check_allowed(requester, Action::Update)?;
+                    value.clone()
+                }
+                EntryAction::Del(version) => {
+                    self.check_next_version(&data, key, version)?;
+                    self.check_allowed(requester, Action::Delete)?;
+                    Value {
+                        content: vec![],
+                        entry_version: version,
+                    }
+                }
+            };
+            let _ = data.insert(key.clone(), new_value);
+        }
+        let previous = ::std::mem::replace(&mut self.data, data);
+        if let Err(error) = self.validate() {
+            self.data = previous;
+            return Err(error);
+        }
+        actions.keys().map(|key| self.entry_identifier(key).ok_or(Error::NotFound)).collect()
+    }
+
+    /// Sets the permissions of `user` on behalf of `requester`.
+    pub fn set_user_permissions(&mut self,
+                                user: User,
+                                actions: BTreeSet<Action>,
+                                requester: &PublicKey)
+                                -> Result<(), Error> {
+        self.check_allowed(requester, Action::ManagePermissions)?;
+        let _ = self.permissions.insert(user, actions);
+        Ok(())
+    }
+
+    /// The identifier committing to the current value and version of entry `key`.
+    pub fn entry_identifier(&self, key: &[u8]) -> Option<BlockIdentifier> {
+        let value = self.data.get(key)?;
+        let entry = serialise(&(&self.name, self.tag, key, value)).ok()?;
+        Some(BlockIdentifier::MutableDataEntry(hash(&entry), self.identifier()))
+    }
+
+    /// Whether `chain` holds a valid block for the current value and version of entry `key`.
+    pub fn is_entry_proven(&self, key: &[u8], chain: &DataChain) -> bool {
+        self.entry_identifier(key)
+            .and_then(|id| chain.find(&id).map(|block| block.valid))
+            .unwrap_or(false)
+    }
+
+    /// Return data size.
+    pub fn payload_size(&self) -> usize {
+        self.data.iter().map(|(key, value)| key.len() + value.content.len()).sum()
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.data.len() > MAX_MUTABLE_DATA_ENTRIES || self.payload_size() > MAX_BYTES {
+            return Err(Error::StorageFull);
+        }
+        Ok(())
+    }
+
+    fn check_allowed(&self, requester: &PublicKey, action: Action) -> Result<(), Error> {
+        if self.is_action_allowed(requester, action) {
+            Ok(())
+        } else {
+            Err(Error::Signature)
+        }
+    }
+
+    fn check_next_version(&self,
+                          data: &BTreeMap<Vec<u8>, Value>,
+                          key: &[u8],
+                          version: u64)
+                          -> Result<(), Error> {
+        match data.get(key) {
+            Some(value) if !value.content.is_empty() => {
+                if version == value.entry_version + 1 {
+                    Ok(())
+                } else {
+                    Err(Error::Version(version as u32))
+                }
+            }
+            _ => Err(Error::NotFound),
+        }
+    }
+}
+
+impl Debug for MutableData {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "MutableData {{ name: {}, tag: {}, entries: {}, owners: {} }}",
+               debug_bytes(self.name()),
+               self.tag,
+               self.data.len(),
+               debug_list(&self.owners.iter().collect::<Vec<_>>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain::{LinkDescriptor, Vote};
+    use rand;
+    use rust_sodium::crypto::sign;
+    use std::iter;
+
+    fn value(content: &[u8], entry_version: u64) -> Value {
+        Value {
+            content: content.to_vec(),
+            entry_version: entry_version,
+        }
+    }
+
+    #[test]
+    fn mutate_and_prove_entries() {
+        let _ = ::rust_sodium::init();
+        let owner = sign::gen_keypair();
+        let inserter = sign::gen_keypair();
+        let permissions = iter::once((User::Key(inserter.0), iter::once(Action::Insert).collect()))
+            .collect();
+        let mut mutable_data = unwrap!(MutableData::new(rand::random(),
+                                                        10,
+                                                        permissions,
+                                                        BTreeMap::new(),
+                                                        iter::once(owner.0).collect()));
+
+        let insert = iter::once((b"key".to_vec(), EntryAction::Ins(value(b"one", 0)))).collect();
+        let ids = unwrap!(mutable_data.mutate_entries(insert, &inserter.0));
+        assert_eq!(ids, vec![unwrap!(mutable_data.entry_identifier(b"key"))]);
+
+        // The inserter may not update, and updates must give the next version.
+        let update = |version| -> BTreeMap<_, _> {
+            iter::once((b"key".to_vec(), EntryAction::Update(value(b"two", version)))).collect()
+        };
+        match mutable_data.mutate_entries(update(1), &inserter.0) {
+            Err(Error::Signature) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        match mutable_data.mutate_entries(update(2), &owner.0) {
+            Err(Error::Version(2)) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(mutable_data.get(b"key"), Some(&value(b"one", 0)));
+        let updated = unwrap!(mutable_data.mutate_entries(update(1), &owner.0));
+        assert!(updated != ids);
+
+        // Once voted in, the chain proves the current version but not the replaced one.
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(owner.0));
+        let _ = chain.add_vote(unwrap!(Vote::new(&owner.0, &owner.1, link)));
+        assert!(!mutable_data.is_entry_proven(b"key", &chain));
+        for id in ids.into_iter().chain(updated) {
+            let _ = chain.add_vote(unwrap!(Vote::new(&owner.0, &owner.1, id)));
+        }
+        assert!(mutable_data.is_entry_proven(b"key", &chain));
+
+        let delete = iter::once((b"key".to_vec(), EntryAction::Del(2))).collect();
+        let _ = unwrap!(mutable_data.mutate_entries(delete, &owner.0));
+        assert_eq!(mutable_data.get(b"key"), Some(&value(b"", 2)));
+        assert!(!mutable_data.is_entry_proven(b"key", &chain));
+        let insert = iter::once((b"key".to_vec(), EntryAction::Ins(value(b"three", 3)))).collect();
+        let _ = unwrap!(mutable_data.mutate_entries(insert, &inserter.0));
+    }
+}
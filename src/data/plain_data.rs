@@ -0,0 +1,99 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use data::MAX_BYTES;
+use debug_format::debug_bytes;
+use error::Error;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::fmt::{self, Debug, Formatter};
+
+/// Data with no fixed content or name: a value stored under a name its creator chose. It is
+/// neither owned nor signed, so unlike `ImmutableData` nothing ties the value to the name.
+#[derive(Hash, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct PlainData {
+    name: [u8; 32],
+    value: Vec<u8>,
+}
+
+impl PlainData {
+    /// Creates a new instance of `PlainData`, failing with `Error::StorageFull` for a value over
+    /// `MAX_BYTES`.
+    pub fn new(name: [u8; 32], value: Vec<u8>) -> Result<PlainData, Error> {
+        if value.len() > MAX_BYTES {
+            return Err(Error::StorageFull);
+        }
+        Ok(PlainData {
+            name: name,
+            value: value,
+        })
+    }
+
+    /// Returns the value
+    pub fn value(&self) -> &Vec<u8> {
+        &self.value
+    }
+
+    /// Returns the name
+    pub fn name(&self) -> &[u8; 32] {
+        &self.name
+    }
+
+    /// Returns size of contained value.
+    pub fn payload_size(&self) -> usize {
+        self.value.len()
+    }
+}
+
+impl Encodable for PlainData {
+    fn encode<E: Encoder>(&self, encoder: &mut E) -> Result<(), E::Error> {
+        (&self.name, &self.value).encode(encoder)
+    }
+}
+
+impl Decodable for PlainData {
+    fn decode<D: Decoder>(decoder: &mut D) -> Result<PlainData, D::Error> {
+        let (name, value): ([u8; 32], Vec<u8>) = Decodable::decode(decoder)?;
+        PlainData::new(name, value).map_err(|_| decoder.error("PlainData value too large"))
+    }
+}
+
+impl Debug for PlainData {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "PlainData {}", debug_bytes(self.name()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maidsafe_utilities::serialisation;
+    use super::*;
+
+    #[test]
+    fn size_limit_and_round_trip() {
+        let data = unwrap!(PlainData::new([1; 32], b"plain data value".to_vec()));
+        assert_eq!(data.payload_size(), 16);
+        let bytes = unwrap!(serialisation::serialise(&data));
+        assert_eq!(unwrap!(serialisation::deserialise::<PlainData>(&bytes)), data);
+
+        match PlainData::new([1; 32], vec![0; MAX_BYTES + 1]) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let oversized = unwrap!(serialisation::serialise(&([1u8; 32], vec![0u8; MAX_BYTES + 1])));
+        assert!(serialisation::deserialise::<PlainData>(&oversized).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::BlockIdentifier;
+use data::ImmutableData;
+use error::Error;
+use rust_sodium::crypto::secretbox;
+use sha3::hash;
+
+/// Content smaller than three chunks of this size is kept in the data map rather than chunked.
+pub const MIN_CHUNK_SIZE: usize = 1024;
+/// Largest chunk content is split into.
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Hashes of one chunk before and after encryption, which with those of the chunks before it are
+/// all that is needed to find and decrypt it.
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct ChunkDetails {
+    /// Hash of the plain content, from which the keys of the next two chunks derive.
+    pub pre_hash: [u8; 32],
+    /// Hash of the encrypted content, the name of its `ImmutableData`.
+    pub post_hash: [u8; 32],
+    /// Size of the plain content.
+    pub size: u64,
+}
+
+/// What is needed to recover self encrypted content: the details of each chunk in order, or the
+/// content itself when too small to chunk.
+#[derive(Clone, Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum DataMap {
+    /// Chunks the content was split into.
+    Chunks(Vec<ChunkDetails>),
+    /// Content too small to chunk.
+    Content(Vec<u8>),
+}
+
+impl DataMap {
+    /// Identifiers of the chunks, to be voted into the chain.
+    pub fn identifiers(&self) -> Vec<BlockIdentifier> {
+        match *self {
+            DataMap::Chunks(ref chunks) => {
+                chunks.iter().map(|x| BlockIdentifier::ImmutableData(x.post_hash)).collect()
+            }
+            DataMap::Content(_) => vec![],
+        }
+    }
+
+    /// Size of the plain content.
+    pub fn len(&self) -> u64 {
+        match *self {
+            DataMap::Chunks(ref chunks) => chunks.iter().map(|x| x.size).sum(),
+            DataMap::Content(ref content) => content.len() as u64,
+        }
+    }
+
+    /// Whether the plain content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl ImmutableData {
+    /// Split `bytes` into at least three chunks and encrypt each with keys derived from the plain
+    /// content of the two chunks before it, so identical content always gives identical chunks
+    /// and no chunk can be read without the data map. Returns the data map and the encrypted
+    /// chunks, whose names are given by `DataMap::identifiers`.
+    pub fn from_plaintext_self_encrypted(bytes: &[u8]) -> (DataMap, Vec<ImmutableData>) {
+        if bytes.len() < 3 * MIN_CHUNK_SIZE {
+            return (DataMap::Content(bytes.to_vec()), vec![]);
+        }
+        let count = ::std::cmp::max(3, bytes.len().div_ceil(MAX_CHUNK_SIZE));
+        let pieces = (0..count)
+            .map(|i| &bytes[i * bytes.len() / count..(i + 1) * bytes.len() / count])
+            .collect::<Vec<_>>();
+        let pre_hashes = pieces.iter().map(|x| hash(x)).collect::<Vec<_>>();
+        let mut details = Vec::with_capacity(count);
+        let mut chunks = Vec::with_capacity(count);
+        for (index, piece) in pieces.iter().enumerate() {
+            let (key, nonce) = chunk_keys(&pre_hashes, index);
+            let chunk = ImmutableData::new(secretbox::seal(piece, &nonce, &key));
+            details.push(ChunkDetails {
+                pre_hash: pre_hashes[index],
+                post_hash: *chunk.name(),
+                size: piece.len() as u64,
+            });
+            chunks.push(chunk);
+        }
+        (DataMap::Chunks(details), chunks)
+    }
+
+    /// Recover the content described by `data_map` from its `chunks`, given in any order.
+    /// Fails with `Error::NotFound` if a chunk is missing and `Error::Crypto` if one does not
+    /// decrypt to the content recorded.
+    pub fn decrypt_self_encrypted(data_map: &DataMap,
+                                  chunks: &[ImmutableData])
+                                  -> Result<Vec<u8>, Error> {
+        let details = match *data_map {
+            DataMap::Chunks(ref details) => details,
+            DataMap::Content(ref content) => return Ok(content.clone()),
+        };
+        let pre_hashes = details.iter().map(|x| x.pre_hash).collect::<Vec<_>>();
+        let mut content = Vec::with_capacity(data_map.len() as usize);
+        for (index, detail) in details.iter().enumerate() {
+            let chunk = chunks.iter()
+                .find(|x| *x.name() == detail.post_hash)
+                .ok_or(Error::NotFound)?;
+            let (key, nonce) = chunk_keys(&pre_hashes, index);
+            let piece = secretbox::open(chunk.value(), &nonce, &key).map_err(|()| Error::Crypto)?;
+            if hash(&piece) != detail.pre_hash || piece.len() as u64 != detail.size {
+                return Err(Error::Crypto);
+            }
+            content.extend(piece);
+        }
+        Ok(content)
+    }
+}
+
+// Key and nonce of chunk `index`, from the plain content hashes of the two chunks before it,
+// wrapping around to the last chunks for the first two.
+fn chunk_keys(pre_hashes: &[[u8; 32]], index: usize) -> (secretbox::Key, secretbox::Nonce) {
+    let len = pre_hashes.len();
+    let key = secretbox::Key(pre_hashes[(index + len - 1) % len]);
+    let mut nonce = [0; secretbox::NONCEBYTES];
+    nonce.copy_from_slice(&pre_hashes[(index + len - 2) % len][..secretbox::NONCEBYTES]);
+    (key, secretbox::Nonce(nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let _ = ::rust_sodium::init();
+        let small = vec![1u8; 3 * MIN_CHUNK_SIZE - 1];
+        let (data_map, chunks) = ImmutableData::from_plaintext_self_encrypted(&small);
+        assert_eq!(data_map, DataMap::Content(small.clone()));
+        assert!(chunks.is_empty() && data_map.identifiers().is_empty());
+        assert_eq!(unwrap!(ImmutableData::decrypt_self_encrypted(&data_map, &[])), small);
+
+        let content = (0..MAX_CHUNK_SIZE * 3 + 100).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+        let (data_map, mut chunks) = ImmutableData::from_plaintext_self_encrypted(&content);
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(data_map.len(), content.len() as u64);
+        assert_eq!(data_map.identifiers(),
+                   chunks.iter()
+                       .map(|x| BlockIdentifier::ImmutableData(*x.name()))
+                       .collect::<Vec<_>>());
+        assert!(chunks.iter().all(|x| x.value().len() > MAX_CHUNK_SIZE / 2));
+        assert_eq!(ImmutableData::from_plaintext_self_encrypted(&content),
+                   (data_map.clone(), chunks.clone()));
+
+        chunks.reverse();
+        assert_eq!(unwrap!(ImmutableData::decrypt_self_encrypted(&data_map, &chunks)),
+                   content);
+        match ImmutableData::decrypt_self_encrypted(&data_map, &chunks[1..]) {
+            Err(Error::NotFound) => (),
+            result => panic!("unexpected {:?}", result.map(|x| x.len())),
+        }
+        if let DataMap::Chunks(mut details) = data_map {
+            details.swap(0, 1);
+            match ImmutableData::decrypt_self_encrypted(&DataMap::Chunks(details), &chunks) {
+                Err(Error::Crypto) => (),
+                result => panic!("unexpected {:?}", result.map(|x| x.len())),
+            }
+        }
+    }
+}
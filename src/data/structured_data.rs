@@ -16,6 +16,7 @@
 // relating to use of the SAFE Network Software.
 
 use data::DataIdentifier;
+use debug_format::{debug_bytes, debug_list};
 use error::Error;
 use maidsafe_utilities::serialisation::serialise;
 use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
@@ -30,6 +31,9 @@ pub const MAX_BYTES: usize = 102400;
 ///
 /// These types may be stored unsigned with previous and current owner keys
 /// set to the same keys. Updates require a signature to validate.
+///
+/// Updates must be signed by more than half of the owners, unless the owners were given an
+/// M-of-N threshold by `transfer_ownership`, in which case M of them must sign.
 #[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
 pub struct StructuredData {
     type_tag: u64,
@@ -40,11 +44,14 @@ pub struct StructuredData {
     current_owner_keys: Vec<PublicKey>,
     previous_owner_signatures: Vec<Signature>,
     ledger: bool,
+    previous_owner_threshold: Option<usize>,
+    current_owner_threshold: Option<usize>,
 }
 
 impl StructuredData {
-    /// Creates a new `StructuredData` signed with `signing_key`.
-    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
+    /// Creates a new `StructuredData` signed with `signing_key`. Fails with `Error::StorageFull`
+    /// for `data` over `MAX_BYTES`.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(type_tag: u64,
                name: [u8; 32],
                version: u64,
@@ -54,7 +61,9 @@ impl StructuredData {
                signing_key: Option<&SecretKey>,
                ledger: bool)
                -> Result<StructuredData, Error> {
-
+        if data.len() > MAX_BYTES {
+            return Err(Error::StorageFull);
+        }
         let mut structured_data = StructuredData {
             type_tag: type_tag,
             name: name,
@@ -64,6 +73,8 @@ impl StructuredData {
             current_owner_keys: current_owner_keys,
             previous_owner_signatures: vec![],
             ledger: ledger,
+            previous_owner_threshold: None,
+            current_owner_threshold: None,
         };
 
         if let Some(key) = signing_key {
@@ -88,9 +99,57 @@ impl StructuredData {
         self.version = other.version;
         self.current_owner_keys = other.current_owner_keys;
         self.previous_owner_signatures = other.previous_owner_signatures;
+        self.previous_owner_threshold = other.previous_owner_threshold;
+        self.current_owner_threshold = other.current_owner_threshold;
         Ok(())
     }
 
+    /// Returns the next version holding `data`, with the same owners and not yet signed.
+    ///
+    /// This is what owners sign for a `Post`, see `add_signature`.
+    pub fn next_version(&self, data: Vec<u8>) -> StructuredData {
+        StructuredData {
+            type_tag: self.type_tag,
+            name: self.name,
+            data: data,
+            previous_owner_keys: vec![],
+            version: self.version + 1,
+            current_owner_keys: self.current_owner_keys.clone(),
+            previous_owner_signatures: vec![],
+            ledger: self.ledger,
+            previous_owner_threshold: None,
+            current_owner_threshold: self.current_owner_threshold,
+        }
+    }
+
+    /// Returns the next version, holding the same data but owned by `new_owner_keys`, and not yet
+    /// signed. It must be signed by the current owners before it is a valid successor.
+    ///
+    /// Updates after the transfer need `threshold` signatures of the new owners, or a majority of
+    /// them if `None`. Fails if `threshold` is zero or more than the number of new owners.
+    pub fn transfer_ownership(&self,
+                              new_owner_keys: Vec<PublicKey>,
+                              threshold: Option<usize>)
+                              -> Result<StructuredData, Error> {
+        if let Some(threshold) = threshold {
+            if threshold == 0 || threshold > new_owner_keys.len() {
+                return Err(Error::Validation);
+            }
+        }
+        Ok(StructuredData {
+            type_tag: self.type_tag,
+            name: self.name,
+            data: self.data.clone(),
+            previous_owner_keys: self.current_owner_keys.clone(),
+            version: self.version + 1,
+            current_owner_keys: new_owner_keys,
+            previous_owner_signatures: vec![],
+            ledger: self.ledger,
+            previous_owner_threshold: self.current_owner_threshold,
+            current_owner_threshold: threshold,
+        })
+    }
+
     /// Returns the name.
     pub fn name(&self) -> &[u8; 32] {
         &self.name
@@ -117,51 +176,99 @@ impl StructuredData {
     /// In case of an ownership transfer, the `previous_owner_keys` in `other` must match the
     /// `current_owner_keys` in `self`.
     pub fn validate_self_against_successor(&self, other: &StructuredData) -> Result<(), Error> {
-        let owner_keys_to_match = if other.previous_owner_keys.is_empty() {
-            &other.current_owner_keys
-        } else {
-            &other.previous_owner_keys
-        };
+        let (owner_keys_to_match, threshold_to_match) = other.signing_owners();
 
+        if other.payload_size() > MAX_BYTES {
+            return Err(Error::StorageFull);
+        }
         // TODO(dirvine) Increase error types to be more descriptive  :07/07/2015
         if other.type_tag != self.type_tag || other.name != self.name ||
            other.version != self.version + 1 ||
-           *owner_keys_to_match != self.current_owner_keys {
+           *owner_keys_to_match != self.current_owner_keys ||
+           threshold_to_match != self.current_owner_threshold {
             return Err(Error::Signature);
         }
         other.verify_previous_owner_signatures(owner_keys_to_match)
     }
 
-    /// Confirms *unique and valid* owner_signatures are more than 50% of total owners.
+    /// Signs a request to delete this version with `secret_key`. Deletion needs as many of these
+    /// from the current owners as an update would, see `verify_delete_signatures`.
+    pub fn delete_signature(&self, secret_key: &SecretKey) -> Result<Signature, Error> {
+        Ok(sign::sign_detached(&self.data_to_delete()?, secret_key))
+    }
+
+    /// Confirms `signatures` of a deletion request are from enough distinct current owners.
+    /// Fails with `Error::Unsigned` for none, `Error::Signature` if any is not from a current
+    /// owner and `Error::Quorum` if too few owners signed.
+    pub fn verify_delete_signatures(&self, signatures: &[Signature]) -> Result<(), Error> {
+        let data = self.data_to_delete()?;
+        if signatures.is_empty() {
+            return Err(Error::Unsigned);
+        }
+        if signatures.iter().any(|sig| {
+            !self.current_owner_keys.iter().any(|key| sign::verify_detached(sig, &data, key))
+        }) {
+            return Err(Error::Signature);
+        }
+        let got = self.current_owner_keys
+            .iter()
+            .filter(|key| signatures.iter().any(|sig| sign::verify_detached(sig, &data, key)))
+            .count();
+        let needed = required_signatures(self.current_owner_keys.len(),
+                                         self.current_owner_threshold);
+        if got < needed {
+            return Err(Error::Quorum {
+                needed: needed,
+                got: got,
+            });
+        }
+        Ok(())
+    }
+
+    // The owners whose signatures make this version valid, and how many of them must sign.
+    fn signing_owners(&self) -> (&Vec<PublicKey>, Option<usize>) {
+        if self.previous_owner_keys.is_empty() {
+            (&self.current_owner_keys, self.current_owner_threshold)
+        } else {
+            (&self.previous_owner_keys, self.previous_owner_threshold)
+        }
+    }
+
+    /// Confirms *unique and valid* owner_signatures are more than 50% of total owners, or at
+    /// least the threshold of the signing owners if they have one. Fails with `Error::Unsigned`
+    /// for none, `Error::Signature` for a duplicate or one not from an owner and
+    /// `Error::Quorum` for too few.
     fn verify_previous_owner_signatures(&self, owner_keys: &[PublicKey]) -> Result<(), Error> {
+        let required = required_signatures(owner_keys.len(), self.signing_owners().1);
+        if self.previous_owner_signatures.is_empty() {
+            return Err(Error::Unsigned);
+        }
         // Refuse any duplicate previous_owner_signatures (people can have many owner keys)
         // Any duplicates invalidates this type.
         for (i, sig) in self.previous_owner_signatures.iter().enumerate() {
             for sig_check in &self.previous_owner_signatures[..i] {
                 if sig == sig_check {
-                    return Err(Error::Validation);
+                    return Err(Error::Signature);
                 }
             }
         }
 
-        // Refuse when not enough previous_owner_signatures found
-        if self.previous_owner_signatures.len() < (owner_keys.len() + 1) / 2 {
-            return Err(Error::Validation);
-        }
-
+        // Refuse any signature not from an owner, so a forgery can't ride along with real ones
         let data = self.data_to_sign()?;
-        // Count valid previous_owner_signatures and refuse if quantity is not enough
-
-        let check_all_keys = |&sig| {
+        let check_all_keys = |sig: &Signature| {
             owner_keys.iter()
-                .any(|pub_key| sign::verify_detached(&sig, &data, pub_key))
+                .any(|pub_key| sign::verify_detached(sig, &data, pub_key))
         };
+        if !self.previous_owner_signatures.iter().all(check_all_keys) {
+            return Err(Error::Signature);
+        }
 
-        if self.previous_owner_signatures
-            .iter()
-            .filter(|&sig| check_all_keys(sig))
-            .count() < (owner_keys.len() / 2 + owner_keys.len() % 2) {
-            return Err(Error::Validation);
+        // Refuse when not enough previous_owner_signatures found
+        if self.previous_owner_signatures.len() < required {
+            return Err(Error::Quorum {
+                needed: required,
+                got: self.previous_owner_signatures.len(),
+            });
         }
         Ok(())
     }
@@ -176,11 +283,18 @@ impl StructuredData {
             previous_owner_keys: &self.previous_owner_keys,
             current_owner_keys: &self.current_owner_keys,
             version: self.version.to_string().as_bytes().to_vec(),
+            previous_owner_threshold: self.previous_owner_threshold,
+            current_owner_threshold: self.current_owner_threshold,
         };
 
         serialise(&sd).map_err(From::from)
     }
 
+    fn data_to_delete(&self) -> Result<Vec<u8>, Error> {
+        // Prefixed so a signature over an update can never be replayed as a deletion
+        serialise(&(b"delete".to_vec(), self.data_to_sign()?)).map_err(From::from)
+    }
+
     /// Adds a signature with the given `secret_key` to the `previous_owner_signatures` and returns
     /// the number of signatures that are still required. If more than 50% of the previous owners
     /// have signed, 0 is returned and validation is complete.
@@ -188,12 +302,11 @@ impl StructuredData {
         let data = self.data_to_sign()?;
         let sig = sign::sign_detached(&data, secret_key);
         self.previous_owner_signatures.push(sig);
-        let owner_keys = if self.previous_owner_keys.is_empty() {
-            &self.current_owner_keys
-        } else {
-            &self.previous_owner_keys
+        let required = match self.signing_owners() {
+            (_, Some(threshold)) => threshold,
+            (owner_keys, None) => (owner_keys.len() / 2) + 1,
         };
-        Ok(((owner_keys.len() / 2) + 1).saturating_sub(self.previous_owner_signatures.len()))
+        Ok(required.saturating_sub(self.previous_owner_signatures.len()))
     }
 
     /// Overwrite any existing signatures with the new signatures provided.
@@ -226,6 +339,11 @@ impl StructuredData {
         &self.current_owner_keys
     }
 
+    /// Get the number of current owners who must sign an update, if not a majority
+    pub fn get_owner_threshold(&self) -> Option<usize> {
+        self.current_owner_threshold
+    }
+
     /// Get previous owner signatures
     pub fn get_previous_owner_signatures(&self) -> &Vec<Signature> {
         &self.previous_owner_signatures
@@ -240,14 +358,14 @@ impl StructuredData {
 impl Debug for StructuredData {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter,
-               "StructuredData {{ type_tag: {}, name: {:?}, previous_owner_keys: {:?}, \
-                version: {}, current_owner_keys: {:?}, previous_owner_signatures: {:?} }}",
+               "StructuredData {{ type_tag: {}, name: {}, previous_owner_keys: {}, \
+                version: {}, current_owner_keys: {}, previous_owner_signatures: {} }}",
                self.type_tag,
-               self.name(),
-               self.previous_owner_keys,
+               debug_bytes(self.name()),
+               debug_list(&self.previous_owner_keys),
                self.version,
-               self.current_owner_keys,
-               self.previous_owner_signatures)
+               debug_list(&self.current_owner_keys),
+               debug_list(&self.previous_owner_signatures))
     }
 }
 
@@ -259,10 +377,18 @@ struct SerialisableStructuredData<'a> {
     previous_owner_keys: &'a [PublicKey],
     current_owner_keys: &'a [PublicKey],
     version: Vec<u8>,
+    previous_owner_threshold: Option<usize>,
+    current_owner_threshold: Option<usize>,
+}
+
+// Signatures needed from `owners` keys, `threshold` if given or else more than half of them.
+fn required_signatures(owners: usize, threshold: Option<usize>) -> usize {
+    threshold.unwrap_or(owners / 2 + owners % 2)
 }
 
 #[cfg(test)]
 mod tests {
+    use error::Error;
     use rand;
     use rust_sodium::crypto::sign;
 
@@ -467,4 +593,86 @@ mod tests {
             Err(error) => panic!("Error: {:?}", error),
         }
     }
+
+    #[test]
+    fn threshold_owners() {
+        let keys: Vec<_> = (0..4).map(|_| sign::gen_keypair()).collect();
+        let owner_keys = keys.iter().map(|x| x.0).collect::<Vec<_>>();
+        let mut structured_data = unwrap!(super::StructuredData::new(0,
+                                                                     rand::random(),
+                                                                     0,
+                                                                     vec![],
+                                                                     vec![keys[0].0],
+                                                                     vec![],
+                                                                     Some(&keys[0].1),
+                                                                     false));
+        assert!(structured_data.transfer_ownership(owner_keys.clone(), Some(0)).is_err());
+        assert!(structured_data.transfer_ownership(owner_keys.clone(), Some(5)).is_err());
+
+        // Hand over to four owners, any three of whom must sign from then on.
+        let mut transferred = unwrap!(structured_data.transfer_ownership(owner_keys, Some(3)));
+        assert!(structured_data.validate_self_against_successor(&transferred).is_err());
+        assert_eq!(unwrap!(transferred.add_signature(&keys[0].1)), 0);
+        unwrap!(structured_data.replace_with_other(transferred));
+        assert_eq!(structured_data.get_owner_threshold(), Some(3));
+
+        let mut update = structured_data.next_version(b"update".to_vec());
+        assert_eq!(unwrap!(update.add_signature(&keys[1].1)), 2);
+        assert_eq!(unwrap!(update.add_signature(&keys[2].1)), 1);
+        assert!(structured_data.validate_self_against_successor(&update).is_err());
+        assert_eq!(unwrap!(update.add_signature(&keys[3].1)), 0);
+        unwrap!(structured_data.replace_with_other(update));
+        assert_eq!(structured_data.get_data(), &b"update".to_vec());
+
+        // Deletion needs the same three signatures, and update signatures don't count.
+        let mut signatures = structured_data.get_previous_owner_signatures().clone();
+        match structured_data.verify_delete_signatures(&signatures) {
+            Err(Error::Signature) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        match structured_data.verify_delete_signatures(&[]) {
+            Err(Error::Unsigned) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        signatures = keys[..2]
+            .iter()
+            .map(|x| unwrap!(structured_data.delete_signature(&x.1)))
+            .collect();
+        match structured_data.verify_delete_signatures(&signatures) {
+            Err(Error::Quorum { needed: 3, got: 2 }) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        signatures.push(unwrap!(structured_data.delete_signature(&keys[3].1)));
+        assert!(structured_data.verify_delete_signatures(&signatures).is_ok());
+    }
+
+    #[test]
+    fn size_limit() {
+        let keys = sign::gen_keypair();
+        match super::StructuredData::new(0,
+                                         rand::random(),
+                                         0,
+                                         vec![0; super::MAX_BYTES + 1],
+                                         vec![keys.0],
+                                         vec![],
+                                         Some(&keys.1),
+                                         false) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let mut structured_data = unwrap!(super::StructuredData::new(0,
+                                                                     rand::random(),
+                                                                     0,
+                                                                     vec![0; super::MAX_BYTES],
+                                                                     vec![keys.0],
+                                                                     vec![],
+                                                                     Some(&keys.1),
+                                                                     false));
+        let mut update = structured_data.next_version(vec![0; super::MAX_BYTES + 1]);
+        let _ = unwrap!(update.add_signature(&keys.1));
+        match structured_data.replace_with_other(update) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
 }
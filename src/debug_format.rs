@@ -0,0 +1,103 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// The current `DebugFormat`, as its index, `Truncated` to start with.
+static FORMAT: AtomicUsize = AtomicUsize::new(1);
+
+/// How `Debug` output throughout the crate shows keys, signatures, names and hashes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DebugFormat {
+    /// Every byte in hex, for tests and local debugging.
+    Full,
+    /// The first and last three bytes in hex. This is the default.
+    Truncated,
+    /// Only the length, so logs carry no key material and reveal no names.
+    Redacted,
+}
+
+/// Set how `Debug` output shows bytes from now on, for every thread.
+pub fn set_debug_format(format: DebugFormat) {
+    FORMAT.store(format as usize, Ordering::Relaxed);
+}
+
+/// How `Debug` output currently shows bytes, see `set_debug_format`.
+pub fn debug_format() -> DebugFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        0 => DebugFormat::Full,
+        1 => DebugFormat::Truncated,
+        _ => DebugFormat::Redacted,
+    }
+}
+
+/// Show `input` as the current `debug_format` says. Inputs of up to six bytes are shown whole
+/// unless redacted.
+pub fn debug_bytes<V: AsRef<[u8]>>(input: V) -> String {
+    let input_ref = input.as_ref();
+    if input_ref.is_empty() {
+        return "<empty>".to_owned();
+    }
+    let format = debug_format();
+    if format == DebugFormat::Redacted {
+        return format!("<{} bytes>", input_ref.len());
+    }
+    if format == DebugFormat::Full || input_ref.len() <= 6 {
+        let mut ret = String::new();
+        for byte in input_ref.iter() {
+            write!(ret, "{:02x}", byte).unwrap_or(());
+        }
+        return ret;
+    }
+    format!("{:02x}{:02x}{:02x}..{:02x}{:02x}{:02x}",
+            input_ref[0],
+            input_ref[1],
+            input_ref[2],
+            input_ref[input_ref.len() - 3],
+            input_ref[input_ref.len() - 2],
+            input_ref[input_ref.len() - 1])
+}
+
+/// Show each of `items` with `debug_bytes`, as a list.
+pub fn debug_list<V: AsRef<[u8]>>(items: &[V]) -> String {
+    let items: Vec<_> = items.iter().map(debug_bytes).collect();
+    format!("[{}]", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let bytes = [0xab; 32];
+        let previous = debug_format();
+        set_debug_format(DebugFormat::Full);
+        assert_eq!(debug_bytes(bytes), "ab".repeat(32));
+        set_debug_format(DebugFormat::Redacted);
+        assert_eq!(debug_bytes(bytes), "<32 bytes>");
+        assert_eq!(debug_list(&[[1u8; 2]]), "[<2 bytes>]");
+        let id = format!("{:?}", ::data::DataIdentifier::Structured(bytes, 7));
+        assert_eq!(id, "Structured(<32 bytes>, 7)");
+        set_debug_format(DebugFormat::Truncated);
+        assert_eq!(debug_bytes(bytes), "ababab..ababab");
+        assert_eq!(debug_list(&[[1u8; 2], [2; 2]]), "[0101, 0202]");
+        assert_eq!(debug_bytes(Vec::<u8>::new()), "<empty>");
+        set_debug_format(previous);
+    }
+}
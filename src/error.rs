@@ -15,25 +15,85 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use chain::BlockIdentifier;
 use maidsafe_utilities::serialisation;
 use std::{error, fmt, io};
 
 /// Error types.
 ///
 /// Hopefully `rust_sodium` eventually defines errors properly, otherwise this makes little sense.
-#[allow(missing_docs)]
 #[derive(Debug)]
 pub enum Error {
+    /// Encoding or decoding failed, see `source`.
     Serialisation(serialisation::SerialisationError),
+    /// Reading or writing a file failed, see `source`.
     Io(io::Error),
+    /// A cryptographic operation, e.g. decrypting a chain at rest, failed.
     Crypto,
+    /// Data or a chain is not valid, e.g. its block has not reached a quorum yet.
     Validation,
+    /// A signature is forged, duplicated or from a key with no say.
     Signature,
-    Majority,
-    NoLink,
-    NoSpace,
-    NoFile,
+    /// No signatures were given where some are needed.
+    Unsigned,
+    /// Too few of the keys that must sign did so.
+    Quorum {
+        /// Signers needed.
+        needed: usize,
+        /// Valid signers found.
+        got: usize,
+    },
+    /// There is no valid link to check against.
+    MissingLink,
+    /// Stored bytes are damaged from `offset` on.
+    Corrupt {
+        /// Offset of the first damaged byte within the file.
+        offset: u64,
+    },
+    /// Another process holds the lock on a store.
+    LockHeld {
+        /// The holder, as recorded by a `SessionLock`, if known.
+        owner: Option<String>,
+    },
+    /// The store has no room for the data.
+    StorageFull,
+    /// The data, file or block asked for is not there.
+    NotFound,
+    /// A deletion of the data asked for is valid.
+    Deleted,
+    /// The identifier does not suit the data or operation.
     BadIdentifier,
+    /// Chain, segment, message or entry format version we can't read.
+    Version(u32),
+}
+
+/// Precise reason a `Vote`, `Block` or `DataChain` failed validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// A signature does not verify against its key and the signed identifier.
+    BadSignature,
+    /// Not enough members of the governing link have signed.
+    NoQuorum,
+    /// There is no valid link to validate against.
+    MissingLink,
+    /// A node voted for a link describing itself.
+    SelfVote,
+    /// The last valid link does not hold a majority of the given group.
+    StaleGroup,
+    /// The vote is not bound to the current state of the receiving chain.
+    BindingMismatch,
+    /// The vote's timestamp is missing or further from our clock than the allowed skew.
+    ClockSkew,
+    /// A proof is signed by a key with no slot in the governing link.
+    UnknownSigner,
+    /// A `CheckPoint` link does not commit to this chain's members and state.
+    CheckpointMismatch,
+    /// A link other than the `CheckPoint` due under `DataChain::set_checkpoint_interval`.
+    CheckpointDue,
+    /// An `Accusation` link whose votes do not prove its offender signed conflicting links.
+    BadEvidence,
+    /// A `Custom` block refused by the `DataChain::set_custom_validator`.
+    CustomRejected,
 }
 
 impl fmt::Display for Error {
@@ -44,11 +104,21 @@ impl fmt::Display for Error {
             Error::Crypto => write!(f, "Crypto failure."),
             Error::Validation => write!(f, "Not enough signatures."),
             Error::Signature => write!(f, "Invalid signature."),
-            Error::Majority => write!(f, "Not enough signatures for validation."),
-            Error::NoLink => write!(f, "Could not get a valid link."),
-            Error::NoSpace => write!(f, "Not enough space."),
-            Error::NoFile => write!(f, "No file."),
+            Error::Unsigned => write!(f, "No signatures."),
+            Error::Quorum { needed, got } => {
+                write!(f, "Not enough signatures, needed {} but got {}.", needed, got)
+            }
+            Error::MissingLink => write!(f, "Could not get a valid link."),
+            Error::Corrupt { offset } => write!(f, "Corrupt data at offset {}.", offset),
+            Error::LockHeld { owner: Some(ref owner) } => {
+                write!(f, "Store locked by another process ({}).", owner)
+            }
+            Error::LockHeld { owner: None } => write!(f, "Store locked by another process."),
+            Error::StorageFull => write!(f, "Not enough space."),
+            Error::NotFound => write!(f, "Not found."),
+            Error::Deleted => write!(f, "Data deleted."),
             Error::BadIdentifier => write!(f, "Invalid identifier type."),
+            Error::Version(version) => write!(f, "Unsupported format version {}.", version),
         }
     }
 }
@@ -61,11 +131,99 @@ impl error::Error for Error {
             Error::Crypto => "Crypto failure.",
             Error::Validation => "Not enough signatures.",
             Error::Signature => "Invalid signature.",
-            Error::Majority => "Not enough signatures for validation.",
-            Error::NoLink => "Could not get a valid link.",
-            Error::NoSpace => "No space.",
-            Error::NoFile => "No file.",
+            Error::Unsigned => "No signatures.",
+            Error::Quorum { .. } => "Not enough signatures.",
+            Error::MissingLink => "Could not get a valid link.",
+            Error::Corrupt { .. } => "Corrupt data.",
+            Error::LockHeld { .. } => "Store locked by another process.",
+            Error::StorageFull => "No space.",
+            Error::NotFound => "Not found.",
+            Error::Deleted => "Data deleted.",
             Error::BadIdentifier => "Invalid identifier type.",
+            Error::Version(_) => "Unsupported format version.",
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Serialisation(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// A structural property broken by a chain, found by `DataChain::check_invariants`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InvariantViolation {
+    /// The identifier appears more than once in the chain.
+    DuplicateBlock(BlockIdentifier),
+    /// The block holds more than one proof from the same key.
+    DuplicateProof(BlockIdentifier),
+    /// The block is marked valid but holds a signature that does not verify.
+    BadSignature(BlockIdentifier),
+    /// The first valid block is not a link.
+    DataBeforeLink(BlockIdentifier),
+    /// The block is marked valid but none of its proofs is from a key that signed an earlier
+    /// valid link.
+    Ungoverned(BlockIdentifier),
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InvariantViolation::DuplicateBlock(ref id) => write!(f, "{:?} appears twice.", id),
+            InvariantViolation::DuplicateProof(ref id) => {
+                write!(f, "{:?} holds two proofs from one key.", id)
+            }
+            InvariantViolation::BadSignature(ref id) => {
+                write!(f, "{:?} is valid with an invalid signature.", id)
+            }
+            InvariantViolation::DataBeforeLink(ref id) => {
+                write!(f, "{:?} is valid before any valid link.", id)
+            }
+            InvariantViolation::Ungoverned(ref id) => {
+                write!(f, "{:?} is valid without a proof from an earlier link.", id)
+            }
+        }
+    }
+}
+
+impl error::Error for InvariantViolation {
+    fn description(&self) -> &str {
+        match *self {
+            InvariantViolation::DuplicateBlock(_) => "Block appears twice.",
+            InvariantViolation::DuplicateProof(_) => "Block holds two proofs from one key.",
+            InvariantViolation::BadSignature(_) => "Valid block with an invalid signature.",
+            InvariantViolation::DataBeforeLink(_) => "Valid block before any valid link.",
+            InvariantViolation::Ungoverned(_) => {
+                "Valid block without a proof from an earlier link."
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", error::Error::description(self))
+    }
+}
+
+impl error::Error for ValidationError {
+    fn description(&self) -> &str {
+        match *self {
+            ValidationError::BadSignature => "Invalid signature.",
+            ValidationError::NoQuorum => "Not enough signatures from the governing link.",
+            ValidationError::MissingLink => "No valid link to validate against.",
+            ValidationError::SelfVote => "Node voted for a link about itself.",
+            ValidationError::StaleGroup => "Last valid link does not hold a majority of group.",
+            ValidationError::BindingMismatch => "Vote is not bound to this chain's current tip.",
+            ValidationError::ClockSkew => "Vote timestamp missing or outside allowed skew.",
+            ValidationError::UnknownSigner => "Proof signed by a key not in the governing link.",
+            ValidationError::CheckpointMismatch => "Checkpoint does not match this chain.",
+            ValidationError::CheckpointDue => "A checkpoint link is due before other links.",
+            ValidationError::BadEvidence => "Accusation does not prove its offence.",
+            ValidationError::CustomRejected => "Custom block refused by the application.",
         }
     }
 }
@@ -0,0 +1,146 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! C functions over a `DataChain` held behind an opaque pointer. Build a C library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Chains are read from the `persistence::encode_chain` format, i.e. a chain file, and any
+//! validity flags in it are ignored: `data_chain_verify` decides validity afresh.
+
+#![allow(unsafe_code)]
+
+use chain::{DataChain, persistence};
+use std::ptr;
+use std::slice;
+
+/// Decode a chain of `group_size` from the `len` bytes at `bytes`, returning null if they are not
+/// a chain. Release it with `data_chain_free`.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn data_chain_from_bytes(bytes: *const u8,
+                                               len: usize,
+                                               group_size: usize)
+                                               -> *mut DataChain {
+    if bytes.is_null() {
+        return ptr::null_mut();
+    }
+    let (mut blocks, pinned) = match persistence::decode_chain(slice::from_raw_parts(bytes, len)) {
+        Ok(decoded) => decoded,
+        Err(_) => return ptr::null_mut(),
+    };
+    for block in &mut blocks {
+        block.valid = false;
+    }
+    let mut chain = DataChain::from_blocks(blocks, group_size);
+    for identifier in &pinned {
+        let _ = chain.pin(identifier);
+    }
+    Box::into_raw(Box::new(chain))
+}
+
+/// Validate every block of `chain` from its proofs and check the chain's invariants. Returns 0 if
+/// they hold, 1 if not and -1 for a null chain.
+///
+/// # Safety
+///
+/// `chain` must be null or come from `data_chain_from_bytes` and not yet be freed.
+#[no_mangle]
+pub unsafe extern "C" fn data_chain_verify(chain: *mut DataChain) -> i32 {
+    match chain.as_mut() {
+        Some(chain) => {
+            chain.mark_blocks_valid();
+            if chain.check_invariants().is_ok() { 0 } else { 1 }
+        }
+        None => -1,
+    }
+}
+
+/// Number of valid blocks in `chain`, 0 for a null chain.
+///
+/// # Safety
+///
+/// As for `data_chain_verify`.
+#[no_mangle]
+pub unsafe extern "C" fn data_chain_valid_len(chain: *const DataChain) -> usize {
+    chain.as_ref().map_or(0, |chain| chain.valid_len())
+}
+
+/// Whether `chain` holds a valid block for the data named by the 32 bytes at `name`: 1 if so, 0
+/// if not and -1 for a null argument.
+///
+/// # Safety
+///
+/// As for `data_chain_verify`, and `name` must be null or point to 32 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn data_chain_find_name(chain: *const DataChain, name: *const u8) -> i32 {
+    let chain = match chain.as_ref() {
+        Some(chain) if !name.is_null() => chain,
+        _ => return -1,
+    };
+    let name = &*(name as *const [u8; 32]);
+    if chain.find_name(name).is_some() { 1 } else { 0 }
+}
+
+/// Release a chain from `data_chain_from_bytes`. Null is ignored.
+///
+/// # Safety
+///
+/// `chain` must be null or come from `data_chain_from_bytes`, and is invalid afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn data_chain_free(chain: *mut DataChain) {
+    if !chain.is_null() {
+        drop(Box::from_raw(chain));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, DataChain, LinkDescriptor, Vote, persistence};
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn verify_through_c_api() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = chain.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let data = BlockIdentifier::ImmutableData([3; 32]);
+        let _ = chain.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, data)));
+        let bytes = unwrap!(persistence::encode_chain(chain.chain(),
+                                                      chain.pinned(),
+                                                      chain.hash_algorithm()));
+
+        unsafe {
+            assert!(data_chain_from_bytes(b"junk".as_ptr(), 4, 999).is_null());
+            let handle = data_chain_from_bytes(bytes.as_ptr(), bytes.len(), 999);
+            assert!(!handle.is_null());
+            assert_eq!(data_chain_valid_len(handle), 0);
+            assert_eq!(data_chain_verify(handle), 0);
+            assert_eq!(data_chain_valid_len(handle), 2);
+            assert_eq!(data_chain_find_name(handle, [3; 32].as_ptr()), 1);
+            assert_eq!(data_chain_find_name(handle, [4; 32].as_ptr()), 0);
+            assert_eq!(data_chain_find_name(handle, ptr::null()), -1);
+            data_chain_free(handle);
+            assert_eq!(data_chain_verify(ptr::null_mut()), -1);
+        }
+    }
+}
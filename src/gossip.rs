@@ -0,0 +1,168 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use error::Error;
+use itertools::Itertools;
+use maidsafe_utilities::serialisation;
+use messages::ChainMessage;
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::BTreeMap;
+
+/// Distributes new votes and blocks to every member of a group, in `ChainMessage::Batch`es that
+/// fit in the network's maximum message size, and keeps each batch until the member it was sent
+/// to acknowledges it, so it can be sent again.
+pub struct Gossip {
+    max_message_size: usize,
+    next_id: u64,
+    unacknowledged: BTreeMap<PublicKey, BTreeMap<u64, ChainMessage>>,
+}
+
+impl Gossip {
+    /// Create a distributor whose encoded batches are at most `max_message_size` bytes.
+    pub fn new(max_message_size: usize) -> Gossip {
+        Gossip {
+            max_message_size: max_message_size,
+            next_id: 0,
+            unacknowledged: BTreeMap::new(),
+        }
+    }
+
+    /// Batch `messages`, in order, for each of `members` and return the batches to send to each.
+    /// A message too large to share a batch is sent alone, even if that exceeds the limit.
+    pub fn fanout(&mut self,
+                  members: &[PublicKey],
+                  messages: &[ChainMessage])
+                  -> Result<Vec<(PublicKey, ChainMessage)>, Error> {
+        let overhead = ChainMessage::Batch {
+                id: 0,
+                messages: vec![],
+            }
+            .encode()?
+            .len();
+        let mut batches: Vec<Vec<ChainMessage>> = vec![];
+        let mut size = overhead;
+        for message in messages {
+            let len = serialisation::serialise(message)?.len();
+            match batches.last_mut() {
+                Some(ref mut batch) if size + len <= self.max_message_size => {
+                    batch.push(message.clone())
+                }
+                _ => {
+                    batches.push(vec![message.clone()]);
+                    size = overhead;
+                }
+            }
+            size += len;
+        }
+
+        let mut sent = vec![];
+        for member in members.iter().unique() {
+            for batch in &batches {
+                let message = ChainMessage::Batch {
+                    id: self.next_id,
+                    messages: batch.clone(),
+                };
+                let _ = self.unacknowledged
+                    .entry(*member)
+                    .or_default()
+                    .insert(self.next_id, message.clone());
+                self.next_id += 1;
+                sent.push((*member, message));
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Record that `member` acknowledged the batch `id`, returns `false` if it was not awaited.
+    pub fn acknowledge(&mut self, member: &PublicKey, id: u64) -> bool {
+        let acknowledged = match self.unacknowledged.get_mut(member) {
+            Some(batches) => batches.remove(&id).is_some(),
+            None => return false,
+        };
+        if self.unacknowledged.get(member).is_some_and(|x| x.is_empty()) {
+            let _ = self.unacknowledged.remove(member);
+        }
+        acknowledged
+    }
+
+    /// Batches not yet acknowledged, with the member to send each to again.
+    pub fn unacknowledged(&self) -> Vec<(PublicKey, ChainMessage)> {
+        self.unacknowledged
+            .iter()
+            .flat_map(|(member, batches)| batches.values().map(move |x| (*member, x.clone())))
+            .collect()
+    }
+
+    /// Whether every batch sent to `member` has been acknowledged.
+    pub fn is_acknowledged(&self, member: &PublicKey) -> bool {
+        !self.unacknowledged.contains_key(member)
+    }
+
+    /// Forget batches awaiting acknowledgement from anyone no longer in `members`.
+    pub fn retain_members(&mut self, members: &[PublicKey]) {
+        self.unacknowledged.retain(|member, _| members.contains(member));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, Vote};
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn fanout_and_acknowledge() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let members = (0..3).map(|_| sign::gen_keypair().0).collect_vec();
+        let votes = (0..10u8)
+            .map(|i| {
+                let identifier = BlockIdentifier::ImmutableData([i; 32]);
+                ChainMessage::Vote(unwrap!(Vote::new(&keys.0, &keys.1, identifier)))
+            })
+            .collect_vec();
+        let vote_len = unwrap!(serialisation::serialise(&votes[0])).len();
+        let mut gossip = Gossip::new(vote_len * 3 + 24);
+
+        let sent = unwrap!(gossip.fanout(&[members[0], members[1], members[1], members[2]],
+                                         &votes));
+        assert_eq!(sent.len(), 12);
+        for member in &members {
+            let mut received = vec![];
+            for batch in sent.iter().filter(|x| x.0 == *member).map(|x| &x.1) {
+                assert!(unwrap!(batch.encode()).len() <= vote_len * 3 + 24);
+                if let ChainMessage::Batch { ref messages, .. } = *batch {
+                    received.extend(messages.iter().cloned());
+                }
+            }
+            assert_eq!(received, votes);
+            assert!(!gossip.is_acknowledged(member));
+        }
+
+        for &(member, ref batch) in &sent[..4] {
+            if let ChainMessage::Batch { id, .. } = *batch {
+                assert!(gossip.acknowledge(&member, id));
+                assert!(!gossip.acknowledge(&member, id));
+            }
+        }
+        assert!(gossip.is_acknowledged(&members[0]));
+        assert_eq!(gossip.unacknowledged().len(), 8);
+        gossip.retain_members(&members[..2]);
+        assert_eq!(gossip.unacknowledged().len(), 4);
+        assert!(gossip.unacknowledged().iter().all(|x| x.0 == members[1]));
+    }
+}
@@ -24,11 +24,23 @@
 //! in a chain. There also exists the mechnism to persist these chains over sessions along with
 //! a copy of the data itself (the `ChunkStore`). Data representation is via the `Data` module.
 //!
+//! The chain types (`Block`, `BlockIdentifier`, `DataChain`, `Proof` and `Vote`) live only in the
+//! `chain` module and the data types only in the `data` module; both are re-exported here.
+//!
 //! # Example
 //!
 //! Basic usage
 //!
-//! TBD
+//! ```no_run
+//! use data_chain::{DataIdentifier, SecuredData};
+//! use data_chain::sha3::hash;
+//!
+//! let mut store = SecuredData::create_in_path("/tmp/data_chain".into(), 1 << 20, 8).unwrap();
+//! let content = b"content";
+//! // Vote for the returned identifier, then fetch the data once the block is valid.
+//! let _identifier = store.put_data_stream(&content[..]).unwrap();
+//! let _fetched = store.get(&DataIdentifier::Immutable(hash(content)));
+//! ```
 //!
 //! [Github repository](https://github.com/dirvine/data_chain)
 
@@ -41,35 +53,66 @@
 
 // For explanation of lint checks, run `rustc -W help` or see
 // https://github.com/maidsafe/QA/blob/master/Documentation/Rust%20Lint%20Checks.md
-#![forbid(bad_style, exceeding_bitshifts, mutable_transmutes, no_mangle_const_items,
-          unknown_crate_types, warnings)]
-#![deny(deprecated, improper_ctypes, missing_docs, non_shorthand_field_patterns,
-        overflowing_literals, plugin_as_library, private_no_mangle_fns, private_no_mangle_statics,
+#![forbid(arithmetic_overflow, bad_style, mutable_transmutes, no_mangle_const_items,
+          unknown_crate_types)]
+#![deny(improper_ctypes, missing_docs, non_shorthand_field_patterns, overflowing_literals,
         stable_features, unconditional_recursion, unknown_lints, unsafe_code, unused,
         unused_allocation, unused_attributes, unused_comparisons, unused_features, unused_parens,
-        while_true)]
+        warnings, while_true)]
 #![warn(trivial_casts, trivial_numeric_casts, unused_extern_crates, unused_import_braces,
         unused_qualifications, unused_results)]
-#![allow(box_pointers, fat_ptr_transmutes, missing_copy_implementations,
-         missing_debug_implementations, variant_size_differences)]
+#![allow(missing_copy_implementations, missing_debug_implementations, variant_size_differences)]
+// The `RustcEncodable`/`RustcDecodable` derives are our wire and disk format. Rust 1.85 (see
+// `rust-toolchain`) is the last release to ship them and flags every use as deprecated and
+// soft-unstable; this can only be allowed crate-wide.
+#![allow(deprecated, soft_unstable)]
+#![allow(clippy::redundant_field_names)]
 
 #[macro_use]
 extern crate log;
 extern crate bincode;
 extern crate fs2;
 extern crate itertools;
+#[cfg(all(unix, not(target_os = "linux")))]
+extern crate libc;
 extern crate maidsafe_utilities;
 #[cfg(test)]
 extern crate rand;
+extern crate rayon;
 extern crate rust_sodium;
 extern crate rustc_serialize;
 #[cfg(test)]
 extern crate tempdir;
 extern crate tiny_keccak;
+#[cfg(feature = "blake3")]
+extern crate blake3;
+#[cfg(feature = "pyo3")]
+extern crate core;
+#[cfg(feature = "pyo3")]
+extern crate pyo3;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 #[cfg(test)]
 #[macro_use]
 extern crate unwrap;
 
+// Record what happened to a vote or block along with fields to filter on, e.g.
+// `chain_event!("vote good", block = id, proofs = 3)`. With the `tracing` feature this is a
+// `tracing` event with those fields, otherwise a `log` record listing them after the message.
+#[cfg(feature = "tracing")]
+macro_rules! chain_event {
+    ($message:literal $(, $field:ident = $value:expr)*) => {
+        ::tracing::info!($($field = ?$value,)* $message)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! chain_event {
+    ($message:literal $(, $field:ident = $value:expr)*) => {
+        info!(concat!($message $(, " ", stringify!($field), "={:?}")*) $(, $value)*)
+    };
+}
+
 /// Error types for this crate
 pub mod error;
 
@@ -84,17 +127,83 @@ pub mod chain;
 /// Structured data (constant name with editable contents and ownership changes)
 /// this type is signed by at least one owner and can be a ledger type (never deleted)
 pub mod data;
+/// How `Debug` output shows keys, signatures, names and hashes: whole, truncated or redacted,
+/// chosen at runtime with `set_debug_format`.
+pub mod debug_format;
 /// sha3 (keccak)
 pub mod sha3;
+/// Signature schemes votes and proofs are signed with, Ed25519 by default.
+pub mod signature;
+
+/// Serialisable envelopes for the votes, chains and data nodes exchange, so routing layers need not
+/// invent their own framing.
+pub mod messages;
+
+/// Fans new votes and blocks out to group members in size-limited batches and tracks which have
+/// been acknowledged.
+pub mod gossip;
+
+/// Plans which peers to fetch missing data from and tracks the fetches until the data is stored.
+pub mod replication;
+
+/// The `ChainAuthority` trait routing layers implement, and the `ChainDriver` running a
+/// `DataChain` through it.
+pub mod authority;
+
+/// Challenges a holder answers to prove it stores the data it claims in its `provable_chain`.
+pub mod challenge;
 
 /// API
 /// This is the entry point to this crate and allows the crate to be
 /// used as a secured data store for all data types mentioned above.
 pub mod secured_data;
 
-/// Persistant store on disk of the data itself as well as the `DataChain`.
-mod chunk_store;
+/// Background upkeep of a `SecuredData` (revalidation, purging, scrubbing, flushing and health
+/// checks) run on one thread that is paused, resumed and shut down as a unit.
+pub mod maintenance;
 
-pub use chain::{Block, BlockIdentifier, DataChain, Proof, Vote};
+/// Prometheus style counters and gauges of votes, validation, chain size and disk use, reported to
+/// a `MetricsSink` installed with `DataChain::set_metrics` or `SecuredData::set_metrics`.
+pub mod metrics;
+
+/// Reusable workloads timing chain generation, vote ingestion, validation and disk round trips,
+/// so integrators can measure their own configuration. Enabled by the `bench` feature.
+#[cfg(feature = "bench")]
+pub mod bench;
+
+/// Seeded churn scenarios driving a `DataChain` and checking its invariants after every step.
+/// Enabled by the `simulation` feature.
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
+
+/// C API for decoding chains and checking them with this crate's validation rules, so other
+/// implementations need not reimplement them. Enabled by the `ffi` feature.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+/// Read-only Python views of chains, blocks and proofs for offline analysis. Enabled by the
+/// `pyo3` feature.
+#[cfg(feature = "pyo3")]
+pub mod python;
 
-pub use data::{Data, DataIdentifier, ImmutableData, MAX_BYTES, StructuredData};
+/// Known keys, identifiers, signatures and an encoded chain kept under `test_vectors/`, checked
+/// so changes to encoding, signing or hashing cannot silently break chains already on disk.
+#[cfg(test)]
+mod test_vectors;
+
+/// Persistant store on disk of the data itself as well as the `DataChain`, and a mock store for
+/// testing how failures are handled.
+pub mod chunk_store;
+
+pub use authority::{ChainAuthority, ChainDriver};
+pub use chain::{Block, BlockIdentifier, DataChain, Proof, Vote};
+pub use challenge::{Challenge, ChallengeResponse};
+pub use chunk_store::{ChunkStorage, ChunkStore};
+pub use data::{AppendableData, AppendedData, Data, DataIdentifier, Filter, ImmutableData,
+               MAX_BYTES, MutableData, PlainData, StructuredData};
+pub use debug_format::{DebugFormat, set_debug_format};
+pub use gossip::Gossip;
+pub use messages::ChainMessage;
+pub use replication::ReplicationPlanner;
+pub use secured_data::{DataKind, EvictionPolicy, ScrubCursor, ScrubReport, SecuredData,
+                       UsageReport};
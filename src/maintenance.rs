@@ -0,0 +1,340 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{DataChain, HealthAlert, Watchdog, WatchdogConfig};
+use chain::data_chain::ValidationCursor;
+use chunk_store::ChunkStorage;
+use data::Data;
+use error::Error;
+use secured_data::{ScrubCursor, SecuredData};
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Which maintenance jobs a `MaintenanceHandle` runs, and how often.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MaintenanceConfig {
+    /// Time between rounds of jobs.
+    pub interval: Duration,
+    /// Blocks revalidated per round with `DataChain::mark_blocks_valid_partial`, 0 to disable.
+    pub validation_slice: usize,
+    /// Remove data without a valid block with `SecuredData::purge_disk`.
+    pub purge: bool,
+    /// Stored chunks checked per round with `SecuredData::scrub_partial`, 0 to disable. Corrupt
+    /// chunks are removed; orphans are left to `purge`.
+    pub scrub_slice: usize,
+    /// Write the chain when it changed, according to its `WritePolicy`.
+    pub flush: bool,
+    /// Check accumulation health, raising alerts read with `MaintenanceHandle::alerts`.
+    pub watchdog: Option<WatchdogConfig>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> MaintenanceConfig {
+        MaintenanceConfig {
+            interval: Duration::from_secs(10),
+            validation_slice: 1000,
+            purge: true,
+            scrub_slice: 0,
+            flush: true,
+            watchdog: Some(WatchdogConfig::default()),
+        }
+    }
+}
+
+/// The background jobs of one `SecuredData`, run in turn by `run_once`.
+pub struct Maintenance<S> {
+    data: Arc<Mutex<SecuredData<S>>>,
+    config: MaintenanceConfig,
+    cursor: ValidationCursor,
+    scrub_cursor: ScrubCursor,
+    watchdog: Option<Watchdog>,
+}
+
+impl<S: ChunkStorage<[u8; 32], Data>> Maintenance<S> {
+    /// Jobs for `data` as selected by `config`.
+    pub fn new(data: Arc<Mutex<SecuredData<S>>>, config: MaintenanceConfig) -> Maintenance<S> {
+        Maintenance {
+            data: data,
+            config: config,
+            cursor: ValidationCursor::default(),
+            scrub_cursor: ScrubCursor::default(),
+            watchdog: config.watchdog.map(Watchdog::new),
+        }
+    }
+
+    /// Run one round of every enabled job, returning any health alerts raised. Jobs still run
+    /// after an earlier one fails, the first error is returned.
+    pub fn run_once(&mut self) -> Result<Vec<HealthAlert>, Error> {
+        let chain = self.data.lock().unwrap().chain();
+        let mut result = Ok(());
+        if self.config.validation_slice > 0 {
+            let _ = chain.lock()
+                .unwrap()
+                .mark_blocks_valid_partial(&mut self.cursor, self.config.validation_slice);
+        }
+        if self.config.purge {
+            result = result.and(self.data.lock().unwrap().purge_disk());
+        }
+        if self.config.scrub_slice > 0 {
+            result = result.and(self.data
+                .lock()
+                .unwrap()
+                .scrub_partial(&mut self.scrub_cursor, self.config.scrub_slice, false)
+                .map(|_| ()));
+        }
+        if self.config.flush {
+            result = result.and(Self::flush(&mut chain.lock().unwrap(), false));
+        }
+        let alerts = match self.watchdog {
+            Some(ref mut watchdog) => watchdog.check(&chain.lock().unwrap()),
+            None => vec![],
+        };
+        result.map(|()| alerts)
+    }
+
+    // Write the chain if it changed, a chain without a store has nothing to write.
+    fn flush(chain: &mut DataChain, force: bool) -> Result<(), Error> {
+        if !chain.is_dirty() {
+            return Ok(());
+        }
+        let result = if force {
+            chain.flush()
+        } else {
+            chain.write().map(|_| ())
+        };
+        match result {
+            Err(Error::NotFound) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Write any change to the chain, ignoring its `WritePolicy`.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        let chain = self.data.lock().unwrap().chain();
+        let mut chain = chain.lock().unwrap();
+        Self::flush(&mut chain, true)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    paused: bool,
+    stopping: bool,
+}
+
+/// Runs the maintenance jobs of a `SecuredData` on one background thread, so they can be paused,
+/// resumed and shut down together.
+///
+/// Shutting down, or dropping the handle, stops the thread after the round in progress and
+/// flushes the chain. Alerts and errors from the jobs are queued for `alerts` and `errors`.
+pub struct MaintenanceHandle {
+    control: Arc<(Mutex<State>, Condvar)>,
+    thread: Option<JoinHandle<Result<(), Error>>>,
+    alerts: Receiver<HealthAlert>,
+    errors: Receiver<Error>,
+}
+
+impl MaintenanceHandle {
+    /// Start running the jobs selected by `config` for `data`.
+    pub fn start<S>(data: Arc<Mutex<SecuredData<S>>>,
+                    config: MaintenanceConfig)
+                    -> MaintenanceHandle
+        where S: ChunkStorage<[u8; 32], Data> + Send + 'static
+    {
+        let control = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let (alert_sender, alerts) = mpsc::channel();
+        let (error_sender, errors) = mpsc::channel();
+        let mut maintenance = Maintenance::new(data, config);
+        let thread_control = control.clone();
+        let thread = thread::spawn(move || {
+            run(&mut maintenance,
+                config.interval,
+                &thread_control,
+                &alert_sender,
+                &error_sender)
+        });
+        MaintenanceHandle {
+            control: control,
+            thread: Some(thread),
+            alerts: alerts,
+            errors: errors,
+        }
+    }
+
+    /// Stop running jobs after the round in progress, until `resume`.
+    pub fn pause(&self) {
+        self.set(|state| state.paused = true);
+    }
+
+    /// Run jobs again after `pause`.
+    pub fn resume(&self) {
+        self.set(|state| state.paused = false);
+    }
+
+    /// Whether jobs are paused.
+    pub fn is_paused(&self) -> bool {
+        self.control.0.lock().unwrap().paused
+    }
+
+    /// Health alerts raised since the last call.
+    pub fn alerts(&self) -> Vec<HealthAlert> {
+        self.alerts.try_iter().collect()
+    }
+
+    /// Errors from jobs since the last call.
+    pub fn errors(&self) -> Vec<Error> {
+        self.errors.try_iter().collect()
+    }
+
+    /// Stop the thread, waiting for the round in progress, and flush the chain.
+    pub fn shutdown(mut self) -> Result<(), Error> {
+        self.stop()
+    }
+
+    fn set<F: FnOnce(&mut State)>(&self, change: F) {
+        change(&mut self.control.0.lock().unwrap());
+        self.control.1.notify_all();
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        self.set(|state| state.stopping = true);
+        match self.thread.take().map(|x| x.join()) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => {
+                Err(Error::Io(io::Error::new(io::ErrorKind::Other, "maintenance thread panicked")))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for MaintenanceHandle {
+    fn drop(&mut self) {
+        if let Err(error) = self.stop() {
+            warn!("maintenance shutdown failed: {}", error);
+        }
+    }
+}
+
+fn run<S: ChunkStorage<[u8; 32], Data>>(maintenance: &mut Maintenance<S>,
+                                        interval: Duration,
+                                        control: &(Mutex<State>, Condvar),
+                                        alerts: &Sender<HealthAlert>,
+                                        errors: &Sender<Error>)
+                                        -> Result<(), Error> {
+    loop {
+        {
+            let mut state = control.0.lock().unwrap();
+            state = control.1.wait_timeout(state, interval).unwrap().0;
+            while state.paused && !state.stopping {
+                state = control.1.wait(state).unwrap();
+            }
+            if state.stopping {
+                break;
+            }
+        }
+        match maintenance.run_once() {
+            Ok(raised) => {
+                for alert in raised {
+                    let _ = alerts.send(alert);
+                }
+            }
+            Err(error) => {
+                let _ = errors.send(error);
+            }
+        }
+    }
+    maintenance.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, DataChain, LinkDescriptor, Vote};
+    use chunk_store::{Fault, MockChunkStore};
+    use data::StructuredData;
+    use rust_sodium::crypto::sign;
+    use super::*;
+    use tempdir::TempDir;
+
+    fn structured(name: u8) -> Data {
+        Data::Structured(unwrap!(StructuredData::new(0,
+                                                     [name; 32],
+                                                     0,
+                                                     vec![name],
+                                                     vec![],
+                                                     vec![],
+                                                     None,
+                                                     false)))
+    }
+
+    #[test]
+    fn run_once() {
+        let _ = ::rust_sodium::init();
+        let mut store = MockChunkStore::new(1024);
+        store.inject(Fault::IoErrorOnOp(4));
+        let data = Arc::new(Mutex::new(SecuredData::with_store(store, DataChain::default())));
+        let keys = sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = unwrap!(data.lock()).add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let _ = unwrap!(unwrap!(data.lock()).put_data(&structured(1)));
+        let _ = unwrap!(unwrap!(data.lock()).put_data(&structured(2)));
+
+        let mut maintenance = Maintenance::new(data.clone(),
+                                               MaintenanceConfig {
+                                                   scrub_slice: 10,
+                                                   ..MaintenanceConfig::default()
+                                               });
+        // Purging the data nobody voted for fails once, and is finished by the next round.
+        match maintenance.run_once() {
+            Err(Error::Io(_)) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(unwrap!(data.lock()).used_space() > 0);
+        assert!(unwrap!(maintenance.run_once()).is_empty());
+        assert_eq!(unwrap!(data.lock()).used_space(), 0);
+        unwrap!(maintenance.finish());
+    }
+
+    #[test]
+    fn lifecycle() {
+        let _ = ::rust_sodium::init();
+        let dir = unwrap!(TempDir::new("test_maintenance"));
+        let secured = unwrap!(SecuredData::create_in_path(dir.path().join("store"), 1024, 999));
+        let chain = secured.chain();
+        let data = Arc::new(Mutex::new(secured));
+        let config = MaintenanceConfig {
+            interval: Duration::from_millis(5),
+            ..MaintenanceConfig::default()
+        };
+        let handle = MaintenanceHandle::start(data.clone(), config);
+        handle.pause();
+        assert!(handle.is_paused());
+        let keys = sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = unwrap!(chain.lock()).add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        thread::sleep(Duration::from_millis(20));
+        assert!(unwrap!(chain.lock()).is_dirty());
+        handle.resume();
+        unwrap!(handle.shutdown());
+        assert!(!unwrap!(chain.lock()).is_dirty());
+        let reopened = unwrap!(DataChain::from_path(dir.path().join("store"), 999));
+        assert_eq!(reopened.len(), 1);
+    }
+}
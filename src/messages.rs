@@ -0,0 +1,121 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0 This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{Block, BlockIdentifier, Vote};
+use data::{Data, DataIdentifier};
+use error::Error;
+use maidsafe_utilities::serialisation;
+use std::cmp;
+
+/// Wire format version written by `ChainMessage::encode`.
+pub const MESSAGE_VERSION: u32 = 1;
+
+/// Messages nodes exchange to build and share chains. Routing layers only need to carry the bytes
+/// from `encode` and hand them to `decode` at the other end.
+#[derive(Debug, PartialEq, Clone, RustcEncodable, RustcDecodable)]
+pub enum ChainMessage {
+    /// A vote for a block, to be added with `DataChain::add_vote`.
+    Vote(Vote),
+    /// Ask for the blocks of the peer's chain from the given link onwards.
+    ChainRequest {
+        /// Last link the requester holds.
+        from_link: BlockIdentifier,
+    },
+    /// Reply to a `ChainRequest`.
+    ChainResponse {
+        /// The blocks requested, in chain order.
+        blocks: Vec<Block>,
+    },
+    /// Ask for a copy of some data.
+    DataRequest(DataIdentifier),
+    /// Reply to a `DataRequest`, without data if the peer does not hold it.
+    DataResponse {
+        /// The identifier requested.
+        identifier: DataIdentifier,
+        /// The data, if held.
+        data: Option<Data>,
+    },
+    /// Several messages sent together by `Gossip`, to be acknowledged with an `Ack` of `id`.
+    Batch {
+        /// Identifier of the batch, unique per sender.
+        id: u64,
+        /// The messages batched.
+        messages: Vec<ChainMessage>,
+    },
+    /// Acknowledges receipt of the `Batch` with this id.
+    Ack(u64),
+}
+
+impl ChainMessage {
+    /// Serialise, prefixed by `MESSAGE_VERSION`.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        Ok(serialisation::serialise(&(MESSAGE_VERSION, self))?)
+    }
+
+    /// Inverse of `encode`, failing with `Error::Version` for messages from a newer format.
+    pub fn decode(bytes: &[u8]) -> Result<ChainMessage, Error> {
+        match serialisation::deserialise::<u32>(&bytes[..cmp::min(4, bytes.len())])? {
+            MESSAGE_VERSION => {
+                Ok(serialisation::deserialise::<(u32, ChainMessage)>(bytes)?.1)
+            }
+            version => Err(Error::Version(version)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::test_blocks;
+    use data::ImmutableData;
+    use rust_sodium::crypto::sign;
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let blocks = test_blocks(3);
+        let data = Data::Immutable(ImmutableData::new(b"data".to_vec()));
+        let messages = vec![ChainMessage::Vote(unwrap!(Vote::new(&keys.0,
+                                                                 &keys.1,
+                                                                 blocks[1]
+                                                                     .identifier()
+                                                                     .clone()))),
+                            ChainMessage::ChainRequest {
+                                from_link: blocks[0].identifier().clone(),
+                            },
+                            ChainMessage::ChainResponse { blocks: blocks.clone() },
+                            ChainMessage::DataRequest(data.identifier()),
+                            ChainMessage::Batch {
+                                id: 7,
+                                messages: vec![ChainMessage::DataRequest(data.identifier())],
+                            },
+                            ChainMessage::Ack(7),
+                            ChainMessage::DataResponse {
+                                identifier: data.identifier(),
+                                data: Some(data),
+                            }];
+        for message in messages {
+            assert_eq!(unwrap!(ChainMessage::decode(&unwrap!(message.encode()))), message);
+        }
+        let future = unwrap!(serialisation::serialise(&(MESSAGE_VERSION + 1, 0u8)));
+        match ChainMessage::decode(&future) {
+            Err(Error::Version(version)) => assert_eq!(version, MESSAGE_VERSION + 1),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use error::ValidationError;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Counter of votes given to `DataChain::add_vote`.
+pub const VOTES_RECEIVED: &str = "data_chain_votes_received_total";
+/// Counter of votes `DataChain::add_vote` refused, labelled by `reason`, see `rejection_reason`.
+pub const VOTES_REJECTED: &str = "data_chain_votes_rejected_total";
+/// Counter of blocks and links that became valid.
+pub const BLOCKS_VALIDATED: &str = "data_chain_blocks_validated_total";
+/// Gauge of the number of blocks in the chain.
+pub const CHAIN_LENGTH: &str = "data_chain_length";
+/// Gauge of the number of valid links in the chain.
+pub const VALID_LINKS: &str = "data_chain_valid_links";
+/// Counter of bytes written to disk by the chain's `ChainStore`.
+pub const DISK_BYTES_WRITTEN: &str = "data_chain_disk_bytes_written_total";
+/// Gauge of the disk space used by a `SecuredData`'s chunk store.
+pub const CHUNK_STORE_USED: &str = "data_chain_chunk_store_used_bytes";
+
+/// Label value of `VOTES_REJECTED` for a vote whose key already signed the block.
+pub const DUPLICATE_PROOF: &str = "duplicate_proof";
+/// Label value of `VOTES_REJECTED` for a vote whose proof doesn't verify against its block.
+pub const BAD_PROOF: &str = "bad_proof";
+/// Label value of `VOTES_REJECTED` for a vote dropped by `DataChain::set_rate_limit`.
+pub const RATE_LIMITED: &str = "rate_limited";
+/// Label value of `VOTES_REJECTED` for a vote from a key on the `DataChain` blocklist.
+pub const BLOCKLISTED: &str = "blocklisted";
+/// Label value of `VOTES_REJECTED` for a vote for a link conflicting with one its key signed.
+pub const CONFLICTING_VOTE: &str = "conflicting_vote";
+
+/// Receives metrics as a chain and data store are used, see `DataChain::set_metrics` and
+/// `SecuredData::set_metrics`. Names are the constants of this module and follow Prometheus
+/// conventions, so an implementation can forward them to a registry as they are.
+///
+/// The same sink may be shared by several chains and stores, so is called through `&self` from
+/// whichever thread is using them.
+pub trait MetricsSink: Send + Sync {
+    /// Add `delta` to the counter `name` with the given label pairs.
+    fn increment(&self, name: &'static str, labels: &[(&'static str, &'static str)], delta: u64);
+
+    /// Set the gauge `name` to `value`.
+    fn set(&self, name: &'static str, value: u64);
+}
+
+/// The `reason` label of `VOTES_REJECTED` for a vote failing `DataChain::verify_vote`.
+pub fn rejection_reason(error: ValidationError) -> &'static str {
+    match error {
+        ValidationError::BadSignature => "bad_signature",
+        ValidationError::NoQuorum => "no_quorum",
+        ValidationError::MissingLink => "missing_link",
+        ValidationError::SelfVote => "self_vote",
+        ValidationError::StaleGroup => "stale_group",
+        ValidationError::BindingMismatch => "binding_mismatch",
+        ValidationError::ClockSkew => "clock_skew",
+        ValidationError::UnknownSigner => "unknown_signer",
+        ValidationError::CheckpointMismatch => "checkpoint_mismatch",
+        ValidationError::CheckpointDue => "checkpoint_due",
+        ValidationError::BadEvidence => "bad_evidence",
+        ValidationError::CustomRejected => "custom_rejected",
+    }
+}
+
+// Keyed by name then rendered labels, holding whether it is a counter and its value.
+type Values = BTreeMap<(&'static str, String), (bool, u64)>;
+
+/// A `MetricsSink` keeping the latest values in memory and rendering them in the Prometheus text
+/// exposition format, ready to be served from a `/metrics` endpoint.
+#[derive(Default)]
+pub struct TextMetrics {
+    values: Mutex<Values>,
+}
+
+impl TextMetrics {
+    /// An empty set of metrics.
+    pub fn new() -> TextMetrics {
+        TextMetrics::default()
+    }
+
+    /// Current value of the metric `name` with exactly the given labels, if it was ever reported.
+    pub fn get(&self, name: &'static str, labels: &[(&'static str, &'static str)]) -> Option<u64> {
+        self.values.lock().unwrap().get(&(name, render_labels(labels))).map(|x| x.1)
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let mut last = None;
+        for (&(name, ref labels), &(counter, value)) in self.values.lock().unwrap().iter() {
+            if last != Some(name) {
+                let _ = writeln!(output,
+                                 "# TYPE {} {}",
+                                 name,
+                                 if counter { "counter" } else { "gauge" });
+                last = Some(name);
+            }
+            let _ = writeln!(output, "{}{} {}", name, labels, value);
+        }
+        output
+    }
+}
+
+impl MetricsSink for TextMetrics {
+    fn increment(&self, name: &'static str, labels: &[(&'static str, &'static str)], delta: u64) {
+        let mut values = self.values.lock().unwrap();
+        let entry = values.entry((name, render_labels(labels))).or_insert((true, 0));
+        entry.1 += delta;
+    }
+
+    fn set(&self, name: &'static str, value: u64) {
+        let _ = self.values.lock().unwrap().insert((name, String::new()), (false, value));
+    }
+}
+
+fn render_labels(labels: &[(&'static str, &'static str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs = labels.iter().map(|&(key, value)| format!("{}=\"{}\"", key, value));
+    format!("{{{}}}", pairs.collect::<Vec<_>>().join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, DataChain, LinkDescriptor, Vote};
+    use rust_sodium::crypto::sign;
+    use std::sync::Arc;
+    use super::*;
+
+    #[test]
+    fn chain_reports_votes_and_validity() {
+        let _ = ::rust_sodium::init();
+        let metrics = Arc::new(TextMetrics::new());
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        chain.set_metrics(Some(metrics.clone()));
+        let keys = sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let link_vote = unwrap!(Vote::new(&keys.0, &keys.1, link));
+        let data = BlockIdentifier::ImmutableData([1; 32]);
+        let _ = chain.add_vote(link_vote.clone());
+        let _ = chain.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, data)));
+        let _ = chain.add_vote(link_vote);
+
+        assert_eq!(metrics.get(VOTES_RECEIVED, &[]), Some(3));
+        assert_eq!(metrics.get(VOTES_REJECTED, &[("reason", DUPLICATE_PROOF)]), Some(1));
+        assert_eq!(metrics.get(BLOCKS_VALIDATED, &[]), Some(2));
+        assert_eq!(metrics.get(CHAIN_LENGTH, &[]), Some(2));
+        assert_eq!(metrics.get(VALID_LINKS, &[]), Some(1));
+        let text = metrics.render();
+        assert!(text.contains("# TYPE data_chain_length gauge\ndata_chain_length 2\n"));
+        assert!(text.contains("data_chain_votes_rejected_total{reason=\"duplicate_proof\"} 1"));
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! A `data_chain` Python module of read-only views over captured chains. Build it as an extension
+//! with e.g. `maturin build --features pyo3`, then:
+//!
+//! ```python
+//! import data_chain
+//! chain = data_chain.DataChain.from_bytes(open("data_chain", "rb").read(), 8)
+//! chain.verify()
+//! print(len(chain), chain.valid_len, [b.identifier for b in chain.blocks()])
+//! ```
+
+// The `pyo3` macros expand to unsafe code, and to conversions of errors already `PyErr`.
+#![allow(unsafe_code, clippy::useless_conversion)]
+
+use chain::{Block, BlockIdentifier, DataChain, Proof, persistence};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+/// A `Proof`: the signer's key, its signature and when it was made, if recorded.
+#[pyclass(name = "Proof")]
+#[derive(Clone)]
+pub struct PyProof {
+    proof: Proof,
+}
+
+#[pymethods]
+impl PyProof {
+    #[getter]
+    fn key(&self) -> &[u8] {
+        &self.proof.key().0
+    }
+
+    #[getter]
+    fn signature(&self) -> &[u8] {
+        &self.proof.sig().0
+    }
+
+    #[getter]
+    fn timestamp(&self) -> Option<u64> {
+        self.proof.timestamp()
+    }
+}
+
+/// A `Block` as it stands in the chain it was read from.
+#[pyclass(name = "Block")]
+pub struct PyBlock {
+    block: Block,
+}
+
+#[pymethods]
+impl PyBlock {
+    /// The identifier in its `Debug` form, e.g. `ImmutableData(0a1b..)`.
+    #[getter]
+    fn identifier(&self) -> String {
+        format!("{:?}", self.block.identifier())
+    }
+
+    #[getter]
+    fn is_link(&self) -> bool {
+        self.block.identifier().is_link()
+    }
+
+    /// Name of the data or node the block is about, if it has one.
+    #[getter]
+    fn name(&self) -> Option<&[u8]> {
+        self.block.identifier().name().map(|x| &x[..])
+    }
+
+    #[getter]
+    fn valid(&self) -> bool {
+        self.block.valid
+    }
+
+    fn proofs(&self) -> Vec<PyProof> {
+        self.block.proofs().iter().map(|x| PyProof { proof: x.clone() }).collect()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.block)
+    }
+}
+
+/// A `DataChain` loaded for analysis. Loading never trusts validity recorded in the input; call
+/// `verify` to work it out from the proofs.
+#[pyclass(name = "DataChain", unsendable)]
+pub struct PyDataChain {
+    chain: DataChain,
+}
+
+#[pymethods]
+impl PyDataChain {
+    /// Load a chain file's bytes, see `persistence::encode_chain`.
+    #[staticmethod]
+    fn from_bytes(bytes: &[u8], group_size: usize) -> PyResult<PyDataChain> {
+        let (blocks, pinned) = persistence::decode_chain(bytes)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(PyDataChain::new(blocks, &pinned, group_size))
+    }
+
+    /// Load a chain from `DataChain::to_json` output.
+    #[staticmethod]
+    fn from_json(input: &str) -> PyResult<PyDataChain> {
+        let chain = DataChain::from_json(input)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(PyDataChain::new(chain.chain().clone(), chain.pinned(), chain.group_size()))
+    }
+
+    fn to_json(&self) -> String {
+        self.chain.to_json()
+    }
+
+    /// Validate every block from its proofs, then raise `ValueError` if the chain breaks an
+    /// invariant, see `DataChain::check_invariants`.
+    fn verify(&mut self) -> PyResult<()> {
+        self.chain.mark_blocks_valid();
+        self.chain
+            .check_invariants()
+            .map_err(|violation| PyValueError::new_err(format!("{:?}", violation)))
+    }
+
+    #[getter]
+    fn group_size(&self) -> usize {
+        self.chain.group_size()
+    }
+
+    #[getter]
+    fn valid_len(&self) -> usize {
+        self.chain.valid_len()
+    }
+
+    fn blocks(&self) -> Vec<PyBlock> {
+        self.chain.chain().iter().map(|x| PyBlock { block: x.clone() }).collect()
+    }
+
+    /// The latest valid block for the 32 byte `name`, if any.
+    fn find_name(&self, name: &[u8]) -> PyResult<Option<PyBlock>> {
+        if name.len() != 32 {
+            return Err(PyValueError::new_err("names are 32 bytes"));
+        }
+        let mut key = [0; 32];
+        key.copy_from_slice(name);
+        Ok(self.chain.find_name(&key).map(|x| PyBlock { block: x.clone() }))
+    }
+
+    fn __len__(&self) -> usize {
+        self.chain.len()
+    }
+}
+
+impl PyDataChain {
+    fn new(mut blocks: Vec<Block>, pinned: &[BlockIdentifier], group_size: usize) -> PyDataChain {
+        for block in &mut blocks {
+            block.valid = false;
+        }
+        let mut chain = DataChain::from_blocks(blocks, group_size);
+        for identifier in pinned {
+            let _ = chain.pin(identifier);
+        }
+        PyDataChain { chain: chain }
+    }
+}
+
+/// The `data_chain` Python module.
+#[pymodule]
+fn data_chain(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_class::<PyDataChain>()?;
+    module.add_class::<PyBlock>()?;
+    module.add_class::<PyProof>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{BlockIdentifier, DataChain, LinkDescriptor, Vote, persistence};
+    use rust_sodium::crypto::sign;
+    use sha3::{HashAlgorithm, hash};
+    use super::*;
+
+    #[test]
+    fn read_only_views() {
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let mut chain = DataChain::from_blocks(vec![], 999);
+        let name = hash(b"data");
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        for id in vec![link, BlockIdentifier::ImmutableData(name)] {
+            assert!(chain.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, id))).is_some());
+        }
+        let bytes = unwrap!(persistence::encode_chain(chain.chain(),
+                                                      chain.pinned(),
+                                                      HashAlgorithm::default()));
+
+        // Validity recorded in the input is not trusted until verified.
+        let mut view = unwrap!(PyDataChain::from_bytes(&bytes, 999));
+        assert_eq!((view.__len__(), view.valid_len(), view.group_size()), (2, 0, 999));
+        unwrap!(view.verify());
+        assert_eq!(view.valid_len(), 2);
+        let blocks = view.blocks();
+        assert!(blocks[0].is_link() && !blocks[1].is_link());
+        assert!(blocks.iter().all(|x| x.valid()));
+        assert_eq!(blocks[1].name(), Some(&name[..]));
+        let proofs = blocks[1].proofs();
+        assert_eq!(proofs.len(), 1);
+        assert_eq!(proofs[0].key(), &(keys.0).0[..]);
+        assert_eq!(proofs[0].timestamp(), None);
+        let found = unwrap!(unwrap!(view.find_name(&name)));
+        assert_eq!(found.identifier(), blocks[1].identifier());
+        assert!(unwrap!(view.find_name(&[0; 32])).is_none());
+        assert!(view.find_name(&name[..31]).is_err());
+        assert!(PyDataChain::from_bytes(&bytes[..bytes.len() - 1], 999).is_err());
+
+        let json = view.to_json();
+        assert_eq!(json, chain.to_json());
+        let copy = unwrap!(PyDataChain::from_json(&json));
+        assert_eq!((copy.__len__(), copy.valid_len()), (2, 0));
+        assert!(PyDataChain::from_json("{").is_err());
+    }
+}
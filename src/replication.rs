@@ -0,0 +1,161 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{BlockIdentifier, DataChain};
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::BTreeMap;
+
+/// Decides which peers to fetch missing data from, see `SecuredData::plan_replication`, and tracks
+/// the fetches until the data is stored, see `SecuredData::store_fetched`.
+///
+/// Peers are known by what they hold, from the `provable_chain` each sends us. Each missing item
+/// is asked of the least busy peer holding it, and no peer is sent more than `max_per_peer`
+/// requests at a time.
+pub struct ReplicationPlanner {
+    max_per_peer: usize,
+    holdings: BTreeMap<PublicKey, Vec<BlockIdentifier>>,
+    in_flight: Vec<(PublicKey, BlockIdentifier)>,
+}
+
+impl ReplicationPlanner {
+    /// Create a planner sending each peer at most `max_per_peer` fetches at a time.
+    pub fn new(max_per_peer: usize) -> ReplicationPlanner {
+        ReplicationPlanner {
+            max_per_peer: max_per_peer,
+            holdings: BTreeMap::new(),
+            in_flight: vec![],
+        }
+    }
+
+    /// Record the data `peer` holds from its `provable_chain`, replacing what it held before.
+    pub fn add_holdings(&mut self, peer: PublicKey, chain: &DataChain) {
+        let held = chain.chain()
+            .iter()
+            .filter(|x| x.valid && !x.identifier().is_link())
+            .map(|x| x.identifier().clone())
+            .collect();
+        let _ = self.holdings.insert(peer, held);
+    }
+
+    /// Forget `peer`, e.g. once it left the group. Its fetches in flight are planned again.
+    pub fn remove_peer(&mut self, peer: &PublicKey) {
+        let _ = self.holdings.remove(peer);
+        self.in_flight.retain(|x| x.0 != *peer);
+    }
+
+    /// Assign each of `required` not already in flight to a peer holding it and return the
+    /// fetches to send. Items no peer holds, or whose holders are all busy, wait for a later call.
+    pub fn plan(&mut self, required: &[BlockIdentifier]) -> Vec<(PublicKey, BlockIdentifier)> {
+        let mut planned = vec![];
+        for identifier in required {
+            if self.in_flight.iter().any(|x| x.1 == *identifier) {
+                continue;
+            }
+            let peer = self.holdings
+                .iter()
+                .filter(|&(_, held)| held.contains(identifier))
+                .map(|(peer, _)| (self.load(peer), *peer))
+                .filter(|&(load, _)| load < self.max_per_peer)
+                .min_by_key(|&(load, _)| load);
+            if let Some((_, peer)) = peer {
+                self.in_flight.push((peer, identifier.clone()));
+                planned.push((peer, identifier.clone()));
+            }
+        }
+        planned
+    }
+
+    /// Record that `peer` could not supply `identifier`, so it is asked of another holder next
+    /// time. Returns `false` if the fetch was not in flight.
+    pub fn failed(&mut self, peer: &PublicKey, identifier: &BlockIdentifier) -> bool {
+        if let Some(held) = self.holdings.get_mut(peer) {
+            held.retain(|x| x != identifier);
+        }
+        let len = self.in_flight.len();
+        self.in_flight.retain(|x| x.0 != *peer || x.1 != *identifier);
+        self.in_flight.len() != len
+    }
+
+    /// Record that the data for `identifier` is stored and valid. Returns `false` if no fetch of
+    /// it was in flight.
+    pub fn complete(&mut self, identifier: &BlockIdentifier) -> bool {
+        let len = self.in_flight.len();
+        self.in_flight.retain(|x| x.1 != *identifier);
+        self.in_flight.len() != len
+    }
+
+    /// Fetches sent and not yet completed or failed, with the peer each was sent to.
+    pub fn in_flight(&self) -> &[(PublicKey, BlockIdentifier)] {
+        &self.in_flight
+    }
+
+    fn load(&self, peer: &PublicKey) -> usize {
+        self.in_flight.iter().filter(|x| x.0 == *peer).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chain::{Block, DataChain};
+    use chunk_store::MockChunkStore;
+    use data::{Data, ImmutableData};
+    use rust_sodium::crypto::sign;
+    use secured_data::SecuredData;
+    use super::*;
+
+    #[test]
+    fn plan_fetch_and_complete() {
+        let _ = ::rust_sodium::init();
+        let peers = (0..2).map(|_| sign::gen_keypair().0).collect::<Vec<_>>();
+        let content = (0..3u8).map(|x| ImmutableData::new(vec![x; 10])).collect::<Vec<_>>();
+        let ids = content.iter()
+            .map(|x| BlockIdentifier::ImmutableData(*x.name()))
+            .collect::<Vec<_>>();
+        let held = |ids: &[BlockIdentifier]| {
+            DataChain::from_blocks(ids.iter().map(|x| Block::from_parts(x.clone(), vec![], true))
+                                       .collect(),
+                                   999)
+        };
+        let mut planner = ReplicationPlanner::new(1);
+        planner.add_holdings(peers[0], &held(&ids));
+        planner.add_holdings(peers[1], &held(&ids[1..]));
+
+        // One fetch per peer at a time; the third waits for a free peer.
+        let planned = planner.plan(&ids);
+        assert_eq!(planned, vec![(peers[0], ids[0].clone()), (peers[1], ids[1].clone())]);
+        assert!(planner.plan(&ids).is_empty());
+
+        assert!(planner.failed(&peers[1], &ids[1]));
+        assert!(!planner.failed(&peers[1], &ids[1]));
+        assert_eq!(planner.plan(&ids), vec![(peers[1], ids[2].clone())]);
+
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  held(&ids[..1]));
+        assert_eq!(secured.required_data(), vec![ids[0].clone()]);
+        assert!(secured.store_fetched(&mut planner, &Data::Immutable(content[2].clone()))
+            .is_err());
+        assert_eq!(unwrap!(secured.store_fetched(&mut planner,
+                                                 &Data::Immutable(content[0].clone()))),
+                   ids[0]);
+        assert!(secured.required_data().is_empty());
+        assert_eq!(planner.in_flight(), &[(peers[1], ids[2].clone())]);
+        assert!(secured.plan_replication(&mut planner).is_empty());
+
+        planner.remove_peer(&peers[1]);
+        assert!(planner.in_flight().is_empty());
+    }
+}
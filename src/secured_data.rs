@@ -15,23 +15,157 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use chain::{BlockIdentifier, DataChain, Vote};
-use chunk_store::ChunkStore;
-use data::{Data, DataIdentifier};
-use error::Error;
+use bincode::rustc_serialize;
+use chain::{Block, BlockIdentifier, ChainEvent, DataChain, TrustConfig, TrustReport, Vote};
+use chain::persistence;
+use challenge::{Challenge, ChallengeResponse, storage_proof};
+use chunk_store::{ChunkStorage, ChunkStore, StreamStorage};
+use data::{AppendedData, Data, DataIdentifier};
+use error::{Error, ValidationError};
 use itertools::Itertools;
 use maidsafe_utilities::serialisation;
-use rust_sodium::crypto::sign::{PublicKey, Signature};
+use metrics::{self, MetricsSink};
+use replication::ReplicationPlanner;
+use rust_sodium::crypto::sign::{PublicKey, SecretKey, Signature};
 use sha3::hash;
-use std::collections::HashSet;
-use std::fs;
+use signature::{Ed25519, SignatureScheme};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+/// Leading bytes of an archive written by `SecuredData::export_archive`.
+const ARCHIVE_MAGIC: &[u8] = b"DCAR";
+
+/// Archive format written by `SecuredData::export_archive`.
+pub const ARCHIVE_VERSION: u32 = 2;
+
+// Version 1 archives are all full, without the link an archive starts after.
+const ARCHIVE_VERSION_FULL: u32 = 1;
+
+// Name, length and hash of each chunk in an archive.
+type Manifest = Vec<([u8; 32], u64, [u8; 32])>;
+
+// An archive opened up to its chunks: the reader and offset of the chunks, the link the archive
+// starts after, the encoded chain and the manifest.
+type OpenArchive = (BufReader<File>, u64, Option<BlockIdentifier>, Vec<u8>, Manifest);
+
 /// API for data based operations.
-pub struct SecuredData {
-    cs: ChunkStore<[u8; 32], Data>,
+///
+/// Data is kept in a `ChunkStore` on disk unless another `ChunkStorage` is given to `with_store`.
+pub struct SecuredData<S = ChunkStore<[u8; 32], Data>> {
+    cs: S,
     dc: Arc<Mutex<DataChain>>,
+    metrics: Option<Arc<dyn MetricsSink>>,
+    eviction: Option<EvictionPolicy>,
+    evicted: Vec<[u8; 32]>,
+    verify_reads: bool,
+    chunks: HashMap<[u8; 32], (DataKind, u64)>,
+    usage: UsageReport,
+    quotas: HashMap<DataKind, u64>,
+}
+
+/// When `SecuredData` evicts data to make room in its store, see
+/// `SecuredData::set_eviction_policy`. Both marks are percentages of the store's `max_space`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EvictionPolicy {
+    /// Evict once a write would take used space above this mark.
+    pub high_water: u8,
+    /// Evict until the write fits below this mark, or nothing more may be evicted.
+    pub low_water: u8,
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> EvictionPolicy {
+        EvictionPolicy {
+            high_water: 90,
+            low_water: 75,
+        }
+    }
+}
+
+/// The kinds of data `SecuredData` accounts space for, see `SecuredData::usage_report`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DataKind {
+    /// `ImmutableData`.
+    Immutable,
+    /// `StructuredData` without the ledger bit, which may be trimmed and evicted.
+    Structured,
+    /// Ledger `StructuredData`, kept for every version and never evicted.
+    Ledger,
+    /// `AppendableData`.
+    Appendable,
+}
+
+impl DataKind {
+    /// The kind of `data`.
+    pub fn of(data: &Data) -> DataKind {
+        match *data {
+            Data::Immutable(_) => DataKind::Immutable,
+            Data::Structured(ref sd) if sd.ledger() => DataKind::Ledger,
+            Data::Structured(_) => DataKind::Structured,
+            Data::Appendable(_) => DataKind::Appendable,
+        }
+    }
+}
+
+/// Space used by each kind of data in a `SecuredData` store, in serialised bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UsageReport {
+    /// Space used by immutable data.
+    pub immutable: u64,
+    /// Space used by structured data without the ledger bit.
+    pub structured: u64,
+    /// Space used by ledger structured data.
+    pub ledger: u64,
+    /// Space used by appendable data.
+    pub appendable: u64,
+}
+
+impl UsageReport {
+    /// Space used by data of `kind`.
+    pub fn get(&self, kind: DataKind) -> u64 {
+        match kind {
+            DataKind::Immutable => self.immutable,
+            DataKind::Structured => self.structured,
+            DataKind::Ledger => self.ledger,
+            DataKind::Appendable => self.appendable,
+        }
+    }
+
+    /// Space used by all data.
+    pub fn total(&self) -> u64 {
+        self.immutable + self.structured + self.ledger + self.appendable
+    }
+
+    fn get_mut(&mut self, kind: DataKind) -> &mut u64 {
+        match kind {
+            DataKind::Immutable => &mut self.immutable,
+            DataKind::Structured => &mut self.structured,
+            DataKind::Ledger => &mut self.ledger,
+            DataKind::Appendable => &mut self.appendable,
+        }
+    }
+}
+
+/// Progress through the store for `SecuredData::scrub_partial`.
+#[derive(Clone, Debug, Default)]
+pub struct ScrubCursor {
+    last: Option<[u8; 32]>,
+}
+
+/// What one call to `SecuredData::scrub_partial` found.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScrubReport {
+    /// Number of chunks looked at.
+    pub checked: usize,
+    /// Chunks of valid blocks that no longer read back intact, which were removed.
+    pub corrupt: Vec<[u8; 32]>,
+    /// Chunks no block in the chain refers to.
+    pub orphans: Vec<[u8; 32]>,
+    /// Whether the pass reached the end of the store.
+    pub finished: bool,
 }
 
 impl SecuredData {
@@ -41,8 +175,7 @@ impl SecuredData {
                           group_size: usize)
                           -> Result<SecuredData, Error> {
         let cs = ChunkStore::new(path.clone(), max_disk_space)?;
-        let dc = Arc::new(Mutex::new(DataChain::create_in_path(path, group_size)?));
-        Ok(SecuredData { cs: cs, dc: dc })
+        Ok(SecuredData::with_store(cs, DataChain::create_in_path(path, group_size)?))
     }
 
     /// Open an existing container from path
@@ -51,14 +184,40 @@ impl SecuredData {
                      group_size: usize)
                      -> Result<SecuredData, Error> {
         let cs = ChunkStore::from_path(path.clone(), max_disk_space)?;
-        let dc = Arc::new(Mutex::new(DataChain::from_path(path, group_size)?));
-        Ok(SecuredData { cs: cs, dc: dc })
+        Ok(SecuredData::with_store(cs, DataChain::from_path(path, group_size)?))
+    }
+}
+
+impl<S: ChunkStorage<[u8; 32], Data>> SecuredData<S> {
+    /// Construct a container keeping data in `store`, for instance a `MockChunkStore`. Data
+    /// already in the store is read once to account for its space by kind.
+    pub fn with_store(store: S, chain: DataChain) -> SecuredData<S> {
+        let mut chunks = HashMap::new();
+        let mut usage = UsageReport::default();
+        for name in store.keys() {
+            if let Ok(data) = store.get(&name) {
+                let (kind, size) = (DataKind::of(&data), rustc_serialize::encoded_size(&data));
+                *usage.get_mut(kind) += size;
+                let _ = chunks.insert(name, (kind, size));
+            }
+        }
+        SecuredData {
+            chunks: chunks,
+            usage: usage,
+            quotas: HashMap::new(),
+            cs: store,
+            dc: Arc::new(Mutex::new(chain)),
+            metrics: None,
+            eviction: None,
+            evicted: vec![],
+            verify_reads: true,
+        }
     }
 
     /// remove all disk based data
     pub fn clear_disk(&self, path: &Path) -> Result<(), Error> {
         self.dc.lock().unwrap().unlock();
-        Ok(fs::remove_dir_all(&path)?)
+        Ok(fs::remove_dir_all(path)?)
     }
 
     /// Access to DataChain
@@ -66,6 +225,65 @@ impl SecuredData {
         self.dc.clone()
     }
 
+    /// Call `callback` on every change to the chain, see `DataChain::subscribe`. Callbacks must
+    /// not use this container, as the chain is locked while they run.
+    pub fn subscribe<F>(&self, callback: F) -> u64
+        where F: FnMut(ChainEvent) + Send + 'static
+    {
+        self.dc.lock().unwrap().subscribe(callback)
+    }
+
+    /// Stop calling a callback given to `subscribe`.
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        self.dc.lock().unwrap().unsubscribe(id)
+    }
+
+    /// Report the chain's metrics and the chunk store's used space to `sink`, or stop with
+    /// `None`, see `DataChain::set_metrics`.
+    pub fn set_metrics(&mut self, sink: Option<Arc<dyn MetricsSink>>) {
+        self.dc.lock().unwrap().set_metrics(sink.clone());
+        self.metrics = sink;
+        self.report_used_space();
+    }
+
+    /// Evict data as writes fill the store past `policy.high_water`, or never with `None`, the
+    /// default. Data without a block in the chain goes first, then data whose block is still
+    /// invalid, oldest first. Data of valid blocks and ledger structured data are never evicted,
+    /// so a write may still fail with `Error::StorageFull`.
+    pub fn set_eviction_policy(&mut self, policy: Option<EvictionPolicy>) {
+        self.eviction = policy;
+    }
+
+    /// Whether `get` checks the data it reads back against the hash recorded in the chain, which
+    /// it does by default. Turning this off saves serialising and hashing every read.
+    pub fn set_verify_reads(&mut self, verify: bool) {
+        self.verify_reads = verify;
+    }
+
+    /// Cap the space data of `kind` may use, or lift the cap with `None`, the default. Writes that
+    /// would take the kind past its quota fail with `Error::StorageFull`, whatever room is left
+    /// in the store, so ledger data that is never evicted cannot crowd out other data.
+    pub fn set_quota(&mut self, kind: DataKind, quota: Option<u64>) {
+        match quota {
+            Some(quota) => {
+                let _ = self.quotas.insert(kind, quota);
+            }
+            None => {
+                let _ = self.quotas.remove(&kind);
+            }
+        }
+    }
+
+    /// Space used so far by each kind of data.
+    pub fn usage_report(&self) -> UsageReport {
+        self.usage
+    }
+
+    /// Hashes of the data evicted so far, in the order evicted.
+    pub fn evicted(&self) -> &[[u8; 32]] {
+        &self.evicted
+    }
+
     /// Add a Vote from another node
     /// If block is valid will return BlockIdentifier
     pub fn add_vote(&mut self, nb: Vote) -> Option<BlockIdentifier> {
@@ -78,7 +296,7 @@ impl SecuredData {
             .lock()
             .unwrap()
             .find_name(data_id.name()) {
-            if let Some(name) = id.identifier().name() {
+            if let Some(name) = held_name(id.identifier(), data_id) {
 
                 return self.cs.has(name);
             }
@@ -87,22 +305,39 @@ impl SecuredData {
         false
     }
 
-    /// Retrieve data we have on disk, that is also marked valid in the data chain.
+    /// Retrieve data we have on disk, that is also marked valid in the data chain. Fails with
+    /// `Error::Deleted` once a deletion of it is valid, and with `Error::Corrupt` if the data no
+    /// longer matches the hash its block records, unless turned off with `set_verify_reads`.
     pub fn get(&self, data_id: &DataIdentifier) -> Result<Data, Error> {
-        if let Some(block_id) = self.dc
-            .lock()
-            .unwrap()
-            .find_name(data_id.name()) {
+        if self.dc.lock().unwrap().is_deleted(data_id.name()) {
+            return Err(Error::Deleted);
+        }
+        let found = self.dc.lock().unwrap().find_name(data_id.name()).cloned();
+        if let Some(block_id) = found {
             if block_id.valid {
-                if let Some(name) = block_id.identifier().name() {
-                    return Ok(self.cs.get(name)?);
+                if let Some(name) = held_name(block_id.identifier(), data_id) {
+                    let data = self.cs.get(name)?;
+                    if self.verify_reads && !self.intact(block_id.identifier(), name, &data)? {
+                        return Err(Error::Corrupt { offset: 0 });
+                    }
+                    return Ok(data);
                 }
 
             } else {
                 return Err(Error::Validation);
             }
         }
-        Err(Error::NoFile)
+        Err(Error::NotFound)
+    }
+
+    // The structured or appendable data we hold for the latest valid block named `name`, if any.
+    fn current_version(&self, name: &[u8; 32]) -> Option<Data> {
+        let found = self.dc.lock().unwrap().find_name(name).cloned();
+        match found.as_ref().map(|x| x.identifier().without_expiry()) {
+            Some(BlockIdentifier::StructuredData(hash, _)) |
+            Some(BlockIdentifier::AppendableData(hash, _)) => self.cs.get(hash).ok(),
+            _ => None,
+        }
     }
 
     /// Will not remove ledger items
@@ -111,11 +346,12 @@ impl SecuredData {
             match *item {
                 Data::Structured(ref sd) => {
                     if !sd.ledger() {
-                        let _ = self.cs.delete(hash);
+                        let _ = self.remove_chunk(hash);
                     }
                 }
-                Data::Immutable(ref _id) => {
-                    let _ = self.cs.delete(hash);
+                Data::Immutable(_) |
+                Data::Appendable(_) => {
+                    let _ = self.remove_chunk(hash);
                 }
             }
         }
@@ -129,29 +365,65 @@ impl SecuredData {
     ///
     /// **Versioned ledger structured data will be Put and paid for**
     pub fn put_data(&mut self, data: &Data) -> Result<BlockIdentifier, Error> {
-        let hash = hash(&serialisation::serialise(&data)?);
+        let serialised = serialisation::serialise(&data)?;
+        // Immutable data is kept under its name, as `put_data_stream` does.
+        let hash = match *data {
+            Data::Immutable(ref im) => *im.name(),
+            _ => self.hash(&serialised),
+        };
         let id = match *data {
-            Data::Immutable(ref im) if *im.name() == hash => BlockIdentifier::ImmutableData(hash),
+            Data::Immutable(_) => BlockIdentifier::ImmutableData(hash),
             Data::Structured(ref sd) if sd.version() == 0 || sd.ledger() => {
                 BlockIdentifier::StructuredData(hash, sd.identifier())
             }
+            Data::Appendable(ref ad) if ad.version() == 0 => {
+                BlockIdentifier::AppendableData(hash, ad.identifier())
+            }
             _ => return Err(Error::BadIdentifier),
         };
         self.trim_previous_data(&hash);
-        self.cs.put(&hash, data)?;
+        self.make_room(serialised.len() as u64);
+        self.store_chunk(&hash, data)?;
+        self.report_used_space();
         Ok(id)
     }
 
+    /// As `put_data`, but the block to vote for expires at `expires_at`, in seconds since the unix
+    /// epoch, after which `expire` drops it and its data. Only one-off data blocks can expire.
+    pub fn put_data_expiring(&mut self,
+                             data: &Data,
+                             expires_at: u64)
+                             -> Result<BlockIdentifier, Error> {
+        BlockIdentifier::expiring(self.put_data(data)?, expires_at)
+    }
+
     /// Handle POST data
-    /// This is a call that will only handle structured data
+    /// This is a call that will only handle structured and appendable data
+    ///
+    /// If we hold the current version, the update must be signed by its owners, see
+    /// `StructuredData::validate_self_against_successor` and
+    /// `AppendableData::validate_self_against_successor`.
     ///
     /// **Will not accept versioned ledger based structuredData !**
     pub fn post_data(&mut self, data: &Data) -> Result<BlockIdentifier, Error> {
-        let hash = hash(&serialisation::serialise(&data)?);
+        let serialised = serialisation::serialise(&data)?;
+        let hash = self.hash(&serialised);
         let id = match *data {
             Data::Structured(ref sd) if !sd.ledger() => {
+                if let Some(Data::Structured(previous)) = self.current_version(sd.name()) {
+                    previous.validate_self_against_successor(sd)?;
+                }
                 BlockIdentifier::StructuredData(hash, sd.identifier())
             }
+            Data::Appendable(ref ad) => {
+                match self.current_version(ad.name()) {
+                    Some(Data::Appendable(previous)) => {
+                        previous.validate_self_against_successor(ad)?
+                    }
+                    _ => return Err(Error::NotFound),
+                }
+                BlockIdentifier::AppendableData(hash, ad.identifier())
+            }
             _ => return Err(Error::BadIdentifier),
         };
         // Remove last element unless marked with ledger
@@ -166,54 +438,126 @@ impl SecuredData {
         //     }
         // }
         self.trim_previous_data(&hash);
-        self.cs.put(&hash, data)?;
+        self.make_room(serialised.len() as u64);
+        self.store_chunk(&hash, data)?;
+        self.report_used_space();
+
+        Ok(id)
+    }
+
+    /// Append `appended` to the valid appendable data named `name`, see `AppendableData::append`.
+    /// As with `put_data`, returns the identifier of the new state to vote for.
+    pub fn append_data(&mut self,
+                       name: &[u8; 32],
+                       appended: AppendedData)
+                       -> Result<BlockIdentifier, Error> {
+        let mut ad = match self.current_version(name) {
+            Some(Data::Appendable(ad)) => ad,
+            _ => return Err(Error::NotFound),
+        };
+        ad.append(appended)?;
+        let data = Data::Appendable(ad);
+        let serialised = serialisation::serialise(&data)?;
+        let hash = self.hash(&serialised);
+        self.make_room(serialised.len() as u64);
+        self.store_chunk(&hash, &data)?;
+        self.report_used_space();
+        Ok(BlockIdentifier::AppendableData(hash, data.identifier()))
+    }
+
+    /// Every version we hold of the ledger structured data named `name`, in chain order, with
+    /// its identifier and whether its block is valid yet. Versions whose data we lack are left
+    /// out, see `required_data`.
+    pub fn ledger_history(&self, name: &[u8; 32]) -> Vec<(BlockIdentifier, Data, bool)> {
+        let versions = self.dc
+            .lock()
+            .unwrap()
+            .chain()
+            .iter()
+            .filter(|x| match *x.identifier() {
+                BlockIdentifier::StructuredData(_, ref id) => id.name() == name,
+                _ => false,
+            })
+            .map(|x| (x.identifier().clone(), x.valid))
+            .collect_vec();
+        versions.into_iter()
+            .filter_map(|(identifier, valid)| {
+                let data = stored_hash(&identifier).and_then(|x| self.cs.get(x).ok());
+                match data {
+                    Some(Data::Structured(ref sd)) if sd.ledger() => {
+                        Some((identifier, Data::Structured(sd.clone()), valid))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
 
+    /// Add related data items to be voted for together under one `BlockIdentifier::Batch`, so
+    /// they become valid together or not at all. Each item is kept under its own name and read
+    /// back with `get` once the batch is valid. If any item cannot be stored none are kept.
+    pub fn put_batch(&mut self, items: &[Data]) -> Result<BlockIdentifier, Error> {
+        if items.is_empty() || items.iter().map(|x| x.name()).unique().count() != items.len() {
+            return Err(Error::BadIdentifier);
+        }
+        let serialised = serialisation::serialise(&items)?;
+        let id = BlockIdentifier::Batch(items.iter().map(|x| x.identifier()).collect(),
+                                        self.hash(&serialised));
+        self.make_room(serialised.len() as u64);
+        for (index, item) in items.iter().enumerate() {
+            if let Err(error) = self.store_chunk(item.name(), item) {
+                for stored in &items[..index] {
+                    let _ = self.remove_chunk(stored.name());
+                }
+                self.report_used_space();
+                return Err(error);
+            }
+        }
+        self.report_used_space();
         Ok(id)
     }
 
     /// Handle Delete data Unless ledger bit is set
+    ///
+    /// Deleting structured data we hold needs `sigs` from its owners, see
+    /// `StructuredData::delete_signature`, and deleting other data needs them from a quorum of
+    /// the group, see `delete_signature`. Returns the `BlockIdentifier::Deleted` tombstone to vote
+    /// for, which stays in the chain as a record of the agreed deletion.
     pub fn delete_data(&mut self,
                        data_id: &DataIdentifier,
-                       _sigs: &[Signature])
+                       sigs: &[Signature])
                        -> Result<BlockIdentifier, Error> {
-        if let Some(block_id) = self.dc
-            .lock()
-            .unwrap()
-            .find_name(data_id.name()) {
+        let found = self.dc.lock().unwrap().find_name(data_id.name()).cloned();
+        if let Some(block_id) = found {
+            match self.current_version(data_id.name()) {
+                Some(Data::Structured(current)) => current.verify_delete_signatures(sigs)?,
+                _ => {
+                    let message = deletion(data_id);
+                    self.dc.lock().unwrap().verify_group_signatures(&message, sigs)?
+                }
+            }
             // if !block_id.identifier().is_ledger() {
-            if let Some(name) = block_id.identifier().name() {
-                let _ = self.cs.delete(name);
+            if let Some(name) = held_name(block_id.identifier(), data_id) {
+                let _ = self.remove_chunk(name);
+                self.report_used_space();
             }
-
-            self.dc.lock().unwrap().remove(block_id.identifier());
-            return Ok(block_id.identifier().clone());
+            return Ok(BlockIdentifier::Deleted(*data_id));
             // }
         }
-        Err(Error::NoFile)
+        Err(Error::NotFound)
     }
 
     /// Return a chain for which we hold **all** of the data.
     /// Restricted to data that has a corresponding valid `Block`.
     pub fn provable_chain(&self, group_size: usize) -> DataChain {
         let keys = self.cs.keys();
-        DataChain::from_blocks(self.dc
-                                   .lock()
-                                   .unwrap()
-                                   .chain()
-                                   .iter()
-                                   .cloned()
-                                   .filter(|x| x.valid)
-                                   .filter(|x| {
-                x.identifier().is_link() ||
-                if let Some(name) = x.identifier().name() {
-                    keys.contains(name)
-                } else {
-                    false
-                }
-
-            })
-                                   .collect_vec(),
-                               group_size)
+        let mut chain = DataChain::from_blocks(self.dc.lock().unwrap().chain().clone(), group_size);
+        chain.retain(|x| {
+            x.valid &&
+            (x.identifier().is_link() ||
+             x.identifier().names().iter().all(|name| keys.contains(name)))
+        });
+        chain
     }
 
     /// Remove any data on disk that we do not have a valid Block for
@@ -225,57 +569,362 @@ impl SecuredData {
             .chain()
             .iter()
             .filter(|x| !x.identifier().is_link() && x.valid)
-            .filter_map(|x| x.identifier().name()) {
+            .flat_map(|x| x.identifier().names()) {
             let _existed = invalid_names.remove(valid_name);
         }
         // only throws error on IO error not missing data
         // TODO test this !!
         for name in invalid_names {
-            self.cs.delete(&name)?;
+            self.remove_chunk(&name)?;
         }
+        self.report_used_space();
         Ok(())
     }
 
-    /// Confirm and merge a DataChain transmitted to us.
-    /// This will trim (purge invalid) exsiting entries then merge valid entries.
-    /// May be used to create a new chain from given chains on node startup.
-    pub fn merge_chain(&mut self, chain: &mut DataChain) {
-        self.dc.lock().unwrap().merge_chain(chain);
+    /// Remove the blocks expiring at or before `now`, in seconds since the unix epoch, from the
+    /// chain, see `DataChain::expire`, and their data from disk unless a valid block still refers
+    /// to it. Returns the identifiers of the expired blocks.
+    pub fn expire(&mut self, now: u64) -> Result<Vec<BlockIdentifier>, Error> {
+        let expired = self.dc.lock().unwrap().expire(now, true);
+        let held = self.held_chunks();
+        for identifier in &expired {
+            for name in chunk_names(identifier) {
+                if !held.get(name).is_some_and(|x| x.1) && self.cs.has(name) {
+                    self.remove_chunk(name)?;
+                }
+            }
+        }
+        if !expired.is_empty() {
+            self.report_used_space();
+        }
+        Ok(expired)
     }
 
-    /// How many network events a given proover has been involved in (proover == node)
-    /// First missed event stops the count
-    // TODO this is very basic and requires some further discussion
-    pub fn trust_level(&self, node: &PublicKey) -> usize {
-        self.dc
+    /// Read back the data of every valid block, removing any copy that no longer reads back intact
+    /// or no longer matches its hash, so `required_data` lists it to be fetched again. Returns the
+    /// identifiers removed.
+    pub fn scrub(&mut self) -> Result<Vec<BlockIdentifier>, Error> {
+        let valid = self.dc
             .lock()
             .unwrap()
             .chain()
             .iter()
-            .rev()
-            .take_while(|x| x.proofs().iter().any(|z| z.key() == node))
-            .count()
+            .filter(|x| !x.identifier().is_link() && x.valid)
+            .map(|x| x.identifier().clone())
+            .collect_vec();
+        let mut removed = vec![];
+        for identifier in valid {
+            let mut damaged = false;
+            for name in chunk_names(&identifier) {
+                let intact = match self.cs.get(name) {
+                    Ok(ref data) => self.intact(&identifier, name, data)?,
+                    Err(Error::Serialisation(_)) => false,
+                    Err(Error::NotFound) => true,
+                    Err(error) => return Err(error),
+                };
+                if !intact {
+                    self.remove_chunk(name)?;
+                    damaged = true;
+                }
+            }
+            if damaged {
+                removed.push(identifier.clone());
+            }
+        }
+        if !removed.is_empty() {
+            self.report_used_space();
+        }
+        Ok(removed)
+    }
+
+    /// Incremental form of `scrub` for long running stores, looking at most `max_chunks` stored
+    /// chunks in key order from `cursor`. Corrupt chunks of valid blocks are removed, chunks no
+    /// block refers to are reported and removed too if `delete_orphans`. Chunks of blocks still
+    /// invalid are left alone. Once a pass has reached the end the cursor starts the next one.
+    pub fn scrub_partial(&mut self,
+                         cursor: &mut ScrubCursor,
+                         max_chunks: usize,
+                         delete_orphans: bool)
+                         -> Result<ScrubReport, Error> {
+        let held = self.held_chunks();
+        let mut keys = self.cs
+            .keys()
+            .into_iter()
+            .filter(|x| cursor.last.is_none_or(|last| *x > last))
+            .collect_vec();
+        keys.sort();
+        let mut report = ScrubReport {
+            finished: keys.len() <= max_chunks,
+            ..ScrubReport::default()
+        };
+        for name in keys.into_iter().take(max_chunks) {
+            report.checked += 1;
+            cursor.last = Some(name);
+            match held.get(&name) {
+                None => {
+                    if delete_orphans {
+                        self.remove_chunk(&name)?;
+                    }
+                    report.orphans.push(name);
+                }
+                Some(&(ref identifier, true)) => {
+                    let intact = match self.cs.get(&name) {
+                        Ok(ref data) => self.intact(identifier, &name, data)?,
+                        Err(Error::Serialisation(_)) => false,
+                        Err(Error::NotFound) => true,
+                        Err(error) => return Err(error),
+                    };
+                    if !intact {
+                        self.remove_chunk(&name)?;
+                        report.corrupt.push(name);
+                    }
+                }
+                Some(&(_, false)) => (),
+            }
+        }
+        if report.finished {
+            *cursor = ScrubCursor::default();
+        }
+        if !report.corrupt.is_empty() || (delete_orphans && !report.orphans.is_empty()) {
+            self.report_used_space();
+        }
+        Ok(report)
+    }
+
+    /// Write the chain and the data of its valid blocks we hold to the single file `path`, to move
+    /// a vault to new hardware or keep as a backup, see `import_archive`. The file holds
+    /// `ARCHIVE_MAGIC` and `ARCHIVE_VERSION`, then the link the archive starts after if any, the
+    /// chain as `encode_chain` writes it, a manifest of each chunk's name, length and hash, and
+    /// the chunks in manifest order, each framed by its length and hash. Returns the number of
+    /// chunks written.
+    pub fn export_archive(&self, path: &Path) -> Result<usize, Error> {
+        let (blocks, pinned) = {
+            let dc = self.dc.lock().unwrap();
+            (dc.chain().to_vec(), dc.pinned().to_vec())
+        };
+        self.write_archive(path, None, &blocks, &pinned)
+    }
+
+    /// As `export_archive`, but only with the blocks after `link` and their data, for a cheap
+    /// backup of what changed since an earlier one, see `apply_incremental`. Fails with
+    /// `Error::MissingLink` if `link` is not a link in our chain.
+    pub fn export_archive_since(&self,
+                                link: &BlockIdentifier,
+                                path: &Path)
+                                -> Result<usize, Error> {
+        let (blocks, pinned) = {
+            let dc = self.dc.lock().unwrap();
+            let blocks = dc.blocks_since(link).ok_or(Error::MissingLink)?;
+            let pinned = dc.pinned()
+                .iter()
+                .filter(|x| blocks.iter().any(|block| block.identifier() == *x))
+                .cloned()
+                .collect_vec();
+            (blocks, pinned)
+        };
+        self.write_archive(path, Some(link), &blocks, &pinned)
     }
 
-    /// Find any data we should have but are missing, given our current chain.
+    /// Take in an archive written by `export_archive`. Its chain is adopted whole if ours is
+    /// empty, and otherwise its data blocks are merged, see `DataChain::merge_chain`. Each chunk
+    /// is checked against the manifest and against the hash its valid block records before it is
+    /// stored, chunks no valid block of ours refers to are skipped. Fails with `Error::Corrupt`
+    /// at the first damage, keeping the chunks stored before it, with `Error::Validation` for an
+    /// archive hashed with another `HashAlgorithm` and with `Error::MissingLink` for an archive
+    /// from `export_archive_since`. Returns the number of chunks stored.
+    pub fn import_archive(&mut self, path: &Path) -> Result<usize, Error> {
+        let (mut file, offset, base, chain, manifest) = open_archive(path)?;
+        if base.is_some() {
+            return Err(Error::MissingLink);
+        }
+        let algorithm = persistence::chain_hash_algorithm(&chain)?;
+        let (blocks, pinned) = persistence::decode_chain(&chain)?;
+        {
+            let mut dc = self.dc.lock().unwrap();
+            if dc.is_empty() {
+                dc.set_hash_algorithm(algorithm)?;
+                for (index, block) in blocks.into_iter().enumerate() {
+                    dc.insert(index, block);
+                }
+                for identifier in &pinned {
+                    let _ = dc.pin(identifier);
+                }
+                dc.mark_blocks_valid();
+            } else {
+                let mut archived = DataChain::from_blocks(vec![], dc.group_size());
+                archived.set_hash_algorithm(algorithm)?;
+                for (index, block) in blocks.into_iter().enumerate() {
+                    archived.insert(index, block);
+                }
+                dc.merge_chain(&mut archived)?;
+            }
+        }
+        let mut stored = 0;
+        let result = self.import_chunks(&mut file, offset, &manifest, &mut stored);
+        self.report_used_space();
+        result.map(|()| stored)
+    }
+
+    /// Bring our chain and data up to date from an archive written by `export_archive_since`.
+    /// Its blocks are appended as `DataChain::apply_delta` does, so nothing is added unless every
+    /// new block holds a quorum from the link governing it, then its chunks are checked and
+    /// stored as by `import_archive`. Fails with `Error::MissingLink` if we do not hold the link
+    /// the archive starts after, and with `Error::Validation` for a full archive or blocks
+    /// failing to verify. Returns the number of chunks stored.
+    pub fn apply_incremental(&mut self, path: &Path) -> Result<usize, Error> {
+        let (mut file, offset, base, chain, manifest) = open_archive(path)?;
+        let base = base.ok_or(Error::Validation)?;
+        let (blocks, pinned) = persistence::decode_chain(&chain)?;
+        {
+            let mut dc = self.dc.lock().unwrap();
+            if !dc.find(&base).is_some_and(|x| x.valid) {
+                return Err(Error::MissingLink);
+            }
+            if persistence::chain_hash_algorithm(&chain)? != dc.hash_algorithm() {
+                return Err(Error::Validation);
+            }
+            match dc.apply_delta(blocks) {
+                Ok(_) => (),
+                Err(ValidationError::MissingLink) => return Err(Error::MissingLink),
+                Err(_) => return Err(Error::Validation),
+            }
+            for identifier in &pinned {
+                let _ = dc.pin(identifier);
+            }
+        }
+        let mut stored = 0;
+        let result = self.import_chunks(&mut file, offset, &manifest, &mut stored);
+        self.report_used_space();
+        result.map(|()| stored)
+    }
+
+    /// Confirm and merge a DataChain transmitted to us.
+    /// This will trim (purge invalid) exsiting entries then merge valid entries.
+    /// May be used to create a new chain from given chains on node startup.
+    /// Fails as `DataChain::merge_chain` does.
+    pub fn merge_chain(&mut self, chain: &mut DataChain) -> Result<(), Error> {
+        self.dc.lock().unwrap().merge_chain(chain)
+    }
+
+    /// How much `node` can be trusted from its part in the latest links and data blocks, see
+    /// `DataChain::trust_report`.
+    pub fn trust_report(&self, node: &PublicKey, config: &TrustConfig) -> TrustReport {
+        self.dc.lock().unwrap().trust_report(node, config)
+    }
+
+    /// Find any data we should have but are missing, given our current chain, other than data
+    /// since deleted.
     /// The output of this gives an identifier we should send to other nodes to get the data.
     /// This is not a `DataIdentifier` as expected as this contains the hash we know the data must
     /// match.
     pub fn required_data(&self) -> Vec<BlockIdentifier> {
         let keys = self.cs.keys();
-        self.dc
-            .lock()
-            .unwrap()
-            .chain()
+        let dc = self.dc.lock().unwrap();
+        let required = dc.chain()
             .iter()
             .filter(|x| !x.identifier().is_link() && x.valid)
-            .filter(|x| if let Some(name) = x.identifier().name() {
-                !keys.contains(name)
-            } else {
-                false
+            .filter(|x| {
+                x.identifier()
+                    .names()
+                    .iter()
+                    .any(|name| !keys.contains(name) && !dc.is_deleted(name))
             })
             .map(|x| x.identifier().clone())
-            .collect_vec()
+            .collect_vec();
+        required
+    }
+
+    /// Challenge a holder to prove it stores the data of the valid block `identifier`, with a
+    /// `nonce` never used before. Fails unless the block is valid in our chain.
+    pub fn create_challenge(&self,
+                            identifier: &BlockIdentifier,
+                            nonce: [u8; 32])
+                            -> Result<Challenge, Error> {
+        let valid = self.dc
+            .lock()
+            .unwrap()
+            .chain()
+            .iter()
+            .any(|x| x.valid && x.identifier() == identifier);
+        if !valid {
+            return Err(Error::Validation);
+        }
+        Ok(Challenge {
+            identifier: identifier.clone(),
+            nonce: nonce,
+        })
+    }
+
+    /// Answer `challenge` from the data we hold, failing with `Error::NotFound` if we have none.
+    pub fn respond(&self, challenge: &Challenge) -> Result<ChallengeResponse, Error> {
+        let name = stored_hash(&challenge.identifier).ok_or(Error::BadIdentifier)?;
+        let content = serialisation::serialise(&self.cs.get(name)?)?;
+        Ok(ChallengeResponse {
+            identifier: challenge.identifier.clone(),
+            nonce: challenge.nonce,
+            proof: storage_proof(&content, &challenge.nonce),
+        })
+    }
+
+    /// Check a holder's `response` to `challenge` against our own copy of the data, itself checked
+    /// against the hash recorded in the chain. Returns whether the holder proved storage, or an
+    /// error if we cannot tell because our copy is missing or corrupt.
+    pub fn verify_response(&self,
+                           challenge: &Challenge,
+                           response: &ChallengeResponse)
+                           -> Result<bool, Error> {
+        if response.identifier != challenge.identifier || response.nonce != challenge.nonce {
+            return Ok(false);
+        }
+        let name = stored_hash(&challenge.identifier).ok_or(Error::BadIdentifier)?;
+        let data = self.cs.get(name)?;
+        let content = serialisation::serialise(&data)?;
+        let intact = match data {
+            Data::Immutable(ref im) => hash(im.value()) == *name,
+            _ => self.hash(&content) == *name,
+        };
+        if !intact {
+            return Err(Error::Validation);
+        }
+        Ok(storage_proof(&content, &challenge.nonce) == response.proof)
+    }
+
+    /// Plan fetches of our `required_data` from the peers known to `planner`, returning the
+    /// requests to send.
+    pub fn plan_replication(&self,
+                            planner: &mut ReplicationPlanner)
+                            -> Vec<(PublicKey, BlockIdentifier)> {
+        planner.plan(&self.required_data())
+    }
+
+    /// Store `data` received from a peer, if it is the data of a valid block in our chain, and mark
+    /// its fetch complete in `planner`. Fails with `Error::Validation` for data no valid block
+    /// vouches for.
+    pub fn store_fetched(&mut self,
+                         planner: &mut ReplicationPlanner,
+                         data: &Data)
+                         -> Result<BlockIdentifier, Error> {
+        let serialised = serialisation::serialise(&data)?;
+        let key = match *data {
+            Data::Immutable(ref im) => *im.name(),
+            _ => self.hash(&serialised),
+        };
+        let found = self.dc
+            .lock()
+            .unwrap()
+            .chain()
+            .iter()
+            .find(|x| x.valid && stored_hash(x.identifier()) == Some(&key))
+            .map(|x| x.identifier().clone());
+        let identifier = match found {
+            Some(identifier) => identifier,
+            None => return Err(Error::Validation),
+        };
+        self.make_room(serialised.len() as u64);
+        self.store_chunk(&key, data)?;
+        self.report_used_space();
+        let _ = planner.complete(&identifier);
+        Ok(identifier)
     }
 
     /// Max space avilable for disk storage (as set by user)
@@ -287,10 +936,357 @@ impl SecuredData {
     pub fn used_space(&self) -> u64 {
         self.cs.used_space()
     }
+
+    // Evict data, per the eviction policy, until a write of `size` bytes fits below the low
+    // water mark.
+    fn make_room(&mut self, size: u64) {
+        let policy = match self.eviction {
+            Some(policy) => policy,
+            None => return,
+        };
+        let max = self.cs.max_space();
+        let mark = |percent: u8| max.saturating_mul(percent as u64) / 100;
+        if self.cs.used_space() + size <= mark(policy.high_water) {
+            return;
+        }
+        let mut changed = false;
+        for name in self.eviction_candidates() {
+            if self.cs.used_space() + size <= mark(policy.low_water) {
+                break;
+            }
+            let ledger = match self.cs.get(&name) {
+                Ok(Data::Structured(ref sd)) => sd.ledger(),
+                _ => false,
+            };
+            if !ledger && self.remove_chunk(&name).is_ok() {
+                chain_event!("data evicted", name = name);
+                self.evicted.push(name);
+                changed = true;
+            }
+        }
+        if changed {
+            self.report_used_space();
+        }
+    }
+
+    // Stored data no valid block needs, in eviction order: data with no block by name, then
+    // data of invalid blocks by chain position.
+    fn eviction_candidates(&self) -> Vec<[u8; 32]> {
+        let dc = self.dc.lock().unwrap();
+        let mut candidates = self.cs
+            .keys()
+            .into_iter()
+            .filter_map(|name| {
+                let holds = |x: &Block| {
+                    x.identifier().names().contains(&&name) ||
+                    stored_hash(x.identifier()) == Some(&name)
+                };
+                if dc.chain().iter().any(|x| x.valid && holds(x)) {
+                    None
+                } else {
+                    Some((dc.chain().iter().position(holds), name))
+                }
+            })
+            .collect_vec();
+        candidates.sort();
+        candidates.into_iter().map(|(_, name)| name).collect()
+    }
+
+    // Write an archive of `blocks`, which follow the link `base` if any, and of the data of the
+    // valid ones we hold, see `export_archive`.
+    fn write_archive(&self,
+                     path: &Path,
+                     base: Option<&BlockIdentifier>,
+                     blocks: &[Block],
+                     pinned: &[BlockIdentifier])
+                     -> Result<usize, Error> {
+        let algorithm = self.dc.lock().unwrap().hash_algorithm();
+        let chain = persistence::encode_chain(blocks, pinned, algorithm)?;
+        let mut names = blocks.iter()
+            .filter(|x| x.valid)
+            .flat_map(|x| chunk_names(x.identifier()))
+            .cloned()
+            .collect_vec();
+        names.sort();
+        names.dedup();
+        let mut manifest = vec![];
+        for name in names {
+            match self.cs.get(&name) {
+                Ok(data) => {
+                    let bytes = serialisation::serialise(&data)?;
+                    manifest.push((name, bytes.len() as u64, hash(&bytes)));
+                }
+                Err(Error::NotFound) => (),
+                Err(error) => return Err(error),
+            }
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(ARCHIVE_MAGIC)?;
+        file.write_all(&serialisation::serialise(&ARCHIVE_VERSION)?)?;
+        write_frame(&mut file, &serialisation::serialise(&base)?)?;
+        write_frame(&mut file, &chain)?;
+        write_frame(&mut file, &serialisation::serialise(&manifest)?)?;
+        for (name, _, _) in &manifest {
+            write_frame(&mut file, &serialisation::serialise(&self.cs.get(name)?)?)?;
+        }
+        file.flush()?;
+        file.get_ref().sync_all()?;
+        Ok(manifest.len())
+    }
+
+    // Store the chunks listed in `manifest` read from `reader`, from `offset` into the archive,
+    // counting them in `stored`.
+    fn import_chunks<R: Read>(&mut self,
+                              reader: &mut R,
+                              mut offset: u64,
+                              manifest: &Manifest,
+                              stored: &mut usize)
+                              -> Result<(), Error> {
+        let held = self.held_chunks();
+        for &(ref name, len, ref expected) in manifest {
+            let start = offset;
+            let bytes = read_frame(reader, &mut offset)?;
+            if bytes.len() as u64 != len || hash(&bytes) != *expected {
+                return Err(Error::Corrupt { offset: start });
+            }
+            if let Some(&(ref identifier, true)) = held.get(name) {
+                let data = serialisation::deserialise::<Data>(&bytes)?;
+                if !self.intact(identifier, name, &data)? {
+                    return Err(Error::Corrupt { offset: start });
+                }
+                self.make_room(len);
+                self.store_chunk(name, &data)?;
+                *stored += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // Every chunk the chain refers to, with the block referring to it and whether any such block
+    // is valid, preferring a valid one.
+    fn held_chunks(&self) -> HashMap<[u8; 32], (BlockIdentifier, bool)> {
+        let mut held = HashMap::new();
+        for block in self.dc.lock().unwrap().chain() {
+            for name in chunk_names(block.identifier()) {
+                let entry = held.entry(*name).or_insert((block.identifier().clone(), false));
+                if block.valid {
+                    *entry = (block.identifier().clone(), true);
+                }
+            }
+        }
+        held
+    }
+
+    // Write `data` under `name`, keeping the usage report and quotas.
+    fn store_chunk(&mut self, name: &[u8; 32], data: &Data) -> Result<(), Error> {
+        let (kind, size) = (DataKind::of(data), rustc_serialize::encoded_size(data));
+        let replaced = match self.chunks.get(name) {
+            Some(&(old_kind, old_size)) if old_kind == kind => old_size,
+            _ => 0,
+        };
+        if let Some(&quota) = self.quotas.get(&kind) {
+            if self.usage.get(kind) - replaced + size > quota {
+                return Err(Error::StorageFull);
+            }
+        }
+        self.cs.put(name, data)?;
+        self.account(name, Some((kind, size)));
+        Ok(())
+    }
+
+    // Remove the data under `name`, keeping the usage report.
+    fn remove_chunk(&mut self, name: &[u8; 32]) -> Result<(), Error> {
+        self.cs.delete(name)?;
+        self.account(name, None);
+        Ok(())
+    }
+
+    // Record that `name` now holds `chunk`, a kind and size, or nothing.
+    fn account(&mut self, name: &[u8; 32], chunk: Option<(DataKind, u64)>) {
+        let old = match chunk {
+            Some(chunk) => self.chunks.insert(*name, chunk),
+            None => self.chunks.remove(name),
+        };
+        if let Some((kind, size)) = old {
+            *self.usage.get_mut(kind) -= size;
+        }
+        if let Some((kind, size)) = chunk {
+            *self.usage.get_mut(kind) += size;
+        }
+    }
+
+    // Whether `data` read back from under `name` for `identifier` still matches the hash the block
+    // records. Batch items other than immutable data have no hash of their own to check.
+    fn intact(&self,
+              identifier: &BlockIdentifier,
+              name: &[u8; 32],
+              data: &Data)
+              -> Result<bool, Error> {
+        Ok(match (identifier.without_expiry(), data) {
+            (_, Data::Immutable(im)) => hash(im.value()) == *name,
+            (&BlockIdentifier::StructuredData(ref expected, _), _) |
+            (&BlockIdentifier::AppendableData(ref expected, _), _) => {
+                self.hash(&serialisation::serialise(data)?) == *expected
+            }
+            _ => true,
+        })
+    }
+
+    // Hash content with the chain's `HashAlgorithm`. Immutable data names stay SHA3-256.
+    fn hash(&self, bytes: &[u8]) -> [u8; 32] {
+        self.dc.lock().unwrap().hasher().hash(bytes)
+    }
+
+    fn report_used_space(&self) {
+        if let Some(ref sink) = self.metrics {
+            sink.set(metrics::CHUNK_STORE_USED, self.cs.used_space());
+        }
+    }
+}
+
+impl<S: ChunkStorage<[u8; 32], Data> + StreamStorage> SecuredData<S> {
+    /// Add immutable data read from `reader`, hashing and storing it in pieces so it need not fit
+    /// in memory. As with `put_data`, returns the identifier to vote for.
+    pub fn put_data_stream<R: Read>(&mut self, mut reader: R) -> Result<BlockIdentifier, Error> {
+        let used = self.cs.used_space();
+        let name = self.cs.put_immutable(&mut reader)?;
+        // The size is only known once written, and counted as the growth of the store.
+        let size = self.cs.used_space().saturating_sub(used) +
+                   self.chunks.get(&name).map_or(0, |x| x.1);
+        self.account(&name, Some((DataKind::Immutable, size)));
+        if self.quotas.get(&DataKind::Immutable).is_some_and(|&x| self.usage.immutable > x) {
+            self.remove_chunk(&name)?;
+            self.report_used_space();
+            return Err(Error::StorageFull);
+        }
+        self.report_used_space();
+        Ok(BlockIdentifier::ImmutableData(name))
+    }
+
+    /// Stream back immutable data we have on disk, that is also marked valid in the data chain.
+    /// The reader fails with `io::ErrorKind::InvalidData` at the end if the content read does not
+    /// match its name.
+    pub fn get_data_stream(&self, data_id: &DataIdentifier) -> Result<Box<dyn Read>, Error> {
+        match *data_id {
+            DataIdentifier::Immutable(..) => (),
+            _ => return Err(Error::BadIdentifier),
+        }
+        if let Some(block_id) = self.dc
+            .lock()
+            .unwrap()
+            .find_name(data_id.name()) {
+            if block_id.valid {
+                if let Some(name) = held_name(block_id.identifier(), data_id) {
+                    return self.cs.get_immutable(name);
+                }
+            } else {
+                return Err(Error::Validation);
+            }
+        }
+        Err(Error::NotFound)
+    }
+}
+
+/// Sign a group deletion of `data_id` with a member's `secret_key`, see
+/// `SecuredData::delete_data`.
+pub fn delete_signature(data_id: &DataIdentifier,
+                        secret_key: &SecretKey)
+                        -> Result<Signature, Error> {
+    Ok(Ed25519::sign(&deletion(data_id), secret_key))
+}
+
+// What group members sign to delete `data_id`: the tombstone they will vote for.
+fn deletion(data_id: &DataIdentifier) -> Vec<u8> {
+    BlockIdentifier::Deleted(*data_id).to_signable_bytes()
+}
+
+// Names the data of `identifier` is stored under: its hash, or the names of a batch's items.
+fn chunk_names(identifier: &BlockIdentifier) -> Vec<&[u8; 32]> {
+    match *identifier {
+        BlockIdentifier::Batch(..) => identifier.names(),
+        BlockIdentifier::Expiring(ref identifier, _) => chunk_names(identifier),
+        _ => stored_hash(identifier).into_iter().collect(),
+    }
+}
+
+// Open an archive written by `SecuredData::export_archive`, reading up to its chunks.
+fn open_archive(path: &Path) -> Result<OpenArchive, Error> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut header = [0; 8];
+    file.read_exact(&mut header).map_err(|_| Error::Corrupt { offset: 0 })?;
+    if !header.starts_with(ARCHIVE_MAGIC) {
+        return Err(Error::Corrupt { offset: 0 });
+    }
+    let mut offset = header.len() as u64;
+    let base = match serialisation::deserialise::<u32>(&header[ARCHIVE_MAGIC.len()..])? {
+        ARCHIVE_VERSION => serialisation::deserialise(&read_frame(&mut file, &mut offset)?)?,
+        ARCHIVE_VERSION_FULL => None,
+        version => return Err(Error::Version(version)),
+    };
+    let chain = read_frame(&mut file, &mut offset)?;
+    let manifest = serialisation::deserialise(&read_frame(&mut file, &mut offset)?)?;
+    Ok((file, offset, base, chain, manifest))
+}
+
+// Write `payload` framed by its length and hash, as chain files frame their blocks.
+fn write_frame<W: Write>(writer: &mut W, payload: &[u8]) -> Result<(), Error> {
+    writer.write_all(&(payload.len() as u64).to_be_bytes())?;
+    writer.write_all(&hash(payload))?;
+    Ok(writer.write_all(payload)?)
+}
+
+// Read the payload of a frame written by `write_frame`, which starts at `offset` into the file and
+// is advanced past it. Fails with `Error::Corrupt` if the frame is incomplete or fails its hash.
+fn read_frame<R: Read>(reader: &mut R, offset: &mut u64) -> Result<Vec<u8>, Error> {
+    let corrupt = Error::Corrupt { offset: *offset };
+    let mut header = [0; 40];
+    if reader.read_exact(&mut header).is_err() {
+        return Err(corrupt);
+    }
+    let mut len = [0; 8];
+    len.copy_from_slice(&header[..8]);
+    let len = u64::from_be_bytes(len);
+    let mut payload = vec![];
+    let _ = reader.take(len).read_to_end(&mut payload)?;
+    if payload.len() as u64 != len || hash(&payload)[..] != header[8..] {
+        return Err(corrupt);
+    }
+    *offset += header.len() as u64 + len;
+    Ok(payload)
+}
+
+// Hash the data of `identifier` is stored under.
+fn stored_hash(identifier: &BlockIdentifier) -> Option<&[u8; 32]> {
+    match *identifier {
+        BlockIdentifier::ImmutableData(ref hash) |
+        BlockIdentifier::StructuredData(ref hash, _) |
+        BlockIdentifier::AppendableData(ref hash, _) |
+        BlockIdentifier::MutableDataEntry(ref hash, _) => Some(hash),
+        BlockIdentifier::Batch(..) |
+        BlockIdentifier::Deleted(_) |
+        BlockIdentifier::Link(_) |
+        BlockIdentifier::Custom { .. } |
+        BlockIdentifier::Transfer { .. } => None,
+        BlockIdentifier::Expiring(ref identifier, _) => stored_hash(identifier),
+    }
+}
+
+// Key the data `data_id` is kept under when `identifier` is the block found for its name: the
+// block's `stored_hash`, or the item's name for a batch.
+fn held_name<'a>(identifier: &'a BlockIdentifier,
+                 data_id: &'a DataIdentifier)
+                 -> Option<&'a [u8; 32]> {
+    match *identifier {
+        BlockIdentifier::Batch(..) => Some(data_id.name()),
+        BlockIdentifier::Expiring(ref identifier, _) => held_name(identifier, data_id),
+        _ => stored_hash(identifier),
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use chunk_store::{Fault, MockChunkStore};
+    use data::StructuredData;
     use super::*;
     use tempdir::TempDir;
 
@@ -308,4 +1304,603 @@ mod tests {
         assert!(!storedir.exists());
     }
 
+    #[test]
+    fn store_faults_propagate() {
+        let data = Data::Structured(unwrap!(StructuredData::new(0,
+                                                                [1; 32],
+                                                                0,
+                                                                b"data".to_vec(),
+                                                                vec![],
+                                                                vec![],
+                                                                None,
+                                                                false)));
+
+        let mut store = MockChunkStore::new(1024);
+        store.inject(Fault::DiskFull);
+        let mut secured = SecuredData::with_store(store, DataChain::default());
+        match secured.put_data(&data) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(secured.used_space(), 0);
+
+        // Operations 0 and 1 are the lookup and write by `put_data`, 2 is the purge.
+        let mut store = MockChunkStore::new(1024);
+        store.inject(Fault::IoErrorOnOp(2));
+        let mut secured = SecuredData::with_store(store, DataChain::default());
+        let _ = unwrap!(secured.put_data(&data));
+        match secured.purge_disk() {
+            Err(Error::Io(_)) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(secured.purge_disk().is_ok());
+        assert_eq!(secured.used_space(), 0);
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_content_hashes() {
+        use sha3::{HashAlgorithm, blake3_hash};
+        let sd = unwrap!(StructuredData::new(0,
+                                             [1; 32],
+                                             0,
+                                             b"data".to_vec(),
+                                             vec![],
+                                             vec![],
+                                             None,
+                                             false));
+        let data = Data::Structured(sd.clone());
+        let mut chain = DataChain::default();
+        unwrap!(chain.set_hash_algorithm(HashAlgorithm::Blake3));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1024), chain);
+        let hash = blake3_hash(&unwrap!(serialisation::serialise(&data)));
+        assert_eq!(unwrap!(secured.put_data(&data)),
+                   BlockIdentifier::StructuredData(hash, sd.identifier()));
+    }
+
+    #[test]
+    fn streaming() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let content = vec![7u8; 100_000];
+        let name = hash(&content);
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let identifier = unwrap!(secured.put_data_stream(&content[..]));
+        assert_eq!(identifier, BlockIdentifier::ImmutableData(name));
+        match secured.get_data_stream(&DataIdentifier::Immutable(name)) {
+            Err(Error::NotFound) => (),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        let keys = sign::gen_keypair();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, identifier)));
+        let mut read = vec![];
+        let _ = unwrap!(unwrap!(secured.get_data_stream(&DataIdentifier::Immutable(name)))
+            .read_to_end(&mut read));
+        assert_eq!(read, content);
+        assert_eq!(unwrap!(secured.get(&DataIdentifier::Immutable(name))),
+                   Data::Immutable(::data::ImmutableData::new(content)));
+    }
+
+    #[test]
+    fn appendable() {
+        use chain::LinkDescriptor;
+        use data::{AppendableData, Filter};
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let owner = sign::gen_keypair();
+        let appender = sign::gen_keypair();
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(owner.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&owner.0, &owner.1, link)));
+
+        let ad = unwrap!(AppendableData::new([3; 32],
+                                             0,
+                                             vec![owner.0],
+                                             vec![],
+                                             Filter::BlackList(Default::default()),
+                                             10,
+                                             Some(&owner.1)));
+        let appended = unwrap!(AppendedData::new(DataIdentifier::Immutable([4; 32]),
+                                                 appender.0,
+                                                 &appender.1));
+        match secured.append_data(ad.name(), appended.clone()) {
+            Err(Error::NotFound) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let identifier = unwrap!(secured.put_data(&Data::Appendable(ad.clone())));
+        let _ = secured.add_vote(unwrap!(Vote::new(&owner.0, &owner.1, identifier)));
+        let identifier = unwrap!(secured.append_data(ad.name(), appended.clone()));
+        let _ = secured.add_vote(unwrap!(Vote::new(&owner.0, &owner.1, identifier)));
+
+        // Owner updates are checked against the appended state now in the chain.
+        let mut held = ad.clone();
+        unwrap!(held.append(appended));
+        let mut next = held.next_version(Filter::WhiteList(Default::default()),
+                                         &Default::default());
+        assert!(secured.post_data(&Data::Appendable(next.clone())).is_err());
+        let _ = unwrap!(next.add_signature(&owner.1));
+        assert!(secured.post_data(&Data::Appendable(next)).is_ok());
+    }
+
+    #[test]
+    fn eviction_under_pressure() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let sd = |name, ledger| {
+            Data::Structured(unwrap!(StructuredData::new(0,
+                                                         name,
+                                                         0,
+                                                         vec![5; 200],
+                                                         vec![],
+                                                         vec![],
+                                                         None,
+                                                         ledger)))
+        };
+        let size = unwrap!(serialisation::serialise(&sd([1; 32], false))).len() as u64;
+        let mut secured = SecuredData::with_store(MockChunkStore::new(10 * size),
+                                                  DataChain::from_blocks(vec![], 999));
+        secured.set_eviction_policy(Some(EvictionPolicy {
+            high_water: 35,
+            low_water: 25,
+        }));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let identifier = unwrap!(secured.put_data(&sd([1; 32], false)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, identifier)));
+        let _ = unwrap!(secured.put_data(&sd([2; 32], true)));
+        let unvoted = unwrap!(secured.put_data(&sd([3; 32], false)));
+        assert!(secured.evicted().is_empty());
+
+        // The fourth write passes the high water mark; only the unvoted, non-ledger data may go.
+        let _ = unwrap!(secured.put_data(&sd([4; 32], false)));
+        match unvoted {
+            BlockIdentifier::StructuredData(ref hash, _) => {
+                assert_eq!(secured.evicted(), &[*hash])
+            }
+            _ => panic!("unexpected {:?}", unvoted),
+        }
+        assert_eq!(secured.used_space(), 3 * size);
+    }
+
+    #[test]
+    fn storage_challenges() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let identifier = unwrap!(secured.put_data_stream(&b"stored"[..]));
+        assert!(secured.create_challenge(&identifier, [1; 32]).is_err());
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, identifier.clone())));
+
+        let challenge = unwrap!(secured.create_challenge(&identifier, [1; 32]));
+        let response = unwrap!(secured.respond(&challenge));
+        assert!(unwrap!(secured.verify_response(&challenge, &response)));
+        let replayed = unwrap!(secured.create_challenge(&identifier, [2; 32]));
+        assert!(!unwrap!(secured.verify_response(&replayed, &response)));
+        let mut forged = response;
+        forged.proof = [0; 32];
+        assert!(!unwrap!(secured.verify_response(&challenge, &forged)));
+
+        let empty = SecuredData::with_store(MockChunkStore::new(1 << 20), DataChain::default());
+        match empty.respond(&challenge) {
+            Err(Error::NotFound) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn ledger_versions() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let version = |version, ledger| {
+            Data::Structured(unwrap!(StructuredData::new(0,
+                                                         [6; 32],
+                                                         version,
+                                                         vec![version as u8],
+                                                         vec![],
+                                                         vec![],
+                                                         None,
+                                                         ledger)))
+        };
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let first = unwrap!(secured.put_data(&version(0, true)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, first.clone())));
+        let second = unwrap!(secured.put_data(&version(1, true)));
+        // A vote from outside the group leaves the second version invalid.
+        let stranger = sign::gen_keypair();
+        let _ = secured.add_vote(unwrap!(Vote::new(&stranger.0, &stranger.1, second.clone())));
+        assert!(secured.ledger_history(&[7; 32]).is_empty());
+
+        let history = secured.ledger_history(&[6; 32]);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0], (first, version(0, true), true));
+        assert_eq!(history[1], (second, version(1, true), false));
+    }
+
+    #[test]
+    fn reads_verified() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let version = |value: &[u8]| {
+            Data::Structured(unwrap!(StructuredData::new(0,
+                                                         [6; 32],
+                                                         0,
+                                                         value.to_vec(),
+                                                         vec![],
+                                                         vec![],
+                                                         None,
+                                                         false)))
+        };
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let id = unwrap!(secured.put_data(&version(b"stored")));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, id.clone())));
+        let data_id = version(b"stored").identifier();
+        assert_eq!(unwrap!(secured.get(&data_id)), version(b"stored"));
+
+        // Swap the stored value for one the chain never agreed to.
+        unwrap!(secured.cs.put(unwrap!(stored_hash(&id)), &version(b"swapped")));
+        match secured.get(&data_id) {
+            Err(Error::Corrupt { .. }) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        secured.set_verify_reads(false);
+        assert_eq!(unwrap!(secured.get(&data_id)), version(b"swapped"));
+    }
+
+    #[test]
+    fn expire() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let immutable = |value: &[u8]| Data::Immutable(ImmutableData::new(value.to_vec()));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let (soon, later) = (1 << 40, 1 << 41);
+        let short = unwrap!(secured.put_data_expiring(&immutable(b"short"), soon));
+        let long = unwrap!(secured.put_data_expiring(&immutable(b"long"), later));
+        let shared = unwrap!(secured.put_data_expiring(&immutable(b"shared"), soon));
+        let kept = unwrap!(secured.put_data(&immutable(b"shared")));
+        for id in vec![short.clone(), long.clone(), shared.clone(), kept] {
+            assert!(secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, id))).is_some());
+        }
+        assert_eq!(short.expires_at(), Some(soon));
+        assert_eq!(unwrap!(secured.get(&immutable(b"short").identifier())),
+                   immutable(b"short"));
+
+        assert_eq!(unwrap!(secured.expire(soon - 1)), vec![]);
+        assert_eq!(unwrap!(secured.expire(soon)), vec![short, shared]);
+        assert!(!secured.has_data(&immutable(b"short").identifier()));
+        assert!(!secured.cs.has(immutable(b"short").name()));
+        assert_eq!(unwrap!(secured.get(&immutable(b"shared").identifier())),
+                   immutable(b"shared"));
+        assert!(secured.has_data(&immutable(b"long").identifier()));
+        assert_eq!(unwrap!(secured.expire(later)), vec![long]);
+        assert!(!secured.cs.has(immutable(b"long").name()));
+    }
+
+    #[test]
+    fn scrub_in_slices() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let immutable = |value: &[u8]| Data::Immutable(ImmutableData::new(value.to_vec()));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let good = unwrap!(secured.put_data(&immutable(b"good")));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, good.clone())));
+        let rotten = unwrap!(secured.put_data(&immutable(b"rotten")));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, rotten.clone())));
+        let orphan = *immutable(b"orphan").name();
+        unwrap!(secured.cs.put(&orphan, &immutable(b"orphan")));
+        // Bit rot: the chunk for `rotten` now holds other content.
+        unwrap!(secured.cs.put(unwrap!(rotten.name()), &immutable(b"decayed")));
+
+        let mut cursor = ScrubCursor::default();
+        let mut reports = vec![];
+        loop {
+            let report = unwrap!(secured.scrub_partial(&mut cursor, 2, false));
+            let finished = report.finished;
+            reports.push(report);
+            if finished {
+                break;
+            }
+        }
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports.iter().map(|x| x.checked).sum::<usize>(), 3);
+        assert_eq!(reports.iter().flat_map(|x| x.corrupt.clone()).collect_vec(),
+                   vec![*unwrap!(rotten.name())]);
+        assert_eq!(reports.iter().flat_map(|x| x.orphans.clone()).collect_vec(),
+                   vec![orphan]);
+        assert!(secured.cs.has(&orphan));
+        assert!(!secured.cs.has(unwrap!(rotten.name())));
+
+        let report = unwrap!(secured.scrub_partial(&mut cursor, 10, true));
+        assert!(report.finished);
+        assert_eq!(report.checked, 2);
+        assert!(report.corrupt.is_empty());
+        assert!(!secured.cs.has(&orphan));
+        assert!(secured.cs.has(unwrap!(good.name())));
+    }
+
+    #[test]
+    fn scrub_structured_data() {
+        use chain::LinkDescriptor;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let structured = |name: u8| {
+            Data::Structured(unwrap!(StructuredData::new(0,
+                                                         [name; 32],
+                                                         0,
+                                                         vec![name; 10],
+                                                         vec![],
+                                                         vec![],
+                                                         None,
+                                                         false)))
+        };
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let good = unwrap!(secured.put_data(&structured(1)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, good.clone())));
+        let rotten = unwrap!(secured.put_data(&structured(2)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, rotten.clone())));
+        let stored = *unwrap!(stored_hash(&rotten));
+        unwrap!(secured.cs.put(&stored, &structured(3)));
+
+        assert_eq!(unwrap!(secured.scrub()), vec![rotten]);
+        assert!(!secured.cs.has(&stored));
+        assert!(secured.cs.has(unwrap!(stored_hash(&good))));
+        assert_eq!(unwrap!(secured.scrub()), vec![]);
+    }
+
+    #[test]
+    fn usage_by_kind() {
+        use data::ImmutableData;
+        let structured = |name: u8, ledger: bool| {
+            Data::Structured(unwrap!(StructuredData::new(0,
+                                                         [name; 32],
+                                                         0,
+                                                         vec![name; 100],
+                                                         vec![],
+                                                         vec![],
+                                                         None,
+                                                         ledger)))
+        };
+        let immutable = Data::Immutable(ImmutableData::new(vec![1; 100]));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::default());
+        let _ = unwrap!(secured.put_data(&immutable));
+        let _ = unwrap!(secured.put_data(&structured(1, false)));
+        let ledger_size = unwrap!(serialisation::serialise(&structured(2, true))).len() as u64;
+        secured.set_quota(DataKind::Ledger, Some(ledger_size));
+        let _ = unwrap!(secured.put_data(&structured(2, true)));
+        match secured.put_data(&structured(3, true)) {
+            Err(Error::StorageFull) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        // Other kinds are not held back by the ledger quota.
+        let _ = unwrap!(secured.put_data(&structured(4, false)));
+
+        let usage = secured.usage_report();
+        assert_eq!(usage.ledger, ledger_size);
+        assert_eq!(usage.get(DataKind::Structured), 2 * ledger_size);
+        assert_eq!(usage.immutable,
+                   unwrap!(serialisation::serialise(&immutable)).len() as u64);
+        assert_eq!(usage.appendable, 0);
+        assert_eq!(usage.total(), secured.used_space());
+
+        unwrap!(secured.purge_disk());
+        assert_eq!(secured.usage_report(), UsageReport::default());
+        secured.set_quota(DataKind::Ledger, None);
+        let _ = unwrap!(secured.put_data(&structured(3, true)));
+        let reopened = SecuredData::with_store(secured.cs, DataChain::default());
+        assert_eq!(reopened.usage_report().ledger, ledger_size);
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let items = vec![Data::Immutable(ImmutableData::new(b"archived".to_vec())),
+                         Data::Structured(unwrap!(StructuredData::new(0,
+                                                                      [5; 32],
+                                                                      0,
+                                                                      b"archived".to_vec(),
+                                                                      vec![],
+                                                                      vec![],
+                                                                      None,
+                                                                      false)))];
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        for item in &items {
+            let id = unwrap!(secured.put_data(item));
+            let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, id)));
+        }
+        // Data nobody voted for stays behind.
+        let _ = unwrap!(secured.put_data(&Data::Immutable(ImmutableData::new(vec![0]))));
+
+        let dir = unwrap!(TempDir::new("test_archive"));
+        let path = dir.path().join("vault.archive");
+        assert_eq!(unwrap!(secured.export_archive(&path)), 2);
+        let mut restored = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                   DataChain::from_blocks(vec![], 999));
+        assert_eq!(unwrap!(restored.import_archive(&path)), 2);
+        for item in &items {
+            assert_eq!(unwrap!(restored.get(&item.identifier())), *item);
+        }
+        assert_eq!(*unwrap!(restored.chain().lock()).chain(),
+                   *unwrap!(secured.chain().lock()).chain());
+
+        // Damage to a chunk is caught.
+        let mut bytes = unwrap!(fs::read(&path));
+        if let Some(byte) = bytes.last_mut() {
+            *byte ^= 0xff;
+        }
+        unwrap!(fs::write(&path, &bytes));
+        let mut damaged = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        match damaged.import_archive(&path) {
+            Err(Error::Corrupt { .. }) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn incremental_archives() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let immutable = |value: &[u8]| Data::Immutable(ImmutableData::new(value.to_vec()));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link.clone())));
+        let old = unwrap!(secured.put_data(&immutable(b"old")));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, old)));
+        let dir = unwrap!(TempDir::new("test_archive"));
+        let full = dir.path().join("full.archive");
+        assert_eq!(unwrap!(secured.export_archive(&full)), 1);
+        let mut restored = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                   DataChain::from_blocks(vec![], 999));
+        assert_eq!(unwrap!(restored.import_archive(&full)), 1);
+
+        let new = unwrap!(secured.put_data(&immutable(b"new")));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, new)));
+        let since = dir.path().join("since.archive");
+        assert_eq!(unwrap!(secured.export_archive_since(&link, &since)), 2);
+        match restored.import_archive(&since) {
+            Err(Error::MissingLink) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        match restored.apply_incremental(&full) {
+            Err(Error::Validation) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let mut empty = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                DataChain::from_blocks(vec![], 999));
+        match empty.apply_incremental(&since) {
+            Err(Error::MissingLink) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(unwrap!(restored.apply_incremental(&since)), 2);
+        assert_eq!(unwrap!(restored.get(&immutable(b"new").identifier())), immutable(b"new"));
+        assert_eq!(*unwrap!(restored.chain().lock()).chain(),
+                   *unwrap!(secured.chain().lock()).chain());
+    }
+
+    #[test]
+    fn batches_commit_together() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let items = vec![Data::Immutable(ImmutableData::new(b"first".to_vec())),
+                         Data::Immutable(ImmutableData::new(b"second".to_vec()))];
+        let ids = items.iter().map(|x| x.identifier()).collect_vec();
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        assert!(secured.put_batch(&[]).is_err());
+        assert!(secured.put_batch(&[items[0].clone(), items[0].clone()]).is_err());
+
+        let batch = unwrap!(secured.put_batch(&items));
+        assert_eq!(batch.names(), vec![items[0].name(), items[1].name()]);
+        assert!(secured.get(&ids[0]).is_err());
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, batch)));
+        assert_eq!(unwrap!(secured.get(&ids[0])), items[0]);
+        assert_eq!(unwrap!(secured.get(&ids[1])), items[1]);
+        assert!(secured.required_data().is_empty());
+        unwrap!(secured.purge_disk());
+        assert!(secured.has_data(&ids[1]));
+
+        // The second put fails, so the first item is removed again.
+        let mut store = MockChunkStore::new(1 << 20);
+        store.inject(Fault::IoErrorOnOp(1));
+        let mut secured = SecuredData::with_store(store, DataChain::default());
+        match secured.put_batch(&items) {
+            Err(Error::Io(_)) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(secured.used_space(), 0);
+    }
+
+    #[test]
+    fn deletion_tombstones() {
+        use chain::LinkDescriptor;
+        use data::ImmutableData;
+        use rust_sodium::crypto::sign;
+        let _ = ::rust_sodium::init();
+        let keys = sign::gen_keypair();
+        let data = Data::Immutable(ImmutableData::new(b"deleted".to_vec()));
+        let mut secured = SecuredData::with_store(MockChunkStore::new(1 << 20),
+                                                  DataChain::from_blocks(vec![], 999));
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, link)));
+        let batch = unwrap!(secured.put_batch(&[data.clone()]));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, batch.clone())));
+        assert_eq!(unwrap!(secured.get(&data.identifier())), data);
+
+        match secured.delete_data(&data.identifier(), &[]) {
+            Err(Error::Unsigned) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        let forger = sign::gen_keypair();
+        let forged = unwrap!(delete_signature(&data.identifier(), &forger.1));
+        match secured.delete_data(&data.identifier(), &[forged]) {
+            Err(Error::Signature) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(secured.has_data(&data.identifier()));
+        let signature = unwrap!(delete_signature(&data.identifier(), &keys.1));
+        let tombstone = unwrap!(secured.delete_data(&data.identifier(), &[signature]));
+        assert_eq!(tombstone, BlockIdentifier::Deleted(data.identifier()));
+        let _ = secured.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, tombstone)));
+        match secured.get(&data.identifier()) {
+            Err(Error::Deleted) => (),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert!(secured.chain().lock().unwrap().contains(&batch));
+        assert!(secured.required_data().is_empty());
+    }
 }
@@ -26,10 +26,78 @@ pub fn hash(data: &[u8]) -> [u8; 32] {
     res
 }
 
+/// BLAKE3 counterpart of `hash`, several times faster on large data. Enabled by the `blake3`
+/// feature.
+#[cfg(feature = "blake3")]
+pub fn blake3_hash(data: &[u8]) -> [u8; 32] {
+    *::blake3::hash(data).as_bytes()
+}
+
+/// Hash algorithm a chain's content hashes are computed with, recorded in the chain file header
+/// so a reloaded chain re-validates its data with the same one, see `DataChain::hash_algorithm`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum HashAlgorithm {
+    /// SHA3-256, as computed by `hash`.
+    #[default]
+    Sha3_256,
+    /// BLAKE3, as computed by `blake3_hash`.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// The backend computing this algorithm.
+    pub fn hasher(self) -> &'static dyn ChainHasher {
+        match self {
+            HashAlgorithm::Sha3_256 => &Sha3,
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => &Blake3,
+        }
+    }
+}
+
+/// A backend computing the 32 byte hashes used to name and check data held against a chain.
+pub trait ChainHasher: Send + Sync {
+    /// The algorithm implemented, recorded with chains using this backend.
+    fn algorithm(&self) -> HashAlgorithm;
+
+    /// Hash `data`.
+    fn hash(&self, data: &[u8]) -> [u8; 32];
+}
+
+/// The default backend, SHA3-256 through `hash`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha3;
+
+impl ChainHasher for Sha3 {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Sha3_256
+    }
+
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        hash(data)
+    }
+}
+
+/// The BLAKE3 backend, through `blake3_hash`.
+#[cfg(feature = "blake3")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Blake3;
+
+#[cfg(feature = "blake3")]
+impl ChainHasher for Blake3 {
+    fn algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::Blake3
+    }
+
+    fn hash(&self, data: &[u8]) -> [u8; 32] {
+        blake3_hash(data)
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::hash;
+    use super::*;
 
     #[test]
     fn empty() {
@@ -68,4 +136,21 @@ mod tests {
         assert_eq!(expected, res);
     }
 
+    #[test]
+    fn default_hasher() {
+        let hasher = HashAlgorithm::default().hasher();
+        assert_eq!(hasher.algorithm(), HashAlgorithm::Sha3_256);
+        assert_eq!(hasher.hash(b"hello"), hash(b"hello"));
+    }
+
+    // https://github.com/BLAKE3-team/BLAKE3/blob/master/test_vectors/test_vectors.json
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn blake3_empty() {
+        let expected = vec![0xaf, 0x13, 0x49, 0xb9, 0xf5, 0xf9, 0xa1, 0xa6, 0xa0, 0x40, 0x4d,
+                            0xea, 0x36, 0xdc, 0xc9, 0x49, 0x9b, 0xcb, 0x25, 0xc9, 0xad, 0xc1,
+                            0x12, 0xb7, 0xcc, 0x9a, 0x93, 0xca, 0xe4, 0x1f, 0x32, 0x62];
+        assert_eq!(expected, blake3_hash(&[]));
+        assert_eq!(HashAlgorithm::Blake3.hasher().hash(&[]), blake3_hash(&[]));
+    }
 }
@@ -0,0 +1,99 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use rust_sodium::crypto::sign;
+
+/// Identifies a `SignatureScheme`, recorded in the chain file header so a reloaded chain is
+/// verified with the scheme its votes were signed with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub enum SchemeId {
+    /// Ed25519, see `Ed25519`.
+    #[default]
+    Ed25519,
+}
+
+/// Signing and verification of votes and proofs, so hybrid or post-quantum schemes can be added
+/// alongside the default `Ed25519`.
+pub trait SignatureScheme {
+    /// Key signatures are verified with.
+    type PublicKey;
+    /// Key signatures are made with.
+    type SecretKey;
+    /// Detached signature.
+    type Signature;
+
+    /// Identifier recorded with chains signed under this scheme.
+    const ID: SchemeId;
+    /// Serialised size of a `PublicKey`.
+    const PUBLIC_KEY_BYTES: usize;
+    /// Serialised size of a `SecretKey`.
+    const SECRET_KEY_BYTES: usize;
+    /// Serialised size of a `Signature`.
+    const SIGNATURE_BYTES: usize;
+
+    /// Generate a new random key pair.
+    fn gen_keypair() -> (Self::PublicKey, Self::SecretKey);
+
+    /// Sign `data` with `secret_key`.
+    fn sign(data: &[u8], secret_key: &Self::SecretKey) -> Self::Signature;
+
+    /// Whether `signature` is of `data` by the holder of `public_key`.
+    fn verify(signature: &Self::Signature, data: &[u8], public_key: &Self::PublicKey) -> bool;
+}
+
+/// The default scheme, Ed25519 through rust_sodium. `Vote`, `Proof` and `SignedBundle` use it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Ed25519;
+
+impl SignatureScheme for Ed25519 {
+    type PublicKey = sign::PublicKey;
+    type SecretKey = sign::SecretKey;
+    type Signature = sign::Signature;
+
+    const ID: SchemeId = SchemeId::Ed25519;
+    const PUBLIC_KEY_BYTES: usize = sign::PUBLICKEYBYTES;
+    const SECRET_KEY_BYTES: usize = sign::SECRETKEYBYTES;
+    const SIGNATURE_BYTES: usize = sign::SIGNATUREBYTES;
+
+    fn gen_keypair() -> (sign::PublicKey, sign::SecretKey) {
+        sign::gen_keypair()
+    }
+
+    fn sign(data: &[u8], secret_key: &sign::SecretKey) -> sign::Signature {
+        sign::sign_detached(data, secret_key)
+    }
+
+    fn verify(signature: &sign::Signature, data: &[u8], public_key: &sign::PublicKey) -> bool {
+        sign::verify_detached(signature, data, public_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ed25519() {
+        let _ = ::rust_sodium::init();
+        let (public_key, secret_key) = Ed25519::gen_keypair();
+        let signature = Ed25519::sign(b"data", &secret_key);
+        assert!(Ed25519::verify(&signature, b"data", &public_key));
+        assert!(!Ed25519::verify(&signature, b"other", &public_key));
+        assert_eq!(signature.0.len(), Ed25519::SIGNATURE_BYTES);
+        assert_eq!(public_key.0.len(), Ed25519::PUBLIC_KEY_BYTES);
+    }
+}
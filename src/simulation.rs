@@ -0,0 +1,325 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Prefix, Vote};
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey};
+use sha3::hash;
+use std::fmt::{self, Display, Formatter};
+
+/// One step of a churn scenario.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// A new node joins: current members vote for a `NodeGained` link.
+    GainNode,
+    /// A random member other than the last leaves: the others vote for a `NodeLost` link.
+    LoseNode,
+    /// The group splits: members vote for a `SplitFrom` link, then half of them leave.
+    Split,
+    /// Members vote for a new piece of data.
+    PutData,
+    /// Deliver up to this many votes still in flight, picked at random so they arrive reordered.
+    Deliver(usize),
+    /// Deliver every vote still in flight, in random order.
+    DeliverAll,
+    /// Deliver again a random vote that was already delivered.
+    DuplicateVote,
+}
+
+/// An invariant broken while running a scenario.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    /// Seed of the scenario, to reproduce it.
+    pub seed: u64,
+    /// Index of the step after which the invariant was found broken.
+    pub step: usize,
+    /// What was wrong.
+    pub description: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "seed {} step {}: {}",
+               self.seed,
+               self.step,
+               self.description)
+    }
+}
+
+/// Drives a `DataChain` through scripted or random churn, checking that:
+///
+/// * a link only becomes valid holding a quorum of the valid link before it, checked as each vote
+///   is delivered since later votes for that earlier link can change what its quorum is, and
+/// * the chain passes `DataChain::check_invariants` after every step.
+///
+/// Everything, including node keys, derives from the seed, so a failing scenario is reproduced by
+/// running it again with the same seed.
+pub struct Simulation {
+    seed: u64,
+    rng: SplitMix64,
+    chain: DataChain,
+    members: Vec<(PublicKey, SecretKey)>,
+    in_flight: Vec<Vote>,
+    delivered: Vec<Vote>,
+    steps: usize,
+    created: u64,
+    broken: Option<String>,
+}
+
+impl Simulation {
+    /// Start a scenario with a single node, whose own link starts the chain.
+    pub fn new(seed: u64, group_size: usize) -> Simulation {
+        let mut simulation = Simulation {
+            seed: seed,
+            rng: SplitMix64(seed),
+            chain: DataChain::from_blocks(vec![], group_size),
+            members: vec![],
+            in_flight: vec![],
+            delivered: vec![],
+            steps: 0,
+            created: 0,
+            broken: None,
+        };
+        let first = simulation.new_node();
+        let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(first.0));
+        if let Ok(vote) = Vote::new(&first.0, &first.1, link) {
+            let _ = simulation.chain.add_vote(vote);
+        }
+        simulation.members.push(first);
+        simulation
+    }
+
+    /// The chain built so far.
+    pub fn chain(&self) -> &DataChain {
+        &self.chain
+    }
+
+    /// Keys of the current members, including those whose link is not yet valid.
+    pub fn members(&self) -> Vec<PublicKey> {
+        self.members.iter().map(|x| x.0).collect()
+    }
+
+    /// Number of votes created but not yet delivered.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Run `steps` in order, stopping at the first broken invariant.
+    pub fn run(&mut self, steps: &[Step]) -> Result<(), Violation> {
+        for &step in steps {
+            self.step(step)?;
+        }
+        Ok(())
+    }
+
+    /// Run `count` steps picked at random, then deliver every vote still in flight.
+    pub fn run_random(&mut self, count: usize) -> Result<(), Violation> {
+        for _ in 0..count {
+            let step = match self.rng.below(10) {
+                0 => Step::GainNode,
+                1 => Step::LoseNode,
+                2 if self.rng.below(4) == 0 => Step::Split,
+                2..=4 => Step::PutData,
+                5 => Step::DuplicateVote,
+                6 => Step::DeliverAll,
+                _ => Step::Deliver(1 + self.rng.below(8)),
+            };
+            self.step(step)?;
+        }
+        self.step(Step::DeliverAll)
+    }
+
+    /// Run one step, then check the invariants.
+    pub fn step(&mut self, step: Step) -> Result<(), Violation> {
+        match step {
+            Step::GainNode => {
+                let node = self.new_node();
+                self.vote_all(BlockIdentifier::Link(LinkDescriptor::NodeGained(node.0)));
+                self.members.push(node);
+            }
+            Step::LoseNode => {
+                if self.members.len() > 1 {
+                    let index = self.rng.below(self.members.len());
+                    let lost = self.members.remove(index);
+                    self.vote_all(BlockIdentifier::Link(LinkDescriptor::NodeLost(lost.0)));
+                }
+            }
+            Step::Split => {
+                self.created += 1;
+                let prefix = Prefix::new(self.created);
+                self.vote_all(BlockIdentifier::Link(LinkDescriptor::SplitFrom(prefix)));
+                for _ in 0..self.members.len() / 2 {
+                    let index = self.rng.below(self.members.len());
+                    let _ = self.members.remove(index);
+                }
+            }
+            Step::PutData => {
+                self.created += 1;
+                let name = hash(format!("{} {}", self.seed, self.created).as_bytes());
+                self.vote_all(BlockIdentifier::ImmutableData(name));
+            }
+            Step::Deliver(count) => {
+                for _ in 0..count {
+                    self.deliver_one();
+                }
+            }
+            Step::DeliverAll => {
+                while !self.in_flight.is_empty() {
+                    self.deliver_one();
+                }
+            }
+            Step::DuplicateVote => {
+                if !self.delivered.is_empty() {
+                    let index = self.rng.below(self.delivered.len());
+                    let vote = self.delivered[index].clone();
+                    self.add_vote(vote);
+                }
+            }
+        }
+        self.steps += 1;
+        self.check().map_err(|description| {
+            Violation {
+                seed: self.seed,
+                step: self.steps - 1,
+                description: description,
+            }
+        })
+    }
+
+    fn new_node(&mut self) -> (PublicKey, SecretKey) {
+        let mut seed = [0; sign::SEEDBYTES];
+        for chunk in seed.chunks_mut(8) {
+            let bytes = self.rng.next().to_be_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        sign::keypair_from_seed(&sign::Seed(seed))
+    }
+
+    fn vote_all(&mut self, identifier: BlockIdentifier) {
+        for member in &self.members {
+            if let Ok(vote) = Vote::new(&member.0, &member.1, identifier.clone()) {
+                self.in_flight.push(vote);
+            }
+        }
+    }
+
+    fn deliver_one(&mut self) {
+        if self.in_flight.is_empty() {
+            return;
+        }
+        let index = self.rng.below(self.in_flight.len());
+        let vote = self.in_flight.swap_remove(index);
+        self.add_vote(vote.clone());
+        self.delivered.push(vote);
+    }
+
+    // Add `vote`, noting if it made a link valid without quorum.
+    fn add_vote(&mut self, vote: Vote) {
+        let identifier = vote.identifier().clone();
+        let was_valid = self.chain.find(&identifier).is_some_and(|x| x.valid);
+        let _ = self.chain.add_vote(vote);
+        if was_valid || !identifier.is_link() || self.broken.is_some() {
+            return;
+        }
+        let blocks = self.chain.chain();
+        if let Some(pos) = blocks.iter().position(|x| *x.identifier() == identifier && x.valid) {
+            let link = blocks[..pos].iter().rev().find(|x| x.valid && x.identifier().is_link());
+            if let Some(link) = link {
+                if !has_quorum(&blocks[pos], link, self.chain.group_size()) {
+                    self.broken = Some(format!("{:?} became valid without a quorum of {:?}",
+                                               identifier,
+                                               link.identifier()));
+                }
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if let Some(ref broken) = self.broken {
+            return Err(broken.clone());
+        }
+        self.chain.check_invariants().map_err(|violation| violation.to_string())
+    }
+}
+
+// The rule `DataChain` validates blocks by, restated so a change there is caught here.
+fn has_quorum(block: &Block, link: &Block, group_size: usize) -> bool {
+    let signed = link.proofs()
+        .iter()
+        .filter(|x| block.proofs().iter().any(|y| y.key() == x.key()))
+        .count();
+    signed * 2 >= link.proofs().len() || signed >= group_size
+}
+
+// Small seeded generator, so scenarios replay identically everywhere without a `rand` dependency.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripted() {
+        let _ = ::rust_sodium::init();
+        let mut simulation = Simulation::new(1, 8);
+        unwrap!(simulation.run(&[Step::GainNode,
+                                 Step::DeliverAll,
+                                 Step::GainNode,
+                                 Step::PutData,
+                                 Step::Deliver(2),
+                                 Step::DuplicateVote,
+                                 Step::DeliverAll,
+                                 Step::LoseNode,
+                                 Step::Split,
+                                 Step::DeliverAll]));
+        assert_eq!(simulation.in_flight(), 0);
+        assert!(simulation.chain().links_len() >= 3);
+
+        // The same seed replays the same scenario.
+        let mut again = Simulation::new(1, 8);
+        unwrap!(again.run_random(30));
+        let mut other = Simulation::new(1, 8);
+        unwrap!(other.run_random(30));
+        assert_eq!(again.members(), other.members());
+        assert_eq!(again.chain(), other.chain());
+    }
+
+    #[test]
+    fn random_churn() {
+        let _ = ::rust_sodium::init();
+        for seed in 0..20 {
+            let mut simulation = Simulation::new(seed, 8);
+            if let Err(violation) = simulation.run_random(60) {
+                panic!("{}", violation);
+            }
+        }
+    }
+}
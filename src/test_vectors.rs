@@ -0,0 +1,203 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Fixtures under `test_vectors/` recording keys, the canonical bytes of every kind of
+//! `BlockIdentifier`, signatures and an encoded chain as nodes wrote them. Changing how any of
+//! these are encoded, signed or hashed breaks chains already held across the network, so the
+//! fixtures are never regenerated to make these tests pass.
+//!
+//! Keys come from Ed25519 seeds of one byte repeated, and Ed25519 signatures are deterministic,
+//! so every value can be checked by recomputing it as well as by verifying it.
+
+use chain::{Block, BlockIdentifier, DataChain, LinkDescriptor, Membership, Prefix, Proof, Role,
+            Vote};
+use data::DataIdentifier;
+use maidsafe_utilities::serialisation;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use rustc_serialize::hex::FromHex;
+use sha3::hash;
+
+const KEYS: &str = include_str!("../test_vectors/keys.txt");
+const IDENTIFIERS: &str = include_str!("../test_vectors/identifiers.txt");
+const SIGNATURES: &str = include_str!("../test_vectors/signatures.txt");
+const CHAIN: &[u8] = include_bytes!("../test_vectors/chain.bin");
+const TIMESTAMP: u64 = 1_700_000_000;
+
+fn keys() -> Vec<(PublicKey, SecretKey)> {
+    (1..3).map(|seed| sign::keypair_from_seed(&sign::Seed([seed; 32]))).collect()
+}
+
+// The fixture lines, split on spaces, without comments.
+fn lines(fixture: &str) -> Vec<Vec<&str>> {
+    fixture.lines()
+        .filter(|x| !x.starts_with('#') && !x.is_empty())
+        .map(|x| x.split(' ').collect())
+        .collect()
+}
+
+fn bytes(hex: &str) -> Vec<u8> {
+    unwrap!(hex.from_hex())
+}
+
+fn identifier(name: &str) -> BlockIdentifier {
+    let keys = keys();
+    let (first, second) = (keys[0].0, keys[1].0);
+    let immutable = BlockIdentifier::ImmutableData(hash(b"immutable"));
+    match name {
+        "immutable" => immutable,
+        "structured" => {
+            BlockIdentifier::StructuredData(hash(b"structured"),
+                                            DataIdentifier::Structured(hash(b"structured name"),
+                                                                       5))
+        }
+        "appendable" => {
+            BlockIdentifier::AppendableData(hash(b"appendable"),
+                                            DataIdentifier::Appendable(hash(b"appendable name")))
+        }
+        "mutable_entry" => {
+            BlockIdentifier::MutableDataEntry(hash(b"entry"),
+                                              DataIdentifier::Mutable(hash(b"mutable name"), 7))
+        }
+        "batch" => {
+            BlockIdentifier::Batch(vec![DataIdentifier::Immutable(hash(b"a")),
+                                        DataIdentifier::Structured(hash(b"b"), 1)],
+                                   hash(b"batch"))
+        }
+        "deleted" => BlockIdentifier::Deleted(DataIdentifier::Immutable(hash(b"immutable"))),
+        "expiring" => unwrap!(BlockIdentifier::expiring(immutable, TIMESTAMP)),
+        "node_gained" => BlockIdentifier::Link(LinkDescriptor::NodeGained(first)),
+        "node_lost" => BlockIdentifier::Link(LinkDescriptor::NodeLost(second)),
+        "cancel_node_lost" => BlockIdentifier::Link(LinkDescriptor::CancelNodeLost(second)),
+        "split_from" => BlockIdentifier::Link(LinkDescriptor::SplitFrom(Prefix::new(0b10))),
+        "cancel_split_from" => {
+            BlockIdentifier::Link(LinkDescriptor::CancelSplitFrom(Prefix::new(0b10)))
+        }
+        "merge_to" => BlockIdentifier::Link(LinkDescriptor::MergeTo(Prefix::new(1))),
+        "checkpoint" => {
+            BlockIdentifier::Link(LinkDescriptor::CheckPoint {
+                prefix: Prefix::new(1),
+                members: vec![first, second],
+                state: hash(b"state"),
+            })
+        }
+        "key_changed" => {
+            BlockIdentifier::Link(LinkDescriptor::KeyChanged {
+                old: first,
+                new: second,
+            })
+        }
+        "membership" => {
+            BlockIdentifier::Link(LinkDescriptor::Membership(Membership {
+                members: vec![(first, Role::Elder), (second, Role::Adult)],
+            }))
+        }
+        _ => panic!("unknown identifier {}", name),
+    }
+}
+
+// A vote by the key from `seed` for the identifier `name`, with the given context.
+fn vote(seed: u8, name: &str, binding: Option<[u8; 32]>, timestamp: Option<u64>) -> Vote {
+    let (public_key, secret_key) = keys()[seed as usize - 1].clone();
+    let identifier = identifier(name);
+    unwrap!(match (binding, timestamp) {
+        (None, None) => Vote::new(&public_key, &secret_key, identifier),
+        (Some(binding), None) => Vote::new_bound(&public_key, &secret_key, identifier, binding),
+        (binding, Some(timestamp)) => {
+            Vote::new_timestamped(&public_key, &secret_key, identifier, binding, timestamp)
+        }
+    })
+}
+
+#[test]
+fn keys_from_seeds() {
+    let _ = ::rust_sodium::init();
+    let fixture = lines(KEYS);
+    assert_eq!(fixture.len(), 2);
+    for (line, keys) in fixture.iter().zip(keys()) {
+        assert_eq!(bytes(line[1]), &keys.0[..]);
+    }
+}
+
+#[test]
+fn canonical_identifiers() {
+    let _ = ::rust_sodium::init();
+    let fixture = lines(IDENTIFIERS);
+    assert_eq!(fixture.len(), 16);
+    for line in fixture {
+        assert_eq!(identifier(line[0]).to_signable_bytes(), bytes(line[1]), "{}", line[0]);
+    }
+}
+
+#[test]
+fn signatures() {
+    let _ = ::rust_sodium::init();
+    let keys = keys();
+    for line in lines(SIGNATURES) {
+        let seed: u8 = unwrap!(line[1].parse());
+        let binding = match line[2] {
+            "-" => None,
+            hex => {
+                let mut binding = [0; 32];
+                binding.copy_from_slice(&bytes(hex));
+                Some(binding)
+            }
+        };
+        let timestamp = match line[3] {
+            "-" => None,
+            timestamp => Some(unwrap!(timestamp.parse())),
+        };
+        let signature = unwrap!(Signature::from_slice(&bytes(line[4])));
+        let proof = Proof::with_context(keys[seed as usize - 1].0, signature, binding, timestamp);
+        assert!(proof.validate_for(&identifier(line[0])), "{}", line.join(" "));
+        assert_eq!(vote(seed, line[0], binding, timestamp).proof(), &proof);
+    }
+}
+
+#[test]
+fn encoded_chain() {
+    let _ = ::rust_sodium::init();
+    let block = |name: &str, votes: Vec<Vote>, valid: bool| {
+        Block::from_parts(identifier(name),
+                          votes.into_iter().map(|x| x.proof().clone()).collect(),
+                          valid)
+    };
+    let binding = Some(hash(b"binding"));
+    let mut expected =
+        DataChain::from_blocks(vec![block("node_gained",
+                                          vec![vote(1, "node_gained", None, None)],
+                                          true),
+                                    block("node_lost",
+                                          vec![vote(1, "node_lost", None, None),
+                                               vote(2, "node_lost", None, None)],
+                                          true),
+                                    block("immutable",
+                                          vec![vote(1, "immutable", None, None),
+                                               vote(2, "immutable", None, Some(TIMESTAMP))],
+                                          true),
+                                    block("structured",
+                                          vec![vote(2, "structured", binding, Some(TIMESTAMP))],
+                                          false)],
+                               4);
+    assert!(expected.pin(&identifier("immutable")));
+
+    let chain: DataChain = unwrap!(serialisation::deserialise(CHAIN));
+    assert_eq!(chain, expected);
+    assert_eq!(unwrap!(serialisation::serialise(&chain)), CHAIN);
+    for block in chain.chain() {
+        assert!(block.validate_block_signatures());
+    }
+}
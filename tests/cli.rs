@@ -0,0 +1,124 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.0.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+// Runs `data_chain-cli` against chain files written by `persistence::encode_chain`.
+
+#![allow(clippy::redundant_field_names)]
+
+extern crate data_chain;
+extern crate rust_sodium;
+extern crate tempdir;
+#[macro_use]
+extern crate unwrap;
+
+use data_chain::DataChain;
+use data_chain::chain::{BlockIdentifier, LinkDescriptor, Vote, persistence};
+use data_chain::sha3::{HashAlgorithm, hash};
+use rust_sodium::crypto::sign;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempdir::TempDir;
+
+fn cli(command: &str, path: &Path) -> Output {
+    unwrap!(Command::new(env!("CARGO_BIN_EXE_data_chain-cli"))
+        .args([command, unwrap!(path.to_str()), "--group-size", "4"])
+        .output())
+}
+
+// Exit code, with the process having ended through `process::exit` rather than a panic.
+fn code(output: &Output) -> i32 {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    unwrap!(output.status.code())
+}
+
+// A link and a data block voted by one node, the data block valid only if `valid_data`.
+fn test_chain(valid_data: bool) -> DataChain {
+    let keys = sign::gen_keypair();
+    let mut chain = DataChain::from_blocks(vec![], 4);
+    let link = BlockIdentifier::Link(LinkDescriptor::NodeGained(keys.0));
+    for id in vec![link, BlockIdentifier::ImmutableData(hash(b"data"))] {
+        assert!(chain.add_vote(unwrap!(Vote::new(&keys.0, &keys.1, id))).is_some());
+    }
+    if !valid_data {
+        // Signed by a key outside the group, so it never reaches quorum.
+        let stranger = sign::gen_keypair();
+        let data = BlockIdentifier::ImmutableData(hash(b"stranger"));
+        let _ = chain.add_vote(unwrap!(Vote::new(&stranger.0, &stranger.1, data)));
+    }
+    chain
+}
+
+fn write(chain: &DataChain, path: &Path) -> Vec<u8> {
+    let bytes = unwrap!(persistence::encode_chain(chain.chain(),
+                                                  chain.pinned(),
+                                                  HashAlgorithm::default()));
+    unwrap!(fs::write(path, &bytes));
+    bytes
+}
+
+#[test]
+fn subcommands() {
+    let _ = rust_sodium::init();
+    let dir = unwrap!(TempDir::new("test_cli"));
+    let path = dir.path().join("data_chain");
+    let chain = test_chain(true);
+    let _ = write(&chain, &path);
+
+    let output = cli("inspect", &path);
+    assert_eq!(code(&output), 0);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 blocks (1 links), 2 valid"));
+    assert_eq!(code(&cli("verify", &path)), 0);
+
+    let output = cli("export", &path);
+    assert_eq!(code(&output), 0);
+    let exported = unwrap!(DataChain::from_json(&String::from_utf8_lossy(&output.stdout)));
+    assert_eq!(exported.chain(), chain.chain());
+    assert_eq!(exported.pinned(), chain.pinned());
+
+    let invalid = test_chain(false);
+    let _ = write(&invalid, &path);
+    assert_eq!(code(&cli("verify", &path)), 1);
+    assert_eq!(code(&cli("prune", &path)), 0);
+    assert_eq!(code(&cli("verify", &path)), 0);
+    assert_eq!(unwrap!(DataChain::from_path(dir.path().to_path_buf(), 4)).len(), 2);
+
+    assert_eq!(code(&cli("export", &dir.path().join("missing"))), 2);
+    assert_eq!(code(&cli("unknown", &path)), 2);
+}
+
+#[test]
+fn truncated_file() {
+    let _ = rust_sodium::init();
+    let dir = unwrap!(TempDir::new("test_cli"));
+    let path = dir.path().join("data_chain");
+    let bytes = write(&test_chain(true), &path);
+    unwrap!(fs::write(&path, &bytes[..bytes.len() - 3]));
+
+    for command in &["inspect", "verify", "prune", "export"] {
+        let output = cli(command, &path);
+        assert_eq!(code(&output), 2, "{}", command);
+        assert!(!output.stderr.is_empty());
+    }
+
+    let output = cli("repair", &path);
+    assert_eq!(code(&output), 0);
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("dropped "), "{:?}", output);
+    assert!(path.with_extension("bak").exists());
+    assert_eq!(code(&cli("verify", &path)), 0);
+}